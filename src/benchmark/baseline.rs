@@ -0,0 +1,173 @@
+use super::benchmark_result::BenchmarkResult;
+use crate::poop_metrics::MetricType;
+use crate::util::units::Scalar;
+
+/// Change of the mean run time of a benchmark, compared to the result of the same benchmark
+/// in a previous export
+pub struct BaselineDelta<'a> {
+    pub baseline: &'a BenchmarkResult,
+
+    /// Relative change of the mean run time. This is positive if the benchmark became slower.
+    pub relative_change: Scalar,
+
+    /// Relative change of every poop metric that has been collected for both benchmarks
+    pub metric_changes: Vec<(MetricType, Scalar)>,
+}
+
+impl BaselineDelta<'_> {
+    /// Whether the mean run time increased by more than the given fraction
+    pub fn is_regression(&self, threshold: Scalar) -> bool {
+        self.relative_change > threshold
+    }
+
+    /// The metrics whose mean value increased by more than the respective threshold
+    pub fn regressed_metrics(&self, thresholds: &[(MetricType, Scalar)]) -> Vec<MetricType> {
+        self.metric_changes
+            .iter()
+            .filter(|(metric, change)| {
+                thresholds
+                    .iter()
+                    .any(|(m, threshold)| m == metric && change > threshold)
+            })
+            .map(|&(metric, _)| metric)
+            .collect()
+    }
+}
+
+/// Relative change of the poop metrics that are available for both results. Metrics with a
+/// baseline value of zero are left out.
+fn metric_changes(
+    result: &BenchmarkResult,
+    baseline: &BenchmarkResult,
+) -> Vec<(MetricType, Scalar)> {
+    let (Some(metrics), Some(baseline_metrics)) = (&result.poop_metrics, &baseline.poop_metrics)
+    else {
+        return vec![];
+    };
+
+    MetricType::all()
+        .into_iter()
+        .filter_map(
+            |metric| match (metrics.get(metric), baseline_metrics.get(metric)) {
+                (Some(value), Some(baseline_value)) if baseline_value > 0 => {
+                    Some((metric, value as Scalar / baseline_value as Scalar - 1.0))
+                }
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// Pair every result with the baseline result that has the same command and the same parameter
+/// values. `None` is returned for results without a (usable) baseline.
+pub fn compare_to_baseline<'a>(
+    results: &'a [BenchmarkResult],
+    baseline: &'a [BenchmarkResult],
+) -> Vec<(&'a BenchmarkResult, Option<BaselineDelta<'a>>)> {
+    results
+        .iter()
+        .map(|result| {
+            let delta = baseline
+                .iter()
+                .find(|b| b.command == result.command && b.parameters == result.parameters)
+                .filter(|b| b.mean > 0.0)
+                .map(|b| BaselineDelta {
+                    baseline: b,
+                    relative_change: result.mean / b.mean - 1.0,
+                    metric_changes: metric_changes(result, b),
+                });
+            (result, delta)
+        })
+        .collect()
+}
+
+#[test]
+fn test_compare_to_baseline() {
+    use approx::assert_relative_eq;
+    use std::collections::BTreeMap;
+
+    let create_result = |command: &str, size: &str, mean| {
+        let mut parameters = BTreeMap::new();
+        parameters.insert("size".to_string(), size.to_string());
+        BenchmarkResult {
+            command: command.to_string(),
+            mean,
+            parameters,
+            ..Default::default()
+        }
+    };
+
+    let baseline = vec![
+        create_result("sort", "10", 2.0),
+        create_result("sort", "20", 4.0),
+        create_result("uniq", "10", 0.0),
+    ];
+    let results = vec![
+        create_result("sort", "20", 5.0),
+        create_result("sort", "10", 1.0),
+        create_result("sort", "30", 1.0),
+        create_result("uniq", "10", 1.0),
+    ];
+
+    let deltas = compare_to_baseline(&results, &baseline);
+    let changes: Vec<Option<Scalar>> = deltas
+        .iter()
+        .map(|(_, delta)| delta.as_ref().map(|d| d.relative_change))
+        .collect();
+
+    assert_eq!(changes.len(), 4);
+    assert_relative_eq!(changes[0].unwrap(), 0.25);
+    assert_relative_eq!(changes[1].unwrap(), -0.5);
+    assert_eq!(changes[2], None);
+    assert_eq!(changes[3], None);
+
+    let delta = deltas[0].1.as_ref().unwrap();
+    assert!(delta.is_regression(0.2));
+    assert!(!delta.is_regression(0.3));
+    assert!(delta.metric_changes.is_empty());
+}
+
+#[test]
+fn test_compare_metrics_to_baseline() {
+    use crate::poop_metrics::PoopMetrics;
+    use approx::assert_relative_eq;
+
+    let create_result = |instructions, cache_misses| BenchmarkResult {
+        command: "sort".to_string(),
+        mean: 1.0,
+        poop_metrics: Some(PoopMetrics {
+            instructions: Some(instructions),
+            cache_misses: Some(cache_misses),
+            branches: Some(10),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let mut baseline = create_result(1000, 100);
+    baseline.poop_metrics.as_mut().unwrap().cache_misses = Some(0);
+    let results = vec![create_result(1003, 141)];
+
+    let deltas = compare_to_baseline(&results, std::slice::from_ref(&baseline));
+    let delta = deltas[0].1.as_ref().unwrap();
+    assert_relative_eq!(delta.relative_change, 0.0);
+    assert_eq!(delta.metric_changes.len(), 2);
+    assert_eq!(delta.metric_changes[0].0, MetricType::Instructions);
+    assert_relative_eq!(delta.metric_changes[0].1, 0.003);
+    assert_eq!(delta.metric_changes[1], (MetricType::Branches, 0.0));
+
+    let mut baseline = create_result(1000, 100);
+    baseline.poop_metrics.as_mut().unwrap().branches = None;
+    let deltas = compare_to_baseline(&results, std::slice::from_ref(&baseline));
+    let delta = deltas[0].1.as_ref().unwrap();
+    assert_eq!(
+        delta.regressed_metrics(&[(MetricType::CacheMisses, 0.4)]),
+        vec![MetricType::CacheMisses]
+    );
+    assert!(delta
+        .regressed_metrics(&[
+            (MetricType::CacheMisses, 0.5),
+            (MetricType::Instructions, 0.01)
+        ])
+        .is_empty());
+}
@@ -1,21 +1,23 @@
 use std::collections::BTreeMap;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::poop_metrics::PoopMetrics;
-use crate::util::units::Second;
+use crate::output::warnings::Warnings;
+use crate::poop_metrics::{PoopMetricRates, PoopMetrics};
+use crate::util::percentile::percentile_of_sorted;
+use crate::util::units::{Scalar, Second};
 
 /// Set of values that will be exported.
 // NOTE: `serde` is used for JSON serialization, but not for CSV serialization due to the
 // `parameters` map. Update `src/hyperfine/export/csv.rs` with new fields, as appropriate.
-#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct BenchmarkResult {
     /// The full command line of the program that is being benchmarked
     pub command: String,
 
     /// The full command line of the program that is being benchmarked, possibly including a list of
     /// parameters that were not used in the command line template.
-    #[serde(skip_serializing)]
+    #[serde(skip)]
     pub command_with_unused_parameters: String,
 
     /// The average run time
@@ -24,6 +26,15 @@ pub struct BenchmarkResult {
     /// The standard deviation of all run times. Not available if only one run has been performed
     pub stddev: Option<Second>,
 
+    /// Lower bound of the (bootstrapped) confidence interval of the mean. Not available if only
+    /// one run has been performed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_ci_lower: Option<Second>,
+
+    /// Upper bound of the (bootstrapped) confidence interval of the mean
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_ci_upper: Option<Second>,
+
     /// The median run time
     pub median: Second,
 
@@ -39,26 +50,142 @@ pub struct BenchmarkResult {
     /// Maximum of all measured times
     pub max: Second,
 
+    /// Percentiles of all measured times
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percentiles: Option<Percentiles>,
+
+    /// Mean of the run times without the fastest and the slowest 10% of the runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trimmed_mean: Option<Second>,
+
+    /// Median absolute deviation of the run times from their median
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_absolute_deviation: Option<Second>,
+
     /// All run time measurements
     #[serde(skip_serializing_if = "Option::is_none")]
     pub times: Option<Vec<Second>>,
 
-    /// Maximum memory usage of the process, in bytes
+    /// Time spent in user mode, for each run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub times_user: Option<Vec<Second>>,
+
+    /// Time spent in kernel mode, for each run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub times_system: Option<Vec<Second>>,
+
+    /// Maximum memory usage of the process, in bytes. This is the maximum resident set size
+    /// on Unix and the peak working set size (without descendants) on Windows
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memory_usage_byte: Option<Vec<u64>>,
 
+    /// Mean of the peak memory usage over all runs, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_mean: Option<f64>,
+
+    /// Largest peak memory usage of all runs, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<u64>,
+
     /// Exit codes of all command invocations
     pub exit_codes: Vec<Option<i32>>,
 
+    /// Number of runs with each exit code
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub exit_code_counts: BTreeMap<i32, usize>,
+
     /// Parameter values for this benchmark
-    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub parameters: BTreeMap<String, String>,
 
     /// Mean poop metrics (if enabled)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub poop_metrics: Option<PoopMetrics>,
 
-    /// All poop metrics measurements
+    /// Standard deviation of the poop metrics across runs (if enabled)
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub poop_metrics_stddev: Option<PoopMetricRates>,
+
+    /// Poop metrics of all runs in which they could be collected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub poop_metrics_all: Option<Vec<PoopMetrics>>,
+
+    /// Poop metrics of every run, parallel to `times` (`null` for runs without data)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_per_run: Option<Vec<Option<PoopMetrics>>>,
+
+    /// Mean poop metrics, divided by the mean run time (only with `--metrics-per-second`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poop_metrics_per_second: Option<PoopMetricRates>,
+
+    /// Size of the benchmarked binary (only available with `--binary-size`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_size_byte: Option<u64>,
+
+    /// Weight of each run (only available with `--weight-file`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<Scalar>>,
+
+    /// The mean run time, weighted by `weights`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weighted_mean: Option<Second>,
+
+    /// The standard deviation of the run times, weighted by `weights`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weighted_stddev: Option<Second>,
+
+    /// Number of measurement attempts (only available with `--retry-on-variance`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u64>,
+
+    /// Number of outliers that are excluded from the statistics (only available with
+    /// `--drop-outliers`). The outliers are still contained in `times`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outliers_dropped: Option<usize>,
+
+    /// Mean wall clock time of the preparation command (only available with `--prepare`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prepare_mean: Option<Second>,
+
+    /// Mean wall clock time of the conclusion command (only available with `--conclude`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclude_mean: Option<Second>,
+
+    /// Start time of each run, relative to the start of the first run (monotonic clock)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_offsets: Option<Vec<Second>>,
+
+    /// System time at the start of the first run, in seconds since the Unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_timestamp: Option<f64>,
+
+    /// The warnings that have been shown for this benchmark
+    #[serde(skip)]
+    pub warnings: Vec<Warnings>,
+}
+
+/// Percentiles of the run times, interpolated linearly between the closest measurements
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Percentiles {
+    pub p50: Second,
+    pub p90: Second,
+    pub p95: Second,
+    pub p99: Second,
+}
+
+impl Percentiles {
+    /// Compute the percentiles of the given, sorted times. Returns `None` if no times are
+    /// given.
+    pub fn of_sorted(sorted: &[Second]) -> Option<Self> {
+        if sorted.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            p50: percentile_of_sorted(sorted, 50.0),
+            p90: percentile_of_sorted(sorted, 90.0),
+            p95: percentile_of_sorted(sorted, 95.0),
+            p99: percentile_of_sorted(sorted, 99.0),
+        })
+    }
 }
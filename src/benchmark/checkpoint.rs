@@ -0,0 +1,240 @@
+//! Checkpoints of completed benchmarks. A checkpoint file is rewritten after every finished
+//! benchmark, such that an interrupted benchmark suite can be resumed with '--resume'.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::benchmark_result::BenchmarkResult;
+use crate::command::Command;
+
+use anyhow::{bail, Context, Result};
+
+/// Version of the checkpoint file format. Increase this whenever the format changes in
+/// an incompatible way.
+const SCHEMA_VERSION: u64 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointEntry {
+    command: String,
+    result: BenchmarkResult,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointFile {
+    schema_version: u64,
+
+    /// Identity keys of all benchmark commands in the suite
+    commands: Vec<String>,
+
+    /// Results of the completed benchmarks
+    results: Vec<CheckpointEntry>,
+}
+
+/// A stable key that identifies a benchmark command across hyperfine invocations. It contains
+/// the position of the command in the suite, such that several benchmarks of the same command
+/// line (e.g. with different names or '--prepare' commands) are kept apart.
+pub fn command_key(number: usize, command: &Command<'_>) -> String {
+    let parameters = command
+        .get_parameters()
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>();
+
+    let mut key = format!("{}: {}", number + 1, command.get_name());
+    if command.get_name() != command.get_command_line() {
+        key += &format!(" ({})", command.get_command_line());
+    }
+    if !parameters.is_empty() {
+        key += &format!(" [{}]", parameters.join(", "));
+    }
+    key
+}
+
+pub struct Checkpoint {
+    path: PathBuf,
+    commands: Vec<String>,
+    completed: BTreeMap<String, BenchmarkResult>,
+}
+
+impl Checkpoint {
+    /// Start a new checkpoint for the given list of benchmark commands
+    pub fn new(path: &Path, commands: Vec<String>) -> Self {
+        Checkpoint {
+            path: path.to_path_buf(),
+            commands,
+            completed: BTreeMap::new(),
+        }
+    }
+
+    /// Load an existing checkpoint, which has to belong to the same list of benchmark
+    /// commands. If the file does not exist, a new checkpoint is started.
+    pub fn resume(path: &Path, commands: Vec<String>) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(path, commands));
+        }
+
+        let file = File::open(path)
+            .with_context(|| format!("Could not open checkpoint file '{}'", path.display()))?;
+        let checkpoint: CheckpointFile = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Could not read checkpoint file '{}'", path.display()))?;
+
+        if checkpoint.schema_version != SCHEMA_VERSION {
+            bail!(
+                "The checkpoint file '{}' has schema version {}, but this version of hyperfine \
+                 requires version {}. Remove the file to start from scratch.",
+                path.display(),
+                checkpoint.schema_version,
+                SCHEMA_VERSION
+            );
+        }
+
+        if checkpoint.commands != commands {
+            bail!(
+                "The checkpoint file '{}' belongs to a different set of benchmark commands. \
+                 Remove the file to start from scratch.",
+                path.display()
+            );
+        }
+
+        Ok(Checkpoint {
+            path: path.to_path_buf(),
+            commands,
+            completed: checkpoint
+                .results
+                .into_iter()
+                .map(|entry| (entry.command, entry.result))
+                .collect(),
+        })
+    }
+
+    /// The result of the command with the given position in the suite, if it has already been
+    /// completed
+    pub fn completed_result(&self, number: usize) -> Option<&BenchmarkResult> {
+        self.completed.get(self.commands.get(number)?)
+    }
+
+    /// Record the result of the command with the given position in the suite and write the
+    /// checkpoint file
+    pub fn add_result(&mut self, number: usize, result: &BenchmarkResult) -> Result<()> {
+        self.completed
+            .insert(self.commands[number].clone(), result.clone());
+        self.write()
+    }
+
+    fn write(&self) -> Result<()> {
+        let checkpoint = CheckpointFile {
+            schema_version: SCHEMA_VERSION,
+            commands: self.commands.clone(),
+            results: self
+                .commands
+                .iter()
+                .filter_map(|key| {
+                    self.completed.get(key).map(|result| CheckpointEntry {
+                        command: key.clone(),
+                        result: result.clone(),
+                    })
+                })
+                .collect(),
+        };
+
+        // Write to a temporary file first, such that an interruption can not leave a
+        // partially written checkpoint behind
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&checkpoint)?).with_context(|| {
+            format!("Could not write checkpoint file '{}'", self.path.display())
+        })?;
+        fs::rename(&tmp_path, &self.path).with_context(|| {
+            format!("Could not write checkpoint file '{}'", self.path.display())
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(command: &str, mean: f64) -> BenchmarkResult {
+        BenchmarkResult {
+            command: command.into(),
+            mean,
+            times: Some(vec![mean, mean]),
+            exit_codes: vec![Some(0), Some(0)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+
+        let first = Command::new(None, "sleep 0.1");
+        let second = Command::new(None, "sleep 0.2");
+        let keys = vec![command_key(0, &first), command_key(1, &second)];
+
+        let mut checkpoint = Checkpoint::new(&path, keys.clone());
+        checkpoint.add_result(0, &result("sleep 0.1", 0.1)).unwrap();
+
+        let resumed = Checkpoint::resume(&path, keys).unwrap();
+        assert_eq!(resumed.completed_result(0), Some(&result("sleep 0.1", 0.1)));
+        assert_eq!(resumed.completed_result(1), None);
+    }
+
+    #[test]
+    fn test_command_key() {
+        let command = Command::new(None, "sleep 0.1");
+        assert_eq!(command_key(0, &command), "1: sleep 0.1");
+        assert_ne!(command_key(0, &command), command_key(1, &command));
+
+        let named = Command::new(Some("fast"), "sleep 0.1");
+        assert_eq!(command_key(1, &named), "2: fast (sleep 0.1)");
+    }
+
+    #[test]
+    fn test_checkpoint_for_different_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+
+        let first = Command::new(None, "sleep 0.1");
+        let mut checkpoint = Checkpoint::new(&path, vec![command_key(0, &first)]);
+        checkpoint.add_result(0, &result("sleep 0.1", 0.1)).unwrap();
+
+        let error = Checkpoint::resume(&path, vec!["sleep 0.3".into()])
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .contains("belongs to a different set of benchmark commands"));
+    }
+
+    #[test]
+    fn test_checkpoint_schema_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        fs::write(
+            &path,
+            r#"{"schema_version": 999, "commands": [], "results": []}"#,
+        )
+        .unwrap();
+
+        let error = Checkpoint::resume(&path, vec![]).err().unwrap();
+        assert!(error.to_string().contains("has schema version 999"));
+    }
+
+    #[test]
+    fn test_resume_without_checkpoint_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("does-not-exist.json");
+
+        let command = Command::new(None, "sleep 0.1");
+        let checkpoint = Checkpoint::resume(&path, vec![command_key(0, &command)]).unwrap();
+        assert_eq!(checkpoint.completed_result(0), None);
+    }
+}
@@ -1,32 +1,54 @@
+use std::io;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
-use std::process::ExitStatus;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
 
 use crate::command::Command;
+use crate::error::CommandFailedError;
 use crate::options::{
-    CmdFailureAction, CommandInputPolicy, CommandOutputPolicy, Options, OutputStyleOption, Shell,
+    CmdFailureAction, CommandEnvironment, CommandInputPolicy, CommandOutputPolicy,
+    CpuTimeAccounting, Options, OutputStyleOption, Shell,
 };
+use crate::output::format::format_duration;
 use crate::output::progress_bar::get_progress_bar;
-use crate::poop_metrics::MetricType;
+use crate::poop_metrics::{MetricType, RawEvent};
 use crate::timer::execute_and_measure;
+#[cfg(target_os = "linux")]
+use crate::util::cpu_affinity::CpuSet;
+use crate::util::priority::SchedulingPriority;
 use crate::util::randomized_environment_offset;
-use crate::util::units::Second;
+use crate::util::units::{Second, Unit};
 
 use super::timing_result::TimingResult;
 
 use anyhow::{bail, Context, Result};
-use statistical::mean;
+use statistical::{mean, standard_deviation};
 
 #[derive(Clone)]
 struct CommandExecutionOptions<'a> {
     command_failure_action: CmdFailureAction,
     command_input_policy: &'a CommandInputPolicy,
+    command_environment: &'a CommandEnvironment,
     command_output_policy: &'a CommandOutputPolicy,
     command_name: &'a str,
     collect_metrics: bool,
     metrics_to_collect: &'a [MetricType],
+    raw_events: &'a [RawEvent],
+    metrics_leader_only: bool,
+    debug_perf: bool,
+    cpu_time_accounting: CpuTimeAccounting,
+    timeout: Option<Second>,
+    /// Unit in which the timeout is shown in error messages
+    time_unit: Option<Unit>,
+    cpu_affinity: Option<&'a [usize]>,
+    priority: SchedulingPriority,
+    /// Capture otherwise discarded output (up to the given number of bytes) to show it if the
+    /// command fails
+    output_capture_on_failure: Option<usize>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BenchmarkIteration {
     NonBenchmarkRun,
     Warmup(u64),
@@ -72,9 +94,27 @@ fn run_command_and_measure_common(
     options: CommandExecutionOptions,
 ) -> Result<(TimingResult, ExitStatus)> {
     let stdin = options.command_input_policy.get_stdin()?;
-    let (stdout, stderr) = options.command_output_policy.get_stdout_stderr()?;
+    // Capture output that would otherwise be discarded, to show it if the command fails
+    let capture_on_failure = match options.command_output_policy {
+        CommandOutputPolicy::Null | CommandOutputPolicy::Pipe => options.output_capture_on_failure,
+        _ => None,
+    };
+    let output_policy = match capture_on_failure {
+        Some(limit) => &CommandOutputPolicy::Capture(limit),
+        None => options.command_output_policy,
+    };
+    let mut output_capture = None;
+    let (stdout, stderr) = match *output_policy {
+        CommandOutputPolicy::Capture(limit) => {
+            let (reader, writer) = std::io::pipe()?;
+            output_capture = Some((reader, limit));
+            (writer.try_clone()?.into(), writer.into())
+        }
+        ref policy => policy.get_stdout_stderr()?,
+    };
     command.stdin(stdin).stdout(stdout).stderr(stderr);
 
+    options.command_environment.apply(&mut command);
     command.env(
         "HYPERFINE_RANDOMIZED_ENVIRONMENT_OFFSET",
         randomized_environment_offset::value(),
@@ -84,16 +124,54 @@ fn run_command_and_measure_common(
         command.env("HYPERFINE_ITERATION", value);
     }
 
-    let timer_result =
-        execute_and_measure(command, options.collect_metrics, options.metrics_to_collect)
-            .with_context(|| format!("Failed to run command '{}'", options.command_name))?;
+    #[cfg(target_os = "linux")]
+    if let Some(cpus) = options.cpu_affinity {
+        CpuSet::new(cpus).pin_command(&mut command);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = options.cpu_affinity;
+
+    #[cfg(unix)]
+    options.priority.set_for_command(&mut command);
+    #[cfg(not(unix))]
+    let _ = options.priority;
+
+    let mut timer_result = execute_and_measure(
+        command,
+        output_capture,
+        options.timeout.map(Duration::from_secs_f64),
+        options.collect_metrics,
+        options.metrics_to_collect,
+        options.raw_events,
+        options.metrics_leader_only,
+        options.debug_perf,
+        options.cpu_time_accounting,
+    )
+    .with_context(|| format!("Failed to run command '{}'", options.command_name))?;
 
     if !timer_result.status.success() {
         use crate::util::exit_code::extract_exit_code;
 
+        let when = match iteration {
+            BenchmarkIteration::NonBenchmarkRun => "a non-benchmark run".to_string(),
+            BenchmarkIteration::Warmup(0) => "the first warmup run".to_string(),
+            BenchmarkIteration::Warmup(i) => format!("warmup iteration {i}"),
+            BenchmarkIteration::Benchmark(0) => "the first benchmark run".to_string(),
+            BenchmarkIteration::Benchmark(i) => format!("benchmark iteration {i}"),
+        };
+        let shown_output = match capture_on_failure {
+            Some(_) => timer_result
+                .captured_output
+                .take()
+                .filter(|output| !output.bytes.is_empty()),
+            None => None,
+        };
+
         let should_fail = match options.command_failure_action {
             CmdFailureAction::RaiseError => true,
             CmdFailureAction::IgnoreAllFailures => false,
+            // Timeouts do not have a meaningful exit code
+            CmdFailureAction::IgnoreSpecificFailures(_) if timer_result.timed_out => true,
             CmdFailureAction::IgnoreSpecificFailures(ref codes) => {
                 // Only fail if the exit code is not in the list of codes to ignore
                 if let Some(exit_code) = extract_exit_code(timer_result.status) {
@@ -106,22 +184,33 @@ fn run_command_and_measure_common(
         };
 
         if should_fail {
-            let when = match iteration {
-                BenchmarkIteration::NonBenchmarkRun => "a non-benchmark run".to_string(),
-                BenchmarkIteration::Warmup(0) => "the first warmup run".to_string(),
-                BenchmarkIteration::Warmup(i) => format!("warmup iteration {i}"),
-                BenchmarkIteration::Benchmark(0) => "the first benchmark run".to_string(),
-                BenchmarkIteration::Benchmark(i) => format!("benchmark iteration {i}"),
-            };
-            bail!(
-                "{cause} in {when}. Use the '-i'/'--ignore-failure' option if you want to ignore this. \
-                Alternatively, use the '--show-output' option to debug what went wrong.",
-                cause=timer_result.status.code().map_or(
-                    "The process has been terminated by a signal".into(),
-                    |c| format!("Command terminated with non-zero exit code {c}")
+            let mut message = format!(
+                    "{cause} in {when}. Use the '-i'/'--ignore-failure' option if you want to ignore this. \
+                    Alternatively, use the '--show-output' option to debug what went wrong.",
+                    cause=if timer_result.timed_out {
+                        format!(
+                            "Command has been killed after exceeding the timeout of {}",
+                            format_duration(options.timeout.unwrap_or_default(), options.time_unit)
+                        )
+                    } else {
+                        timer_result.status.code().map_or(
+                            "The process has been terminated by a signal".into(),
+                            |c| format!("Command terminated with non-zero exit code {c}")
+                        )
+                    },
+                );
+            if let Some(output) = &shown_output {
+                message.push_str(&format!("\n\nOutput of the command:\n{output}"));
+            }
+            return Err(CommandFailedError {
+                message,
+                output: timer_result.captured_output,
+            }
+            .into());
+        }
 
-                ),
-            );
+        if let Some(output) = shown_output {
+            eprintln!("Output of the failing command in {when}:\n{output}\n");
         }
     }
 
@@ -134,6 +223,7 @@ fn run_command_and_measure_common(
             time_system: timer_result.time_system,
             memory_usage_byte: timer_result.memory_usage_byte,
             poop_metrics: timer_result.poop_metrics,
+            timed_out: timer_result.timed_out,
         },
         status,
     ))
@@ -164,10 +254,23 @@ impl Executor for RawExecutor<'_> {
                 command_failure_action: command_failure_action
                     .unwrap_or_else(|| self.options.command_failure_action.clone()),
                 command_input_policy: &self.options.command_input_policy,
+                command_environment: &self.options.command_environment,
                 command_output_policy: output_policy,
                 command_name: &command.get_command_line(),
                 collect_metrics: false,
                 metrics_to_collect: &[],
+                raw_events: &[],
+                metrics_leader_only: false,
+                debug_perf: false,
+                cpu_time_accounting: self.options.cpu_time_accounting,
+                timeout: self.options.timeout,
+                time_unit: self.options.time_unit,
+                cpu_affinity: self.options.cpu_affinity.as_deref(),
+                priority: self.options.priority,
+                output_capture_on_failure: self
+                    .options
+                    .show_output_on_failure
+                    .then_some(self.options.output_capture_limit),
             },
         )
     }
@@ -195,6 +298,33 @@ impl<'a> ShellExecutor<'a> {
             shell_spawning_time: None,
         }
     }
+
+    /// Fail with a clear error message if the shell can not be started at all
+    fn check_shell(&self) -> Result<()> {
+        let mut command = self.shell.command();
+        // The shell is looked up in the PATH of the commands, which differs with '--clear-env'
+        self.options.command_environment.apply(&mut command);
+        let Err(error) = command
+            .args(self.shell.command_flag())
+            .arg("")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        else {
+            return Ok(());
+        };
+
+        let problem = match error.kind() {
+            io::ErrorKind::NotFound => "could not be found".to_string(),
+            io::ErrorKind::PermissionDenied => "is not executable".to_string(),
+            _ => format!("could not be started: {error}"),
+        };
+        bail!(
+            "The shell '{}' {problem}. Use the '--shell' option to select a different shell.",
+            self.shell.program()
+        );
+    }
 }
 
 impl Executor for ShellExecutor<'_> {
@@ -207,7 +337,7 @@ impl Executor for ShellExecutor<'_> {
     ) -> Result<(TimingResult, ExitStatus)> {
         let on_windows_cmd = cfg!(windows) && *self.shell == Shell::Default("cmd.exe");
         let mut command_builder = self.shell.command();
-        command_builder.arg(if on_windows_cmd { "/C" } else { "-c" });
+        command_builder.args(self.shell.command_flag());
 
         // Windows needs special treatment for its behavior on parsing cmd arguments
         if on_windows_cmd {
@@ -224,15 +354,28 @@ impl Executor for ShellExecutor<'_> {
                 command_failure_action: command_failure_action
                     .unwrap_or_else(|| self.options.command_failure_action.clone()),
                 command_input_policy: &self.options.command_input_policy,
+                command_environment: &self.options.command_environment,
                 command_output_policy: output_policy,
                 command_name: &command.get_command_line(),
                 collect_metrics: self.options.poop_metrics_enabled,
                 metrics_to_collect: &self.options.metrics_to_collect,
+                raw_events: &self.options.raw_events,
+                metrics_leader_only: self.options.metrics_leader_only,
+                debug_perf: self.options.debug_perf,
+                cpu_time_accounting: self.options.cpu_time_accounting,
+                timeout: self.options.timeout,
+                time_unit: self.options.time_unit,
+                cpu_affinity: self.options.cpu_affinity.as_deref(),
+                priority: self.options.priority,
+                output_capture_on_failure: self
+                    .options
+                    .show_output_on_failure
+                    .then_some(self.options.output_capture_limit),
             },
         )?;
 
         // Subtract shell spawning time
-        if let Some(spawning_time) = self.shell_spawning_time {
+        if let Some(spawning_time) = &self.shell_spawning_time {
             timing_result.time_real = (timing_result.time_real - spawning_time.time_real).max(0.0);
             timing_result.time_user = (timing_result.time_user - spawning_time.time_user).max(0.0);
             timing_result.time_system =
@@ -242,14 +385,27 @@ impl Executor for ShellExecutor<'_> {
         Ok((timing_result, status))
     }
 
-    /// Measure the average shell spawning time
+    /// Measure the average shell spawning time, unless it has been given via
+    /// `--shell-overhead`
     fn calibrate(&mut self) -> Result<()> {
         const COUNT: u64 = 50;
-        let progress_bar = if self.options.output_style != OutputStyleOption::Disabled {
+
+        self.check_shell()?;
+
+        if let Some(overhead) = self.options.shell_overhead {
+            self.shell_spawning_time = Some(TimingResult {
+                time_real: overhead,
+                ..Default::default()
+            });
+            return Ok(());
+        }
+
+        let progress_bar = if self.options.show_progress_bar() {
             Some(get_progress_bar(
                 COUNT,
                 "Measuring shell spawning time",
                 self.options.output_style,
+                self.options.progress_update_interval,
             ))
         } else {
             None
@@ -270,12 +426,10 @@ impl Executor for ShellExecutor<'_> {
 
             match res {
                 Err(_) => {
-                    let shell_cmd = if cfg!(windows) {
-                        format!("{} /C \"\"", self.shell)
-                    } else {
-                        format!("{} -c \"\"", self.shell)
+                    let shell_cmd = match self.shell.command_flag() {
+                        Some(flag) => format!("{} {flag} \"\"", self.shell),
+                        None => format!("{} \"\"", self.shell),
                     };
-
                     bail!(
                         "Could not measure shell execution time. Make sure you can run '{}'.",
                         shell_cmd
@@ -303,13 +457,36 @@ impl Executor for ShellExecutor<'_> {
             time_system: mean(&times_system),
             memory_usage_byte: 0,
             poop_metrics: None,
+            timed_out: false,
         });
 
+        // Show the correction that is applied to all measurements when debugging commands
+        if self.options.output_style != OutputStyleOption::Disabled
+            && self
+                .options
+                .command_output_policies
+                .contains(&CommandOutputPolicy::Inherit)
+        {
+            println!(
+                "Shell spawning time: {} ± {} (user: {}, system: {}), subtracted from all runs",
+                format_duration(mean(&times_real), self.options.time_unit),
+                format_duration(
+                    standard_deviation(&times_real, None),
+                    self.options.time_unit
+                ),
+                format_duration(mean(&times_user), self.options.time_unit),
+                format_duration(mean(&times_system), self.options.time_unit),
+            );
+            println!();
+        }
+
         Ok(())
     }
 
     fn time_overhead(&self) -> Second {
-        self.shell_spawning_time.unwrap().time_real
+        self.shell_spawning_time
+            .as_ref()
+            .map_or(0.0, |t| t.time_real)
     }
 }
 
@@ -360,6 +537,7 @@ impl Executor for MockExecutor {
                 time_system: 0.0,
                 memory_usage_byte: 0,
                 poop_metrics: None,
+                timed_out: false,
             },
             status,
         ))
@@ -0,0 +1,128 @@
+//! A deterministic executor for testing the benchmarking engine without spawning processes.
+
+use std::cell::RefCell;
+use std::process::ExitStatus;
+
+use crate::command::Command;
+use crate::options::{CmdFailureAction, CommandOutputPolicy};
+use crate::util::units::Second;
+
+use super::executor::{BenchmarkIteration, Executor};
+use super::timing_result::TimingResult;
+
+use anyhow::Result;
+
+/// Scripted outcome of a single command invocation
+#[derive(Debug, Default, Clone)]
+pub struct FakeRun {
+    pub timing_result: TimingResult,
+    pub exit_code: i32,
+}
+
+impl FakeRun {
+    /// A successful run with the given wall clock time
+    pub fn with_time(time_real: Second) -> Self {
+        FakeRun {
+            timing_result: TimingResult {
+                time_real,
+                ..Default::default()
+            },
+            exit_code: 0,
+        }
+    }
+}
+
+type Script = Box<dyn Fn(&Command<'_>, BenchmarkIteration) -> FakeRun>;
+
+/// An `Executor` that returns scripted timing results and exit codes for each
+/// `BenchmarkIteration`. No time passes and no processes are spawned. All
+/// invocations are recorded so that tests can inspect the execution order.
+pub struct FakeExecutor {
+    script: Script,
+    time_overhead: Second,
+    invocations: RefCell<Vec<(String, BenchmarkIteration)>>,
+}
+
+impl FakeExecutor {
+    pub fn new(script: impl Fn(&Command<'_>, BenchmarkIteration) -> FakeRun + 'static) -> Self {
+        FakeExecutor {
+            script: Box::new(script),
+            time_overhead: 0.0,
+            invocations: RefCell::new(vec![]),
+        }
+    }
+
+    /// Return the measured benchmark times from the given list, one per iteration.
+    /// Warmup and non-benchmark runs take the time of the first entry.
+    pub fn from_times(times: Vec<Second>) -> Self {
+        Self::new(move |_, iteration| match iteration {
+            BenchmarkIteration::Benchmark(i) => {
+                FakeRun::with_time(times[(i as usize).min(times.len() - 1)])
+            }
+            _ => FakeRun::with_time(times[0]),
+        })
+    }
+
+    /// Set the value that is reported by `Executor::time_overhead`
+    pub fn with_time_overhead(mut self, time_overhead: Second) -> Self {
+        self.time_overhead = time_overhead;
+        self
+    }
+
+    /// All recorded invocations, in the order in which they happened
+    pub fn invocations(&self) -> Vec<(String, BenchmarkIteration)> {
+        self.invocations.borrow().clone()
+    }
+
+    /// Number of measured (non-warmup) benchmark invocations
+    pub fn num_benchmark_runs(&self) -> usize {
+        self.invocations
+            .borrow()
+            .iter()
+            .filter(|(_, it)| matches!(it, BenchmarkIteration::Benchmark(_)))
+            .count()
+    }
+}
+
+#[cfg(unix)]
+pub fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+pub fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+impl Executor for FakeExecutor {
+    fn run_command_and_measure(
+        &self,
+        command: &Command<'_>,
+        iteration: BenchmarkIteration,
+        _command_failure_action: Option<CmdFailureAction>,
+        _output_policy: &CommandOutputPolicy,
+    ) -> Result<(TimingResult, ExitStatus)> {
+        self.invocations
+            .borrow_mut()
+            .push((command.get_command_line(), iteration));
+
+        let run = (self.script)(command, iteration);
+        Ok((run.timing_result, exit_status(run.exit_code)))
+    }
+
+    fn calibrate(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn time_overhead(&self) -> Second {
+        self.time_overhead
+    }
+}
+
+#[test]
+fn test_fake_exit_status() {
+    assert!(exit_status(0).success());
+    assert_eq!(exit_status(3).code(), Some(3));
+}
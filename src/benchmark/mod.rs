@@ -1,30 +1,55 @@
+pub mod baseline;
 pub mod benchmark_result;
+pub mod checkpoint;
 pub mod executor;
+#[cfg(test)]
+pub mod fake_executor;
 pub mod relative_speed;
 pub mod scheduler;
+#[cfg(test)]
+mod tests;
 pub mod timing_result;
 
+use std::borrow::Cow;
 use std::cmp;
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::process::ExitStatus;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::benchmark::executor::BenchmarkIteration;
 use crate::command::Command;
+use crate::error::CommandFailedError;
 use crate::options::{
-    CmdFailureAction, CommandOutputPolicy, ExecutorKind, Options, OutputStyleOption,
+    CmdFailureAction, CommandOutputPolicy, ExecutorKind, Options, OutputStyleOption, RunBounds,
 };
-use crate::outlier_detection::{modified_zscores, OUTLIER_THRESHOLD};
-use crate::output::format::{format_duration, format_duration_unit};
-use crate::output::progress_bar::get_progress_bar;
+use crate::outlier_detection::{
+    bimodal_cluster_means, median_absolute_deviation, modified_zscores, trend_correlation,
+    trend_p_value, OUTLIER_THRESHOLD,
+};
+use crate::output::format::{
+    format_bytes, format_bytes_unit, format_duration, format_duration_unit,
+    format_exit_code_counts, format_poop_metrics, PoopMetricsDetails,
+};
+use crate::output::histogram::{default_bin_count, format_histogram};
+use crate::output::progress_bar::{get_progress_bar, SessionProgress};
+use crate::output::progress_events::ProgressEvent;
 use crate::output::warnings::{OutlierWarningOptions, Warnings};
 use crate::parameter::ParameterNameAndValue;
-use crate::poop_metrics::PoopMetrics;
-use crate::util::exit_code::extract_exit_code;
+use crate::poop_metrics::{DerivedRatio, MetricType, PoopMetricRates, PoopMetrics};
+use crate::util::bootstrap::bootstrap_mean_ci;
+use crate::util::exit_code::{count_exit_codes, extract_exit_code};
 use crate::util::min_max::{max, min};
-use crate::util::units::Second;
-use benchmark_result::BenchmarkResult;
+use crate::util::percentile::trimmed_mean_of_sorted;
+use crate::util::units::{Scalar, Second};
+use crate::util::weighted::{weighted_mean, weighted_standard_deviation};
+use benchmark_result::{BenchmarkResult, Percentiles};
 use timing_result::TimingResult;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::*;
+use serde::Serialize;
 use statistical::{mean, median, standard_deviation};
 
 use self::executor::Executor;
@@ -42,81 +67,261 @@ fn aggregate_poop_metrics(timing_results: &[TimingResult]) -> Option<PoopMetrics
 
     let mut aggregated = PoopMetrics::new();
 
-    // Count how many results have each metric
-    let cpu_cycles_count = metrics_with_data
-        .iter()
-        .filter(|m| m.cpu_cycles.is_some())
-        .count();
-    let instructions_count = metrics_with_data
-        .iter()
-        .filter(|m| m.instructions.is_some())
-        .count();
-    let cache_refs_count = metrics_with_data
-        .iter()
-        .filter(|m| m.cache_references.is_some())
-        .count();
-    let cache_misses_count = metrics_with_data
-        .iter()
-        .filter(|m| m.cache_misses.is_some())
-        .count();
-    let branches_count = metrics_with_data
-        .iter()
-        .filter(|m| m.branches.is_some())
-        .count();
-    let branch_misses_count = metrics_with_data
-        .iter()
-        .filter(|m| m.branch_misses.is_some())
-        .count();
-    let page_faults_count = metrics_with_data
+    // Average each metric over the runs in which it has been collected
+    for metric in MetricType::all() {
+        let values: Vec<u64> = metrics_with_data
+            .iter()
+            .filter_map(|m| m.get(metric))
+            .collect();
+        if !values.is_empty() {
+            aggregated.set(metric, values.iter().sum::<u64>() / values.len() as u64);
+        }
+    }
+    let raw_labels: BTreeSet<&String> = metrics_with_data
         .iter()
-        .filter(|m| m.page_faults.is_some())
-        .count();
-
-    // Aggregate each metric
-    if cpu_cycles_count > 0 {
-        let sum: u64 = metrics_with_data.iter().filter_map(|m| m.cpu_cycles).sum();
-        aggregated.cpu_cycles = Some(sum / cpu_cycles_count as u64);
+        .flat_map(|m| m.raw.keys())
+        .collect();
+    for label in raw_labels {
+        let values: Vec<u64> = metrics_with_data
+            .iter()
+            .filter_map(|m| m.raw.get(label).copied())
+            .collect();
+        aggregated.raw.insert(
+            label.clone(),
+            values.iter().sum::<u64>() / values.len() as u64,
+        );
     }
-    if instructions_count > 0 {
-        let sum: u64 = metrics_with_data
+
+    // Average the derived ratios over the runs; the ratio of the averaged counters would be
+    // dominated by the runs with the largest counts
+    for ratio in DerivedRatio::all() {
+        let values: Vec<f64> = metrics_with_data
             .iter()
-            .filter_map(|m| m.instructions)
-            .sum();
-        aggregated.instructions = Some(sum / instructions_count as u64);
+            .filter_map(|m| m.ratio(ratio))
+            .collect();
+        if !values.is_empty() {
+            aggregated.mean_ratios.insert(ratio, mean(&values));
+        }
+    }
+
+    // Keep the worst scaling factor so that unreliable runs are not hidden by the average
+    aggregated.scaling_factor = metrics_with_data
+        .iter()
+        .filter_map(|m| m.scaling_factor)
+        .reduce(f64::max);
+
+    aggregated.permission_denied = metrics_with_data.iter().any(|m| m.permission_denied);
+
+    // Counters can fail for individual runs, e.g. if the process exited before they could be
+    // attached. Only those that have not been collected in any run are unavailable.
+    for (name, reason) in metrics_with_data.iter().flat_map(|m| &m.unavailable) {
+        let collected = aggregated.raw.contains_key(name)
+            || MetricType::all()
+                .into_iter()
+                .any(|metric| metric.cli_name() == name && aggregated.get(metric).is_some());
+        if !collected {
+            aggregated
+                .unavailable
+                .entry(name.clone())
+                .or_insert_with(|| reason.clone());
+        }
     }
-    if cache_refs_count > 0 {
-        let sum: u64 = metrics_with_data
+
+    Some(aggregated)
+}
+
+/// Standard deviation of each poop metric over the runs in which it has been collected. Metrics
+/// that have been collected in less than two runs are left out.
+fn poop_metrics_stddev(timing_results: &[TimingResult]) -> Option<PoopMetricRates> {
+    let mut stddev = PoopMetricRates::default();
+    let mut has_data = false;
+
+    for metric in MetricType::all() {
+        let values: Vec<f64> = timing_results
             .iter()
-            .filter_map(|m| m.cache_references)
-            .sum();
-        aggregated.cache_references = Some(sum / cache_refs_count as u64);
+            .filter_map(|tr| tr.poop_metrics.as_ref().and_then(|m| m.get(metric)))
+            .map(|value| value as f64)
+            .collect();
+        if values.len() > 1 {
+            stddev.set(metric, standard_deviation(&values, None));
+            has_data = true;
+        }
     }
-    if cache_misses_count > 0 {
-        let sum: u64 = metrics_with_data
+    let raw_labels: BTreeSet<&String> = timing_results
+        .iter()
+        .filter_map(|tr| tr.poop_metrics.as_ref())
+        .flat_map(|m| m.raw.keys())
+        .collect();
+    for label in raw_labels {
+        let values: Vec<f64> = timing_results
             .iter()
-            .filter_map(|m| m.cache_misses)
-            .sum();
-        aggregated.cache_misses = Some(sum / cache_misses_count as u64);
+            .filter_map(|tr| tr.poop_metrics.as_ref().and_then(|m| m.raw.get(label)))
+            .map(|&value| value as f64)
+            .collect();
+        if values.len() > 1 {
+            stddev
+                .raw
+                .insert(label.clone(), standard_deviation(&values, None));
+            has_data = true;
+        }
     }
-    if branches_count > 0 {
-        let sum: u64 = metrics_with_data.iter().filter_map(|m| m.branches).sum();
-        aggregated.branches = Some(sum / branches_count as u64);
+
+    has_data.then_some(stddev)
+}
+
+/// Indices of the runs that remain after removing statistical outliers, or `None` if no
+/// runs are removed. Outliers are only removed if at least two runs remain.
+fn runs_without_outliers(times: &[Second]) -> Option<Vec<usize>> {
+    if times.is_empty() {
+        return None;
     }
-    if branch_misses_count > 0 {
-        let sum: u64 = metrics_with_data
-            .iter()
-            .filter_map(|m| m.branch_misses)
-            .sum();
-        aggregated.branch_misses = Some(sum / branch_misses_count as u64);
+
+    let scores = modified_zscores(times);
+    let kept: Vec<usize> = (0..times.len())
+        .filter(|&i| scores[i].abs() <= OUTLIER_THRESHOLD)
+        .collect();
+
+    (kept.len() < times.len() && kept.len() >= 2).then_some(kept)
+}
+
+/// The values of the given runs, or all values if `runs` is `None`
+fn retain_runs<'a, T: Clone>(values: &'a [T], runs: Option<&[usize]>) -> Cow<'a, [T]> {
+    match runs {
+        Some(runs) => Cow::Owned(runs.iter().map(|&i| values[i].clone()).collect()),
+        None => Cow::Borrowed(values),
+    }
+}
+
+/// Raw data gathered during the measurement phase of a benchmark
+struct Measurements {
+    times_real: Vec<Second>,
+    times_user: Vec<Second>,
+    times_system: Vec<Second>,
+    memory_usage_byte: Vec<u64>,
+    exit_codes: Vec<Option<i32>>,
+    timing_results: Vec<TimingResult>,
+    weights: Vec<Scalar>,
+    preparation_times: Vec<Second>,
+    conclusion_times: Vec<Second>,
+    start_times: Vec<Instant>,
+    all_succeeded: bool,
+}
+
+impl Default for Measurements {
+    fn default() -> Self {
+        Measurements {
+            times_real: vec![],
+            times_user: vec![],
+            times_system: vec![],
+            memory_usage_byte: vec![],
+            exit_codes: vec![],
+            timing_results: vec![],
+            weights: vec![],
+            preparation_times: vec![],
+            conclusion_times: vec![],
+            start_times: vec![],
+            all_succeeded: true,
+        }
     }
-    if page_faults_count > 0 {
-        let sum: u64 = metrics_with_data.iter().filter_map(|m| m.page_faults).sum();
-        aggregated.page_faults = Some(sum / page_faults_count as u64);
+}
+
+impl Measurements {
+    fn push(&mut self, res: TimingResult, status: ExitStatus) {
+        self.times_real.push(res.time_real);
+        self.times_user.push(res.time_user);
+        self.times_system.push(res.time_system);
+        self.memory_usage_byte.push(res.memory_usage_byte);
+        self.exit_codes.push(extract_exit_code(status));
+        self.timing_results.push(res);
+
+        self.all_succeeded = self.all_succeeded && status.success();
     }
 
-    Some(aggregated)
+    fn relative_stddev(&self) -> Option<Scalar> {
+        relative_stddev(&self.times_real)
+    }
+}
+
+/// A single benchmark run, as written to the `--export-jsonl` file
+#[derive(Serialize)]
+struct StreamedRun<'a> {
+    benchmark: usize,
+    command: &'a str,
+    run: usize,
+    time_real: Second,
+    time_user: Second,
+    time_system: Second,
+    memory_usage_byte: u64,
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    poop_metrics: Option<&'a PoopMetrics>,
+}
+
+/// Standard deviation of the given times, relative to their mean. Not available for less than
+/// two runs or a mean of zero.
+fn relative_stddev(times: &[Second]) -> Option<Scalar> {
+    if times.len() < 2 {
+        return None;
+    }
+    let t_mean = mean(times);
+    if t_mean == 0.0 {
+        return None;
+    }
+    Some(standard_deviation(times, Some(t_mean)) / t_mean)
 }
 
+/// Half-width of the 95% confidence interval of the mean, relative to the mean. This uses
+/// the normal approximation, i.e. 1.96 standard errors. Not available for less than two runs.
+fn relative_margin_of_error(times: &[Second]) -> Option<Scalar> {
+    relative_stddev(times).map(|r| 1.96 * r / (times.len() as f64).sqrt())
+}
+
+/// Describe the current perf_event_paranoid setting, which is the most common reason for
+/// missing metrics
+fn perf_event_paranoid_hint() -> String {
+    match fs::read_to_string("/proc/sys/kernel/perf_event_paranoid") {
+        Ok(level) => format!(
+            " The current value of /proc/sys/kernel/perf_event_paranoid is {}. Lower values \
+             allow unprivileged users to collect more metrics (see 'man perf_event_open'). \
+             Note that hardware counters are often not available in virtual machines.",
+            level.trim()
+        ),
+        Err(_) => "".into(),
+    }
+}
+
+/// Relative standard deviation below which CPU cycle counts are considered stable
+const STABLE_CYCLES_THRESHOLD: Scalar = 0.02;
+
+/// Relative standard deviation above which run times are considered unstable
+const UNSTABLE_TIMES_THRESHOLD: Scalar = 0.05;
+
+/// Minimum ratio between the relative standard deviations of times and cycles
+const EXTERNAL_VARIANCE_RATIO: Scalar = 5.0;
+
+/// Scaling factor of multiplexed perf counters above which the values are considered unreliable
+const MAX_COUNTER_SCALING_FACTOR: Scalar = 2.0;
+
+/// Mean number of CPU migrations per run above which the measurements are considered noisy
+const MAX_CPU_MIGRATIONS_PER_RUN: u64 = 2;
+
+/// Ratio of the mean preparation time to the mean run time above which the preparation
+/// command is considered to dominate the benchmark
+const MAX_PREPARATION_OVERHEAD_RATIO: Scalar = 0.5;
+
+/// Significance level of the rank correlation between run index and run time below which the
+/// run times are considered to drift
+const RUN_TIME_TREND_SIGNIFICANCE: Scalar = 0.001;
+
+/// Number of runs below which the statistics of a benchmark are considered unreliable
+pub const MIN_RUNS_FOR_STATISTICS: usize = 3;
+
+/// Fraction of the fastest and of the slowest runs that are excluded from the trimmed mean
+const TRIMMED_MEAN_FRACTION: Scalar = 0.1;
+
+/// Minimum number of runs for which the (tail) percentiles are shown in the console output
+const MIN_RUNS_FOR_PERCENTILES: usize = 20;
+
 /// Threshold for warning about fast execution time
 pub const MIN_EXECUTION_TIME: Second = 5e-3;
 
@@ -125,6 +330,8 @@ pub struct Benchmark<'a> {
     command: &'a Command<'a>,
     options: &'a Options,
     executor: &'a dyn Executor,
+    measures_overhead: bool,
+    session: SessionProgress,
 }
 
 impl<'a> Benchmark<'a> {
@@ -139,9 +346,23 @@ impl<'a> Benchmark<'a> {
             command,
             options,
             executor,
+            measures_overhead: false,
+            session: SessionProgress::default(),
         }
     }
 
+    /// Mark this as a benchmark of the startup overhead (see `--measure-overhead`)
+    pub fn measuring_overhead(mut self) -> Self {
+        self.measures_overhead = true;
+        self
+    }
+
+    /// Set the progress of the session, to show its remaining time in the progress bar
+    pub fn with_session_progress(mut self, session: SessionProgress) -> Self {
+        self.session = session;
+        self
+    }
+
     /// Run setup, cleanup, or preparation commands
     fn run_intermediate_command(
         &self,
@@ -149,6 +370,14 @@ impl<'a> Benchmark<'a> {
         error_output: &'static str,
         output_policy: &CommandOutputPolicy,
     ) -> Result<TimingResult> {
+        // Capture the output which would otherwise be discarded, to show it in case of errors
+        let output_policy = match output_policy {
+            CommandOutputPolicy::Null => {
+                &CommandOutputPolicy::Capture(self.options.output_capture_limit)
+            }
+            policy => policy,
+        };
+
         self.executor
             .run_command_and_measure(
                 command,
@@ -157,7 +386,28 @@ impl<'a> Benchmark<'a> {
                 output_policy,
             )
             .map(|r| r.0)
-            .map_err(|_| anyhow!(error_output))
+            .map_err(|e| match e.downcast::<CommandFailedError>() {
+                // Keep the error type, such that the failure is reflected in the exit status
+                Ok(failure) => CommandFailedError {
+                    message: match failure.output.filter(|output| !output.bytes.is_empty()) {
+                        Some(output) => {
+                            if output.bytes_truncated > 0 {
+                                let warning = Warnings::OutputTruncated(
+                                    output.bytes_truncated,
+                                    self.options.output_capture_limit,
+                                );
+                                eprintln!("{}: {}", "Warning".yellow(), warning);
+                            }
+
+                            format!("{error_output}\n\nOutput of the command:\n{output}")
+                        }
+                        None => error_output.to_string(),
+                    },
+                    output: None,
+                }
+                .into(),
+                Err(_) => anyhow!(error_output),
+            })
     }
 
     /// Run the command specified by `--setup`.
@@ -226,28 +476,168 @@ impl<'a> Benchmark<'a> {
         self.run_intermediate_command(command, error_output, output_policy)
     }
 
-    /// Run the benchmark for a single command
-    pub fn run(&self) -> Result<BenchmarkResult> {
-        if self.options.output_style != OutputStyleOption::Disabled {
-            println!(
-                "{}{}: {}",
-                "Benchmark ".bold(),
-                (self.number + 1).to_string().bold(),
-                self.command.get_name_with_unused_parameters(),
-            );
+    /// Determine all warnings that apply to the given benchmark measurements
+    fn collect_warnings(
+        &self,
+        times_real: &[Second],
+        all_succeeded: bool,
+        timed_out_runs: usize,
+        poop_metrics: Option<&PoopMetrics>,
+        cpu_cycles: &[u64],
+        prepare_mean: Option<Second>,
+    ) -> Vec<Warnings> {
+        let mut warnings = vec![];
+
+        // Check execution time
+        if matches!(self.options.executor_kind, ExecutorKind::Shell(_))
+            && !self.measures_overhead
+            && times_real.iter().any(|&t| t < MIN_EXECUTION_TIME)
+        {
+            warnings.push(Warnings::FastExecutionTime);
         }
 
-        let mut times_real: Vec<Second> = vec![];
-        let mut times_user: Vec<Second> = vec![];
-        let mut times_system: Vec<Second> = vec![];
-        let mut memory_usage_byte: Vec<u64> = vec![];
-        let mut exit_codes: Vec<Option<i32>> = vec![];
-        let mut timing_results: Vec<TimingResult> = vec![];
-        let mut all_succeeded = true;
+        if times_real.len() < MIN_RUNS_FOR_STATISTICS {
+            warnings.push(Warnings::TooFewRuns(times_real.len()));
+        }
 
-        let output_policy = &self.options.command_output_policies[self.number];
+        // Check program exit codes
+        if !all_succeeded {
+            warnings.push(Warnings::NonZeroExitCode);
+        }
 
-        let preparation_command = self.options.preparation_command.as_ref().map(|values| {
+        if let Some(precision) = self.options.precision {
+            if let Some(margin) = relative_margin_of_error(times_real).filter(|&m| m >= precision) {
+                warnings.push(Warnings::PrecisionNotReached(
+                    margin,
+                    precision,
+                    times_real.len(),
+                ));
+            }
+        }
+
+        if let Some(timeout) = self.options.timeout.filter(|_| timed_out_runs > 0) {
+            warnings.push(Warnings::TimedOut(
+                timed_out_runs,
+                timeout,
+                self.options.time_unit,
+            ));
+        }
+
+        // Check whether the preparation command takes a large part of the benchmarking time
+        let t_mean = mean(times_real);
+        if let Some(ratio) = prepare_mean
+            .filter(|_| t_mean > 0.0)
+            .map(|prepare_mean| prepare_mean / t_mean)
+            .filter(|&r| r > MAX_PREPARATION_OVERHEAD_RATIO)
+        {
+            warnings.push(Warnings::PreparationOverhead(ratio));
+        }
+
+        // Check whether the run times drift over the course of the benchmark
+        if let Some(correlation) = trend_correlation(times_real)
+            .filter(|&c| trend_p_value(c, times_real.len()) < RUN_TIME_TREND_SIGNIFICANCE)
+        {
+            warnings.push(Warnings::RunTimeTrend(correlation));
+        }
+
+        if let Some((lower, upper)) = bimodal_cluster_means(times_real) {
+            warnings.push(Warnings::BimodalDistribution(
+                lower,
+                upper,
+                self.options.time_unit,
+            ));
+        }
+
+        // Run outlier detection
+        let scores = modified_zscores(times_real);
+
+        let outlier_warning_options = OutlierWarningOptions {
+            warmup_in_use: self.options.warmup_count > 0 || self.options.warmup_time.is_some(),
+            prepare_in_use: self
+                .options
+                .preparation_command
+                .as_ref()
+                .map(|v| v.len())
+                .unwrap_or(0)
+                > 0,
+        };
+
+        if scores[0] > OUTLIER_THRESHOLD {
+            warnings.push(Warnings::SlowInitialRun(
+                times_real[0],
+                self.options.time_unit,
+                outlier_warning_options,
+            ));
+        } else if scores.iter().any(|&s| s.abs() > OUTLIER_THRESHOLD)
+            && !(self.options.drop_outliers && runs_without_outliers(times_real).is_some())
+        {
+            // There is no need to warn about outliers that have been removed already
+            warnings.push(Warnings::OutliersDetected(outlier_warning_options));
+        }
+
+        // Warn if poop metrics were requested but not collected
+        if self.options.poop_metrics_enabled && poop_metrics.is_none_or(|m| !m.has_data()) {
+            warnings.push(Warnings::PoopMetricsUnavailable(
+                poop_metrics.is_some_and(|m| m.permission_denied),
+            ));
+        }
+
+        // Without an explicit list, all metrics are tried, and some of them are usually not
+        // supported by the CPU. Only the explicitly requested ones are reported.
+        if let Some(metrics) = poop_metrics.filter(|m| {
+            self.options.poop_metrics_enabled
+                && !self.options.collects_all_metrics()
+                && !m.unavailable.is_empty()
+        }) {
+            warnings.push(Warnings::UnavailableMetrics(metrics.unavailable.clone()));
+        }
+
+        if let Some(factor) = poop_metrics
+            .and_then(|m| m.scaling_factor)
+            .filter(|&f| f > MAX_COUNTER_SCALING_FACTOR)
+        {
+            warnings.push(Warnings::MultiplexedCounters(factor));
+        }
+
+        if let Some(migrations) = poop_metrics
+            .and_then(|m| m.cpu_migrations)
+            .filter(|&n| n > MAX_CPU_MIGRATIONS_PER_RUN)
+        {
+            warnings.push(Warnings::FrequentCpuMigrations(migrations));
+        }
+
+        // Check whether the variance of the times is caused by something else than the CPU work
+        if self.options.external_variance_warning && cpu_cycles.len() == times_real.len() {
+            let cycles: Vec<Scalar> = cpu_cycles.iter().map(|&c| c as Scalar).collect();
+            if let (Some(cv_cycles), Some(cv_times)) =
+                (relative_stddev(&cycles), relative_stddev(times_real))
+            {
+                if cv_cycles < STABLE_CYCLES_THRESHOLD
+                    && cv_times > UNSTABLE_TIMES_THRESHOLD
+                    && cv_times > EXTERNAL_VARIANCE_RATIO * cv_cycles
+                {
+                    warnings.push(Warnings::ExternalVariance(cv_cycles, cv_times));
+                }
+            }
+        }
+
+        // Check whether the variance target of `--retry-on-variance` has been met
+        if let Some(target) = self.options.max_relative_stddev {
+            if let Some(r) = relative_stddev(times_real).filter(|&r| r > target) {
+                warnings.push(Warnings::VarianceTargetNotMet(
+                    r,
+                    target,
+                    self.options.max_attempts,
+                ));
+            }
+        }
+
+        warnings
+    }
+
+    /// The command specified by `--prepare` for this benchmark, if any
+    fn preparation_command(&self) -> Option<Command<'a>> {
+        self.options.preparation_command.as_ref().map(|values| {
             let preparation_command = if values.len() == 1 {
                 &values[0]
             } else {
@@ -258,16 +648,12 @@ impl<'a> Benchmark<'a> {
                 preparation_command,
                 self.command.get_parameters().iter().cloned(),
             )
-        });
-
-        let run_preparation_command = || {
-            preparation_command
-                .as_ref()
-                .map(|cmd| self.run_preparation_command(cmd, output_policy))
-                .transpose()
-        };
+        })
+    }
 
-        let conclusion_command = self.options.conclusion_command.as_ref().map(|values| {
+    /// The command specified by `--conclude` for this benchmark, if any
+    fn conclusion_command(&self) -> Option<Command<'a>> {
+        self.options.conclusion_command.as_ref().map(|values| {
             let conclusion_command = if values.len() == 1 {
                 &values[0]
             } else {
@@ -278,103 +664,287 @@ impl<'a> Benchmark<'a> {
                 conclusion_command,
                 self.command.get_parameters().iter().cloned(),
             )
-        });
-        let run_conclusion_command = || {
-            conclusion_command
-                .as_ref()
-                .map(|cmd| self.run_conclusion_command(cmd, output_policy))
-                .transpose()
+        })
+    }
+
+    /// The size of the benchmarked binary, if requested via `--binary-size`
+    fn binary_size(&self) -> Result<Option<u64>> {
+        if !self.options.binary_size {
+            return Ok(None);
+        }
+
+        let path = match self.options.binary_paths.as_slice() {
+            [] => self.command.resolve_binary_path()?,
+            [path] => path.clone(),
+            paths => paths[self.number].clone(),
         };
 
-        self.run_setup_command(self.command.get_parameters().iter().cloned(), output_policy)?;
+        let metadata = fs::metadata(&path)
+            .with_context(|| format!("Could not determine the size of '{}'", path.display()))?;
+        Ok(Some(metadata.len()))
+    }
 
-        // Warmup phase
-        if self.options.warmup_count > 0 {
-            let progress_bar = if self.options.output_style != OutputStyleOption::Disabled {
-                Some(get_progress_bar(
-                    self.options.warmup_count,
-                    "Performing warmup runs",
-                    self.options.output_style,
-                ))
-            } else {
-                None
-            };
+    /// Read the weight of the last run from the file given by `--weight-file`, if any
+    fn read_run_weight(&self, measurements: &mut Measurements) -> Result<()> {
+        let Some(path) = self.options.weight_file.as_ref() else {
+            return Ok(());
+        };
 
-            for i in 0..self.options.warmup_count {
-                let _ = run_preparation_command()?;
-                let _ = self.executor.run_command_and_measure(
-                    self.command,
-                    BenchmarkIteration::Warmup(i),
-                    None,
-                    output_policy,
-                )?;
-                let _ = run_conclusion_command()?;
-                if let Some(bar) = progress_bar.as_ref() {
-                    bar.inc(1)
-                }
-            }
-            if let Some(bar) = progress_bar.as_ref() {
-                bar.finish_and_clear()
-            }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read run weight from '{}'", path.display()))?;
+        let weight = content
+            .trim()
+            .parse::<Scalar>()
+            .ok()
+            .filter(|w| w.is_finite() && *w >= 0.0)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Expected a non-negative number as run weight in '{}', found '{}'",
+                    path.display(),
+                    content.trim()
+                )
+            })?;
+
+        measurements.weights.push(weight);
+        Ok(())
+    }
+
+    /// Write a progress event to stderr if '--progress-json' has been given. The startup
+    /// overhead benchmark does not report any progress.
+    fn report_progress(&self, event: ProgressEvent<'_>) {
+        if self.options.progress_json && !self.measures_overhead {
+            event.emit();
+        }
+    }
+
+    /// Append the most recent run to the `--export-jsonl` file. The file is opened for each run,
+    /// such that every line is on disk before the next run starts.
+    fn stream_run(&self, measurements: &Measurements) -> Result<()> {
+        let Some(path) = self
+            .options
+            .jsonl_file
+            .as_ref()
+            .filter(|_| !self.measures_overhead)
+        else {
+            return Ok(());
+        };
+        let (Some(res), Some(&exit_code)) = (
+            measurements.timing_results.last(),
+            measurements.exit_codes.last(),
+        ) else {
+            return Ok(());
+        };
+
+        let mut line = serde_json::to_vec(&StreamedRun {
+            benchmark: self.number,
+            command: &self.command.get_name(),
+            run: measurements.timing_results.len() - 1,
+            time_real: res.time_real,
+            time_user: res.time_user,
+            time_system: res.time_system,
+            memory_usage_byte: res.memory_usage_byte,
+            exit_code,
+            poop_metrics: res.poop_metrics.as_ref(),
+        })?;
+        line.push(b'\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(&line))
+            .with_context(|| format!("Could not write run to '{}'", path.display()))
+    }
+
+    /// With `--require-metrics`, fail as soon as a run without any performance metrics has
+    /// been measured, instead of finishing the benchmark
+    fn check_required_metrics(&self, res: &TimingResult, run: usize) -> Result<()> {
+        if self.options.require_metrics
+            && self.options.poop_metrics_enabled
+            && res.poop_metrics.as_ref().is_none_or(|m| !m.has_data())
+        {
+            bail!(
+                "Performance metrics could not be collected for '{}' in benchmark run {}, but \
+                 '--require-metrics' has been given.{}",
+                self.command.get_name(),
+                run + 1,
+                perf_event_paranoid_hint()
+            );
+        }
+        Ok(())
+    }
+
+    /// Perform a single timing run, surrounded by the `--prepare` and `--conclude` commands (if
+    /// any), and record it. Returns the time that was spent on the latter two.
+    fn measure_once(
+        &self,
+        measurements: &mut Measurements,
+        preparation_command: Option<&Command<'_>>,
+        conclusion_command: Option<&Command<'_>>,
+        output_policy: &CommandOutputPolicy,
+    ) -> Result<Second> {
+        let overhead_of = |res: TimingResult| res.time_real + self.executor.time_overhead();
+
+        let preparation_result = preparation_command
+            .map(|cmd| self.run_preparation_command(cmd, output_policy))
+            .transpose()?;
+        if let Some(res) = &preparation_result {
+            measurements.preparation_times.push(res.time_real);
+        }
+
+        if self.options.record_start_times {
+            measurements.start_times.push(Instant::now());
+        }
+        let (res, status) = self.executor.run_command_and_measure(
+            self.command,
+            BenchmarkIteration::Benchmark(measurements.times_real.len() as u64),
+            None,
+            output_policy,
+        )?;
+        self.check_required_metrics(&res, measurements.times_real.len())?;
+        measurements.push(res, status);
+        self.read_run_weight(measurements)?;
+        self.stream_run(measurements)?;
+        self.report_progress(ProgressEvent::Measure {
+            benchmark: self.number,
+            run: measurements.times_real.len() - 1,
+        });
+
+        let conclusion_result = conclusion_command
+            .map(|cmd| self.run_conclusion_command(cmd, output_policy))
+            .transpose()?;
+        if let Some(res) = &conclusion_result {
+            measurements.conclusion_times.push(res.time_real);
         }
 
+        Ok(
+            preparation_result.map_or(0.0, overhead_of)
+                + conclusion_result.map_or(0.0, overhead_of),
+        )
+    }
+
+    /// Estimate the number of runs that fit into the minimum benchmarking time, given the
+    /// duration of the first run and the time spent on preparation/conclusion commands
+    fn estimated_runs(&self, first_run: Second, overhead: Second) -> u64 {
+        (self.options.min_benchmarking_time
+            / (first_run + self.executor.time_overhead() + overhead)) as u64
+    }
+
+    /// Bounds for the number of runs: an exact number of runs if one of the parameter values
+    /// of the command matches a `--runs-for` override, the global bounds otherwise
+    fn run_bounds(&self) -> RunBounds {
+        let parameters = self.command.get_parameters();
+        self.options
+            .run_count_overrides
+            .iter()
+            .find(|o| {
+                parameters
+                    .iter()
+                    .any(|(name, value)| *name == o.parameter && value.to_string() == o.value)
+            })
+            .map_or_else(
+                || self.options.run_bounds.clone(),
+                |o| RunBounds::exact(o.runs),
+            )
+    }
+
+    /// Limit an estimated number of runs to the bounds given by `--min-runs`/`--max-runs`
+    /// (or `--runs-for`). An exact number of runs is used as is.
+    fn bounded_run_count(&self, estimated_runs: u64) -> u64 {
+        let run_bounds = self.run_bounds();
+        if let Some(runs) = run_bounds.exact_count() {
+            return runs;
+        }
+        let min = cmp::max(estimated_runs, run_bounds.min);
+
+        run_bounds
+            .max
+            .as_ref()
+            .map(|max| cmp::min(min, *max))
+            .unwrap_or(min)
+    }
+
+    /// Perform the actual measurement phase: an initial timing run which is used to determine
+    /// the number of runs, followed by the remaining timing runs.
+    fn measure(
+        &self,
+        preparation_command: Option<&Command<'_>>,
+        conclusion_command: Option<&Command<'_>>,
+        output_policy: &CommandOutputPolicy,
+    ) -> Result<Measurements> {
+        let mut measurements = Measurements::default();
+        let run_bounds = self.run_bounds();
+
         // Set up progress bar (and spinner for initial measurement)
-        let progress_bar = if self.options.output_style != OutputStyleOption::Disabled {
+        let progress_bar = if self.options.show_progress_bar() {
             Some(get_progress_bar(
-                self.options.run_bounds.min,
+                run_bounds.min,
                 "Initial time measurement",
                 self.options.output_style,
+                self.options.progress_update_interval,
             ))
         } else {
             None
         };
 
-        let preparation_result = run_preparation_command()?;
-        let preparation_overhead =
-            preparation_result.map_or(0.0, |res| res.time_real + self.executor.time_overhead());
-
         // Initial timing run
-        let (res, status) = self.executor.run_command_and_measure(
-            self.command,
-            BenchmarkIteration::Benchmark(0),
-            None,
+        let overhead = self.measure_once(
+            &mut measurements,
+            preparation_command,
+            conclusion_command,
             output_policy,
         )?;
-        let success = status.success();
 
-        let conclusion_result = run_conclusion_command()?;
-        let conclusion_overhead =
-            conclusion_result.map_or(0.0, |res| res.time_real + self.executor.time_overhead());
+        let instructions_of = |measurements: &Measurements| {
+            measurements
+                .timing_results
+                .last()
+                .and_then(|res| res.poop_metrics.as_ref())
+                .and_then(|metrics| metrics.instructions)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The '--target-instructions' option requires the 'instructions' \
+                         counter, but it could not be collected for '{}'.{}",
+                        self.command.get_name(),
+                        perf_event_paranoid_hint()
+                    )
+                })
+        };
+        let mut total_instructions = 0;
 
         // Determine number of benchmark runs
-        let runs_in_min_time = (self.options.min_benchmarking_time
-            / (res.time_real
-                + self.executor.time_overhead()
-                + preparation_overhead
-                + conclusion_overhead)) as u64;
-
-        let count = {
-            let min = cmp::max(runs_in_min_time, self.options.run_bounds.min);
-
-            self.options
-                .run_bounds
-                .max
-                .as_ref()
-                .map(|max| cmp::min(min, *max))
-                .unwrap_or(min)
+        let estimated_runs = if let Some(target) = self.options.target_instructions {
+            total_instructions = instructions_of(&measurements)?;
+            target.div_ceil(cmp::max(total_instructions, 1))
+        } else if self.options.precision.is_some() || run_bounds.exact_count().is_some() {
+            // The number of runs depends on the variance, which is not known yet (or it is
+            // fixed, such that the run time does not matter)
+            0
+        } else {
+            self.estimated_runs(measurements.times_real[0], overhead)
         };
 
-        let count_remaining = count - 1;
-
-        // Save the first result
-        times_real.push(res.time_real);
-        times_user.push(res.time_user);
-        times_system.push(res.time_system);
-        memory_usage_byte.push(res.memory_usage_byte);
-        exit_codes.push(extract_exit_code(status));
-        timing_results.push(res);
-
-        all_succeeded = all_succeeded && success;
+        let count = self.bounded_run_count(estimated_runs);
+
+        // With a target instruction count or precision, the estimate from the first run only
+        // determines the length of the progress bar. We keep going until the target has actually
+        // been reached.
+        let below_max_runs = |num_runs: u64| run_bounds.max.is_none_or(|max| num_runs < max);
+        let needs_more_runs = |num_runs: u64, total_instructions: u64, times: &[Second]| match (
+            self.options.target_instructions,
+            self.options.precision,
+        ) {
+            (Some(target), _) => {
+                (total_instructions < target || num_runs < run_bounds.min)
+                    && below_max_runs(num_runs)
+            }
+            (None, Some(precision)) => {
+                (num_runs < run_bounds.min
+                    || relative_margin_of_error(times).is_none_or(|m| m >= precision))
+                    && below_max_runs(num_runs)
+            }
+            (None, None) => num_runs < count,
+        };
 
         // Re-configure the progress bar
         if let Some(bar) = progress_bar.as_ref() {
@@ -385,46 +955,253 @@ impl<'a> Benchmark<'a> {
         }
 
         // Gather statistics (perform the actual benchmark)
-        for i in 0..count_remaining {
-            run_preparation_command()?;
+        let mut num_runs = 1;
+        while needs_more_runs(num_runs, total_instructions, &measurements.times_real) {
+            if num_runs >= count {
+                if let Some(bar) = progress_bar.as_ref() {
+                    bar.inc_length(1)
+                }
+            }
 
             let msg = {
-                let mean = format_duration(mean(&times_real), self.options.time_unit);
+                let mean = format_duration(mean(&measurements.times_real), self.options.time_unit);
                 format!("Current estimate: {}", mean.to_string().green())
             };
 
             if let Some(bar) = progress_bar.as_ref() {
-                bar.set_message(msg.to_owned())
+                bar.set_message(msg.to_owned());
+
+                if self.session.remaining_benchmarks > 0 {
+                    let per_run =
+                        mean(&measurements.times_real) + self.executor.time_overhead() + overhead;
+                    let runs = cmp::max(count, num_runs + 1);
+                    bar.set_session_eta(self.session.remaining_time(
+                        Duration::from_secs_f64(per_run * (runs - num_runs) as f64),
+                        Duration::from_secs_f64(per_run * runs as f64),
+                    ));
+                }
             }
 
-            let (res, status) = self.executor.run_command_and_measure(
-                self.command,
-                BenchmarkIteration::Benchmark(i + 1),
-                None,
+            self.measure_once(
+                &mut measurements,
+                preparation_command,
+                conclusion_command,
                 output_policy,
             )?;
-            let success = status.success();
-
-            times_real.push(res.time_real);
-            times_user.push(res.time_user);
-            times_system.push(res.time_system);
-            memory_usage_byte.push(res.memory_usage_byte);
-            exit_codes.push(extract_exit_code(status));
-            timing_results.push(res);
 
-            all_succeeded = all_succeeded && success;
+            if self.options.target_instructions.is_some() {
+                total_instructions += instructions_of(&measurements)?;
+            }
 
             if let Some(bar) = progress_bar.as_ref() {
                 bar.inc(1)
             }
 
-            run_conclusion_command()?;
+            num_runs += 1;
         }
 
         if let Some(bar) = progress_bar.as_ref() {
             bar.finish_and_clear()
         }
 
+        Ok(measurements)
+    }
+
+    /// Print the header line that introduces the results of this benchmark
+    fn print_header(&self) {
+        if self.options.output_style == OutputStyleOption::Disabled || self.options.quiet {
+            return;
+        }
+
+        if self.measures_overhead {
+            println!(
+                "{}: {}",
+                "Startup overhead".bold(),
+                self.command.get_name_with_unused_parameters(),
+            );
+        } else {
+            println!(
+                "{}{}: {}",
+                "Benchmark ".bold(),
+                (self.number + 1).to_string().bold(),
+                self.command.get_name_with_unused_parameters(),
+            );
+        }
+    }
+
+    /// Whether the warmup phase is complete after the given number of runs, which took
+    /// `elapsed` seconds in total
+    fn warmup_done(&self, runs: u64, elapsed: Second) -> bool {
+        let count = self.options.warmup_count;
+        (count > 0 && runs >= count) || self.options.warmup_time.is_some_and(|t| elapsed >= t)
+    }
+
+    /// Run the `--setup` command, followed by the warmup runs
+    fn setup_and_warmup(&self) -> Result<()> {
+        let output_policy = &self.options.command_output_policies[self.number];
+        let preparation_command = self.preparation_command();
+        let conclusion_command = self.conclusion_command();
+
+        self.run_setup_command(self.command.get_parameters().iter().cloned(), output_policy)?;
+
+        let warmup_count = self.options.warmup_count;
+        if warmup_count > 0 || self.options.warmup_time.is_some() {
+            let progress_bar = if self.options.show_progress_bar() {
+                Some(get_progress_bar(
+                    warmup_count,
+                    "Performing warmup runs",
+                    self.options.output_style,
+                    self.options.progress_update_interval,
+                ))
+            } else {
+                None
+            };
+
+            let mut i = 0;
+            let mut elapsed = 0.0;
+            while !self.warmup_done(i, elapsed) {
+                if let Some(cmd) = preparation_command.as_ref() {
+                    self.run_preparation_command(cmd, output_policy)?;
+                }
+                let (res, _) = self.executor.run_command_and_measure(
+                    self.command,
+                    BenchmarkIteration::Warmup(i),
+                    None,
+                    output_policy,
+                )?;
+                if let Some(cmd) = conclusion_command.as_ref() {
+                    self.run_conclusion_command(cmd, output_policy)?;
+                }
+
+                let run_time = res.time_real + self.executor.time_overhead();
+                if i == 0 {
+                    if let (Some(bar), Some(warmup_time)) =
+                        (progress_bar.as_ref(), self.options.warmup_time)
+                    {
+                        // Estimate the number of warmup runs that fit into the time budget
+                        let estimated = (warmup_time / run_time).ceil() as u64;
+                        bar.set_length(match warmup_count {
+                            0 => estimated,
+                            count => cmp::min(estimated, count),
+                        });
+                    }
+                }
+                elapsed += run_time;
+                self.report_progress(ProgressEvent::Warmup {
+                    benchmark: self.number,
+                    run: i,
+                });
+                i += 1;
+
+                if let Some(bar) = progress_bar.as_ref() {
+                    bar.inc(1)
+                }
+            }
+            if let Some(bar) = progress_bar.as_ref() {
+                bar.finish_and_clear()
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the benchmark for a single command
+    pub fn run(&self) -> Result<BenchmarkResult> {
+        self.print_header();
+
+        let output_policy = &self.options.command_output_policies[self.number];
+
+        let binary_size_byte = self.binary_size()?;
+
+        let preparation_command = self.preparation_command();
+        let conclusion_command = self.conclusion_command();
+
+        self.setup_and_warmup()?;
+
+        // Measurement phase. If a variance target has been given, the full measurement
+        // phase is repeated until the target is met, and the least noisy attempt is kept.
+        let mut attempts = 0;
+        let mut best: Option<Measurements> = None;
+        loop {
+            attempts += 1;
+            let measurements = self.measure(
+                preparation_command.as_ref(),
+                conclusion_command.as_ref(),
+                output_policy,
+            )?;
+
+            let is_better = best.as_ref().is_none_or(|b| {
+                measurements.relative_stddev().unwrap_or(0.0) < b.relative_stddev().unwrap_or(0.0)
+            });
+            if is_better {
+                best = Some(measurements);
+            }
+
+            let target_met = self.options.max_relative_stddev.is_none_or(|target| {
+                best.as_ref()
+                    .and_then(Measurements::relative_stddev)
+                    .is_none_or(|r| r <= target)
+            });
+            if target_met || attempts >= self.options.max_attempts {
+                break;
+            }
+
+            if self.options.output_style != OutputStyleOption::Disabled && !self.options.quiet {
+                println!(
+                    "  {} attempt {}/{}: relative standard deviation above {:.1}%",
+                    "Retrying:".yellow(),
+                    attempts + 1,
+                    self.options.max_attempts,
+                    self.options.max_relative_stddev.unwrap_or_default() * 1e2
+                );
+            }
+        }
+
+        self.summarize(
+            best.expect("at least one measurement attempt"),
+            attempts,
+            binary_size_byte,
+        )
+    }
+
+    /// Compute the statistics of the given measurements, print them together with all
+    /// warnings, and run the `--cleanup` command
+    fn summarize(
+        &self,
+        measurements: Measurements,
+        attempts: u64,
+        binary_size_byte: Option<u64>,
+    ) -> Result<BenchmarkResult> {
+        let output_policy = &self.options.command_output_policies[self.number];
+
+        let Measurements {
+            times_real: all_times_real,
+            times_user: all_times_user,
+            times_system: all_times_system,
+            memory_usage_byte,
+            exit_codes,
+            timing_results: all_timing_results,
+            weights: all_weights,
+            preparation_times,
+            conclusion_times,
+            start_times,
+            all_succeeded,
+        } = measurements;
+
+        // Exclude statistical outliers from the statistics (if requested). All runs are
+        // still exported.
+        let kept_runs = if self.options.drop_outliers {
+            runs_without_outliers(&all_times_real)
+        } else {
+            None
+        };
+        let kept_runs = kept_runs.as_deref();
+        let outliers_dropped = kept_runs.map_or(0, |runs| all_times_real.len() - runs.len());
+        let times_real = retain_runs(&all_times_real, kept_runs);
+        let times_user = retain_runs(&all_times_user, kept_runs);
+        let times_system = retain_runs(&all_times_system, kept_runs);
+        let timing_results = retain_runs(&all_timing_results, kept_runs);
+
         // Compute statistical quantities
         let t_num = times_real.len();
         let t_mean = mean(&times_real);
@@ -433,26 +1210,98 @@ impl<'a> Benchmark<'a> {
         } else {
             None
         };
+        let t_mean_ci = bootstrap_mean_ci(
+            &times_real,
+            self.options.bootstrap_resamples,
+            self.options.confidence_level,
+            &mut rand::thread_rng(),
+        );
         let t_median = median(&times_real);
         let t_min = min(&times_real);
         let t_max = max(&times_real);
 
+        // Order statistics
+        let mut sorted_times = times_real.to_vec();
+        sorted_times.sort_by(|a, b| a.total_cmp(b));
+        let t_percentiles = Percentiles::of_sorted(&sorted_times);
+        let t_trimmed_mean = trimmed_mean_of_sorted(&sorted_times, TRIMMED_MEAN_FRACTION);
+        let t_mad = median_absolute_deviation(&sorted_times, t_median);
+
+        // Weighted statistics, falling back to unweighted ones if no weights are available
+        let weights = Some(all_weights).filter(|w| !w.is_empty());
+        let kept_weights = weights.as_ref().map(|w| retain_runs(w, kept_runs));
+        let t_weighted_mean = kept_weights
+            .as_ref()
+            .and_then(|w| weighted_mean(&times_real, w));
+        let t_weighted_stddev = kept_weights
+            .as_ref()
+            .zip(t_weighted_mean)
+            .and_then(|(w, m)| {
+                weighted_standard_deviation(&times_real, w, m).filter(|_| times_real.len() > 1)
+            });
+
         let user_mean = mean(&times_user);
         let system_mean = mean(&times_system);
+        let exit_code_counts = count_exit_codes(&exit_codes);
+
+        let prepare_mean = Some(preparation_times)
+            .filter(|t| !t.is_empty())
+            .map(|t| mean(&t));
+        let conclude_mean = Some(conclusion_times)
+            .filter(|t| !t.is_empty())
+            .map(|t| mean(&t));
+
+        // The start of every run, relative to the first one. The system time of the first
+        // run is derived from the monotonic clock, to match the offsets.
+        let start_offsets = start_times.first().map(|&first| {
+            start_times
+                .iter()
+                .map(|start| start.duration_since(first).as_secs_f64())
+                .collect::<Vec<_>>()
+        });
+        let start_timestamp = start_times.first().and_then(|first| {
+            SystemTime::now()
+                .checked_sub(first.elapsed())?
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|t| t.as_secs_f64())
+        });
+
+        // The peak memory usage is not available on all platforms
+        let kept_memory_usage = retain_runs(&memory_usage_byte, kept_runs);
+        let (memory_mean, memory_max) = match kept_memory_usage.iter().max() {
+            Some(&max) if max > 0 => {
+                let total: f64 = kept_memory_usage.iter().map(|&m| m as f64).sum();
+                (Some(total / kept_memory_usage.len() as f64), Some(max))
+            }
+            _ => (None, None),
+        };
 
         // Collect poop metrics for display
         let aggregated_poop_metrics = aggregate_poop_metrics(&timing_results);
+        let poop_metrics_stddev = poop_metrics_stddev(&timing_results);
+        let poop_metrics_per_second = if self.options.metrics_per_second {
+            aggregated_poop_metrics
+                .as_ref()
+                .and_then(|metrics| metrics.per_second(t_mean))
+        } else {
+            None
+        };
 
         // Formatting and console output
         let (mean_str, time_unit) = format_duration_unit(t_mean, self.options.time_unit);
         let min_str = format_duration(t_min, Some(time_unit));
         let max_str = format_duration(t_max, Some(time_unit));
-        let num_str = format!("{t_num} runs");
+        let num_str = if outliers_dropped > 0 {
+            format!("{t_num} runs, {outliers_dropped} outliers dropped")
+        } else {
+            format!("{t_num} runs")
+        };
 
         let user_str = format_duration(user_mean, Some(time_unit));
         let system_str = format_duration(system_mean, Some(time_unit));
 
-        if self.options.output_style != OutputStyleOption::Disabled {
+        if self.options.output_style != OutputStyleOption::Disabled && !self.options.quiet {
             if times_real.len() == 1 {
                 println!(
                     "  Time ({} ≡):        {:>8}  {:>8}     [User: {}, System: {}]",
@@ -474,83 +1323,29 @@ impl<'a> Benchmark<'a> {
                     user_str.blue(),
                     system_str.blue()
                 );
+                if let Some((lower, upper)) = t_mean_ci {
+                    println!(
+                        "                        ({}% CI: {} … {})",
+                        (self.options.confidence_level * 1000.0).round() / 10.0,
+                        format_duration(lower, Some(time_unit)).green(),
+                        format_duration(upper, Some(time_unit)).green()
+                    );
+                }
 
                 // Display poop metrics if collected
                 if let Some(metrics) = aggregated_poop_metrics.as_ref() {
-                    println!();
-                    if let Some(cycles) = metrics.cpu_cycles {
-                        print!("  CPU cycles:              {}", cycles.to_string().cyan());
-                        if let Some(_instructions) = metrics.instructions {
-                            if let Some(ipc) = metrics.instructions_per_cycle() {
-                                println!("\t(IPC: {})", format!("{:.2}", ipc).cyan());
-                            } else {
-                                println!();
-                            }
-                        } else {
-                            println!();
-                        }
-                    }
-                    if let Some(instructions) = metrics.instructions {
-                        println!(
-                            "  Instructions:            {}",
-                            instructions.to_string().cyan()
-                        );
-                    }
-                    if let Some(cache_refs) = metrics.cache_references {
-                        print!(
-                            "  Cache references:        {}",
-                            cache_refs.to_string().cyan()
-                        );
-                        if let Some(_cache_misses) = metrics.cache_misses {
-                            if let Some(miss_rate) = metrics.cache_miss_rate() {
-                                println!(
-                                    "\t(miss rate: {}%)",
-                                    format!("{:.1}", miss_rate * 100.0).yellow()
-                                );
-                            } else {
-                                println!();
-                            }
-                        } else {
-                            println!();
-                        }
-                    }
-                    if let Some(cache_misses) = metrics.cache_misses {
-                        if metrics.cache_references.is_none() {
-                            println!(
-                                "  Cache misses:            {}",
-                                cache_misses.to_string().cyan()
-                            );
-                        }
-                    }
-                    if let Some(branches) = metrics.branches {
-                        print!("  Branch instructions:     {}", branches.to_string().cyan());
-                        if let Some(_branch_misses) = metrics.branch_misses {
-                            if let Some(miss_rate) = metrics.branch_miss_rate() {
-                                println!(
-                                    "\t(miss rate: {}%)",
-                                    format!("{:.1}", miss_rate * 100.0).yellow()
-                                );
-                            } else {
-                                println!();
-                            }
-                        } else {
-                            println!();
-                        }
-                    }
-                    if let Some(branch_misses) = metrics.branch_misses {
-                        if metrics.branches.is_none() {
-                            println!(
-                                "  Branch misses:           {}",
-                                branch_misses.to_string().cyan()
-                            );
-                        }
-                    }
-                    if let Some(page_faults) = metrics.page_faults {
-                        println!(
-                            "  Page faults:             {}",
-                            page_faults.to_string().cyan()
-                        );
-                    }
+                    let details = PoopMetricsDetails {
+                        stddev: poop_metrics_stddev.as_ref(),
+                        per_second_of: poop_metrics_per_second.as_ref().map(|_| t_mean),
+                        reference_frequency: self
+                            .options
+                            .reference_frequency_ghz
+                            .map(|frequency| (frequency, time_unit)),
+                    };
+                    print!(
+                        "{}",
+                        format_poop_metrics(metrics, &details, self.options.output_style)
+                    );
                 }
 
                 println!(
@@ -561,95 +1356,174 @@ impl<'a> Benchmark<'a> {
                     max_str.purple(),
                     num_str.dimmed()
                 );
-            }
-        }
 
-        // Warnings
-        let mut warnings = vec![];
+                if let Some(percentiles) =
+                    t_percentiles.filter(|_| t_num >= MIN_RUNS_FOR_PERCENTILES)
+                {
+                    println!(
+                        "  Percentiles:          p50: {}   p95: {}   p99: {}",
+                        format_duration(percentiles.p50, Some(time_unit)).cyan(),
+                        format_duration(percentiles.p95, Some(time_unit)).cyan(),
+                        format_duration(percentiles.p99, Some(time_unit)).cyan()
+                    );
+                }
 
-        // Check execution time
-        if matches!(self.options.executor_kind, ExecutorKind::Shell(_))
-            && times_real.iter().any(|&t| t < MIN_EXECUTION_TIME)
-        {
-            warnings.push(Warnings::FastExecutionTime);
-        }
+                if self.options.robust_stats {
+                    println!(
+                        "  Robust:               trimmed mean: {}   MAD: {}",
+                        format_duration(t_trimmed_mean, Some(time_unit)).green(),
+                        format_duration(t_mad, Some(time_unit)).green()
+                    );
+                }
 
-        // Check program exit codes
-        if !all_succeeded {
-            warnings.push(Warnings::NonZeroExitCode);
-        }
+                if self.options.show_histogram {
+                    let bins = self
+                        .options
+                        .histogram_bins
+                        .unwrap_or_else(|| default_bin_count(t_num));
+                    println!();
+                    for line in format_histogram(&times_real, bins, Some(time_unit)) {
+                        println!("  {line}");
+                    }
+                }
 
-        // Run outlier detection
-        let scores = modified_zscores(&times_real);
+                if let Some(weighted_mean) = t_weighted_mean {
+                    println!(
+                        "  Weighted ({} ± {}): {:>8} ± {:>8}",
+                        "mean".green().bold(),
+                        "σ".green(),
+                        format_duration(weighted_mean, Some(time_unit))
+                            .green()
+                            .bold(),
+                        format_duration(t_weighted_stddev.unwrap_or(0.0), Some(time_unit)).green(),
+                    );
+                }
+            }
 
-        let outlier_warning_options = OutlierWarningOptions {
-            warmup_in_use: self.options.warmup_count > 0,
-            prepare_in_use: self
-                .options
-                .preparation_command
-                .as_ref()
-                .map(|v| v.len())
-                .unwrap_or(0)
-                > 0,
-        };
+            if let (Some(mean), Some(max)) = (memory_mean, memory_max) {
+                let (mean_str, memory_unit) = format_bytes_unit(mean.round() as u64, None);
+                println!(
+                    "  Memory (peak):       {:>8}  (max: {})",
+                    mean_str.cyan(),
+                    format_bytes_unit(max, Some(memory_unit)).0.cyan()
+                );
+            }
 
-        if scores[0] > OUTLIER_THRESHOLD {
-            warnings.push(Warnings::SlowInitialRun(
-                times_real[0],
-                outlier_warning_options,
-            ));
-        } else if scores.iter().any(|&s| s.abs() > OUTLIER_THRESHOLD) {
-            warnings.push(Warnings::OutliersDetected(outlier_warning_options));
-        }
+            if let Some(size) = binary_size_byte {
+                println!("  Binary size:         {:>8}", format_bytes(size).cyan());
+            }
 
-        // Warn if poop metrics were requested but not collected
-        if self.options.poop_metrics_enabled
-            && aggregated_poop_metrics
-                .as_ref()
-                .is_none_or(|m| !m.has_data())
-        {
-            warnings.push(Warnings::PoopMetricsUnavailable);
+            if !all_succeeded {
+                println!(
+                    "  Exit codes:          {}",
+                    format_exit_code_counts(&exit_code_counts).yellow()
+                );
+            }
+
+            if self.options.show_setup_time {
+                if let Some(prepare_mean) = prepare_mean {
+                    println!(
+                        "  Prepare ({}):      {:>8}",
+                        "mean".green().bold(),
+                        format_duration(prepare_mean, Some(time_unit)).green()
+                    );
+                }
+                if let Some(conclude_mean) = conclude_mean {
+                    println!(
+                        "  Conclude ({}):     {:>8}",
+                        "mean".green().bold(),
+                        format_duration(conclude_mean, Some(time_unit)).green()
+                    );
+                }
+            }
         }
 
+        let cpu_cycles: Vec<u64> = all_timing_results
+            .iter()
+            .filter_map(|t| t.poop_metrics.as_ref().and_then(|m| m.cpu_cycles))
+            .collect();
+        let timed_out_runs = all_timing_results.iter().filter(|t| t.timed_out).count();
+        let warnings = self.collect_warnings(
+            &all_times_real,
+            all_succeeded,
+            timed_out_runs,
+            aggregated_poop_metrics.as_ref(),
+            &cpu_cycles,
+            prepare_mean,
+        );
+
         if !warnings.is_empty() {
-            eprintln!(" ");
+            if self.options.quiet {
+                // The header has not been printed, so the warnings need some context
+                eprintln!(
+                    "{}{}: {}",
+                    "Benchmark ".bold(),
+                    (self.number + 1).to_string().bold(),
+                    self.command.get_name_with_unused_parameters(),
+                );
+            } else {
+                eprintln!(" ");
+            }
 
             for warning in &warnings {
                 eprintln!("  {}: {}", "Warning".yellow(), warning);
             }
         }
 
-        if self.options.output_style != OutputStyleOption::Disabled {
+        if self.options.output_style != OutputStyleOption::Disabled && !self.options.quiet {
             println!(" ");
         }
 
         self.run_cleanup_command(self.command.get_parameters().iter().cloned(), output_policy)?;
 
-        // Collect poop metrics
-        let poop_metrics_all: Vec<PoopMetrics> = timing_results
+        // Collect per-run poop metrics, keeping one entry per measured run
+        let metrics_per_run: Option<Vec<_>> = if all_timing_results
             .iter()
-            .filter_map(|tr| tr.poop_metrics)
-            .collect();
-        let poop_metrics_all = if poop_metrics_all.is_empty() {
-            None
+            .any(|tr| tr.poop_metrics.is_some())
+        {
+            Some(
+                all_timing_results
+                    .iter()
+                    .map(|tr| tr.poop_metrics.clone())
+                    .collect(),
+            )
         } else {
-            Some(poop_metrics_all)
+            None
         };
+        let poop_metrics_all = metrics_per_run
+            .as_ref()
+            .map(|per_run| per_run.iter().flatten().cloned().collect());
         let poop_metrics = aggregated_poop_metrics;
 
+        self.report_progress(ProgressEvent::BenchmarkDone {
+            index: self.number,
+            command: &self.command.get_name(),
+            mean: t_mean,
+        });
+
         Ok(BenchmarkResult {
             command: self.command.get_name(),
             command_with_unused_parameters: self.command.get_name_with_unused_parameters(),
             mean: t_mean,
             stddev: t_stddev,
+            mean_ci_lower: t_mean_ci.map(|(lower, _)| lower),
+            mean_ci_upper: t_mean_ci.map(|(_, upper)| upper),
             median: t_median,
             user: user_mean,
             system: system_mean,
             min: t_min,
             max: t_max,
-            times: Some(times_real),
+            percentiles: t_percentiles,
+            trimmed_mean: Some(t_trimmed_mean),
+            median_absolute_deviation: Some(t_mad),
+            times: Some(all_times_real),
+            times_user: Some(all_times_user),
+            times_system: Some(all_times_system),
             memory_usage_byte: Some(memory_usage_byte),
+            memory_mean,
+            memory_max,
             exit_codes,
+            exit_code_counts,
             parameters: self
                 .command
                 .get_parameters()
@@ -657,7 +1531,21 @@ impl<'a> Benchmark<'a> {
                 .map(|(name, value)| (name.to_string(), value.to_string()))
                 .collect(),
             poop_metrics,
+            poop_metrics_stddev,
             poop_metrics_all,
+            metrics_per_run,
+            poop_metrics_per_second,
+            binary_size_byte,
+            weights,
+            weighted_mean: t_weighted_mean,
+            weighted_stddev: t_weighted_stddev,
+            attempts: self.options.max_relative_stddev.map(|_| attempts),
+            outliers_dropped: self.options.drop_outliers.then_some(outliers_dropped),
+            prepare_mean,
+            conclude_mean,
+            start_offsets,
+            start_timestamp,
+            warnings,
         })
     }
 }
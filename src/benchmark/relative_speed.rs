@@ -1,6 +1,10 @@
 use std::cmp::Ordering;
 
+use serde::Serialize;
+
 use super::benchmark_result::BenchmarkResult;
+use crate::poop_metrics::ComparisonMetric;
+use crate::util::student_t::two_sided_p_value;
 use crate::{options::SortOrder, util::units::Scalar};
 
 #[derive(Debug)]
@@ -75,6 +79,9 @@ fn compute_relative_speeds<'a>(
 
     match sort_order {
         SortOrder::Command => {}
+        SortOrder::Name => {
+            results.sort_by(|r1, r2| r1.result.command.cmp(&r2.result.command));
+        }
         SortOrder::MeanTime => {
             results.sort_unstable_by(|r1, r2| compare_mean_time(r1.result, r2.result));
         }
@@ -118,10 +125,82 @@ pub fn compute(
     compute_relative_speeds(results, fastest, sort_order)
 }
 
+/// Relative change of the mean time of `after` with respect to `before`, i.e.
+/// `(after - before) / before`. Returns `None` if the mean time of `before` is zero.
+pub fn relative_mean_change(before: &BenchmarkResult, after: &BenchmarkResult) -> Option<Scalar> {
+    if before.mean == 0.0 {
+        return None;
+    }
+
+    Some((after.mean - before.mean) / before.mean)
+}
+
+/// Relative change of the given metric for `subject` with respect to `other`, i.e.
+/// `(subject - other) / other`. Returns `None` if the metric is not available for one
+/// of the two benchmarks or if the value for `other` is zero.
+pub fn relative_metric_change(
+    subject: &BenchmarkResult,
+    other: &BenchmarkResult,
+    metric: ComparisonMetric,
+) -> Option<Scalar> {
+    let subject = subject
+        .poop_metrics
+        .as_ref()
+        .and_then(|m| metric.value(m))?;
+    let other = other.poop_metrics.as_ref().and_then(|m| metric.value(m))?;
+
+    if other == 0.0 {
+        return None;
+    }
+
+    Some((subject - other) / other)
+}
+
+/// Result of Welch's t-test for the difference of the mean times of two benchmarks
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WelchTTest {
+    pub t_statistic: Scalar,
+    pub degrees_of_freedom: Scalar,
+    /// Two-sided p-value for the null hypothesis that both mean times are equal
+    pub p_value: Scalar,
+}
+
+/// Significance level below which a difference of the mean times is reported as significant
+pub const SIGNIFICANCE_LEVEL: Scalar = 0.05;
+
+/// Welch's (unequal variances) t-test for the difference of the mean times of two
+/// benchmarks. Returns `None` if one of the benchmarks has less than two runs, or if both
+/// do not show any variance.
+pub fn welch_t_test(a: &BenchmarkResult, b: &BenchmarkResult) -> Option<WelchTTest> {
+    // Only the runs that remain after removing outliers contribute to the standard deviation
+    let num_runs = |r: &BenchmarkResult| r.exit_codes.len() - r.outliers_dropped.unwrap_or(0);
+    let (n_a, n_b) = (num_runs(a) as Scalar, num_runs(b) as Scalar);
+    if n_a < 2.0 || n_b < 2.0 {
+        return None;
+    }
+
+    // Squared standard errors of the means
+    let se2_a = a.stddev?.powi(2) / n_a;
+    let se2_b = b.stddev?.powi(2) / n_b;
+    let se2 = se2_a + se2_b;
+    if se2 <= 0.0 {
+        return None;
+    }
+
+    let t_statistic = (a.mean - b.mean) / se2.sqrt();
+    // Welch–Satterthwaite equation
+    let degrees_of_freedom =
+        se2.powi(2) / (se2_a.powi(2) / (n_a - 1.0) + se2_b.powi(2) / (n_b - 1.0));
+
+    Some(WelchTTest {
+        t_statistic,
+        degrees_of_freedom,
+        p_value: two_sided_p_value(t_statistic, degrees_of_freedom),
+    })
+}
+
 #[cfg(test)]
 fn create_result(name: &str, mean: Scalar) -> BenchmarkResult {
-    use std::collections::BTreeMap;
-
     BenchmarkResult {
         command: name.into(),
         command_with_unused_parameters: name.into(),
@@ -132,12 +211,7 @@ fn create_result(name: &str, mean: Scalar) -> BenchmarkResult {
         system: 0.0,
         min: mean,
         max: mean,
-        times: None,
-        memory_usage_byte: None,
-        exit_codes: Vec::new(),
-        parameters: BTreeMap::new(),
-        poop_metrics: None,
-        poop_metrics_all: None,
+        ..Default::default()
     }
 }
 
@@ -158,6 +232,27 @@ fn test_compute_relative_speed() {
     assert_relative_eq!(2.5, annotated_results[2].relative_speed);
 }
 
+#[test]
+fn test_compute_relative_speed_sort_orders() {
+    let results = vec![
+        create_result("cmd2", 3.0),
+        create_result("cmd3", 2.0),
+        create_result("cmd1", 5.0),
+    ];
+
+    let commands = |sort_order| -> Vec<String> {
+        compute_with_check(&results, sort_order)
+            .unwrap()
+            .iter()
+            .map(|r| r.result.command.clone())
+            .collect()
+    };
+
+    assert_eq!(commands(SortOrder::Command), ["cmd2", "cmd3", "cmd1"]);
+    assert_eq!(commands(SortOrder::Name), ["cmd1", "cmd2", "cmd3"]);
+    assert_eq!(commands(SortOrder::MeanTime), ["cmd3", "cmd2", "cmd1"]);
+}
+
 #[test]
 fn test_compute_relative_speed_with_reference() {
     use approx::assert_relative_eq;
@@ -180,3 +275,89 @@ fn test_compute_relative_speed_for_zero_times() {
 
     assert!(annotated_results.is_none());
 }
+
+#[test]
+fn test_relative_metric_change() {
+    use crate::poop_metrics::{MetricType, PoopMetrics};
+    use approx::assert_relative_eq;
+
+    let mut fast = create_result("fast", 1.0);
+    fast.poop_metrics = Some(PoopMetrics {
+        cpu_cycles: Some(1000),
+        instructions: Some(2600),
+        cache_misses: Some(60),
+        ..Default::default()
+    });
+    let mut slow = create_result("slow", 2.0);
+    slow.poop_metrics = Some(PoopMetrics {
+        cpu_cycles: Some(1000),
+        instructions: Some(2000),
+        cache_misses: Some(100),
+        ..Default::default()
+    });
+    let without_metrics = create_result("other", 3.0);
+
+    let ipc = relative_metric_change(&fast, &slow, ComparisonMetric::Ipc).unwrap();
+    assert_relative_eq!(ipc, 0.3);
+
+    let misses = ComparisonMetric::Counter(MetricType::CacheMisses);
+    assert_relative_eq!(relative_metric_change(&fast, &slow, misses).unwrap(), -0.4);
+
+    assert!(relative_metric_change(&fast, &slow, ComparisonMetric::CacheMissRate).is_none());
+    assert!(relative_metric_change(&fast, &without_metrics, ComparisonMetric::Ipc).is_none());
+}
+
+#[test]
+fn test_relative_mean_change() {
+    use approx::assert_relative_eq;
+
+    let before = create_result("before", 2.0);
+    let after = create_result("after", 1.5);
+
+    assert_relative_eq!(relative_mean_change(&before, &after).unwrap(), -0.25);
+    assert_relative_eq!(relative_mean_change(&after, &before).unwrap(), 1.0 / 3.0);
+    assert!(relative_mean_change(&create_result("zero", 0.0), &after).is_none());
+}
+
+#[test]
+fn test_welch_t_test() {
+    use approx::assert_relative_eq;
+
+    let result = |mean, stddev, runs| BenchmarkResult {
+        stddev: Some(stddev),
+        exit_codes: vec![Some(0); runs],
+        ..create_result("cmd", mean)
+    };
+
+    // se² = 0.5²/10 + 1.0²/20 = 0.075, t = -1 / sqrt(0.075)
+    let test = welch_t_test(&result(10.0, 0.5, 10), &result(11.0, 1.0, 20)).unwrap();
+    assert_relative_eq!(test.t_statistic, -3.651_483_7, epsilon = 1e-6);
+    assert_relative_eq!(test.degrees_of_freedom, 27.981_818_2, epsilon = 1e-6);
+    assert!(test.p_value > 0.0005 && test.p_value < 0.002);
+
+    let test = welch_t_test(&result(10.0, 1.0, 10), &result(10.1, 1.0, 10)).unwrap();
+    assert!(test.p_value > SIGNIFICANCE_LEVEL);
+
+    assert_eq!(
+        welch_t_test(&result(10.0, 0.0, 10), &result(11.0, 0.0, 10)),
+        None
+    );
+    assert_eq!(
+        welch_t_test(&result(10.0, 1.0, 1), &result(11.0, 1.0, 10)),
+        None
+    );
+
+    // Dropped outliers do not count as runs
+    let with_outliers = |mean, stddev, runs, outliers| BenchmarkResult {
+        outliers_dropped: Some(outliers),
+        ..result(mean, stddev, runs)
+    };
+    assert_eq!(
+        welch_t_test(&with_outliers(10.0, 0.5, 12, 2), &result(11.0, 1.0, 20)),
+        welch_t_test(&result(10.0, 0.5, 10), &result(11.0, 1.0, 20))
+    );
+    assert_eq!(
+        welch_t_test(&with_outliers(10.0, 1.0, 3, 2), &result(11.0, 1.0, 10)),
+        None
+    );
+}
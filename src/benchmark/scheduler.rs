@@ -1,14 +1,24 @@
 use super::benchmark_result::BenchmarkResult;
+use super::checkpoint::{command_key, Checkpoint};
 use super::executor::{Executor, MockExecutor, RawExecutor, ShellExecutor};
-use super::{relative_speed, Benchmark};
+use super::{baseline, relative_speed, Benchmark, Measurements};
 use colored::*;
 use std::cmp::Ordering;
+use std::fs::File;
+use std::time::{Duration, Instant};
 
 use crate::command::{Command, Commands};
+use crate::error::PerformanceRegressionError;
 use crate::export::ExportManager;
-use crate::options::{ExecutorKind, Options, OutputStyleOption, SortOrder};
+use crate::options::{CommandOutputPolicy, ExecutorKind, Options, OutputStyleOption, SortOrder};
+use crate::output::format::{format_bytes, format_duration, format_duration_unit};
+use crate::output::progress_bar::{get_progress_bar, SessionProgress};
+use crate::poop_metrics::ComparisonMetric;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 
 pub struct Scheduler<'a> {
     commands: &'a Commands<'a>,
@@ -31,12 +41,38 @@ impl<'a> Scheduler<'a> {
         }
     }
 
-    pub fn run_benchmarks(&mut self) -> Result<()> {
-        let mut executor: Box<dyn Executor> = match self.options.executor_kind {
-            ExecutorKind::Raw => Box::new(RawExecutor::new(self.options)),
+    fn create_executor(options: &Options) -> Box<dyn Executor + '_> {
+        match options.executor_kind {
+            ExecutorKind::Raw => Box::new(RawExecutor::new(options)),
             ExecutorKind::Mock(ref shell) => Box::new(MockExecutor::new(shell.clone())),
-            ExecutorKind::Shell(ref shell) => Box::new(ShellExecutor::new(shell, self.options)),
+            ExecutorKind::Shell(ref shell) => Box::new(ShellExecutor::new(shell, options)),
+        }
+    }
+
+    /// Benchmark a trivial command with an uncalibrated executor, such that the full
+    /// startup overhead (including the shell spawning time) is measured.
+    fn run_overhead_benchmark(&self, overhead_command: &str) -> Result<()> {
+        let options = Options {
+            preparation_command: None,
+            conclusion_command: None,
+            setup_command: None,
+            cleanup_command: None,
+            command_output_policies: vec![CommandOutputPolicy::Null],
+            target_instructions: None,
+            ..self.options.clone()
         };
+        let executor = Self::create_executor(&options);
+        let command = Command::new(None, overhead_command);
+
+        Benchmark::new(0, &command, &options, &*executor)
+            .measuring_overhead()
+            .run()?;
+
+        Ok(())
+    }
+
+    pub fn run_benchmarks(&mut self) -> Result<()> {
+        let mut executor = Self::create_executor(self.options);
 
         let reference = self
             .options
@@ -46,18 +82,203 @@ impl<'a> Scheduler<'a> {
 
         executor.calibrate()?;
 
-        for (number, cmd) in reference.iter().chain(self.commands.iter()).enumerate() {
-            self.results
-                .push(Benchmark::new(number, cmd, self.options, &*executor).run()?);
+        // Runs are appended to the JSON Lines file as they complete, starting from an empty file
+        if let Some(path) = &self.options.jsonl_file {
+            File::create(path)
+                .with_context(|| format!("Could not create export file '{}'", path.display()))?;
+        }
+
+        if self.options.output_style != OutputStyleOption::Disabled {
+            for description in &self.options.descriptions {
+                println!("{} {}", "Description:".bold(), description);
+            }
+            if !self.options.descriptions.is_empty() {
+                println!();
+            }
+        }
+
+        if let Some(overhead_command) = &self.options.overhead_command {
+            self.run_overhead_benchmark(overhead_command)?;
+        }
+
+        let all_commands: Vec<&Command> = reference.iter().chain(self.commands.iter()).collect();
+
+        let mut checkpoint = self
+            .options
+            .checkpoint_file
+            .as_ref()
+            .map(|path| {
+                let keys = all_commands
+                    .iter()
+                    .enumerate()
+                    .map(|(number, cmd)| command_key(number, cmd))
+                    .collect();
+                if self.options.resume {
+                    Checkpoint::resume(path, keys)
+                } else {
+                    Ok(Checkpoint::new(path, keys))
+                }
+            })
+            .transpose()?;
+
+        let mut order: Vec<usize> = (0..all_commands.len()).collect();
+        if let Some(seed) = self.options.shuffle_seed {
+            order.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+
+        if self.options.interleave {
+            self.run_interleaved(&all_commands, &order, &*executor)?;
+        } else {
+            let mut finished_benchmarks = 0;
+            let mut total_duration = Duration::ZERO;
+            for (position, &number) in order.iter().enumerate() {
+                let cmd = all_commands[number];
+                if let Some(result) = checkpoint.as_ref().and_then(|c| c.completed_result(number)) {
+                    if self.options.output_style != OutputStyleOption::Disabled
+                        && !self.options.quiet
+                    {
+                        println!(
+                            "{}{}: {} {}\n",
+                            "Benchmark ".bold(),
+                            (number + 1).to_string().bold(),
+                            cmd.get_name_with_unused_parameters(),
+                            "(resumed from checkpoint)".dimmed(),
+                        );
+                    }
+                    self.results.push(BenchmarkResult {
+                        command_with_unused_parameters: cmd.get_name_with_unused_parameters(),
+                        ..result.clone()
+                    });
+                } else {
+                    let session = SessionProgress {
+                        remaining_benchmarks: order.len() - position - 1,
+                        mean_benchmark_duration: (finished_benchmarks > 0)
+                            .then(|| total_duration / finished_benchmarks),
+                    };
+                    let start = Instant::now();
+                    let result = Benchmark::new(number, cmd, self.options, &*executor)
+                        .with_session_progress(session)
+                        .run()?;
+                    total_duration += start.elapsed();
+                    finished_benchmarks += 1;
+                    if let Some(checkpoint) = checkpoint.as_mut() {
+                        checkpoint.add_result(number, &result)?;
+                    }
+                    self.results.push(result);
+                }
+
+                // We export results after each individual benchmark, because
+                // we would risk losing them if a later benchmark fails.
+                self.export_manager.write_results(&self.results, true)?;
+            }
+        }
+
+        // Report the results in the order in which the commands were given
+        if self.options.shuffle_seed.is_some() {
+            let mut numbered: Vec<_> = order.into_iter().zip(self.results.drain(..)).collect();
+            numbered.sort_by_key(|(number, _)| *number);
+            self.results = numbered.into_iter().map(|(_, result)| result).collect();
+            self.export_manager.write_results(&self.results, true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Perform the benchmarks of all commands at the same time (see `--interleave`). After the
+    /// setup and warmup of every command, one run of each command is performed in turn until
+    /// all of them have reached their number of runs.
+    fn run_interleaved(
+        &mut self,
+        commands: &[&Command],
+        order: &[usize],
+        executor: &dyn Executor,
+    ) -> Result<()> {
+        let benchmarks: Vec<Benchmark> = order
+            .iter()
+            .map(|&number| Benchmark::new(number, commands[number], self.options, executor))
+            .collect();
+
+        let mut binary_sizes = vec![];
+        for benchmark in &benchmarks {
+            binary_sizes.push(benchmark.binary_size()?);
+            benchmark.setup_and_warmup()?;
+        }
+
+        let intermediate_commands: Vec<_> = benchmarks
+            .iter()
+            .map(|b| (b.preparation_command(), b.conclusion_command()))
+            .collect();
+        let measure_once = |index: usize, measurements: &mut Measurements| {
+            let benchmark = &benchmarks[index];
+            let (preparation_command, conclusion_command) = &intermediate_commands[index];
+            benchmark.measure_once(
+                measurements,
+                preparation_command.as_ref(),
+                conclusion_command.as_ref(),
+                &self.options.command_output_policies[benchmark.number],
+            )
+        };
+
+        let progress_bar = self.options.show_progress_bar().then(|| {
+            get_progress_bar(
+                benchmarks.iter().map(|b| b.run_bounds().min).sum(),
+                "Interleaved runs",
+                self.options.output_style,
+                self.options.progress_update_interval,
+            )
+        });
+
+        // The initial run of each command determines its number of runs
+        let mut measurements: Vec<Measurements> =
+            benchmarks.iter().map(|_| Measurements::default()).collect();
+        let mut counts = vec![];
+        for (index, benchmark) in benchmarks.iter().enumerate() {
+            let overhead = measure_once(index, &mut measurements[index])?;
+            let estimated_runs =
+                benchmark.estimated_runs(measurements[index].times_real[0], overhead);
+            counts.push(benchmark.bounded_run_count(estimated_runs));
+        }
+        if let Some(bar) = progress_bar.as_ref() {
+            bar.set_length(counts.iter().sum());
+            bar.inc(benchmarks.len() as u64);
+        }
+
+        let mut round = 1;
+        while counts.iter().any(|&count| round < count) {
+            for (index, &count) in counts.iter().enumerate() {
+                if round < count {
+                    measure_once(index, &mut measurements[index])?;
+                    if let Some(bar) = progress_bar.as_ref() {
+                        bar.inc(1)
+                    }
+                }
+            }
+            round += 1;
+        }
+
+        if let Some(bar) = progress_bar.as_ref() {
+            bar.finish_and_clear()
+        }
 
-            // We export results after each individual benchmark, because
-            // we would risk losing them if a later benchmark fails.
+        for ((benchmark, measurements), binary_size_byte) in
+            benchmarks.iter().zip(measurements).zip(binary_sizes)
+        {
+            benchmark.print_header();
+            let result = benchmark.summarize(measurements, 1, binary_size_byte)?;
+            self.results.push(result);
             self.export_manager.write_results(&self.results, true)?;
         }
 
         Ok(())
     }
 
+    /// Use previously exported results instead of running the benchmarks. Like finished
+    /// benchmarks, they are written to all export files right away.
+    pub fn import_results(&mut self, results: Vec<BenchmarkResult>) -> Result<()> {
+        self.results = results;
+        self.export_manager.write_results(&self.results, true)
+    }
+
     pub fn print_relative_speed_comparison(&self) {
         if self.options.output_style == OutputStyleOption::Disabled {
             return;
@@ -67,12 +288,19 @@ impl<'a> Scheduler<'a> {
             return;
         }
 
-        let reference = self
-            .options
-            .reference_command
-            .as_ref()
-            .map(|_| &self.results[0])
-            .unwrap_or_else(|| relative_speed::fastest_of(&self.results));
+        if self.options.before_after {
+            self.print_before_after_comparison(&self.results[0], &self.results[1]);
+            self.print_significance();
+            return;
+        }
+
+        let reference = if self.options.reference_command.is_some() {
+            &self.results[0]
+        } else if let Some(index) = self.options.reference_index {
+            &self.results[index]
+        } else {
+            relative_speed::fastest_of(&self.results)
+        };
 
         if let Some(annotated_results) = relative_speed::compute_with_check_from_reference(
             &self.results,
@@ -119,9 +347,14 @@ impl<'a> Scheduler<'a> {
                             comparator,
                             &item.result.command_with_unused_parameters.magenta()
                         );
+                        if let Some(comparison) =
+                            self.format_metric_comparison(reference.result, item.result)
+                        {
+                            println!("         ({comparison})");
+                        }
                     }
                 }
-                SortOrder::Command => {
+                SortOrder::Command | SortOrder::Name => {
                     println!("{}", "Relative speed comparison".bold());
 
                     for item in annotated_results {
@@ -137,6 +370,14 @@ impl<'a> Scheduler<'a> {
                             },
                             &item.result.command_with_unused_parameters,
                         );
+                        if item.is_reference {
+                            continue;
+                        }
+                        if let Some(comparison) =
+                            self.format_metric_comparison(item.result, reference)
+                        {
+                            println!("{:20}({comparison})", "");
+                        }
                     }
                 }
             }
@@ -151,11 +392,260 @@ impl<'a> Scheduler<'a> {
                  "Note".bold().red()
             );
         }
+
+        self.print_significance();
+    }
+
+    /// Print whether the difference of the mean times of exactly two benchmarks is
+    /// statistically significant, according to Welch's t-test
+    fn print_significance(&self) {
+        let [a, b] = self.results.as_slice() else {
+            return;
+        };
+        let Some(test) = relative_speed::welch_t_test(a, b) else {
+            return;
+        };
+
+        let p_value = if test.p_value < 0.001 {
+            "p < 0.001".to_string()
+        } else {
+            format!("p = {:.3}", test.p_value)
+        };
+        if test.p_value < relative_speed::SIGNIFICANCE_LEVEL {
+            println!(
+                "  Difference is {} ({p_value})",
+                "statistically significant".bold()
+            );
+        } else {
+            println!(
+                "  Difference is {} ({p_value})",
+                "not statistically significant".yellow()
+            );
+        }
+    }
+
+    fn print_before_after_comparison(&self, before: &BenchmarkResult, after: &BenchmarkResult) {
+        let format_time = |result: &BenchmarkResult| {
+            let (mean_str, unit) = format_duration_unit(result.mean, self.options.time_unit);
+            match result.stddev {
+                Some(stddev) => format!(
+                    "{} ± {}",
+                    mean_str.green().bold(),
+                    format_duration(stddev, Some(unit)).green()
+                ),
+                None => mean_str.green().bold().to_string(),
+            }
+        };
+
+        println!("{}", "Before/after comparison".bold());
+        println!(
+            "  before: {}  ({})",
+            format_time(before),
+            before.command_with_unused_parameters.cyan()
+        );
+        println!(
+            "  after:  {}  ({})",
+            format_time(after),
+            after.command_with_unused_parameters.magenta()
+        );
+
+        let annotated = relative_speed::compute_with_check_from_reference(
+            &self.results,
+            before,
+            SortOrder::Command,
+        );
+        let change = relative_speed::relative_mean_change(before, after);
+
+        match (annotated, change) {
+            (Some(annotated), Some(change)) => {
+                let after = annotated.iter().find(|r| !r.is_reference).unwrap();
+                let stddev = after
+                    .relative_speed_stddev
+                    .map(|stddev| format!(" ± {stddev:.2}"))
+                    .unwrap_or_default();
+                let percentage = format!("{:+.1}%", change * 100.0);
+                let verdict = match after.relative_ordering {
+                    Ordering::Less => format!(
+                        "{}, {:.2}{stddev} times faster",
+                        "faster".green(),
+                        after.relative_speed
+                    ),
+                    Ordering::Greater => format!(
+                        "{}, {:.2}{stddev} times slower",
+                        "slower".red(),
+                        after.relative_speed
+                    ),
+                    Ordering::Equal => "no change".to_string(),
+                };
+                println!("  change: {} ({verdict})", percentage.bold());
+
+                if let Some(comparison) = self.format_metric_comparison(after.result, before) {
+                    println!("          ({comparison})");
+                }
+            }
+            _ => println!("  change: not available (zero mean time)"),
+        }
+
+        if let (Some(size_before), Some(size_after)) =
+            (before.binary_size_byte, after.binary_size_byte)
+        {
+            let change = if size_before == 0 {
+                "".into()
+            } else {
+                let change = (size_after as f64 - size_before as f64) / size_before as f64;
+                format!("{:+.1}% ", change * 100.0)
+            };
+            println!(
+                "  size:   {}({} → {})",
+                change.bold(),
+                format_bytes(size_before),
+                format_bytes(size_after)
+            );
+        }
+    }
+
+    /// Describe how the metrics selected via '--compare-metric' differ for `subject` with
+    /// respect to `other`, e.g. "12.0% better IPC, 40.0% fewer cache misses".
+    fn format_metric_comparison(
+        &self,
+        subject: &BenchmarkResult,
+        other: &BenchmarkResult,
+    ) -> Option<String> {
+        if self.options.comparison_metrics.is_empty() {
+            return None;
+        }
+
+        let parts: Vec<String> = self
+            .options
+            .comparison_metrics
+            .iter()
+            .map(|&metric| {
+                let label = metric.label();
+                match relative_speed::relative_metric_change(subject, other, metric) {
+                    None => format!("{label} not available"),
+                    Some(0.0) => format!("same {label}"),
+                    Some(change) => {
+                        let improved = (change > 0.0) == metric.higher_is_better();
+                        let word = match (metric, improved) {
+                            (ComparisonMetric::Counter(_), _) if change < 0.0 => "fewer",
+                            (ComparisonMetric::Counter(_), _) => "more",
+                            (_, true) => "better",
+                            (_, false) => "worse",
+                        };
+                        let percentage = format!("{:.1}%", change.abs() * 100.0);
+                        let percentage = if improved {
+                            percentage.green()
+                        } else {
+                            percentage.red()
+                        };
+                        format!("{percentage} {word} {label}")
+                    }
+                }
+            })
+            .collect();
+
+        Some(parts.join(", "))
     }
 
     pub fn final_export(&self) -> Result<()> {
         self.export_manager.write_results(&self.results, false)
     }
+
+    /// Print the relative change of the mean run time (and of the poop metrics) of every
+    /// benchmark, compared to the given baseline results. Fails if any benchmark regressed by
+    /// more than one of the thresholds given with '--regression-threshold'.
+    pub fn compare_to_baseline(&self, baseline: &[BenchmarkResult]) -> Result<()> {
+        let deltas = baseline::compare_to_baseline(&self.results, baseline);
+        let threshold = self.options.regression_threshold;
+        let metric_thresholds = &self.options.metric_regression_thresholds;
+
+        if self.options.output_style != OutputStyleOption::Disabled {
+            let width = self
+                .results
+                .iter()
+                .map(|r| r.command_with_unused_parameters.chars().count())
+                .max()
+                .unwrap_or(0);
+
+            // Separate the table from the relative speed comparison
+            if self.results.len() > 1 {
+                println!();
+            }
+            println!("{}", "Comparison with baseline".bold());
+            for (result, delta) in &deltas {
+                let command = format!("{:width$}", result.command_with_unused_parameters);
+                match delta {
+                    Some(delta) => {
+                        let percentage = format!("{:>+7.1}%", delta.relative_change * 1e2);
+                        let percentage = if delta.is_regression(threshold.unwrap_or(0.0)) {
+                            percentage.red()
+                        } else {
+                            percentage.green()
+                        };
+                        println!(
+                            "  {}  {:>8} → {:>8}  {}",
+                            command.cyan(),
+                            format_duration(delta.baseline.mean, self.options.time_unit),
+                            format_duration(result.mean, self.options.time_unit),
+                            percentage
+                        );
+
+                        for &(metric, change) in &delta.metric_changes {
+                            let percentage = format!("{:+.1}%", change * 1e2);
+                            // Like for the run time, any increase is highlighted if there is
+                            // no threshold for this metric
+                            let threshold = metric_thresholds
+                                .iter()
+                                .find(|&&(m, _)| m == metric)
+                                .map_or(0.0, |&(_, threshold)| threshold);
+                            let is_regression = change > threshold;
+                            println!(
+                                "    {:<24} {}",
+                                format!("{}:", metric.display_name()),
+                                if is_regression {
+                                    percentage.red()
+                                } else {
+                                    percentage.green()
+                                }
+                            );
+                        }
+                    }
+                    None => println!("  {}  {}", command.cyan(), "no baseline result".dimmed()),
+                }
+            }
+        }
+
+        let count = deltas
+            .iter()
+            .filter_map(|(_, delta)| delta.as_ref())
+            .filter(|delta| {
+                threshold.is_some_and(|threshold| delta.is_regression(threshold))
+                    || !delta.regressed_metrics(metric_thresholds).is_empty()
+            })
+            .count();
+        if count > 0 {
+            let thresholds = threshold
+                .map(|threshold| format!("{:.1}%", threshold * 1e2))
+                .into_iter()
+                .chain(metric_thresholds.iter().map(|(metric, threshold)| {
+                    format!("{:.1}% ({})", threshold * 1e2, metric.cli_name())
+                }))
+                .collect::<Vec<_>>()
+                .join(" or ");
+            return Err(PerformanceRegressionError { count, thresholds }.into());
+        }
+
+        Ok(())
+    }
+
+    /// The first non-zero exit code of all benchmarked runs, if any
+    pub fn first_failing_exit_code(&self) -> Option<i32> {
+        self.results
+            .iter()
+            .flat_map(|result| result.exit_codes.iter().flatten())
+            .copied()
+            .find(|&code| code != 0)
+    }
 }
 
 #[cfg(test)]
@@ -171,14 +661,11 @@ fn generate_results(args: &[&'static str]) -> Result<Vec<BenchmarkResult>> {
     assert_eq!(options.executor_kind, ExecutorKind::Mock(None));
 
     let commands = Commands::from_cli_arguments(&cli_arguments)?;
-    let export_manager = ExportManager::from_cli_arguments(
-        &cli_arguments,
-        options.time_unit,
-        options.sort_order_exports,
-    )?;
 
     options.validate_against_command_list(&commands)?;
 
+    let export_manager = ExportManager::from_cli_arguments(&cli_arguments, &options)?;
+
     let mut scheduler = Scheduler::new(&commands, &options, &export_manager);
 
     scheduler.run_benchmarks()?;
@@ -191,38 +678,99 @@ fn scheduler_basic() -> Result<()> {
     - command: sleep 0.123
       mean: 0.123
       stddev: 0
+      mean_ci_lower: 0.123
+      mean_ci_upper: 0.123
       median: 0.123
       user: 0
       system: 0
       min: 0.123
       max: 0.123
+      percentiles:
+        p50: 0.123
+        p90: 0.123
+        p95: 0.123
+        p99: 0.123
+      trimmed_mean: 0.123
+      median_absolute_deviation: 0
       times:
         - 0.123
         - 0.123
+      times_user:
+        - 0
+        - 0
+      times_system:
+        - 0
+        - 0
       memory_usage_byte:
         - 0
         - 0
       exit_codes:
         - 0
         - 0
+      exit_code_counts:
+        0: 2
     - command: sleep 0.456
       mean: 0.456
       stddev: 0
+      mean_ci_lower: 0.456
+      mean_ci_upper: 0.456
       median: 0.456
       user: 0
       system: 0
       min: 0.456
       max: 0.456
+      percentiles:
+        p50: 0.456
+        p90: 0.456
+        p95: 0.456
+        p99: 0.456
+      trimmed_mean: 0.456
+      median_absolute_deviation: 0
       times:
         - 0.456
         - 0.456
+      times_user:
+        - 0
+        - 0
+      times_system:
+        - 0
+        - 0
       memory_usage_byte:
         - 0
         - 0
       exit_codes:
         - 0
         - 0
+      exit_code_counts:
+        0: 2
     "#);
 
     Ok(())
 }
+
+#[test]
+fn scheduler_shuffle_keeps_result_order() -> Result<()> {
+    let commands = ["sleep 0.1", "sleep 0.2", "sleep 0.3", "sleep 0.4"];
+    let mut args = vec!["--runs=2", "--shuffle", "--shuffle-seed=2"];
+    args.extend(commands);
+
+    let results = generate_results(&args)?;
+    let order: Vec<&str> = results.iter().map(|r| r.command.as_str()).collect();
+    assert_eq!(order, commands);
+
+    Ok(())
+}
+
+#[test]
+fn scheduler_interleave_matches_sequential_results() -> Result<()> {
+    let args = ["--runs=3", "sleep 0.123", "sleep 0.456"];
+    let mut interleaved_args = vec!["--interleave"];
+    interleaved_args.extend(args);
+
+    assert_eq!(
+        generate_results(&interleaved_args)?,
+        generate_results(&args)?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,1054 @@
+use std::cell::Cell;
+
+use super::benchmark_result::BenchmarkResult;
+use super::executor::BenchmarkIteration;
+use super::fake_executor::{FakeExecutor, FakeRun};
+use super::timing_result::TimingResult;
+use super::{aggregate_poop_metrics, relative_margin_of_error, runs_without_outliers, Benchmark};
+use crate::command::Command;
+use crate::options::{Options, OutputStyleOption, RunBounds};
+use crate::output::warnings::Warnings;
+use crate::poop_metrics::{MetricType, PoopMetrics};
+use crate::util::units::Second;
+
+use approx::assert_relative_eq;
+
+fn quiet_options() -> Options {
+    Options {
+        output_style: OutputStyleOption::Disabled,
+        // Fast fake runs lead to thousands of runs within the minimum benchmarking time
+        bootstrap_resamples: 100,
+        ..Default::default()
+    }
+}
+
+fn run_and_collect_warnings(
+    options: &Options,
+    executor: &FakeExecutor,
+) -> (BenchmarkResult, Vec<Warnings>) {
+    let command = Command::new(None, "fake");
+    let result = Benchmark::new(0, &command, options, executor)
+        .run()
+        .unwrap();
+    let warnings = result.warnings.clone();
+    (result, warnings)
+}
+
+/// A successful run with the given wall clock time and poop metrics
+fn run_with_metrics(time_real: Second, poop_metrics: PoopMetrics) -> FakeRun {
+    FakeRun {
+        timing_result: TimingResult {
+            time_real,
+            poop_metrics: Some(poop_metrics),
+            ..Default::default()
+        },
+        exit_code: 0,
+    }
+}
+
+#[test]
+fn test_run_count_is_derived_from_min_benchmarking_time() {
+    let options = quiet_options();
+
+    // 3 s / (0.1 s + 0.1 s overhead) = 15 runs
+    let executor = FakeExecutor::from_times(vec![0.1]).with_time_overhead(0.1);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    assert_eq!(result.times.unwrap().len(), 15);
+    assert_eq!(executor.num_benchmark_runs(), 15);
+}
+
+#[test]
+fn test_run_count_respects_run_bounds() {
+    // Slow commands still perform the minimum number of runs
+    let options = quiet_options();
+    let executor = FakeExecutor::from_times(vec![2.0]);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.times.unwrap().len(), 10);
+
+    // Fast commands are capped by the maximum number of runs
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 2,
+            max: Some(5),
+        },
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::from_times(vec![0.01]);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.times.unwrap().len(), 5);
+}
+
+#[test]
+fn test_exact_run_count() {
+    // Without an exact number of runs, thousands of runs would fit into the minimum
+    // benchmarking time
+    let options = Options {
+        run_bounds: RunBounds::exact(10),
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::from_times(vec![0.0001]);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    assert_eq!(result.times.unwrap().len(), 10);
+    assert_eq!(executor.num_benchmark_runs(), 10);
+
+    assert_eq!(RunBounds::exact(3).exact_count(), Some(3));
+    assert_eq!(RunBounds::default().exact_count(), None);
+    let bounds = RunBounds {
+        min: 2,
+        max: Some(5),
+    };
+    assert_eq!(bounds.exact_count(), None);
+}
+
+#[test]
+fn test_run_count_overrides_for_parameter_values() {
+    use crate::options::RunCountOverride;
+    use crate::parameter::ParameterValue;
+
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 5,
+            max: Some(5),
+        },
+        run_count_overrides: vec![
+            RunCountOverride {
+                parameter: "size".into(),
+                value: "large".into(),
+                runs: 2,
+            },
+            RunCountOverride {
+                parameter: "size".into(),
+                value: "medium".into(),
+                runs: 3,
+            },
+        ],
+        ..quiet_options()
+    };
+
+    let run_count = |size: &str| {
+        let command = Command::new_parametrized(
+            None,
+            "fake {size}",
+            vec![("size", ParameterValue::Text(size.into()))],
+        );
+        let executor = FakeExecutor::from_times(vec![0.01]);
+        let result = Benchmark::new(0, &command, &options, &executor)
+            .run()
+            .unwrap();
+        result.times.unwrap().len()
+    };
+
+    assert_eq!(run_count("large"), 2);
+    assert_eq!(run_count("medium"), 3);
+    assert_eq!(run_count("small"), 5);
+}
+
+#[test]
+fn test_start_offsets_of_runs() {
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 3,
+            max: Some(3),
+        },
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::from_times(vec![0.1]);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.start_offsets, None);
+    assert_eq!(result.start_timestamp, None);
+
+    let options = Options {
+        record_start_times: true,
+        ..options
+    };
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    let offsets = result.start_offsets.unwrap();
+    assert_eq!(offsets.len(), 3);
+    assert_eq!(offsets[0], 0.0);
+    assert!(offsets.windows(2).all(|w| w[0] <= w[1]));
+    assert!(result.start_timestamp.unwrap() > 0.0);
+}
+
+#[test]
+fn test_warmup_runs_are_not_measured() {
+    let options = Options {
+        warmup_count: 3,
+        run_bounds: RunBounds {
+            min: 4,
+            max: Some(4),
+        },
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::from_times(vec![0.1]);
+    run_and_collect_warnings(&options, &executor);
+
+    let iterations: Vec<_> = executor.invocations().into_iter().map(|i| i.1).collect();
+    assert_eq!(
+        iterations,
+        vec![
+            BenchmarkIteration::Warmup(0),
+            BenchmarkIteration::Warmup(1),
+            BenchmarkIteration::Warmup(2),
+            BenchmarkIteration::Benchmark(0),
+            BenchmarkIteration::Benchmark(1),
+            BenchmarkIteration::Benchmark(2),
+            BenchmarkIteration::Benchmark(3),
+        ]
+    );
+}
+
+#[test]
+fn test_warmup_time() {
+    let num_warmup_runs = |warmup_count, warmup_time| {
+        let options = Options {
+            warmup_count,
+            warmup_time: Some(warmup_time),
+            run_bounds: RunBounds {
+                min: 2,
+                max: Some(2),
+            },
+            ..quiet_options()
+        };
+        // Each warmup run takes 0.25 s, including the overhead
+        let executor = FakeExecutor::from_times(vec![0.2]).with_time_overhead(0.05);
+        run_and_collect_warnings(&options, &executor);
+
+        executor
+            .invocations()
+            .iter()
+            .filter(|(_, it)| matches!(it, BenchmarkIteration::Warmup(_)))
+            .count()
+    };
+
+    assert_eq!(num_warmup_runs(0, 1.0), 4);
+    assert_eq!(num_warmup_runs(0, 1.1), 5);
+    // The warmup phase ends as soon as either limit is reached
+    assert_eq!(num_warmup_runs(2, 1.0), 2);
+    assert_eq!(num_warmup_runs(10, 0.6), 3);
+}
+
+#[test]
+fn test_statistics() {
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 4,
+            max: Some(4),
+        },
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::from_times(vec![0.1, 0.2, 0.3, 0.6]);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    assert_relative_eq!(result.mean, 0.3);
+    assert_relative_eq!(result.median, 0.25);
+    assert_relative_eq!(result.min, 0.1);
+    assert_relative_eq!(result.max, 0.6);
+}
+
+#[test]
+fn test_slow_initial_run_warning() {
+    let mut times: Vec<Second> = vec![0.1; 10];
+    times[0] = 1.0;
+    let executor = FakeExecutor::from_times(times);
+    let (_, warnings) = run_and_collect_warnings(&quiet_options(), &executor);
+
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::SlowInitialRun(t, _, _) if *t == 1.0)));
+}
+
+#[test]
+fn test_drop_outliers() {
+    let mut times: Vec<Second> = vec![0.1; 10];
+    times[5] = 1.0;
+    let executor = FakeExecutor::from_times(times);
+    let options = Options {
+        drop_outliers: true,
+        ..quiet_options()
+    };
+    let (result, warnings) = run_and_collect_warnings(&options, &executor);
+
+    assert_relative_eq!(result.mean, 0.1);
+    assert_relative_eq!(result.max, 0.1);
+    assert_eq!(result.outliers_dropped, Some(1));
+    // The raw run times still contain the outlier
+    assert!(result.times.unwrap().contains(&1.0));
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::OutliersDetected(_))));
+}
+
+#[test]
+fn test_drop_outliers_from_memory_usage() {
+    let executor = FakeExecutor::new(|_, iteration| {
+        let outlier = iteration == BenchmarkIteration::Benchmark(5);
+        FakeRun {
+            timing_result: TimingResult {
+                time_real: if outlier { 1.0 } else { 0.1 },
+                memory_usage_byte: if outlier { 1000 } else { 100 },
+                ..Default::default()
+            },
+            exit_code: 0,
+        }
+    });
+    let options = Options {
+        run_bounds: RunBounds::exact(10),
+        drop_outliers: true,
+        ..quiet_options()
+    };
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    assert_eq!(result.outliers_dropped, Some(1));
+    assert_relative_eq!(result.memory_mean.unwrap(), 100.0);
+    assert_eq!(result.memory_max, Some(100));
+}
+
+#[test]
+fn test_drop_outliers_keeps_at_least_two_runs() {
+    assert_eq!(runs_without_outliers(&[]), None);
+    assert_eq!(runs_without_outliers(&[0.1, 1.0]), None);
+    assert_eq!(runs_without_outliers(&[0.1, 0.1, 0.1]), None);
+    assert_eq!(runs_without_outliers(&[0.1, 0.1, 5.0]), Some(vec![0, 1]));
+}
+
+#[test]
+fn test_outliers_detected_warning() {
+    let mut times: Vec<Second> = vec![0.1; 10];
+    times[5] = 1.0;
+    let executor = FakeExecutor::from_times(times);
+    let (_, warnings) = run_and_collect_warnings(&quiet_options(), &executor);
+
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::OutliersDetected(_))));
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::SlowInitialRun(_, _, _))));
+}
+
+#[test]
+fn test_fast_execution_time_warning() {
+    let executor = FakeExecutor::from_times(vec![0.001]);
+    let (_, warnings) = run_and_collect_warnings(&quiet_options(), &executor);
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::FastExecutionTime)));
+
+    let executor = FakeExecutor::from_times(vec![0.1]);
+    let (_, warnings) = run_and_collect_warnings(&quiet_options(), &executor);
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn test_preparation_overhead_warning() {
+    let run_with_preparation_time = |preparation_time| {
+        let options = Options {
+            preparation_command: Some(vec!["prepare".into()]),
+            ..quiet_options()
+        };
+        let executor = FakeExecutor::new(move |command, _| {
+            if command.get_command_line() == "prepare" {
+                FakeRun::with_time(preparation_time)
+            } else {
+                FakeRun::with_time(0.1)
+            }
+        });
+        run_and_collect_warnings(&options, &executor)
+    };
+
+    let (result, warnings) = run_with_preparation_time(0.3);
+    assert_relative_eq!(result.prepare_mean.unwrap(), 0.3);
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::PreparationOverhead(r) if (*r - 3.0).abs() < 1e-9)));
+
+    let (_, warnings) = run_with_preparation_time(0.04);
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::PreparationOverhead(_))));
+}
+
+#[test]
+fn test_run_time_trend_warning() {
+    let trend = |times: Vec<Second>| {
+        let options = Options {
+            run_bounds: RunBounds {
+                min: times.len() as u64,
+                max: Some(times.len() as u64),
+            },
+            ..quiet_options()
+        };
+        let executor = FakeExecutor::from_times(times);
+        let (_, warnings) = run_and_collect_warnings(&options, &executor);
+        warnings.iter().find_map(|w| match w {
+            Warnings::RunTimeTrend(c) => Some(*c),
+            _ => None,
+        })
+    };
+
+    let increasing: Vec<Second> = (0..10).map(|i| 0.1 + 0.001 * i as Second).collect();
+    assert_relative_eq!(trend(increasing.clone()).unwrap(), 1.0);
+
+    let decreasing: Vec<Second> = increasing.iter().rev().copied().collect();
+    assert_relative_eq!(trend(decreasing).unwrap(), -1.0);
+
+    let shuffled = vec![
+        0.105, 0.101, 0.108, 0.103, 0.100, 0.109, 0.102, 0.106, 0.104, 0.107,
+    ];
+    assert_eq!(trend(shuffled), None);
+
+    // Too few runs to detect a trend
+    assert_eq!(trend(increasing[..5].to_vec()), None);
+}
+
+#[test]
+fn test_too_few_runs_warning() {
+    let has_warning = |runs| {
+        let options = Options {
+            run_bounds: RunBounds {
+                min: runs,
+                max: Some(runs),
+            },
+            ..quiet_options()
+        };
+        let executor = FakeExecutor::from_times(vec![0.1]);
+        let (_, warnings) = run_and_collect_warnings(&options, &executor);
+        warnings
+            .iter()
+            .any(|w| matches!(w, Warnings::TooFewRuns(n) if *n == runs as usize))
+    };
+
+    assert!(has_warning(1));
+    assert!(has_warning(2));
+    assert!(!has_warning(3));
+}
+
+#[test]
+fn test_non_zero_exit_code_warning() {
+    let executor = FakeExecutor::new(|_, iteration| FakeRun {
+        exit_code: if iteration == BenchmarkIteration::Benchmark(3) {
+            1
+        } else {
+            0
+        },
+        ..FakeRun::with_time(0.1)
+    });
+    let (result, warnings) = run_and_collect_warnings(&quiet_options(), &executor);
+
+    assert_eq!(result.exit_codes[3], Some(1));
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::NonZeroExitCode)));
+}
+
+#[test]
+fn test_poop_metrics_aggregation() {
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 2,
+            max: Some(2),
+        },
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::new(|_, iteration| {
+        let i = match iteration {
+            BenchmarkIteration::Benchmark(i) => i + 1,
+            _ => 0,
+        };
+        run_with_metrics(
+            0.1,
+            PoopMetrics {
+                cpu_cycles: Some(1000 * i),
+                instructions: Some(2000 * i),
+                ..Default::default()
+            },
+        )
+    });
+    let (result, warnings) = run_and_collect_warnings(&options, &executor);
+
+    let metrics = result.poop_metrics.unwrap();
+    assert_eq!(metrics.cpu_cycles, Some(1500));
+    assert_eq!(metrics.instructions, Some(3000));
+    assert_eq!(metrics.cache_misses, None);
+    assert_eq!(result.metrics_per_run.unwrap().len(), 2);
+    let stddev = result.poop_metrics_stddev.unwrap();
+    assert_relative_eq!(stddev.cpu_cycles.unwrap(), 1000.0 / 2f64.sqrt());
+    assert_eq!(stddev.cache_misses, None);
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::PoopMetricsUnavailable(_))));
+}
+
+#[test]
+fn test_poop_metrics_unavailable_warning() {
+    let options = Options {
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::from_times(vec![0.1]);
+    let (result, warnings) = run_and_collect_warnings(&options, &executor);
+
+    assert!(result.poop_metrics.is_none());
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::PoopMetricsUnavailable(false))));
+}
+
+#[test]
+fn test_poop_metrics_permission_denied_warning() {
+    let options = Options {
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::new(|_, _| {
+        run_with_metrics(
+            0.01,
+            PoopMetrics {
+                permission_denied: true,
+                ..Default::default()
+            },
+        )
+    });
+    let (_, warnings) = run_and_collect_warnings(&options, &executor);
+
+    let warning = warnings
+        .iter()
+        .find(|w| matches!(w, Warnings::PoopMetricsUnavailable(true)))
+        .unwrap();
+    assert!(warning
+        .to_string()
+        .contains("sysctl kernel.perf_event_paranoid=1"));
+    assert!(warning.to_string().contains("CAP_PERFMON"));
+}
+
+/// Ten runs per attempt. The first attempt is noisy, all later attempts are stable
+/// unless `always_noisy` is set.
+fn noisy_executor(always_noisy: bool) -> FakeExecutor {
+    let runs = Cell::new(0);
+    FakeExecutor::new(move |_, _| {
+        let run = runs.get();
+        runs.set(run + 1);
+        if (run < 10 || always_noisy) && run % 2 == 1 {
+            FakeRun::with_time(0.3)
+        } else {
+            FakeRun::with_time(0.1)
+        }
+    })
+}
+
+fn retry_options(max_attempts: u64) -> Options {
+    Options {
+        run_bounds: RunBounds {
+            min: 10,
+            max: Some(10),
+        },
+        max_relative_stddev: Some(0.05),
+        max_attempts,
+        ..quiet_options()
+    }
+}
+
+#[test]
+fn test_retry_on_high_variance() {
+    let executor = noisy_executor(false);
+    let (result, warnings) = run_and_collect_warnings(&retry_options(3), &executor);
+
+    assert_eq!(result.attempts, Some(2));
+    assert_eq!(executor.num_benchmark_runs(), 20);
+    assert_relative_eq!(result.stddev.unwrap(), 0.0);
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::VarianceTargetNotMet(..))));
+}
+
+#[test]
+fn test_retry_on_high_variance_target_not_met() {
+    let executor = noisy_executor(true);
+    let (result, warnings) = run_and_collect_warnings(&retry_options(3), &executor);
+
+    assert_eq!(result.attempts, Some(3));
+    assert_eq!(executor.num_benchmark_runs(), 30);
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::VarianceTargetNotMet(_, _, 3))));
+}
+
+#[test]
+fn test_no_retry_by_default() {
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 10,
+            max: Some(10),
+        },
+        ..quiet_options()
+    };
+    let executor = noisy_executor(true);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    assert_eq!(result.attempts, None);
+    assert_eq!(executor.num_benchmark_runs(), 10);
+}
+
+#[test]
+fn test_weighted_statistics() {
+    let dir = tempfile::tempdir().unwrap();
+    let weight_file = dir.path().join("weight");
+
+    // Odd runs take three times as long, but also process three times as much data
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 4,
+            max: Some(4),
+        },
+        weight_file: Some(weight_file.clone()),
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::new(move |_, iteration| {
+        let (time, weight) = match iteration {
+            BenchmarkIteration::Benchmark(i) if i % 2 == 1 => (0.3, "3"),
+            _ => (0.1, "1"),
+        };
+        std::fs::write(&weight_file, weight).unwrap();
+        FakeRun::with_time(time)
+    });
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    assert_eq!(result.weights, Some(vec![1.0, 3.0, 1.0, 3.0]));
+    assert_relative_eq!(result.mean, 0.2);
+    // (2 * 0.1 + 6 * 0.3) / 8
+    assert_relative_eq!(result.weighted_mean.unwrap(), 0.25);
+    assert!(result.weighted_stddev.is_some());
+}
+
+#[test]
+fn test_no_weighted_statistics_without_weight_file() {
+    let executor = FakeExecutor::from_times(vec![0.1, 0.2]);
+    let (result, _) = run_and_collect_warnings(&quiet_options(), &executor);
+
+    assert_eq!(result.weights, None);
+    assert_eq!(result.weighted_mean, None);
+    assert_eq!(result.weighted_stddev, None);
+}
+
+#[test]
+fn test_require_metrics() {
+    let options = Options {
+        poop_metrics_enabled: true,
+        require_metrics: true,
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::from_times(vec![0.1]);
+    let command = Command::new(None, "fake");
+    let error = Benchmark::new(0, &command, &options, &executor)
+        .run()
+        .err()
+        .unwrap();
+
+    assert!(error
+        .to_string()
+        .contains("Performance metrics could not be collected for 'fake' in benchmark run 1"));
+    // The benchmark is aborted after the first run without metrics
+    assert_eq!(executor.num_benchmark_runs(), 1);
+
+    // Metrics that are missing in a later run fail the benchmark at that run
+    let executor = FakeExecutor::new(|_, iteration| match iteration {
+        BenchmarkIteration::Benchmark(i) if i >= 2 => FakeRun::with_time(0.1),
+        _ => run_with_metrics(
+            0.1,
+            PoopMetrics {
+                cpu_cycles: Some(1_000_000),
+                ..Default::default()
+            },
+        ),
+    });
+    let error = Benchmark::new(0, &command, &options, &executor)
+        .run()
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("in benchmark run 3"));
+    assert_eq!(executor.num_benchmark_runs(), 3);
+}
+
+fn stable_cycles_executor(times: Vec<Second>) -> FakeExecutor {
+    FakeExecutor::new(move |_, iteration| {
+        let time = match iteration {
+            BenchmarkIteration::Benchmark(i) => times[i as usize % times.len()],
+            _ => times[0],
+        };
+        run_with_metrics(
+            time,
+            PoopMetrics {
+                cpu_cycles: Some(1_000_000),
+                ..Default::default()
+            },
+        )
+    })
+}
+
+#[test]
+fn test_external_variance_warning() {
+    let options = Options {
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+
+    let executor = stable_cycles_executor(vec![0.1, 0.2]);
+    let (_, warnings) = run_and_collect_warnings(&options, &executor);
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::ExternalVariance(..))));
+
+    let executor = stable_cycles_executor(vec![0.1]);
+    let (_, warnings) = run_and_collect_warnings(&options, &executor);
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::ExternalVariance(..))));
+
+    let options = Options {
+        external_variance_warning: false,
+        ..options
+    };
+    let executor = stable_cycles_executor(vec![0.1, 0.2]);
+    let (_, warnings) = run_and_collect_warnings(&options, &executor);
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::ExternalVariance(..))));
+}
+
+#[test]
+fn test_aggregated_ratios_are_averaged_per_run() {
+    let run = |instructions, cpu_cycles| TimingResult {
+        poop_metrics: Some(PoopMetrics {
+            instructions: Some(instructions),
+            cpu_cycles: Some(cpu_cycles),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    // A short run with an IPC of 1 and a long run with an IPC of 4
+    let timing_results = vec![run(100, 100), run(1000, 250)];
+    let aggregated = aggregate_poop_metrics(&timing_results).unwrap();
+
+    // The ratio of the averaged counters (550 / 175) is dominated by the long run
+    let ratio_of_means =
+        aggregated.instructions.unwrap() as f64 / aggregated.cpu_cycles.unwrap() as f64;
+    assert_relative_eq!(ratio_of_means, 550.0 / 175.0);
+
+    let per_run_ipcs: Vec<f64> = timing_results
+        .iter()
+        .filter_map(|tr| tr.poop_metrics.as_ref()?.instructions_per_cycle())
+        .collect();
+    assert_eq!(per_run_ipcs, vec![1.0, 4.0]);
+    assert_relative_eq!(aggregated.instructions_per_cycle().unwrap(), 2.5);
+    assert!((aggregated.instructions_per_cycle().unwrap() - ratio_of_means).abs() > 0.5);
+}
+
+fn instructions_executor(instructions: Option<u64>) -> FakeExecutor {
+    FakeExecutor::new(move |_, _| {
+        run_with_metrics(
+            0.01,
+            PoopMetrics {
+                instructions,
+                ..Default::default()
+            },
+        )
+    })
+}
+
+#[test]
+fn test_target_instructions() {
+    // 25 runs are needed to execute 2.5 billion instructions, independent of the run time
+    let options = Options {
+        target_instructions: Some(2_500_000_000),
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = instructions_executor(Some(100_000_000));
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.times.unwrap().len(), 25);
+
+    // The minimum number of runs is still respected
+    let options = Options {
+        target_instructions: Some(1),
+        ..options
+    };
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.times.unwrap().len(), 10);
+
+    // ... as well as the maximum number of runs
+    let options = Options {
+        target_instructions: Some(u64::MAX),
+        run_bounds: RunBounds {
+            min: 2,
+            max: Some(5),
+        },
+        ..options
+    };
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.times.unwrap().len(), 5);
+}
+
+#[test]
+fn test_precision() {
+    // Alternating run times with a relative standard deviation of about 10%
+    let alternating = || {
+        FakeExecutor::new(|_, iteration| match iteration {
+            BenchmarkIteration::Benchmark(i) if i % 2 == 1 => FakeRun::with_time(1.1),
+            _ => FakeRun::with_time(0.9),
+        })
+    };
+    let options = Options {
+        precision: Some(0.05),
+        ..quiet_options()
+    };
+    let (result, warnings) = run_and_collect_warnings(&options, &alternating());
+    let times = result.times.unwrap();
+
+    // The runs stop as soon as the margin of error is below 5%
+    assert!(times.len() > 10);
+    assert!(relative_margin_of_error(&times).unwrap() < 0.05);
+    assert!(relative_margin_of_error(&times[..times.len() - 1]).unwrap() >= 0.05);
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::PrecisionNotReached(..))));
+
+    // The minimum number of runs is still respected
+    let executor = FakeExecutor::from_times(vec![0.1]);
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.times.unwrap().len(), 10);
+
+    // ... as well as the maximum number of runs
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 2,
+            max: Some(5),
+        },
+        ..options
+    };
+    let (result, warnings) = run_and_collect_warnings(&options, &alternating());
+    assert_eq!(result.times.unwrap().len(), 5);
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::PrecisionNotReached(_, _, 5))));
+}
+
+#[test]
+fn test_target_instructions_requires_instructions_counter() {
+    let options = Options {
+        target_instructions: Some(1_000),
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = instructions_executor(None);
+    let command = Command::new(None, "fake");
+    let error = Benchmark::new(0, &command, &options, &executor)
+        .run()
+        .err()
+        .unwrap();
+
+    assert!(error
+        .to_string()
+        .contains("requires the 'instructions' counter, but it could not be collected for 'fake'"));
+}
+
+#[test]
+fn test_metrics_per_second() {
+    let options = Options {
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = instructions_executor(Some(100_000_000));
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    assert_eq!(result.poop_metrics_per_second, None);
+
+    let options = Options {
+        metrics_per_second: true,
+        ..options
+    };
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+    let rates = result.poop_metrics_per_second.unwrap();
+    assert_relative_eq!(rates.instructions.unwrap(), 1e10, max_relative = 1e-9);
+    assert_eq!(rates.cache_misses, None);
+    assert_eq!(result.poop_metrics.unwrap().instructions, Some(100_000_000));
+}
+
+#[test]
+fn test_multiplexed_counters_warning() {
+    let options = Options {
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = |scaling_factor| {
+        FakeExecutor::new(move |_, _| {
+            run_with_metrics(
+                0.01,
+                PoopMetrics {
+                    cpu_cycles: Some(1_000_000),
+                    scaling_factor,
+                    ..Default::default()
+                },
+            )
+        })
+    };
+
+    let (result, warnings) = run_and_collect_warnings(&options, &executor(Some(3.0)));
+    assert_eq!(result.poop_metrics.unwrap().scaling_factor, Some(3.0));
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::MultiplexedCounters(_))));
+
+    let (_, warnings) = run_and_collect_warnings(&options, &executor(Some(1.5)));
+    assert!(!warnings
+        .iter()
+        .any(|w| matches!(w, Warnings::MultiplexedCounters(_))));
+}
+
+#[test]
+fn test_unavailable_metrics_warning() {
+    let executor = FakeExecutor::new(|_, _| {
+        run_with_metrics(
+            0.01,
+            PoopMetrics {
+                cpu_cycles: Some(1_000_000),
+                unavailable: std::collections::BTreeMap::from([(
+                    "llc-loads".to_string(),
+                    "No such file or directory".to_string(),
+                )]),
+                ..Default::default()
+            },
+        )
+    });
+    let unavailable_metrics = |options: &Options| {
+        let (result, warnings) = run_and_collect_warnings(options, &executor);
+        assert!(result
+            .poop_metrics
+            .unwrap()
+            .unavailable
+            .contains_key("llc-loads"));
+        warnings.into_iter().find_map(|w| match w {
+            Warnings::UnavailableMetrics(unavailable) => Some(unavailable),
+            _ => None,
+        })
+    };
+
+    let options = Options {
+        poop_metrics_enabled: true,
+        metrics_to_collect: vec![MetricType::CpuCycles, MetricType::LlcLoads],
+        ..quiet_options()
+    };
+    let unavailable = unavailable_metrics(&options).unwrap();
+    assert_eq!(
+        unavailable.get("llc-loads").map(String::as_str),
+        Some("No such file or directory")
+    );
+
+    // Unsupported metrics are expected if all metrics are collected
+    let options = Options {
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    assert!(unavailable_metrics(&options).is_none());
+}
+
+#[test]
+fn test_frequent_cpu_migrations_warning() {
+    let options = Options {
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = |cpu_migrations| {
+        FakeExecutor::new(move |_, _| {
+            run_with_metrics(
+                0.01,
+                PoopMetrics {
+                    cpu_migrations: Some(cpu_migrations),
+                    ..Default::default()
+                },
+            )
+        })
+    };
+    let has_warning = |warnings: &[Warnings]| {
+        warnings
+            .iter()
+            .any(|w| matches!(w, Warnings::FrequentCpuMigrations(_)))
+    };
+
+    let (_, warnings) = run_and_collect_warnings(&options, &executor(10));
+    assert!(has_warning(&warnings));
+
+    let (_, warnings) = run_and_collect_warnings(&options, &executor(1));
+    assert!(!has_warning(&warnings));
+}
+
+#[test]
+fn test_metrics_per_run_parallel_to_times() {
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 3,
+            max: Some(3),
+        },
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::new(|_, iteration| match iteration {
+        BenchmarkIteration::Benchmark(i) if i != 1 => run_with_metrics(
+            0.1,
+            PoopMetrics {
+                page_faults: Some(10 * (i + 1)),
+                ..Default::default()
+            },
+        ),
+        _ => FakeRun::with_time(0.1),
+    });
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    let per_run = result.metrics_per_run.as_ref().unwrap();
+    assert_eq!(per_run.len(), result.times.as_ref().unwrap().len());
+    let page_faults: Vec<Option<u64>> = per_run
+        .iter()
+        .map(|m| m.as_ref().and_then(|m| m.page_faults))
+        .collect();
+    assert_eq!(page_faults, vec![Some(10), None, Some(30)]);
+    assert_eq!(result.poop_metrics_all.as_ref().map(Vec::len), Some(2));
+
+    let json = serde_json::to_value(&result).unwrap();
+    assert!(json["metrics_per_run"][1].is_null());
+    assert_eq!(json["poop_metrics_all"][1]["page_faults"], 30);
+    assert_eq!(json["metrics_per_run"][2]["page_faults"], 30);
+    assert!(json.get("poop_metrics").is_some());
+}
+
+#[test]
+fn test_raw_event_aggregation() {
+    let options = Options {
+        run_bounds: RunBounds {
+            min: 2,
+            max: Some(2),
+        },
+        poop_metrics_enabled: true,
+        ..quiet_options()
+    };
+    let executor = FakeExecutor::new(|_, iteration| {
+        let i = match iteration {
+            BenchmarkIteration::Benchmark(i) => i + 1,
+            _ => 0,
+        };
+        run_with_metrics(
+            0.1,
+            PoopMetrics {
+                raw: vec![("r003c".to_string(), 100 * i)].into_iter().collect(),
+                ..Default::default()
+            },
+        )
+    });
+    let (result, _) = run_and_collect_warnings(&options, &executor);
+
+    assert_eq!(result.poop_metrics.unwrap().raw["r003c"], 150);
+    assert_relative_eq!(
+        result.poop_metrics_stddev.unwrap().raw["r003c"],
+        100.0 / 2f64.sqrt()
+    );
+}
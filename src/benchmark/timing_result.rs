@@ -2,7 +2,7 @@ use crate::poop_metrics::PoopMetrics;
 use crate::util::units::Second;
 
 /// Results from timing a single command
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct TimingResult {
     /// Wall clock time
     pub time_real: Second,
@@ -18,4 +18,7 @@ pub struct TimingResult {
 
     /// poop performance metrics (if enabled)
     pub poop_metrics: Option<PoopMetrics>,
+
+    /// Whether the command has been killed because it exceeded the timeout
+    pub timed_out: bool,
 }
@@ -1,8 +1,8 @@
 use std::ffi::OsString;
 
 use clap::{
-    builder::NonEmptyStringValueParser, crate_version, Arg, ArgAction, ArgMatches, Command,
-    ValueHint,
+    builder::{NonEmptyStringValueParser, PossibleValue},
+    crate_version, Arg, ArgAction, ArgMatches, Command, ValueHint,
 };
 
 pub fn get_cli_arguments<'a, I, T>(args: I) -> ArgMatches
@@ -14,6 +14,15 @@ where
     command.get_matches_from(args)
 }
 
+/// The accepted names of all metric types, see `MetricType::cli_names`
+fn metric_values() -> Vec<PossibleValue> {
+    let names: &[&[&str]] = include!("poop_metrics/cli_names.rs");
+    names
+        .iter()
+        .map(|names| PossibleValue::new(names[0]).aliases(names[1..].iter().copied()))
+        .collect()
+}
+
 /// Build the clap command for parsing command line arguments
 fn build_command() -> Command {
     Command::new("hyperfine")
@@ -30,7 +39,7 @@ fn build_command() -> Command {
                        The latter is only available if the shell is not explicitly disabled via \
                        '--shell=none'. If multiple commands are given, hyperfine will show a \
                        comparison of the respective runtimes.")
-                .required(true)
+                .required_unless_present_any(["list-metrics", "from-json"])
                 .action(ArgAction::Append)
                 .value_hint(ValueHint::CommandString)
                 .value_parser(NonEmptyStringValueParser::new()),
@@ -46,6 +55,17 @@ fn build_command() -> Command {
                      to fill (disk) caches for I/O-heavy programs.",
                 ),
         )
+        .arg(
+            Arg::new("warmup-time")
+                .long("warmup-time")
+                .value_name("SECONDS")
+                .action(ArgAction::Set)
+                .help(
+                    "Perform warmup runs until their accumulated run time exceeds the given \
+                     number of seconds. If '--warmup' is given as well, the warmup phase ends \
+                     as soon as either of the two limits is reached.",
+                ),
+        )
         .arg(
             Arg::new("min-runs")
                 .long("min-runs")
@@ -70,7 +90,22 @@ fn build_command() -> Command {
                 .action(ArgAction::Set)
                 .value_name("NUM")
                 .help("Perform exactly NUM runs for each command. If this option is not specified, \
-                       hyperfine automatically determines the number of runs."),
+                       hyperfine automatically determines the number of runs (within the bounds \
+                       given by '--min-runs' and '--max-runs', which can not be combined with \
+                       this option)."),
+        )
+        .arg(
+            Arg::new("runs-for")
+                .long("runs-for")
+                .action(ArgAction::Append)
+                .allow_hyphen_values(true)
+                .value_names(["NAME=VALUE", "NUM"])
+                .help("Perform exactly NUM runs for all benchmarks in which the parameter NAME \
+                       has the given VALUE, instead of the number of runs given by '--runs', \
+                       '--min-runs' and '--max-runs'. This can be used to perform fewer runs \
+                       for slow parameter values. The option can be specified multiple times, \
+                       the first matching override is used.\n\n\
+                       Example:  hyperfine -P size 1 4 --runs-for size=4 3 'sort {size}.txt'"),
         )
         .arg(
             Arg::new("setup")
@@ -93,7 +128,10 @@ fn build_command() -> Command {
                 .value_name("CMD")
                 .help(
                     "The reference command for the relative comparison of results. \
-                    If this is unset, results are compared with the fastest command as reference."
+                    If this is unset, results are compared with the fastest command as reference. \
+                    One of the benchmarked commands can be selected by its number (e.g. '1' for \
+                    the first command) or by the name given with '--command-name'. Any other \
+                    value is run as an additional command."
                 )
         )
         .arg(
@@ -104,6 +142,23 @@ fn build_command() -> Command {
                 .help("Give a meaningful name to the reference command.")
                 .requires("reference")
         )
+        .arg(
+            Arg::new("measure-overhead")
+                .long("measure-overhead")
+                .action(ArgAction::Set)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(if cfg!(windows) { "exit" } else { "true" })
+                .value_name("CMD")
+                .help(
+                    "Benchmark the startup overhead of a trivial command before running the \
+                    actual benchmarks, and report its full statistics. The measured times \
+                    include the shell spawning time, which is otherwise calibrated and \
+                    subtracted internally. This is useful to understand the noise floor of \
+                    the machine and the shell. The trivial command defaults to 'true' \
+                    ('exit' on Windows), use '--measure-overhead=<CMD>' to choose another one."
+                )
+        )
         .arg(
             Arg::new("prepare")
                 .long("prepare")
@@ -172,6 +227,7 @@ fn build_command() -> Command {
         .arg(
             Arg::new("parameter-step-size")
                 .long("parameter-step-size")
+                .visible_alias("parameter-step")
                 .short('D')
                 .action(ArgAction::Set)
                 .value_names(["DELTA"])
@@ -200,6 +256,39 @@ fn build_command() -> Command {
                      possible parameter combinations.\n"
                 ),
         )
+        .arg(
+            Arg::new("max-benchmarks")
+                .long("max-benchmarks")
+                .action(ArgAction::Set)
+                .value_name("NUM")
+                .help("Abort if a parameter sweep ('--parameter-scan', '--parameter-list', ...) \
+                       results in more than NUM benchmarks, which is the product of the number \
+                       of commands and the numbers of values of all parameters [default: 500]."),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .visible_alias("yes")
+                .action(ArgAction::SetTrue)
+                .help("Run all benchmarks of a parameter sweep, even if there are more than \
+                       allowed by '--max-benchmarks'."),
+        )
+        .arg(
+            Arg::new("parameter-list-file")
+                .long("parameter-list-file")
+                .action(ArgAction::Append)
+                .allow_hyphen_values(true)
+                .value_names(["VAR", "FILE"])
+                .value_hint(ValueHint::FilePath)
+                .conflicts_with_all(["parameter-scan", "parameter-step-size"])
+                .help(
+                    "Like '--parameter-list', but read the values of the parameter VAR from FILE, \
+                     with one value per line. Blank lines and lines starting with '#' are \
+                     skipped. The option can be specified multiple times, also together with \
+                     '--parameter-list', to run benchmarks for all possible parameter \
+                     combinations."
+                ),
+        )
         .arg(
             Arg::new("shell")
                 .long("shell")
@@ -210,19 +299,38 @@ fn build_command() -> Command {
                 .value_hint(ValueHint::CommandString)
                 .help("Set the shell to use for executing benchmarked commands. This can be the \
                        name or the path to the shell executable, or a full command line \
-                       like \"bash --norc\". It can also be set to \"default\" to explicitly select \
+                       like \"bash --norc\". The argument that makes the shell execute a command \
+                       (\"-c\", \"/C\" for cmd.exe or \"-Command\" for PowerShell) is added \
+                       automatically, unless the command line already ends with it, as in \
+                       \"pwsh -NoProfile -Command\". It can also be set to \"default\" to explicitly select \
                        the default shell on this platform. Finally, this can also be set to \
                        \"none\" to disable the shell. In this case, commands will be executed \
-                       directly. They can still have arguments, but more complex things like \
-                       \"sleep 0.1; sleep 0.2\" are not possible without a shell.")
+                       directly, which avoids the overhead of spawning a shell. The command line \
+                       is split into arguments like a POSIX shell would do it, honoring quotes \
+                       and escapes. Shell features like pipes, redirections or command lists \
+                       (\"sleep 0.1 && sleep 0.2\") are not available without a shell, and \
+                       commands that make use of them result in an error. Globs, variables and \
+                       '~' are passed on literally, since there is no shell to expand them.")
         )
         .arg(
             Arg::new("no-shell")
+                .long("no-shell")
                 .short('N')
                 .action(ArgAction::SetTrue)
                 .conflicts_with_all(["shell", "debug-mode"])
                 .help("An alias for '--shell=none'.")
         )
+        .arg(
+            Arg::new("shell-overhead")
+                .long("shell-overhead")
+                .action(ArgAction::Set)
+                .value_name("SECONDS")
+                .conflicts_with("no-shell")
+                .help("Use the given time (in seconds) as the shell spawning time that is subtracted \
+                       from the wall clock time of each run, instead of measuring it before the \
+                       first benchmark. Set this to 0 to disable the correction. The measured \
+                       spawning time is shown when '--show-output' is used."),
+        )
         .arg(
             Arg::new("ignore-failure")
                 .long("ignore-failure")
@@ -236,6 +344,57 @@ fn build_command() -> Command {
                        'all-non-zero', all non-zero exit codes are ignored. You can also provide \
                        a comma-separated list of exit codes to ignore (e.g., --ignore-failure=1,2)."),
         )
+        .arg(
+            Arg::new("error-exit-code")
+                .long("error-exit-code")
+                .action(ArgAction::SetTrue)
+                .help("Exit with the first non-zero exit code of the benchmarked commands once \
+                       all benchmarks have finished. In combination with '-i'/'--ignore-failure', \
+                       the statistics of failing commands are still reported and exported, but \
+                       the failure is visible in the exit status of hyperfine, e.g. for CI jobs. \
+                       Note that the exit code of the command is used as it is, so it can not be \
+                       told apart from hyperfine's own exit codes (1: error, 2: invalid \
+                       arguments, 3: a command failed, 4: performance regression)."),
+        )
+        .arg(
+            Arg::new("cpu")
+                .long("cpu")
+                .action(ArgAction::Set)
+                .value_name("LIST")
+                .help("Pin hyperfine and the benchmarked commands to the given CPUs, e.g. \
+                       '--cpu=2' or '--cpu=0,2-3'. This avoids measurement noise from migrations \
+                       between CPUs. Only supported on Linux."),
+        )
+        .arg(
+            Arg::new("nice")
+                .long("nice")
+                .action(ArgAction::Set)
+                .allow_negative_numbers(true)
+                .value_name("VALUE")
+                .help("Run the benchmarked commands with the given nice value (from -20 to 19). \
+                       Setting a negative value usually requires elevated privileges. If the \
+                       priority can not be set, a warning is shown and the commands run at the \
+                       priority of hyperfine. Not supported on Windows."),
+        )
+        .arg(
+            Arg::new("realtime")
+                .long("realtime")
+                .action(ArgAction::SetTrue)
+                .help("Run the benchmarked commands with the real-time scheduling policy \
+                       SCHED_FIFO (at its lowest priority). This usually requires elevated \
+                       privileges. If the policy can not be set, a warning is shown and the \
+                       commands run with the policy of hyperfine. Only supported on Linux."),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .action(ArgAction::Set)
+                .value_name("SECONDS")
+                .help("Kill a command (including all processes that it started) if a single run \
+                       takes longer than the given number of seconds. A timed-out run counts as \
+                       failed: hyperfine aborts unless all failures are ignored with \
+                       '-i'/'--ignore-failure', in which case a warning is shown."),
+        )
         .arg(
             Arg::new("style")
                 .long("style")
@@ -251,12 +410,46 @@ fn build_command() -> Command {
                      the output of the tool.",
                 ),
         )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Do not print the results of the individual benchmarks. Warnings and the \
+                     final comparison of all benchmarks are still shown.",
+                ),
+        )
+        .arg(
+            Arg::new("progress-json")
+                .long("progress-json")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Write the progress as newline-delimited JSON events to stderr instead of \
+                     showing a progress bar, e.g. for graphical frontends. Events are emitted \
+                     after every warmup run ({\"event\":\"warmup\",\"benchmark\":0,\"run\":3}), \
+                     every timing run ({\"event\":\"measure\",...}) and after each benchmark \
+                     ({\"event\":\"benchmark_done\",\"index\":0,\"mean\":...}). Other lines on \
+                     stderr, like warnings, are not JSON.",
+                ),
+        )
+        .arg(
+            Arg::new("progress-interval")
+                .long("progress-interval")
+                .action(ArgAction::Set)
+                .value_name("MS")
+                .help(
+                    "Update the progress bar at most once every MS milliseconds instead of after \
+                     every run. Updates in between are coalesced. For very fast commands, this \
+                     reduces the overhead (and noise) that is caused by frequently redrawing the \
+                     progress bar.",
+                ),
+        )
         .arg(
             Arg::new("sort")
             .long("sort")
             .action(ArgAction::Set)
             .value_name("METHOD")
-            .value_parser(["auto", "command", "mean-time"])
+            .value_parser(["auto", "command", "mean-time", "name", "none"])
             .default_value("auto")
             .hide_default_value(true)
             .help(
@@ -265,24 +458,133 @@ fn build_command() -> Command {
                    * 'auto' (default): the speed comparison will be ordered by time and\n    \
                      the markup tables will be ordered by command (input order).\n  \
                    * 'command': order benchmarks in the way they were specified\n  \
-                   * 'mean-time': order benchmarks by mean runtime\n"
+                   * 'mean-time': order benchmarks by mean runtime (fastest first)\n  \
+                   * 'name': order benchmarks lexicographically by command name\n  \
+                   * 'none': same as 'command'\n"
+            ),
+        )
+        .arg(
+            Arg::new("weight-file")
+            .long("weight-file")
+            .action(ArgAction::Set)
+            .value_name("FILE")
+            .value_hint(ValueHint::FilePath)
+            .help(
+                "Compute weighted statistics for commands that perform a varying amount of \
+                 work per run. After each benchmark run, a single non-negative number (e.g. the \
+                 number of bytes processed in that run) is read from FILE, which the command is \
+                 expected to write. Each run then contributes to the weighted mean and standard \
+                 deviation in proportion to its weight: mean = Σ w·t / Σ w. The unweighted \
+                 statistics are still reported."
+            ),
+        )
+        .arg(
+            Arg::new("binary-size")
+            .long("binary-size")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Report the file size of the binary that is run by each command. The binary \
+                 is resolved from the first word of the command line (via PATH, if necessary). \
+                 Command lines that use shell syntax like pipes or command lists require an \
+                 explicit '--binary-path'."
+            ),
+        )
+        .arg(
+            Arg::new("binary-path")
+            .long("binary-path")
+            .action(ArgAction::Append)
+            .value_name("PATH")
+            .value_hint(ValueHint::FilePath)
+            .help(
+                "The binary whose size is reported (implies '--binary-size'). This option can \
+                 be specified once for all commands or multiple times, once for each command."
+            ),
+        )
+        .arg(
+            Arg::new("before-after")
+            .long("before-after")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Compare exactly two commands and show the summary as a before/after \
+                 comparison instead of the usual speed comparison. The first command \
+                 (or the '--reference' command) is 'before', the second one is 'after'. \
+                 The change is reported as a percentage of the 'before' mean time, e.g. \
+                 'change: -12.0% (faster)'."
+            ),
+        )
+        .arg(
+            Arg::new("interleave")
+            .long("interleave")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all([
+                "target-instructions",
+                "precision",
+                "retry-on-variance",
+                "checkpoint",
+            ])
+            .help(
+                "Perform the runs of all commands in turns (one run of each command, then the \
+                 next run of each command, ...) instead of finishing one benchmark before \
+                 starting the next. This way, a slow drift of the system state (e.g. thermal \
+                 throttling) affects all commands equally. The setup and warmup runs of all \
+                 commands are performed first; the results are shown once all runs have \
+                 finished."
+            ),
+        )
+        .arg(
+            Arg::new("shuffle")
+            .long("shuffle")
+            .action(ArgAction::SetTrue)
+            .help(
+                "Perform the benchmarks in a random order instead of the order in which the \
+                 commands were given, to avoid a systematic bias from a drifting thermal or \
+                 cache state. Only the order of the benchmarks changes; all runs of one command \
+                 are still performed together, and '--setup'/'--cleanup' still run once before \
+                 and after each benchmark. The results are reported in the original order."
+            ),
+        )
+        .arg(
+            Arg::new("shuffle-seed")
+            .long("shuffle-seed")
+            .action(ArgAction::Set)
+            .value_name("NUM")
+            .requires("shuffle")
+            .help(
+                "Seed for the random order of '--shuffle', such that the order can be \
+                 reproduced exactly. The seed is stored in the metadata of the JSON export."
             ),
         )
         .arg(
             Arg::new("time-unit")
                 .long("time-unit")
                 .short('u')
+                .visible_alias("output-unit")
                 .action(ArgAction::Set)
                 .value_name("UNIT")
-                .value_parser(["microsecond", "millisecond", "second"])
-                .help("Set the time unit to be used. Possible values: microsecond, millisecond, second. \
+                .value_parser([
+                    "nanosecond",
+                    "microsecond",
+                    "millisecond",
+                    "second",
+                    "minute",
+                    "hour",
+                    "ns",
+                    "us",
+                    "ms",
+                    "s",
+                    "min",
+                    "h",
+                ])
+                .help("Set the time unit to be used. Possible values: nanosecond, microsecond, millisecond, \
+                       second, minute, hour (or short: ns, us, ms, s, min, h). All durations are shown in this unit, even if they \
+                       are very large or small. \
                        If the option is not given, the time unit is determined automatically. \
                        This option affects the standard output as well as all export formats except for CSV and JSON."),
         )
         .arg(
             Arg::new("export-asciidoc")
                 .long("export-asciidoc")
-                .action(ArgAction::Set)
+                .action(ArgAction::Append)
                 .value_name("FILE")
                 .value_hint(ValueHint::FilePath)
                 .help("Export the timing summary statistics as an AsciiDoc table to the given FILE. \
@@ -291,26 +593,146 @@ fn build_command() -> Command {
         .arg(
             Arg::new("export-csv")
                 .long("export-csv")
-                .action(ArgAction::Set)
+                .action(ArgAction::Append)
                 .value_name("FILE")
                 .value_hint(ValueHint::FilePath)
                 .help("Export the timing summary statistics as CSV to the given FILE. If you need \
-                       the timing results for each individual run, use the JSON export format. \
-                       The output time unit is always seconds."),
+                       the timing results for each individual run, use the JSON export format \
+                       or '--export-timings-csv'. The output time unit is always seconds."),
+        )
+        .arg(
+            Arg::new("from-json")
+                .long("from-json")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .conflicts_with("command")
+                .help("Do not run any benchmarks, but load the results from a FILE that has been \
+                       written with '--export-json' before. The relative speed comparison is \
+                       computed and shown for these results, and they can be exported to other \
+                       formats with the '--export-*' options. A reference can be selected with \
+                       '--reference <NUMBER or COMMAND>'."),
+        )
+        .arg(
+            Arg::new("baseline")
+                .long("baseline")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Compare the mean run time of every benchmark with the result of the same \
+                       command (and parameter values) in a FILE that has been written with \
+                       '--export-json' before, and show the relative change."),
+        )
+        .arg(
+            Arg::new("regression-threshold")
+                .long("regression-threshold")
+                .action(ArgAction::Append)
+                .value_name("[METRIC=]PERCENT")
+                .requires("baseline")
+                .help("Fail (with exit status 4) if the mean run time of any benchmark is more \
+                       than PERCENT percent higher than in the '--baseline' results. With \
+                       METRIC=PERCENT (e.g. 'cache-misses=20'), the threshold applies to the mean \
+                       value of the given poop metric instead, which is collected automatically. \
+                       This option can be specified multiple times."),
+        )
+        .arg(
+            Arg::new("export-timings-csv")
+                .long("export-timings-csv")
+                .action(ArgAction::Append)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Export the wall clock, user and system time, the peak memory usage and the \
+                       exit code of each individual run as CSV to the given FILE, with one row \
+                       per run. The output time unit is always seconds."),
+        )
+        .arg(
+            Arg::new("export-influxdb")
+                .long("export-influxdb")
+                .action(ArgAction::Append)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Export the timing summary statistics and the collected performance counters \
+                       in the InfluxDB line protocol to the given FILE, with one point per command. \
+                       All points carry the time at which hyperfine was started. The output time \
+                       unit is always seconds."),
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Save the results of all completed benchmarks to the given FILE after each \
+                       benchmark has finished. Together with '--resume', this allows to continue \
+                       an interrupted benchmark suite."),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .action(ArgAction::SetTrue)
+                .requires("checkpoint")
+                .help("Resume an interrupted benchmark suite from the file given via \
+                       '--checkpoint'. Commands that have already been benchmarked are skipped \
+                       and their saved results are used instead. The checkpoint has to belong \
+                       to the same set of commands. If the file does not exist yet, all \
+                       benchmarks are run."),
         )
         .arg(
             Arg::new("export-json")
                 .long("export-json")
-                .action(ArgAction::Set)
+                .action(ArgAction::Append)
                 .value_name("FILE")
                 .value_hint(ValueHint::FilePath)
                 .help("Export the timing summary statistics and timings of individual runs as JSON to the given FILE. \
-                       The output time unit is always seconds"),
+                       The output time unit is always seconds. Like all other export options, this \
+                       can be given multiple times (also in combination with other formats), as long \
+                       as every FILE is only used once."),
+        )
+        .arg(
+            Arg::new("export-json-binary")
+                .long("export-json-binary")
+                .action(ArgAction::Append)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Export the results in the same format as '--export-json', but store the \
+                       timings of individual runs as base64-encoded arrays of little-endian 64-bit \
+                       floats ('times_binary' field). This is much more compact for benchmarks \
+                       with a large number of runs. The output time unit is always seconds."),
+        )
+        .arg(
+            Arg::new("json-pretty")
+                .long("json-pretty")
+                .action(ArgAction::SetTrue)
+                .overrides_with("json-compact")
+                .help("Indent the output of '--export-json' and '--export-json-binary', with one \
+                       field per line. This is the default. The fields are always written in the \
+                       same order, such that the exports of two benchmark sessions can be \
+                       compared with a line-based diff."),
+        )
+        .arg(
+            Arg::new("json-compact")
+                .long("json-compact")
+                .action(ArgAction::SetTrue)
+                .overrides_with("json-pretty")
+                .help("Write the output of '--export-json' and '--export-json-binary' on a single \
+                       line, without any whitespace."),
+        )
+        .arg(
+            Arg::new("export-jsonl")
+                .long("export-jsonl")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Write each individual run to the given FILE as soon as it has completed, \
+                       as one JSON object per line (JSON Lines). The objects contain the index of \
+                       the benchmark and run, the wall clock, user and system time, the exit code \
+                       and the collected performance counters. This allows to follow long \
+                       benchmarks live and retains partial results if hyperfine is interrupted."),
         )
         .arg(
             Arg::new("export-markdown")
                 .long("export-markdown")
-                .action(ArgAction::Set)
+                .action(ArgAction::Append)
                 .value_name("FILE")
                 .value_hint(ValueHint::FilePath)
                 .help("Export the timing summary statistics as a Markdown table to the given FILE. \
@@ -319,12 +741,44 @@ fn build_command() -> Command {
         .arg(
             Arg::new("export-orgmode")
                 .long("export-orgmode")
-                .action(ArgAction::Set)
+                .action(ArgAction::Append)
                 .value_name("FILE")
                 .value_hint(ValueHint::FilePath)
                 .help("Export the timing summary statistics as an Emacs org-mode table to the given FILE. \
                        The output time unit can be changed using the --time-unit option."),
         )
+        .arg(
+            Arg::new("export-prometheus")
+                .long("export-prometheus")
+                .action(ArgAction::Append)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Export the timing summary statistics and the collected performance counters \
+                       in the Prometheus text exposition format to the given FILE, e.g. for the \
+                       textfile collector of node_exporter. The output time unit is always seconds."),
+        )
+        .arg(
+            Arg::new("export-sqlite")
+                .long("export-sqlite")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .help("Append the timing summary statistics and all individual runs (including \
+                       their exit codes, memory usage and performance counters) to the SQLite \
+                       database in the given FILE. The database is created if it does not exist. \
+                       This requires hyperfine to be built with the 'sqlite' feature."),
+        )
+        .arg(
+            Arg::new("description")
+                .long("description")
+                .action(ArgAction::Append)
+                .num_args(1)
+                .value_name("TEXT")
+                .help("Attach a free-form note to this benchmark session, e.g. \"after kernel \
+                       upgrade\". The note is shown before the first benchmark and stored in \
+                       the 'metadata' block of the JSON export. This option can be specified \
+                       multiple times to add several notes."),
+        )
         .arg(
             Arg::new("show-output")
                 .long("show-output")
@@ -337,6 +791,18 @@ fn build_command() -> Command {
                      when trying to benchmark output speed.",
                 ),
         )
+        .arg(
+            Arg::new("show-output-on-failure")
+                .long("show-output-on-failure")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("show-output")
+                .help(
+                    "Capture the output of the benchmark (which is otherwise discarded) and print \
+                     it for runs that fail, e.g. to debug intermittent failures. Only the last \
+                     bytes of the output are kept, see '--output-capture-limit'. The output is \
+                     fed through a pipe, like with '--output=pipe'.",
+                ),
+        )
         .arg(
             Arg::new("output")
                 .long("output")
@@ -364,6 +830,18 @@ fn build_command() -> Command {
                     hyperfine 'my-command > output-${HYPERFINE_ITERATION}.log'\n\n",
                 ),
         )
+        .arg(
+            Arg::new("output-capture-limit")
+                .long("output-capture-limit")
+                .action(ArgAction::Set)
+                .value_name("BYTES")
+                .help(
+                    "Maximum number of bytes of output that hyperfine keeps in memory when it \
+                     captures the output of a command, e.g. to show the output of a failing \
+                     setup, preparation, conclusion, or cleanup command. Only the last BYTES \
+                     bytes are kept, older output is discarded. Default: 65536.",
+                ),
+        )
         .arg(
             Arg::new("input")
                 .long("input")
@@ -378,6 +856,35 @@ fn build_command() -> Command {
                        \n  \
                          <FILE>:   Read the input from the given file."),
         )
+        .arg(
+            Arg::new("input-string")
+                .long("input-string")
+                .action(ArgAction::Set)
+                .num_args(1)
+                .value_name("TEXT")
+                .conflicts_with("input")
+                .help("Pass the given text to the standard input of the benchmark. The text is \
+                       fed to every run anew."),
+        )
+        .arg(
+            Arg::new("clear-env")
+                .long("clear-env")
+                .action(ArgAction::SetTrue)
+                .help("Run all commands (including setup, preparation, conclusion, and cleanup \
+                       commands) with an empty environment instead of the environment of \
+                       hyperfine, such that the results do not depend on it. Only a minimal PATH \
+                       (and the HYPERFINE_* variables) is set, more variables can be passed \
+                       with '--env'."),
+        )
+        .arg(
+            Arg::new("env")
+                .long("env")
+                .action(ArgAction::Append)
+                .value_name("KEY=VALUE")
+                .help("Set the environment variable KEY to VALUE for all commands (including \
+                       setup, preparation, conclusion, and cleanup commands). The option can be \
+                       specified multiple times."),
+        )
         .arg(
             Arg::new("command-name")
                 .long("command-name")
@@ -399,13 +906,111 @@ fn build_command() -> Command {
                    benchmark runs is additionally influenced by the `--min-runs`, `--max-runs`, and \
                    `--runs` option.")
         )
+        .arg(
+            Arg::new("retry-on-variance")
+                .long("retry-on-variance")
+                .action(ArgAction::Set)
+                .value_name("PERCENT")
+                .help("Retry the benchmark of a command if the relative standard deviation of \
+                       its run times exceeds PERCENT (e.g. '5' for 5%). All results of the noisy \
+                       attempt are discarded and the full measurement phase is repeated, up to \
+                       '--retry-attempts' times. The least noisy attempt is reported, and a \
+                       warning is shown if the target has never been met."),
+        )
+        .arg(
+            Arg::new("retry-attempts")
+                .long("retry-attempts")
+                .action(ArgAction::Set)
+                .value_name("NUM")
+                .requires("retry-on-variance")
+                .help("Maximum number of measurement attempts per command when using \
+                       '--retry-on-variance' (default: 3)."),
+        )
+        .arg(
+            Arg::new("precision")
+                .long("precision")
+                .action(ArgAction::Set)
+                .value_name("PERCENT")
+                .conflicts_with("target-instructions")
+                .help("Keep performing benchmark runs until the 95% confidence interval of the \
+                       mean is narrower than ±PERCENT of the mean (e.g. '1' for 1%), instead of \
+                       deriving the number of runs from a time budget. '--min-runs' and \
+                       '--max-runs' are still respected, and a warning is shown if the \
+                       maximum number of runs is reached before the precision."),
+        )
+        .arg(
+            Arg::new("drop-outliers")
+                .long("drop-outliers")
+                .action(ArgAction::SetTrue)
+                .help("Exclude statistical outliers from the mean, standard deviation, median, \
+                       minimum and maximum. These are the runs that would otherwise trigger the \
+                       outlier warning. All runs are still included in the exported run times. \
+                       At least two runs are always kept."),
+        )
+        .arg(
+            Arg::new("robust-stats")
+                .long("robust-stats")
+                .action(ArgAction::SetTrue)
+                .help("Show robust statistics of the run times after each benchmark: the mean \
+                       without the fastest and the slowest 10% of the runs (trimmed mean), and \
+                       the median absolute deviation (MAD). They are less affected by outliers \
+                       than the mean and the standard deviation."),
+        )
+        .arg(
+            Arg::new("histogram")
+                .long("histogram")
+                .action(ArgAction::SetTrue)
+                .help("Show a histogram of the run times after each benchmark. This reveals \
+                       multimodal distributions that are hidden by the mean and standard \
+                       deviation."),
+        )
+        .arg(
+            Arg::new("histogram-bins")
+                .long("histogram-bins")
+                .action(ArgAction::Set)
+                .value_name("NUM")
+                .requires("histogram")
+                .help("Number of bins of the histogram (default: the square root of the number \
+                       of runs)."),
+        )
+        .arg(
+            Arg::new("show-setup-time")
+                .long("show-setup-time")
+                .visible_alias("setup-time-shown")
+                .action(ArgAction::SetTrue)
+                .help("Show the mean wall clock time of the '--prepare' and '--conclude' \
+                       commands below the result of each benchmark. This reveals whether the \
+                       preparation (e.g. dropping caches) takes much longer than the benchmarked \
+                       command itself. The means are always included in the JSON export."),
+        )
+        .arg(
+            Arg::new("confidence-level")
+                .long("confidence-level")
+                .action(ArgAction::Set)
+                .value_name("PERCENT")
+                .help("Confidence level of the interval that is reported for the mean run time \
+                       (default: 95). The interval is estimated by bootstrapping, i.e. by \
+                       resampling the measured run times, which does not assume that they are \
+                       normally distributed."),
+        )
+        .arg(
+            Arg::new("bootstrap-resamples")
+                .long("bootstrap-resamples")
+                .action(ArgAction::Set)
+                .value_name("NUM")
+                .help("Number of bootstrap resamples used to estimate the confidence interval \
+                       of the mean run time (default: 10000). For benchmarks with very many \
+                       runs, fewer resamples are used to limit the computation time."),
+        )
         .arg(
             Arg::new("metrics")
             .long("metrics")
             .action(ArgAction::SetTrue)
             .help("Enable collection of poop [https://github.com/andrewrk/poop] like performance metrics (Linux only). \
                    This will collect CPU cycles, instructions, cache references/misses, \
-                   branches/branch-misses, and page faults using Linux perf_event_open. \
+                   branches/branch-misses, frontend/backend stall cycles, L1 data cache and last \
+                   level cache loads/misses, TLB misses, page faults, context switches, CPU \
+                   migrations, and system calls using Linux perf_event_open. \
                    Note: May require adjusted permissions (see /proc/sys/kernel/perf_event_paranoid).")
         )
         .arg(
@@ -413,14 +1018,139 @@ fn build_command() -> Command {
             .long("metric")
             .action(ArgAction::Append)
             .value_name("TYPE")
-            .value_parser(["cpu-cycles", "instructions", "cache-references", "cache-misses", 
-                          "branches", "branch-misses", "page-faults"])
             .help("Enable collection of specific poop [https://github.com/andrewrk/poop] like performance metric(s). \
                    This option can be specified multiple times to collect multiple metrics. \
                    Available metrics: cpu-cycles, instructions, cache-references, cache-misses, \
-                   branches, branch-misses, page-faults. \
+                   branches, branch-misses, stalled-cycles-frontend, stalled-cycles-backend, \
+                   ref-cycles, bus-cycles, l1-dcache-loads, l1-dcache-load-misses, llc-loads, \
+                   llc-load-misses, dtlb-load-misses, itlb-load-misses, page-faults, \
+                   context-switches, cpu-migrations, syscalls (see '--list-metrics' for all \
+                   accepted names). \
+                   Raw, CPU-specific events can be given as 'r<hex>' (e.g. 'r003c'), like with \
+                   'perf stat'. They are reported by their hex code. \
+                   The 'syscalls' metric is based on the raw_syscalls:sys_enter tracepoint and \
+                   requires read access to tracefs (/sys/kernel/tracing). \
                    If not specified but --metrics is used, all metrics will be collected.")
         )
+        .arg(
+            Arg::new("compare-metric")
+            .long("compare-metric")
+            .action(ArgAction::Append)
+            .value_name("METRIC")
+            .value_parser(
+                [
+                    vec![
+                        PossibleValue::new("ipc").alias("instructions-per-cycle"),
+                        PossibleValue::new("cache-miss-rate"),
+                        PossibleValue::new("branch-miss-rate"),
+                    ],
+                    metric_values(),
+                ]
+                .concat(),
+            )
+            .help("Compare the given performance metric across commands in the summary, in \
+                   addition to the speed comparison (Linux only). METRIC can be 'ipc', \
+                   'cache-miss-rate', 'branch-miss-rate', or any of the counters accepted by \
+                   '--metric'. The required counters are collected automatically. This option \
+                   can be specified multiple times.")
+        )
+        .arg(
+            Arg::new("target-instructions")
+            .long("target-instructions")
+            .action(ArgAction::Set)
+            .value_name("NUM")
+            .help("Keep performing benchmark runs until the command has executed a total of NUM \
+                   instructions (summed over all runs), instead of deriving the number of runs \
+                   from a time budget. Unlike a time budget, this makes the number of runs \
+                   independent of the speed of the machine, which is useful for comparing \
+                   results across machines. '--min-runs' and '--max-runs' are still respected. \
+                   Requires the 'instructions' counter (see '--metrics'); hyperfine stops with \
+                   an error if it can not be collected.")
+        )
+        .arg(
+            Arg::new("metrics-per-second")
+            .long("metrics-per-second")
+            .action(ArgAction::SetTrue)
+            .help("In addition to the raw counts, show the collected performance metrics \
+                   divided by the mean run time (e.g. cache misses per second). Raw counts \
+                   grow with the run time, so the normalized values are better suited for \
+                   comparing commands with different run times. Both values are included in \
+                   the JSON export.")
+        )
+        .arg(
+            Arg::new("reference-frequency")
+            .long("reference-frequency")
+            .action(ArgAction::Set)
+            .value_name("GHZ")
+            .help("Show an estimate of the run time on a CPU with a fixed clock frequency of \
+                   GHZ, computed as the number of CPU cycles divided by the frequency. This is \
+                   useful for comparisons across machines, but it is only an estimate: it \
+                   assumes that the cycle count does not depend on the frequency (which \
+                   ignores memory stalls) and does not include time spent waiting. Only shown \
+                   if CPU cycles are collected (see '--metrics').")
+        )
+        .arg(
+            Arg::new("no-external-variance-warning")
+            .long("no-external-variance-warning")
+            .action(ArgAction::SetTrue)
+            .help("Do not warn if the wall clock times of a command vary a lot while its CPU \
+                   cycle counts are stable (which points to I/O, scheduling, or frequency \
+                   scaling as the cause). This check is only performed if CPU cycles are \
+                   collected.")
+        )
+        .arg(
+            Arg::new("require-metrics")
+            .long("require-metrics")
+            .action(ArgAction::SetTrue)
+            .help("Fail with an error instead of showing a warning if performance metrics \
+                   have been requested (via '--metrics', '--metric', or '--compare-metric') \
+                   but could not be collected.")
+        )
+        .arg(
+            Arg::new("metrics-leader-only")
+            .long("metrics-leader-only")
+            .action(ArgAction::SetTrue)
+            .help("Only count the performance metrics of the process that hyperfine starts for \
+                   every run (usually the shell), but not of its child processes and threads. \
+                   By default, the counters are attached before the process starts, and all \
+                   descendants are counted as well. Note that this includes the startup of the \
+                   shell itself, unless the shell directly executes the command.")
+        )
+        .arg(
+            Arg::new("cpu-time")
+            .long("cpu-time")
+            .action(ArgAction::Set)
+            .value_name("WHAT")
+            .value_parser(["children", "include-self"])
+            .help("Select which processes are taken into account for the reported user and \
+                   system times:\n  \
+                     * 'children' (default): the CPU time of the benchmarked process and of all \
+                   its descendants that have terminated and been waited for. If the command \
+                   is run through a shell, this includes the shell itself, whose startup time \
+                   is subtracted afterwards. Background processes that outlive the command \
+                   are not included.\n  \
+                     * 'include-self': additionally include the CPU time that hyperfine itself \
+                   spends while the command is running, for example to read its output when \
+                   using '--output=pipe' (Unix only).")
+        )
+        .arg(
+            Arg::new("list-metrics")
+            .long("list-metrics")
+            .action(ArgAction::SetTrue)
+            .exclusive(true)
+            .help("Print all metrics that can be selected via '--metric', together with their \
+                   accepted names and whether the corresponding counter can be opened on this \
+                   system. Then exit.")
+        )
+        .arg(
+            Arg::new("debug-perf")
+            .long("debug-perf")
+            .action(ArgAction::SetTrue)
+            .help("Print the raw values that the kernel returned for each perf counter to \
+                   stderr after every run, together with the counter type and config value \
+                   (Linux only). This is a diagnostic for the metrics collector itself. \
+                   Implies '--metrics' if no metrics have been selected otherwise.")
+        )
         .arg(
             Arg::new("debug-mode")
             .long("debug-mode")
@@ -434,3 +1164,19 @@ fn build_command() -> Command {
 fn verify_app() {
     build_command().debug_assert();
 }
+
+#[test]
+fn metric_values_match_metric_types() {
+    use crate::poop_metrics::MetricType;
+
+    let values = metric_values();
+    let names: Vec<Vec<&str>> = values
+        .iter()
+        .map(|value| value.get_name_and_aliases().collect())
+        .collect();
+    let expected: Vec<Vec<&str>> = MetricType::all()
+        .iter()
+        .map(|metric| metric.cli_names().to_vec())
+        .collect();
+    assert_eq!(names, expected);
+}
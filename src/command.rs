@@ -1,8 +1,11 @@
 use std::collections::BTreeMap;
+use std::env;
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::parameter::tokenize::tokenize;
+use crate::parameter::tokenize::{tokenize, tokenize_lines};
 use crate::parameter::ParameterValue;
 use crate::{
     error::{OptionsError, ParameterScanError},
@@ -14,9 +17,56 @@ use crate::{
 
 use clap::{parser::ValuesRef, ArgMatches};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use rust_decimal::Decimal;
 
+/// Characters that have a special meaning in a POSIX shell when they are not quoted
+const SHELL_SYNTAX: &[char] = &['|', '&', ';', '<', '>', '(', ')', '$', '`', '\n'];
+
+/// Control operators of a POSIX shell. Unlike a shell, the argument splitter that is used when
+/// running commands without a shell does not treat them as separate words, so they would end up
+/// in the arguments of the command instead of starting a pipeline, command list or redirection.
+const SHELL_OPERATORS: &[char] = &['|', '&', ';', '<', '>', '\n'];
+
+/// Find the first unquoted shell operator in the command line, taking quotes and escapes into
+/// account. Other shell syntax (globs, variables, ...) is passed on literally without a shell.
+fn find_shell_syntax(command_line: &str) -> Option<char> {
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+
+    let mut chars = command_line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if in_single_quotes => in_single_quotes = c != '\'',
+            '\\' => {
+                chars.next();
+            }
+            '"' => in_double_quotes = !in_double_quotes,
+            _ if in_double_quotes => {}
+            '\'' => in_single_quotes = true,
+            _ if SHELL_OPERATORS.contains(&c) => return Some(c),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Maximum number of benchmarks that a parameter sweep may result in, unless '--max-benchmarks'
+/// or '--force' is given
+const DEFAULT_MAX_BENCHMARKS: usize = 500;
+
+/// Read the values of a parameter from a file (see `--parameter-list-file`)
+fn read_parameter_values_file(path: &str) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Could not read parameter values file '{path}'"))?;
+    let values = tokenize_lines(&content);
+    if values.is_empty() {
+        bail!("The parameter values file '{path}' does not contain any values");
+    }
+    Ok(values)
+}
+
 /// A command that should be benchmarked.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Command<'a> {
@@ -51,6 +101,11 @@ impl<'a> Command<'a> {
         }
     }
 
+    /// Whether an explicit name has been given for this command (see `--command-name`)
+    pub fn has_name(&self) -> bool {
+        self.name.is_some()
+    }
+
     pub fn get_name(&self) -> String {
         self.name.map_or_else(
             || self.get_command_line(),
@@ -78,7 +133,21 @@ impl<'a> Command<'a> {
         self.replace_parameters_in(self.expression)
     }
 
+    /// Check that the command can be run without a shell, i.e. that it does not contain any
+    /// shell operators that the argument splitter would pass on literally
+    pub fn check_without_shell(&self) -> Result<(), OptionsError<'static>> {
+        let command_line = self.get_command_line();
+        match find_shell_syntax(&command_line) {
+            Some(c) => Err(OptionsError::ShellSyntaxWithoutShell(command_line, c)),
+            None => Ok(()),
+        }
+    }
+
+    /// Build a process that runs this command directly, without a shell. The command line is
+    /// split into arguments like a POSIX shell would do it (honoring quotes and escapes), but
+    /// shell operators like pipes or '&&' result in an error.
     pub fn get_command(&self) -> Result<std::process::Command> {
+        self.check_without_shell()?;
         let command_line = self.get_command_line();
         let mut tokens = shell_words::split(&command_line)
             .with_context(|| format!("Failed to parse command '{command_line}'"))?
@@ -93,6 +162,50 @@ impl<'a> Command<'a> {
         }
     }
 
+    /// Resolve the path of the executable that is started by this command. This fails for
+    /// command lines that make use of shell syntax (pipelines, command lists, redirections,
+    /// variables, ...), since the binary can not be determined unambiguously in that case.
+    pub fn resolve_binary_path(&self) -> Result<PathBuf> {
+        let command_line = self.get_command_line();
+        let ambiguous = || {
+            anyhow!(
+                "Could not determine the binary of the command '{command_line}' unambiguously. \
+                 Use '--binary-path' to specify it explicitly."
+            )
+        };
+
+        if command_line.contains(SHELL_SYNTAX) {
+            return Err(ambiguous());
+        }
+
+        let tokens = shell_words::split(&command_line).map_err(|_| ambiguous())?;
+        let program = match tokens.first() {
+            Some(program) if !program.contains('=') => program,
+            _ => return Err(ambiguous()),
+        };
+
+        let path = Path::new(program);
+        if path.components().count() > 1 {
+            return Ok(path.to_path_buf());
+        }
+
+        env::var_os("PATH")
+            .iter()
+            .flat_map(env::split_paths)
+            .flat_map(|dir| {
+                let candidate = dir.join(program);
+                let with_extension = cfg!(windows).then(|| candidate.with_extension("exe"));
+                std::iter::once(candidate).chain(with_extension)
+            })
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Could not find the binary '{program}' of the command '{command_line}' in \
+                     PATH. Use '--binary-path' to specify it explicitly."
+                )
+            })
+    }
+
     pub fn get_parameters(&self) -> &[(&'a str, ParameterValue)] {
         &self.parameters
     }
@@ -146,18 +259,36 @@ impl<'a> Commands<'a> {
             let step_size = matches
                 .get_one::<String>("parameter-step-size")
                 .map(|s| s.as_str());
-            Ok(Self(Self::get_parameter_scan_commands(
+            let param_name = args.clone().next().map_or("", |name| name.as_str());
+            let num_command_strings = command_strings.len();
+            let check_count = |num_values| {
+                Self::check_benchmark_count(
+                    matches,
+                    &[(num_command_strings, None), (num_values, Some(param_name))],
+                )
+            };
+            let commands = Self::get_parameter_scan_commands(
                 command_names,
                 command_strings,
                 args,
                 step_size,
-            )?))
-        } else if let Some(args) = matches.get_many::<String>("parameter-list") {
+                check_count,
+            )?;
+            Ok(Self(commands))
+        } else if matches.get_many::<String>("parameter-list").is_some()
+            || matches.get_many::<String>("parameter-list-file").is_some()
+        {
             let command_names = command_names.map_or(vec![], |names| {
                 names.map(|v| v.as_str()).collect::<Vec<_>>()
             });
-            let args: Vec<_> = args.map(|v| v.as_str()).collect::<Vec<_>>();
-            let param_names_and_values: Vec<(&str, Vec<String>)> = args
+            let pairs = |id| {
+                matches
+                    .get_many::<String>(id)
+                    .unwrap_or_default()
+                    .map(|v| v.as_str())
+                    .collect::<Vec<_>>()
+            };
+            let mut param_names_and_values: Vec<(&str, Vec<String>)> = pairs("parameter-list")
                 .chunks_exact(2)
                 .map(|pair| {
                     let name = pair[0];
@@ -165,11 +296,14 @@ impl<'a> Commands<'a> {
                     (name, tokenize(list_str))
                 })
                 .collect();
+            for pair in pairs("parameter-list-file").chunks_exact(2) {
+                param_names_and_values.push((pair[0], read_parameter_values_file(pair[1])?));
+            }
             {
                 let duplicates =
                     Self::find_duplicates(param_names_and_values.iter().map(|(name, _)| *name));
                 if !duplicates.is_empty() {
-                    bail!("Duplicate parameter names: {}", &duplicates.join(", "));
+                    return Err(OptionsError::DuplicateParameterNames(duplicates.join(", ")).into());
                 }
             }
 
@@ -185,6 +319,13 @@ impl<'a> Commands<'a> {
                 return Ok(Self(Vec::new()));
             }
 
+            let names = std::iter::once(None)
+                .chain(param_names_and_values.iter().map(|(name, _)| Some(*name)));
+            Self::check_benchmark_count(
+                matches,
+                &dimensions.iter().copied().zip(names).collect::<Vec<_>>(),
+            )?;
+
             // `--command-name` should appear exactly once or exactly B times,
             // where B is the total number of benchmarks.
             let command_name_count = command_names.len();
@@ -247,6 +388,43 @@ impl<'a> Commands<'a> {
         }
     }
 
+    /// Abort if a parameter sweep results in more benchmarks than allowed by '--max-benchmarks',
+    /// unless '--force' is given. The total number of benchmarks is the product of the given
+    /// dimensions: the number of commands, and the number of values of each parameter.
+    fn check_benchmark_count(
+        matches: &ArgMatches,
+        dimensions: &[(usize, Option<&str>)],
+    ) -> Result<()> {
+        if matches.get_flag("force") {
+            return Ok(());
+        }
+
+        let max_benchmarks = match matches.get_one::<String>("max-benchmarks") {
+            Some(max) => max
+                .parse::<usize>()
+                .map_err(|e| OptionsError::IntParsingError("max-benchmarks", e))?,
+            None => DEFAULT_MAX_BENCHMARKS,
+        };
+
+        let count = dimensions.iter().map(|(n, _)| n).product::<usize>();
+        if count > max_benchmarks {
+            let breakdown = dimensions
+                .iter()
+                .map(|(n, parameter)| {
+                    let plural = if *n == 1 { "" } else { "s" };
+                    match parameter {
+                        None => format!("{n} command{plural}"),
+                        Some(name) => format!("{n} value{plural} of '{name}'"),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" × ");
+            return Err(OptionsError::TooManyBenchmarks(count, breakdown, max_benchmarks).into());
+        }
+
+        Ok(())
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &Command<'a>> {
         self.0.iter()
     }
@@ -268,6 +446,8 @@ impl<'a> Commands<'a> {
             .collect()
     }
 
+    /// Build the commands of a parameter scan. The number of parameter values is passed to
+    /// `check_count` before any command is built, such that huge scans fail early.
     fn build_parameter_scan_commands<'b, T: Numeric>(
         param_name: &'b str,
         param_min: T,
@@ -275,8 +455,10 @@ impl<'a> Commands<'a> {
         step: T,
         command_names: Vec<&'b str>,
         command_strings: Vec<&'b str>,
-    ) -> Result<Vec<Command<'b>>, ParameterScanError> {
+        check_count: impl FnOnce(usize) -> Result<()>,
+    ) -> Result<Vec<Command<'b>>> {
         let param_range = RangeStep::new(param_min, param_max, step)?;
+        check_count(param_range.clone().count())?;
         let command_name_count = command_names.len();
 
         let mut i = 0;
@@ -303,7 +485,8 @@ impl<'a> Commands<'a> {
             return Err(ParameterScanError::UnexpectedCommandNameCount(
                 command_name_count,
                 command_count,
-            ));
+            )
+            .into());
         }
 
         Ok(commands)
@@ -314,7 +497,8 @@ impl<'a> Commands<'a> {
         command_strings: Vec<&'b str>,
         mut vals: ValuesRef<'b, String>,
         step: Option<&str>,
-    ) -> Result<Vec<Command<'b>>, ParameterScanError> {
+        check_count: impl FnOnce(usize) -> Result<()>,
+    ) -> Result<Vec<Command<'b>>> {
         let command_names = command_names.map_or(vec![], |names| {
             names.map(|v| v.as_str()).collect::<Vec<_>>()
         });
@@ -335,18 +519,19 @@ impl<'a> Commands<'a> {
                 step,
                 command_names,
                 command_strings,
+                check_count,
             );
         }
 
         // try parsing them as decimals
-        let param_min = Decimal::from_str(param_min)?;
-        let param_max = Decimal::from_str(param_max)?;
+        let param_min = Decimal::from_str(param_min).map_err(ParameterScanError::from)?;
+        let param_max = Decimal::from_str(param_max).map_err(ParameterScanError::from)?;
 
         if step.is_none() {
-            return Err(ParameterScanError::StepRequired);
+            return Err(ParameterScanError::StepRequired.into());
         }
 
-        let step = Decimal::from_str(step.unwrap())?;
+        let step = Decimal::from_str(step.unwrap()).map_err(ParameterScanError::from)?;
         Self::build_parameter_scan_commands(
             param_name,
             param_min,
@@ -354,10 +539,97 @@ impl<'a> Commands<'a> {
             step,
             command_names,
             command_strings,
+            check_count,
         )
     }
 }
 
+#[cfg(unix)]
+#[test]
+fn test_resolve_binary_path() {
+    let path = Command::new(None, "/bin/sh -c true")
+        .resolve_binary_path()
+        .unwrap();
+    assert_eq!(path, PathBuf::from("/bin/sh"));
+
+    let path = Command::new(None, "sh -c true")
+        .resolve_binary_path()
+        .unwrap();
+    assert!(path.ends_with("sh"));
+    assert!(path.is_absolute());
+
+    for ambiguous in [
+        "sh -c true | cat",
+        "cd /tmp && ls",
+        "FOO=1 ls",
+        "$SHELL",
+        "",
+    ] {
+        let error = Command::new(None, ambiguous)
+            .resolve_binary_path()
+            .unwrap_err();
+        assert!(error.to_string().contains("unambiguously"), "{}", ambiguous);
+    }
+
+    let error = Command::new(
+        None,
+        "some-nonexisting-program-b5d9574198b7e4b12a71fa4747c0a577",
+    )
+    .resolve_binary_path()
+    .unwrap_err();
+    assert!(error.to_string().contains("in PATH"));
+}
+
+#[test]
+fn test_find_shell_syntax() {
+    for literal in [
+        "sleep 0.1",
+        "grep 'a|b' file",
+        "echo \"a && b\" 'it'\\''s'",
+        "echo a\\;b \\>",
+        // Passed on literally, as there is no shell to expand them
+        "ls *.rs file? [ab]",
+        "echo $HOME `date` ~/file (a)",
+        "",
+    ] {
+        assert_eq!(find_shell_syntax(literal), None, "{}", literal);
+    }
+
+    for (command_line, syntax) in [
+        ("sort input | uniq", '|'),
+        ("make && ./run", '&'),
+        ("sleep 1 &", '&'),
+        ("echo foo > out", '>'),
+        ("wc -l < input", '<'),
+        ("echo 'a' ; echo b", ';'),
+        ("echo a\necho b", '\n'),
+    ] {
+        assert_eq!(
+            find_shell_syntax(command_line),
+            Some(syntax),
+            "{}",
+            command_line
+        );
+    }
+}
+
+#[test]
+fn test_get_command_without_shell() {
+    let command = Command::new(None, "echo 'a b' \"c\\\"d\" e\\ f")
+        .get_command()
+        .unwrap();
+    assert_eq!(command.get_program(), "echo");
+    assert_eq!(
+        command.get_args().collect::<Vec<_>>(),
+        vec!["a b", "c\"d", "e f"]
+    );
+
+    let error = Command::new(None, "echo a | cat")
+        .get_command()
+        .unwrap_err();
+    assert!(error.to_string().contains("contains '|'"));
+}
+
 #[test]
 fn test_get_command_line_nonoverlapping() {
     let cmd = Command::new_parametrized(
@@ -515,6 +787,7 @@ fn test_parameter_scan_commands_int() {
         3i32,
         vec![],
         vec!["echo {val}"],
+        |_| Ok(()),
     )
     .unwrap();
     assert_eq!(commands.len(), 3);
@@ -535,6 +808,7 @@ fn test_parameter_scan_commands_decimal() {
         step,
         vec![],
         vec!["echo {val}"],
+        |_| Ok(()),
     )
     .unwrap();
     assert_eq!(commands.len(), 4);
@@ -542,6 +816,25 @@ fn test_parameter_scan_commands_decimal() {
     assert_eq!(commands[3].get_command_line(), "echo 0.99");
 }
 
+#[test]
+fn test_parameter_scan_commands_checks_count_first() {
+    let mut count = None;
+    let result = Commands::build_parameter_scan_commands(
+        "val",
+        Decimal::from(0),
+        Decimal::from(1),
+        Decimal::from_str("0.1").unwrap(),
+        vec![],
+        vec!["echo {val}"],
+        |n| {
+            count = Some(n);
+            bail!("too many")
+        },
+    );
+    assert_eq!(count, Some(11));
+    assert_eq!(result.unwrap_err().to_string(), "too many");
+}
+
 #[test]
 fn test_parameter_scan_commands_names() {
     let commands = Commands::build_parameter_scan_commands(
@@ -551,6 +844,7 @@ fn test_parameter_scan_commands_names() {
         1i32,
         vec!["name-{val}"],
         vec!["echo {val}"],
+        |_| Ok(()),
     )
     .unwrap();
     assert_eq!(commands.len(), 3);
@@ -570,6 +864,7 @@ fn test_get_specified_command_names() {
         1i32,
         vec!["name-a", "name-b", "name-c"],
         vec!["echo {val}"],
+        |_| Ok(()),
     )
     .unwrap();
     assert_eq!(commands.len(), 3);
@@ -589,9 +884,10 @@ fn test_different_command_name_count_with_parameters() {
         1i32,
         vec!["name-1", "name-2"],
         vec!["echo {val}"],
+        |_| Ok(()),
     );
     assert!(matches!(
-        result.unwrap_err(),
-        ParameterScanError::UnexpectedCommandNameCount(2, 3)
+        result.unwrap_err().downcast_ref(),
+        Some(ParameterScanError::UnexpectedCommandNameCount(2, 3))
     ));
 }
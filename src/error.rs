@@ -3,6 +3,8 @@ use std::num::{self, ParseFloatError, ParseIntError};
 use rust_decimal::Error as DecimalError;
 use thiserror::Error;
 
+use crate::timer::CapturedOutput;
+
 #[derive(Debug, Error)]
 pub enum ParameterScanError {
     #[error("Error while parsing parameter scan arguments ({0})")]
@@ -36,9 +38,15 @@ impl From<DecimalError> for ParameterScanError {
 #[derive(Debug, Error)]
 pub enum OptionsError<'a> {
     #[error(
-        "Conflicting requirements for the number of runs (empty range, min is larger than max)"
+        "Conflicting requirements for the number of runs: '--min-runs' ({0}) is larger than \
+         '--max-runs' ({1})"
+    )]
+    EmptyRunsRange(u64, u64),
+    #[error(
+        "The parameter sweep results in {0} benchmarks ({1}), which is more than the maximum of \
+         {2}. Use '--max-benchmarks' to raise the limit, or '--force' to run all benchmarks anyway."
     )]
-    EmptyRunsRange,
+    TooManyBenchmarks(usize, String, usize),
     #[error("Too many --command-name options: Expected {0} at most")]
     TooManyCommandNames(usize),
     #[error("'--command-name' has been specified {0} times. It has to appear exactly once, or exactly {1} times (number of benchmarks)")]
@@ -47,12 +55,105 @@ pub enum OptionsError<'a> {
     IntParsingError(&'a str, ParseIntError),
     #[error("Could not read numeric floating point argument to '--{0}': {1}")]
     FloatParsingError(&'a str, ParseFloatError),
+    #[error("The argument to '--{0}' has to be a positive number")]
+    NonPositiveArgument(&'a str),
+    #[error("The argument to '--{0}' can not be negative")]
+    NegativeArgument(&'a str),
+    #[error(
+        "The argument to '--confidence-level' has to be a percentage between 0 and 100 (exclusive)"
+    )]
+    ConfidenceLevelOutOfRange,
+    #[error(
+        "Invalid CPU list '{0}' for '--cpu'. Expected a comma-separated list of CPU numbers or \
+         ranges, e.g. '0,2-3'."
+    )]
+    InvalidCpuList(String),
+    #[error("CPU {0} given to '--cpu' is not available: hyperfine may only run on the CPUs {1}")]
+    CpuNotAvailable(usize, String),
+    #[error("The '--cpu' option is only supported on Linux")]
+    CpuAffinityNotSupported,
+    #[error("The argument to '--nice' has to be a nice value between -20 and 19")]
+    NiceOutOfRange,
+    #[error("The '--{0}' option is not supported on this platform")]
+    PriorityNotSupported(&'static str),
+    #[error("The '--cpu-time include-self' option is not supported on this platform")]
+    CpuTimeIncludeSelfNotSupported,
+    #[error("The argument '{0}' to '--runs-for' has to be of the form NAME=VALUE")]
+    InvalidRunCountOverride(String),
+    #[error("The argument '{0}' to '--runs-for' has to be followed by a number of runs")]
+    MissingRunCountOverride(String),
+    #[error("The argument '{0}' to '--env' has to be of the form KEY=VALUE")]
+    InvalidEnvironmentVariable(String),
     #[error("An empty command has been specified for the '--shell <command>' option")]
     EmptyShell,
     #[error("Failed to parse '--shell <command>' expression as command line: {0}")]
     ShellParseError(shell_words::ParseError),
+    #[error("The '--shell-overhead' option can not be used when running commands without a shell")]
+    ShellOverheadWithoutShell,
+    #[error(
+        "The command '{0}' contains '{1}', which would have a special meaning in a shell (pipes, \
+         command lists, redirections). This is not supported when running commands without a \
+         shell ('-N'/'--shell=none'). Quote the character if it should be passed on literally, \
+         or run the command through a shell."
+    )]
+    ShellSyntaxWithoutShell(String, char),
+    #[error(
+        "The '--reference-name' option can not be used if '--reference' refers to one of the \
+         benchmarked commands."
+    )]
+    ReferenceNameForBenchmarkedCommand,
+    #[error("The '--reference-name' option can not be used together with '--from-json'.")]
+    ReferenceNameWithImportedResults,
+    #[error(
+        "The reference '{0}' is neither the number nor the command of one of the imported \
+         benchmark results."
+    )]
+    UnknownImportedReference(String),
+    #[error(
+        "The '--before-after' option requires exactly two benchmark commands (including a \
+         potential reference), but {0} have been given."
+    )]
+    BeforeAfterCommandCount(usize),
+    #[error(
+        "The '--before-after' option requires exactly two benchmark results, but {0} have been \
+         imported."
+    )]
+    BeforeAfterResultCount(usize),
+    #[error(
+        "The '--{0}' option has to be provided just once or N times, where N={1} is the number of \
+         benchmark commands (including a potential reference)."
+    )]
+    UnexpectedOptionCount(&'a str, usize),
+    #[error("The file '{0}' is used for more than one export")]
+    DuplicateExportFile(String),
+    #[error("Duplicate parameter names: {0}")]
+    DuplicateParameterNames(String),
     #[error("Unknown output policy '{0}'. Use './{0}' to output to a file named '{0}'.")]
     UnknownOutputPolicy(String),
     #[error("The file '{0}' specified as '--input' does not exist")]
     StdinDataFileDoesNotExist(String),
+    #[error(
+        "Invalid argument to '--metric': {0}. Raw, CPU-specific events can be given as 'r<hex>'."
+    )]
+    UnknownMetric(String),
+    #[error("Invalid argument to '--regression-threshold': {0}")]
+    UnknownRegressionMetric(String),
+}
+
+/// A command terminated unsuccessfully (and the failure has not been ignored)
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct CommandFailedError {
+    pub message: String,
+    /// The tail of the output, if it has been captured
+    pub output: Option<CapturedOutput>,
+}
+
+/// At least one benchmark became slower than allowed by '--regression-threshold'
+#[derive(Debug, Error)]
+#[error("{count} benchmark(s) regressed by more than {thresholds} compared to the baseline")]
+pub struct PerformanceRegressionError {
+    pub count: usize,
+    /// Description of the exceeded thresholds, e.g. "10.0%" or "5.0% (cache-misses)"
+    pub thresholds: String,
 }
@@ -65,3 +65,44 @@ fn test_asciidoc_exporter_table_header() {
 
     assert_eq!(expect, actual);
 }
+
+/// Check that a full export parses into one header row plus one row per result
+#[test]
+fn test_asciidoc_exporter_table_rows() {
+    use crate::benchmark::benchmark_result::BenchmarkResult;
+    use crate::export::Exporter;
+    use crate::options::SortOrder;
+
+    let results: Vec<_> = (1..=3)
+        .map(|i| BenchmarkResult {
+            command: format!("sleep {i}"),
+            command_with_unused_parameters: format!("sleep {i}"),
+            mean: i as f64,
+            stddev: Some(0.1),
+            min: i as f64,
+            max: i as f64,
+            exit_codes: vec![Some(0), Some(0)],
+            ..Default::default()
+        })
+        .collect();
+
+    let output = AsciidocExporter::default()
+        .serialize(&results, None, SortOrder::Command)
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let body = output
+        .split("|===")
+        .nth(1)
+        .expect("table is delimited by '|==='");
+    let rows: Vec<Vec<&str>> = body
+        .split("\n\n")
+        .map(|row| row.split('|').map(str::trim).skip(1).collect())
+        .filter(|cells: &Vec<&str>| !cells.is_empty())
+        .collect();
+
+    assert_eq!(rows.len(), 1 + results.len());
+    assert!(rows.iter().all(|cells| cells.len() == 5));
+    assert_eq!(rows[0][0], "Command");
+    assert_eq!(rows[3][0], "`sleep 3`");
+}
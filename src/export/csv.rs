@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 
 use csv::WriterBuilder;
 
@@ -21,6 +22,13 @@ impl Exporter for CsvExporter {
     ) -> Result<Vec<u8>> {
         let mut writer = WriterBuilder::new().from_writer(vec![]);
 
+        // Commands may not share the same set of parameters, so there is one column for
+        // every parameter that is used by at least one of them
+        let param_names: BTreeSet<&String> = results
+            .iter()
+            .flat_map(|res| res.parameters.keys())
+            .collect();
+
         {
             let mut headers: Vec<Cow<[u8]>> = [
                 // The list of times and exit codes cannot be exported to the CSV file - omit them.
@@ -29,10 +37,8 @@ impl Exporter for CsvExporter {
             .iter()
             .map(|x| Cow::Borrowed(x.as_bytes()))
             .collect();
-            if let Some(res) = results.first() {
-                for param_name in res.parameters.keys() {
-                    headers.push(Cow::Owned(format!("parameter_{param_name}").into_bytes()));
-                }
+            for param_name in &param_names {
+                headers.push(Cow::Owned(format!("parameter_{param_name}").into_bytes()));
             }
             writer.write_record(headers)?;
         }
@@ -50,14 +56,19 @@ impl Exporter for CsvExporter {
             ] {
                 fields.push(Cow::Owned(f.to_string().into_bytes()))
             }
-            for v in res.parameters.values() {
-                fields.push(Cow::Borrowed(v.as_bytes()))
+            for param_name in &param_names {
+                let value = res.parameters.get(*param_name).map_or("", String::as_str);
+                fields.push(Cow::Borrowed(value.as_bytes()))
             }
             writer.write_record(fields)?;
         }
 
         Ok(writer.into_inner()?)
     }
+
+    fn comment(&self, text: &str) -> Option<String> {
+        Some(format!("# {text}\n"))
+    }
 }
 
 #[test]
@@ -77,7 +88,6 @@ fn test_csv() {
             min: 5.0,
             max: 6.0,
             times: Some(vec![7.0, 8.0, 9.0]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
             parameters: {
                 let mut params = BTreeMap::new();
@@ -85,8 +95,7 @@ fn test_csv() {
                 params.insert("bar".into(), "two".into());
                 params
             },
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
         BenchmarkResult {
             command: String::from("command_b"),
@@ -99,7 +108,6 @@ fn test_csv() {
             min: 15.0,
             max: 16.5,
             times: Some(vec![17.0, 18.0, 19.0]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
             parameters: {
                 let mut params = BTreeMap::new();
@@ -107,8 +115,7 @@ fn test_csv() {
                 params.insert("bar".into(), "seven".into());
                 params
             },
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
     ];
 
@@ -125,3 +132,45 @@ fn test_csv() {
     command_b,11,12,11,13,14,15,16.5,seven,one
     "#);
 }
+
+#[test]
+fn test_csv_union_of_parameters_and_escaping() {
+    use std::collections::BTreeMap;
+    let exporter = CsvExporter::default();
+
+    let params = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    let results = vec![
+        BenchmarkResult {
+            command: String::from("echo \"a, b\""),
+            mean: 1.0,
+            parameters: params(&[("foo", "1")]),
+            ..Default::default()
+        },
+        BenchmarkResult {
+            command: String::from("echo c"),
+            mean: 2.0,
+            parameters: params(&[("bar", "x")]),
+            ..Default::default()
+        },
+    ];
+
+    let actual = String::from_utf8(
+        exporter
+            .serialize(&results, Some(Unit::Second), SortOrder::Command)
+            .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        actual,
+        "command,mean,stddev,median,user,system,min,max,parameter_bar,parameter_foo\n\
+         \"echo \"\"a, b\"\"\",1,0,0,0,0,0,0,,1\n\
+         echo c,2,0,0,0,0,0,0,x,\n"
+    );
+}
@@ -0,0 +1,162 @@
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::Exporter;
+use crate::benchmark::benchmark_result::BenchmarkResult;
+use crate::options::SortOrder;
+use crate::poop_metrics::MetricType;
+use crate::util::units::Unit;
+
+use anyhow::Result;
+
+const MEASUREMENT: &str = "hyperfine";
+
+/// Exports one InfluxDB line protocol point per benchmark
+pub struct InfluxDbExporter {
+    /// Timestamp of all points in nanoseconds since the Unix epoch. Without a timestamp,
+    /// the server assigns its own time when receiving the points.
+    timestamp: Option<u128>,
+}
+
+impl Default for InfluxDbExporter {
+    /// Create an exporter that marks all points with the current time
+    fn default() -> Self {
+        InfluxDbExporter {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|t| t.as_nanos()),
+        }
+    }
+}
+
+impl Exporter for InfluxDbExporter {
+    fn serialize(
+        &self,
+        results: &[BenchmarkResult],
+        _unit: Option<Unit>,
+        _sort_order: SortOrder,
+    ) -> Result<Vec<u8>> {
+        let mut output = String::new();
+
+        for result in results {
+            output.push_str(&escape(MEASUREMENT, &[',', ' ']));
+            let _ = write!(output, ",command={}", escape_tag(&result.command));
+            for (name, value) in &result.parameters {
+                // Tags with empty values are not allowed by the line protocol
+                if !value.is_empty() {
+                    let _ = write!(
+                        output,
+                        ",parameter_{}={}",
+                        escape_tag(name),
+                        escape_tag(value)
+                    );
+                }
+            }
+
+            let mut fields = vec![format!("mean={}", result.mean)];
+            if let Some(stddev) = result.stddev {
+                fields.push(format!("stddev={stddev}"));
+            }
+            fields.push(format!("median={}", result.median));
+            fields.push(format!("min={}", result.min));
+            fields.push(format!("max={}", result.max));
+            if let Some(metrics) = &result.poop_metrics {
+                for metric in MetricType::all() {
+                    if let Some(value) = metrics.get(metric) {
+                        fields.push(format!(
+                            "{}={value}i",
+                            escape_tag(&metric.cli_name().replace('-', "_"))
+                        ));
+                    }
+                }
+            }
+            let _ = write!(output, " {}", fields.join(","));
+
+            if let Some(timestamp) = self.timestamp {
+                let _ = write!(output, " {timestamp}");
+            }
+            output.push('\n');
+        }
+
+        Ok(output.into_bytes())
+    }
+}
+
+/// Escape tag keys, tag values and field keys
+fn escape_tag(value: &str) -> String {
+    escape(value, &[',', '=', ' '])
+}
+
+/// Prefix the given special characters (and backslashes) with a backslash. Newlines can
+/// not be escaped and are replaced by spaces.
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        let c = if c == '\n' { ' ' } else { c };
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[test]
+fn test_influxdb_export() {
+    use crate::poop_metrics::PoopMetrics;
+    use std::collections::BTreeMap;
+
+    let mut parameters = BTreeMap::new();
+    parameters.insert("threads".to_string(), "4".to_string());
+    parameters.insert("empty".to_string(), "".to_string());
+
+    let results = vec![
+        BenchmarkResult {
+            command: String::from("sort -t, -k=2 input"),
+            mean: 0.5,
+            stddev: Some(0.1),
+            median: 0.5,
+            min: 0.25,
+            max: 1.0,
+            parameters,
+            poop_metrics: Some(PoopMetrics {
+                cpu_cycles: Some(2000),
+                instructions: Some(1000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        BenchmarkResult {
+            command: String::from("sleep 1"),
+            mean: 1.0,
+            median: 1.0,
+            min: 1.0,
+            max: 1.0,
+            ..Default::default()
+        },
+    ];
+
+    let serialize = |exporter: InfluxDbExporter| {
+        String::from_utf8(
+            exporter
+                .serialize(&results, None, SortOrder::Command)
+                .unwrap(),
+        )
+        .unwrap()
+    };
+
+    assert_eq!(
+        serialize(InfluxDbExporter { timestamp: None }),
+        "hyperfine,command=sort\\ -t\\,\\ -k\\=2\\ input,parameter_threads=4 \
+         mean=0.5,stddev=0.1,median=0.5,min=0.25,max=1,cpu_cycles=2000i,instructions=1000i\n\
+         hyperfine,command=sleep\\ 1 mean=1,median=1,min=1,max=1\n"
+    );
+
+    let output = serialize(InfluxDbExporter {
+        timestamp: Some(1_700_000_000_000_000_000),
+    });
+    assert!(output
+        .lines()
+        .all(|line| line.ends_with(" 1700000000000000000")));
+}
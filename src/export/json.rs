@@ -1,20 +1,114 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
 use serde::*;
-use serde_json::to_vec_pretty;
+use serde_json::{to_vec, to_vec_pretty, Value};
 
-use super::Exporter;
+use super::{Exporter, HYPERFINE_VERSION, SCHEMA_VERSION};
 use crate::benchmark::benchmark_result::BenchmarkResult;
-use crate::options::SortOrder;
-use crate::util::units::Unit;
+use crate::benchmark::relative_speed::{self, welch_t_test};
+use crate::options::{OptionsSnapshot, SortOrder};
+use crate::util::units::{Scalar, Unit};
+
+use anyhow::{bail, Context, Result};
+
+/// Oldest schema version that can still be imported with '--from-json'. This needs to be
+/// increased whenever the format changes in a way that prevents older exports from being read.
+const MIN_IMPORTED_SCHEMA_VERSION: u64 = 1;
+
+/// Additional information about the benchmark session that is exported alongside the results
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Metadata {
+    /// Free-form notes given via '--description'
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub descriptions: Vec<String>,
 
-use anyhow::Result;
+    /// The options that have been used for this benchmark session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<OptionsSnapshot>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.descriptions.is_empty() && self.options.is_none()
+    }
+}
 
 #[derive(Serialize, Debug)]
 struct HyperfineSummary<'a> {
+    schema_version: u64,
+    hyperfine_version: &'static str,
+    #[serde(skip_serializing_if = "Metadata::is_empty")]
+    metadata: &'a Metadata,
     results: &'a [BenchmarkResult],
+    /// Speed of all benchmarks relative to the fastest one, if there are at least two
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comparison: Option<Comparison<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct Comparison<'a> {
+    /// Command of the fastest benchmark
+    fastest: &'a str,
+    /// All other benchmarks, with the ratio of their mean time to the one of the fastest
+    relative_speeds: Vec<RelativeSpeed<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct RelativeSpeed<'a> {
+    command: &'a str,
+    ratio: Scalar,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ratio_stddev: Option<Scalar>,
+    /// Two-sided p-value of Welch's t-test for the difference to the fastest benchmark (only
+    /// if exactly two benchmarks are compared)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p_value: Option<Scalar>,
+}
+
+impl<'a> Comparison<'a> {
+    /// Compare all results to the fastest one. This is not possible if there is only a single
+    /// result, or if the fastest one has a mean time of zero.
+    fn new(results: &'a [BenchmarkResult]) -> Option<Self> {
+        if results.len() < 2 {
+            return None;
+        }
+
+        let annotated = relative_speed::compute_with_check(results, SortOrder::Command)?;
+        let fastest = relative_speed::fastest_of(results);
+        Some(Comparison {
+            fastest: &fastest.command,
+            relative_speeds: annotated
+                .iter()
+                .filter(|r| !r.is_reference)
+                .map(|r| RelativeSpeed {
+                    command: &r.result.command,
+                    ratio: r.relative_speed,
+                    ratio_stddev: r.relative_speed_stddev,
+                    p_value: match results {
+                        [_, _] => welch_t_test(fastest, r.result).map(|test| test.p_value),
+                        _ => None,
+                    },
+                })
+                .collect(),
+        })
+    }
 }
 
 #[derive(Default)]
-pub struct JsonExporter {}
+pub struct JsonExporter {
+    metadata: Metadata,
+
+    /// Write everything on a single line instead of indenting the output
+    compact: bool,
+}
+
+impl JsonExporter {
+    pub fn new(metadata: Metadata, compact: bool) -> Self {
+        JsonExporter { metadata, compact }
+    }
+}
 
 impl Exporter for JsonExporter {
     fn serialize(
@@ -23,7 +117,18 @@ impl Exporter for JsonExporter {
         _unit: Option<Unit>,
         _sort_order: SortOrder,
     ) -> Result<Vec<u8>> {
-        let mut output = to_vec_pretty(&HyperfineSummary { results });
+        let summary = HyperfineSummary {
+            schema_version: SCHEMA_VERSION,
+            hyperfine_version: HYPERFINE_VERSION,
+            metadata: &self.metadata,
+            results,
+            comparison: Comparison::new(results),
+        };
+        let mut output = if self.compact {
+            to_vec(&summary)
+        } else {
+            to_vec_pretty(&summary)
+        };
         if let Ok(ref mut content) = output {
             content.push(b'\n');
         }
@@ -31,3 +136,241 @@ impl Exporter for JsonExporter {
         Ok(output?)
     }
 }
+
+/// Load the benchmark results from a file that has been written with '--export-json'
+pub fn import_results(path: &Path) -> Result<Vec<BenchmarkResult>> {
+    let file = File::open(path)
+        .with_context(|| format!("Could not open JSON file '{}'", path.display()))?;
+    let mut summary: Value = serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("Could not parse JSON file '{}'", path.display()))?;
+
+    match summary.get("schema_version").map(Value::as_u64) {
+        Some(Some(version))
+            if (MIN_IMPORTED_SCHEMA_VERSION..=SCHEMA_VERSION).contains(&version) => {}
+        Some(Some(version)) => bail!(
+            "The JSON file '{}' has schema version {version}, but this version of hyperfine \
+             can only import versions {MIN_IMPORTED_SCHEMA_VERSION} to {SCHEMA_VERSION}. Run \
+             the benchmarks again to create a compatible export.",
+            path.display()
+        ),
+        _ => bail!(
+            "The JSON file '{}' does not contain a valid schema version. Only files that have \
+             been written with '--export-json' by this version of hyperfine can be imported.",
+            path.display()
+        ),
+    }
+
+    let mut results: Vec<BenchmarkResult> = serde_json::from_value(summary["results"].take())
+        .with_context(|| {
+            format!(
+                "Could not read the results in JSON file '{}'",
+                path.display()
+            )
+        })?;
+
+    // The unused parameters are not exported, so only the command itself can be shown
+    for result in &mut results {
+        result.command_with_unused_parameters = result.command.clone();
+    }
+
+    Ok(results)
+}
+
+#[test]
+fn test_json_export_metadata() {
+    let results = vec![BenchmarkResult {
+        command: String::from("sleep 0.1"),
+        ..Default::default()
+    }];
+
+    let serialize = |exporter: JsonExporter| -> serde_json::Value {
+        let output = exporter
+            .serialize(&results, None, SortOrder::Command)
+            .unwrap();
+        serde_json::from_slice(&output).unwrap()
+    };
+
+    let json = serialize(JsonExporter::default());
+    assert!(json.get("metadata").is_none());
+    assert_eq!(json["schema_version"], SCHEMA_VERSION);
+    assert_eq!(json["hyperfine_version"], env!("CARGO_PKG_VERSION"));
+
+    let json = serialize(JsonExporter::new(
+        Metadata {
+            descriptions: vec!["after kernel upgrade".into(), "quote \" and \\".into()],
+            options: None,
+        },
+        false,
+    ));
+    assert_eq!(
+        json["metadata"]["descriptions"],
+        serde_json::json!(["after kernel upgrade", "quote \" and \\"])
+    );
+    assert_eq!(json["results"][0]["command"], "sleep 0.1");
+}
+
+#[test]
+fn test_json_export_compact() {
+    let results = vec![BenchmarkResult {
+        command: String::from("sleep 0.1"),
+        times: Some(vec![0.1, 0.2]),
+        parameters: [("b", "2"), ("a", "1")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        ..Default::default()
+    }];
+    let serialize = |exporter: JsonExporter| {
+        String::from_utf8(
+            exporter
+                .serialize(&results, None, SortOrder::Command)
+                .unwrap(),
+        )
+        .unwrap()
+    };
+
+    let pretty = serialize(JsonExporter::default());
+    let compact = serialize(JsonExporter::new(Metadata::default(), true));
+    assert!(pretty.lines().count() > 1);
+    assert_eq!(compact.lines().count(), 1);
+    assert!(compact.ends_with('\n'));
+    assert_eq!(
+        serde_json::from_str::<Value>(&pretty).unwrap(),
+        serde_json::from_str::<Value>(&compact).unwrap()
+    );
+
+    // The field order does not depend on the insertion order of the parameters
+    assert!(compact.contains(r#""parameters":{"a":"1","b":"2"}"#));
+    assert_eq!(serialize(JsonExporter::default()), pretty);
+}
+
+#[test]
+fn test_json_export_round_trip() {
+    use crate::poop_metrics::PoopMetrics;
+    use std::collections::BTreeMap;
+
+    let metrics = |cycles| PoopMetrics {
+        cpu_cycles: Some(cycles),
+        instructions: Some(2 * cycles),
+        ..Default::default()
+    };
+
+    let mut parameters = BTreeMap::new();
+    parameters.insert("size".to_string(), "10".to_string());
+
+    let results = vec![BenchmarkResult {
+        command: String::from("sort input"),
+        mean: 0.2,
+        times: Some(vec![0.1, 0.2, 0.3]),
+        memory_usage_byte: Some(vec![1024, 2048, 4096]),
+        exit_codes: vec![Some(0), Some(0), Some(1)],
+        parameters,
+        poop_metrics: Some(metrics(200)),
+        metrics_per_run: Some(vec![Some(metrics(100)), None, Some(metrics(300))]),
+        ..Default::default()
+    }];
+
+    let output = JsonExporter::default()
+        .serialize(&results, None, SortOrder::Command)
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let parsed: Vec<BenchmarkResult> = serde_json::from_value(json["results"].clone()).unwrap();
+
+    assert_eq!(parsed.len(), 1);
+    let result = &parsed[0];
+    assert_eq!(result.times.as_ref().map(Vec::len), Some(3));
+    assert_eq!(result.memory_usage_byte.as_ref().map(Vec::len), Some(3));
+    assert_eq!(result.exit_codes.len(), 3);
+    assert_eq!(result.metrics_per_run.as_ref().map(Vec::len), Some(3));
+    assert_eq!(parsed, results);
+}
+
+#[test]
+fn test_json_import() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.json");
+
+    let results = vec![
+        BenchmarkResult {
+            command: String::from("sleep 0.1"),
+            command_with_unused_parameters: String::from("sleep 0.1"),
+            mean: 0.1,
+            times: Some(vec![0.1, 0.1]),
+            exit_codes: vec![Some(0), Some(0)],
+            ..Default::default()
+        },
+        BenchmarkResult {
+            command: String::from("sleep 0.2"),
+            command_with_unused_parameters: String::from("sleep 0.2"),
+            mean: 0.2,
+            ..Default::default()
+        },
+    ];
+    let output = JsonExporter::default()
+        .serialize(&results, None, SortOrder::Command)
+        .unwrap();
+    std::fs::write(&path, output).unwrap();
+    assert_eq!(import_results(&path).unwrap(), results);
+
+    let import_error = |content: &str| {
+        std::fs::write(&path, content).unwrap();
+        import_results(&path).unwrap_err().to_string()
+    };
+    assert!(import_error(r#"{"schema_version": 999, "results": []}"#)
+        .contains("has schema version 999"));
+    assert!(import_error(r#"{"results": []}"#).contains("does not contain a valid schema version"));
+    assert!(
+        import_error(r#"{"schema_version": 1, "results": [{"command": 1}]}"#)
+            .contains("Could not read the results")
+    );
+}
+
+#[test]
+fn test_json_export_comparison() {
+    let result = |command: &str, mean, stddev| BenchmarkResult {
+        command: command.into(),
+        mean,
+        stddev,
+        ..Default::default()
+    };
+
+    let serialize = |results: &[BenchmarkResult]| -> serde_json::Value {
+        let output = JsonExporter::default()
+            .serialize(results, None, SortOrder::Command)
+            .unwrap();
+        serde_json::from_slice(&output).unwrap()
+    };
+
+    let json = serialize(&[
+        result("sleep 0.3", 0.3, Some(0.03)),
+        result("sleep 0.1", 0.1, Some(0.01)),
+        result("sleep 0.2", 0.2, None),
+    ]);
+    let comparison = &json["comparison"];
+    assert_eq!(comparison["fastest"], "sleep 0.1");
+    let speeds = comparison["relative_speeds"].as_array().unwrap();
+    assert_eq!(speeds.len(), 2);
+    assert_eq!(speeds[0]["command"], "sleep 0.3");
+    assert!((speeds[0]["ratio"].as_f64().unwrap() - 3.0).abs() < 1e-9);
+    assert!((speeds[0]["ratio_stddev"].as_f64().unwrap() - 3.0 * 0.02f64.sqrt()).abs() < 1e-9);
+    assert_eq!(speeds[1]["command"], "sleep 0.2");
+    assert!(speeds[1].get("ratio_stddev").is_none());
+    assert!(speeds[0].get("p_value").is_none());
+
+    // Exactly two benchmarks are also compared with Welch's t-test
+    let ten_runs = |result: BenchmarkResult| BenchmarkResult {
+        exit_codes: vec![Some(0); 10],
+        ..result
+    };
+    let json = serialize(&[
+        ten_runs(result("sleep 0.2", 0.2, Some(0.01))),
+        ten_runs(result("sleep 0.1", 0.1, Some(0.01))),
+    ]);
+    let speeds = json["comparison"]["relative_speeds"].as_array().unwrap();
+    assert_eq!(speeds.len(), 1);
+    assert!(speeds[0]["p_value"].as_f64().unwrap() < 0.001);
+    assert!(json.get("welch_t_test").is_none());
+
+    let json = serialize(&[result("sleep 0.1", 0.1, None)]);
+    assert!(json.get("comparison").is_none());
+}
@@ -0,0 +1,173 @@
+//! A compact variant of the JSON export, where the list of run times for each benchmark
+//! is stored as a binary blob instead of an array of JSON numbers.
+//!
+//! Every `times` array is replaced by a `times_binary` object:
+//!
+//! ```json
+//! "times_binary": {
+//!   "count": 3,
+//!   "unit": "s",
+//!   "encoding": "f64-le-base64",
+//!   "data": "mpmZmZmZuT+amZmZmZnJPzMzMzMzM9M/"
+//! }
+//! ```
+//!
+//! `data` is the standard (padded) base64 encoding of `count` IEEE-754 double precision
+//! values in little-endian byte order. The values are in seconds, exactly like in the
+//! regular JSON export. They can be decoded in Python via:
+//!
+//! ```python
+//! import base64, json, struct
+//!
+//! results = json.load(open("results.json"))["results"]
+//! blob = results[0]["times_binary"]
+//! times = struct.unpack(f"<{blob['count']}d", base64.b64decode(blob["data"]))
+//! ```
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::*;
+use serde_json::{to_vec, to_vec_pretty, Value};
+
+use super::json::Metadata;
+use super::{Exporter, HYPERFINE_VERSION, SCHEMA_VERSION};
+use crate::benchmark::benchmark_result::BenchmarkResult;
+use crate::options::SortOrder;
+use crate::util::units::{Second, Unit};
+
+use anyhow::Result;
+
+/// Name of the encoding that is stored in the `times_binary.encoding` field
+const ENCODING: &str = "f64-le-base64";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct BinaryTimes {
+    count: usize,
+    unit: String,
+    encoding: String,
+    data: String,
+}
+
+impl BinaryTimes {
+    fn encode(times: &[Second]) -> Self {
+        let bytes: Vec<u8> = times.iter().flat_map(|t| t.to_le_bytes()).collect();
+        BinaryTimes {
+            count: times.len(),
+            unit: Unit::Second.short_name(),
+            encoding: ENCODING.into(),
+            data: BASE64.encode(bytes),
+        }
+    }
+
+    #[cfg(test)]
+    fn decode(&self) -> Result<Vec<Second>> {
+        use anyhow::ensure;
+        use std::convert::TryInto;
+
+        ensure!(
+            self.encoding == ENCODING,
+            "Unknown encoding '{}'",
+            self.encoding
+        );
+        let bytes = BASE64.decode(&self.data)?;
+        ensure!(
+            bytes.len() == self.count * std::mem::size_of::<f64>(),
+            "Unexpected length of binary data"
+        );
+        Ok(bytes
+            .chunks_exact(std::mem::size_of::<f64>())
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+}
+
+#[derive(Default)]
+pub struct JsonBinaryExporter {
+    metadata: Metadata,
+
+    /// Write everything on a single line instead of indenting the output
+    compact: bool,
+}
+
+impl JsonBinaryExporter {
+    pub fn new(metadata: Metadata, compact: bool) -> Self {
+        JsonBinaryExporter { metadata, compact }
+    }
+}
+
+impl Exporter for JsonBinaryExporter {
+    fn serialize(
+        &self,
+        results: &[BenchmarkResult],
+        _unit: Option<Unit>,
+        _sort_order: SortOrder,
+    ) -> Result<Vec<u8>> {
+        let mut entries = vec![];
+        for result in results {
+            let mut entry = serde_json::to_value(result)?;
+            if let (Value::Object(ref mut map), Some(times)) = (&mut entry, &result.times) {
+                map.remove("times");
+                map.insert(
+                    "times_binary".into(),
+                    serde_json::to_value(BinaryTimes::encode(times))?,
+                );
+            }
+            entries.push(entry);
+        }
+
+        let mut summary = serde_json::Map::new();
+        summary.insert("schema_version".into(), SCHEMA_VERSION.into());
+        summary.insert("hyperfine_version".into(), HYPERFINE_VERSION.into());
+        if !self.metadata.is_empty() {
+            summary.insert("metadata".into(), serde_json::to_value(&self.metadata)?);
+        }
+        summary.insert("results".into(), Value::Array(entries));
+
+        let mut output = if self.compact {
+            to_vec(&summary)?
+        } else {
+            to_vec_pretty(&summary)?
+        };
+        output.push(b'\n');
+
+        Ok(output)
+    }
+}
+
+#[test]
+fn test_binary_times_round_trip() {
+    let times = vec![0.1, 0.2, 0.3, 1.0 / 3.0, 1e-9, 12345.678];
+
+    let blob = BinaryTimes::encode(&times);
+    assert_eq!(blob.count, 6);
+    assert_eq!(blob.unit, "s");
+    assert_eq!(blob.data.len(), 64);
+
+    assert_eq!(blob.decode().unwrap(), times);
+}
+
+#[test]
+fn test_json_binary_export_round_trip() {
+    let results = vec![BenchmarkResult {
+        command: String::from("sleep 0.1"),
+        command_with_unused_parameters: String::from("sleep 0.1"),
+        mean: 0.2,
+        times: Some(vec![0.1, 0.2, 0.3]),
+        exit_codes: vec![Some(0), Some(0), Some(0)],
+        ..Default::default()
+    }];
+
+    let output = JsonBinaryExporter::default()
+        .serialize(&results, None, SortOrder::Command)
+        .unwrap();
+    let json: Value = serde_json::from_slice(&output).unwrap();
+    let entry = &json["results"][0];
+
+    assert_eq!(json["schema_version"], SCHEMA_VERSION);
+    assert!(entry.get("times").is_none());
+    assert_eq!(entry["command"], "sleep 0.1");
+
+    let blob: BinaryTimes = serde_json::from_value(entry["times_binary"].clone()).unwrap();
+    assert_eq!(blob.data, "mpmZmZmZuT+amZmZmZnJPzMzMzMzM9M/");
+    assert_eq!(blob.decode().unwrap(), vec![0.1, 0.2, 0.3]);
+}
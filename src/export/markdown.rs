@@ -26,6 +26,10 @@ impl MarkupExporter for MarkdownExporter {
     fn command(&self, cmd: &str) -> String {
         format!("`{cmd}`")
     }
+
+    fn comment(&self, text: &str) -> Option<String> {
+        Some(format!("<!-- {text} -->\n"))
+    }
 }
 
 /// Check Markdown-based data row formatting
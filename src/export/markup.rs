@@ -93,6 +93,11 @@ pub trait MarkupExporter {
     }
 
     fn command(&self, size: &str) -> String;
+
+    /// Turn the given text into a comment line, if the markup language supports comments
+    fn comment(&self, _text: &str) -> Option<String> {
+        None
+    }
 }
 
 fn determine_unit_from_results(results: &[BenchmarkResult]) -> Unit {
@@ -118,4 +123,8 @@ impl<T: MarkupExporter> Exporter for T {
         let table = self.table_results(&entries, unit);
         Ok(table.as_bytes().to_vec())
     }
+
+    fn comment(&self, text: &str) -> Option<String> {
+        MarkupExporter::comment(self, text)
+    }
 }
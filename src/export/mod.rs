@@ -1,28 +1,56 @@
-use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
 
 mod asciidoc;
 mod csv;
+mod influxdb;
 mod json;
+mod json_binary;
 mod markdown;
 mod markup;
 mod orgmode;
+mod prometheus;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 #[cfg(test)]
 mod tests;
+mod timings_csv;
 
 use self::asciidoc::AsciidocExporter;
 use self::csv::CsvExporter;
-use self::json::JsonExporter;
+use self::influxdb::InfluxDbExporter;
+pub use self::json::import_results;
+use self::json::{JsonExporter, Metadata};
+use self::json_binary::JsonBinaryExporter;
 use self::markdown::MarkdownExporter;
 use self::orgmode::OrgmodeExporter;
+use self::prometheus::PrometheusExporter;
+#[cfg(feature = "sqlite")]
+use self::sqlite::SqliteExporter;
+use self::timings_csv::TimingsCsvExporter;
 
 use crate::benchmark::benchmark_result::BenchmarkResult;
-use crate::options::SortOrder;
+use crate::error::OptionsError;
+use crate::options::{Options, OptionsSnapshot, SortOrder};
 use crate::util::units::Unit;
 
 use anyhow::{Context, Result};
 use clap::ArgMatches;
 
+/// Version of the export formats. This needs to be increased whenever fields are added to,
+/// removed from or changed in any of the exports.
+pub const SCHEMA_VERSION: u64 = 4;
+
+/// Version of hyperfine that is stated in all exports
+pub const HYPERFINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Description of the version of an export, for formats that support comments
+fn version_description() -> String {
+    format!("Exported by hyperfine {HYPERFINE_VERSION} (schema version {SCHEMA_VERSION})")
+}
+
 /// The desired form of exporter to use for a given file.
 #[derive(Clone)]
 pub enum ExportType {
@@ -32,14 +60,26 @@ pub enum ExportType {
     /// CSV (comma separated values) format
     Csv,
 
+    /// InfluxDB line protocol
+    InfluxDb,
+
     /// JSON format
     Json,
 
+    /// JSON format with run times stored as base64-encoded binary blobs
+    JsonBinary,
+
     /// Markdown table
     Markdown,
 
     /// Emacs org-mode tables
     Orgmode,
+
+    /// Prometheus text exposition format
+    Prometheus,
+
+    /// CSV with the measurements of each individual run
+    TimingsCsv,
 }
 
 /// Interface for different exporters.
@@ -51,6 +91,28 @@ trait Exporter {
         unit: Option<Unit>,
         sort_order: SortOrder,
     ) -> Result<Vec<u8>>;
+
+    /// Turn the given text into a comment line, for formats that support comments
+    fn comment(&self, _text: &str) -> Option<String> {
+        None
+    }
+}
+
+/// A path that is the same for all spellings of the same export file (like "a.json" and
+/// "./a.json"), as far as this can be determined before the file is created
+fn normalized_path(filename: &str) -> PathBuf {
+    let path = Path::new(filename);
+    let directory = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    match (fs::canonicalize(directory), path.file_name()) {
+        (Ok(directory), Some(file_name)) => directory.join(file_name),
+        _ => path
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect(),
+    }
 }
 
 pub enum ExportTarget {
@@ -66,47 +128,109 @@ struct ExporterWithTarget {
 /// Handles the management of multiple file exporters.
 pub struct ExportManager {
     exporters: Vec<ExporterWithTarget>,
+    #[cfg(feature = "sqlite")]
+    databases: Vec<SqliteExporter>,
     time_unit: Option<Unit>,
     sort_order: SortOrder,
+    metadata: Metadata,
+    json_compact: bool,
 }
 
 impl ExportManager {
     /// Build the ExportManager that will export the results specified
     /// in the given ArgMatches
-    pub fn from_cli_arguments(
-        matches: &ArgMatches,
-        time_unit: Option<Unit>,
-        sort_order: SortOrder,
-    ) -> Result<Self> {
+    pub fn from_cli_arguments(matches: &ArgMatches, options: &Options) -> Result<Self> {
         let mut export_manager = Self {
             exporters: vec![],
-            time_unit,
-            sort_order,
+            #[cfg(feature = "sqlite")]
+            databases: vec![],
+            time_unit: options.time_unit,
+            sort_order: options.sort_order_exports,
+            metadata: Metadata {
+                descriptions: options.descriptions.clone(),
+                options: Some(OptionsSnapshot::new(options)),
+            },
+            json_compact: options.json_compact,
         };
+
+        // Collect all exports first, such that conflicting targets are detected before any
+        // file is created
+        let mut targets: Vec<(ExportType, &str)> = vec![];
+        for (flag, export_type) in [
+            ("export-asciidoc", ExportType::Asciidoc),
+            ("export-json", ExportType::Json),
+            ("export-json-binary", ExportType::JsonBinary),
+            ("export-csv", ExportType::Csv),
+            ("export-influxdb", ExportType::InfluxDb),
+            ("export-markdown", ExportType::Markdown),
+            ("export-orgmode", ExportType::Orgmode),
+            ("export-prometheus", ExportType::Prometheus),
+            ("export-timings-csv", ExportType::TimingsCsv),
+        ] {
+            for filename in matches.get_many::<String>(flag).unwrap_or_default() {
+                targets.push((export_type.clone(), filename));
+            }
+        }
+
+        // The SQLite database and the JSON Lines file are written separately, but must not
+        // collide with the other exports either
+        let appended_files = ["export-sqlite", "export-jsonl"]
+            .iter()
+            .filter_map(|&flag| matches.get_one::<String>(flag).map(|f| f.as_str()));
+
+        let mut paths = HashSet::new();
+        if let Some(filename) = targets
+            .iter()
+            .map(|(_, filename)| *filename)
+            .chain(appended_files)
+            .filter(|&filename| filename != "-")
+            .find(|&filename| !paths.insert(normalized_path(filename)))
         {
-            let mut add_exporter = |flag, exporttype| -> Result<()> {
-                if let Some(filename) = matches.get_one::<String>(flag) {
-                    export_manager.add_exporter(exporttype, filename)?;
-                }
-                Ok(())
-            };
-            add_exporter("export-asciidoc", ExportType::Asciidoc)?;
-            add_exporter("export-json", ExportType::Json)?;
-            add_exporter("export-csv", ExportType::Csv)?;
-            add_exporter("export-markdown", ExportType::Markdown)?;
-            add_exporter("export-orgmode", ExportType::Orgmode)?;
+            return Err(OptionsError::DuplicateExportFile(filename.to_string()).into());
+        }
+
+        for (export_type, filename) in targets {
+            export_manager.add_exporter(export_type, filename)?;
+        }
+
+        if let Some(filename) = matches.get_one::<String>("export-sqlite") {
+            export_manager.add_database(filename)?;
         }
         Ok(export_manager)
     }
 
+    /// Add a SQLite database to which the results are appended
+    #[cfg(feature = "sqlite")]
+    pub fn add_database(&mut self, filename: &str) -> Result<()> {
+        self.databases.push(SqliteExporter::open(filename)?);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    pub fn add_database(&mut self, _filename: &str) -> Result<()> {
+        anyhow::bail!(
+            "This version of hyperfine was built without SQLite support. \
+             Rebuild it with '--features sqlite' to use '--export-sqlite'."
+        )
+    }
+
     /// Add an additional exporter to the ExportManager
     pub fn add_exporter(&mut self, export_type: ExportType, filename: &str) -> Result<()> {
         let exporter: Box<dyn Exporter> = match export_type {
             ExportType::Asciidoc => Box::<AsciidocExporter>::default(),
             ExportType::Csv => Box::<CsvExporter>::default(),
-            ExportType::Json => Box::<JsonExporter>::default(),
+            ExportType::InfluxDb => Box::<InfluxDbExporter>::default(),
+            ExportType::Json => {
+                Box::new(JsonExporter::new(self.metadata.clone(), self.json_compact))
+            }
+            ExportType::JsonBinary => Box::new(JsonBinaryExporter::new(
+                self.metadata.clone(),
+                self.json_compact,
+            )),
             ExportType::Markdown => Box::<MarkdownExporter>::default(),
             ExportType::Orgmode => Box::<OrgmodeExporter>::default(),
+            ExportType::Prometheus => Box::<PrometheusExporter>::default(),
+            ExportType::TimingsCsv => Box::<TimingsCsvExporter>::default(),
         };
 
         self.exporters.push(ExporterWithTarget {
@@ -114,7 +238,9 @@ impl ExportManager {
             target: if filename == "-" {
                 ExportTarget::Stdout
             } else {
-                let _ = File::create(filename)
+                // Write an empty file the same way as the results, to make sure that the
+                // export does not fail after running all benchmarks
+                write_to_file(filename, &[])
                     .with_context(|| format!("Could not create export file '{filename}'"))?;
                 ExportTarget::File(filename.to_string())
             },
@@ -131,9 +257,17 @@ impl ExportManager {
     /// order not to clutter the output of hyperfine with intermediate results).
     pub fn write_results(&self, results: &[BenchmarkResult], intermediate: bool) -> Result<()> {
         for e in &self.exporters {
-            let content = || {
-                e.exporter
-                    .serialize(results, self.time_unit, self.sort_order)
+            let content = || -> Result<Vec<u8>> {
+                let mut content = e
+                    .exporter
+                    .comment(&version_description())
+                    .unwrap_or_default()
+                    .into_bytes();
+                content.extend(
+                    e.exporter
+                        .serialize(results, self.time_unit, self.sort_order)?,
+                );
+                Ok(content)
             };
 
             match e.target {
@@ -150,13 +284,35 @@ impl ExportManager {
                 }
             }
         }
+        #[cfg(feature = "sqlite")]
+        if intermediate {
+            for database in &self.databases {
+                database.write(results)?;
+            }
+        }
         Ok(())
     }
 }
 
-/// Write the given content to a file with the specified name
+/// Write the given content to a file with the specified name. If the file does not exist yet
+/// or is a regular file, the content is written to a temporary file first and then moved into
+/// place, such that an interruption can not leave a truncated export behind. Everything else,
+/// e.g. `/dev/stdout`, a named pipe or a symbolic link, is written to directly.
 fn write_to_file(filename: &str, content: &[u8]) -> Result<()> {
-    let mut file = OpenOptions::new().write(true).open(filename)?;
-    file.write_all(content)
-        .with_context(|| format!("Failed to export results to '{filename}'"))
+    let replaceable = match fs::symlink_metadata(filename) {
+        Ok(metadata) => metadata.file_type().is_file(),
+        Err(e) => e.kind() == io::ErrorKind::NotFound,
+    };
+
+    let result = if replaceable {
+        let tmp_filename = format!("{filename}.tmp");
+        fs::write(&tmp_filename, content)
+            .and_then(|_| fs::rename(&tmp_filename, filename))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmp_filename);
+            })
+    } else {
+        fs::write(filename, content)
+    };
+    result.with_context(|| format!("Failed to export results to '{filename}'"))
 }
@@ -0,0 +1,184 @@
+use std::fmt::Write;
+
+use super::Exporter;
+use crate::benchmark::benchmark_result::BenchmarkResult;
+use crate::options::SortOrder;
+use crate::poop_metrics::MetricType;
+use crate::util::units::Unit;
+
+use anyhow::Result;
+
+/// Exports the summary statistics in the Prometheus text exposition format, e.g. for the
+/// textfile collector of node_exporter
+#[derive(Default)]
+pub struct PrometheusExporter {}
+
+type TimingValue = fn(&BenchmarkResult) -> Option<f64>;
+
+impl Exporter for PrometheusExporter {
+    fn serialize(
+        &self,
+        results: &[BenchmarkResult],
+        _unit: Option<Unit>,
+        _sort_order: SortOrder,
+    ) -> Result<Vec<u8>> {
+        let mut output = String::new();
+
+        let timings: [(&str, &str, TimingValue); 4] = [
+            ("mean", "Mean wall clock time", |r| Some(r.mean)),
+            ("stddev", "Standard deviation of the wall clock time", |r| {
+                r.stddev
+            }),
+            ("min", "Minimum wall clock time", |r| Some(r.min)),
+            ("max", "Maximum wall clock time", |r| Some(r.max)),
+        ];
+        for (name, help, value) in &timings {
+            write_family(
+                &mut output,
+                &format!("hyperfine_{name}_seconds"),
+                help,
+                "gauge",
+                results,
+                value,
+            );
+        }
+
+        for metric in MetricType::all() {
+            write_family(
+                &mut output,
+                &format!("hyperfine_{}", metric.cli_name().replace('-', "_")),
+                &format!("{} per run, averaged over all runs", metric.display_name()),
+                "gauge",
+                results,
+                |r| {
+                    r.poop_metrics
+                        .as_ref()
+                        .and_then(|m| m.get(metric))
+                        .map(|v| v as f64)
+                },
+            );
+        }
+
+        Ok(output.into_bytes())
+    }
+}
+
+/// Write the `# HELP` and `# TYPE` lines of a metric family, followed by one sample for
+/// every result that has a value. Families without any samples are omitted.
+fn write_family(
+    output: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    results: &[BenchmarkResult],
+    value: impl Fn(&BenchmarkResult) -> Option<f64>,
+) {
+    let samples: Vec<_> = results
+        .iter()
+        .filter_map(|r| value(r).map(|v| (r, v)))
+        .collect();
+    if samples.is_empty() {
+        return;
+    }
+
+    let _ = writeln!(output, "# HELP {name} {help}");
+    let _ = writeln!(output, "# TYPE {name} {kind}");
+    for (result, value) in samples {
+        let _ = writeln!(output, "{name}{{{}}} {value}", labels(result));
+    }
+}
+
+/// Label set identifying a benchmark: the command and the values of all its parameters
+fn labels(result: &BenchmarkResult) -> String {
+    let mut labels = vec![format!(
+        "command=\"{}\"",
+        escape_label_value(&result.command)
+    )];
+    for (name, value) in &result.parameters {
+        labels.push(format!(
+            "parameter_{}=\"{}\"",
+            sanitize_label_name(name),
+            escape_label_value(value)
+        ));
+    }
+    labels.join(",")
+}
+
+/// Escape a label value as required by the exposition format
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Label names may only consist of ASCII letters, digits and underscores
+fn sanitize_label_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[test]
+fn test_prometheus_export() {
+    use crate::poop_metrics::PoopMetrics;
+    use std::collections::BTreeMap;
+
+    let mut parameters = BTreeMap::new();
+    parameters.insert("file-name".to_string(), "a\"b".to_string());
+
+    let results = vec![
+        BenchmarkResult {
+            command: String::from("grep \"x\\y\"\nfoo"),
+            mean: 0.5,
+            stddev: Some(0.1),
+            min: 0.25,
+            max: 1.0,
+            parameters,
+            poop_metrics: Some(PoopMetrics {
+                instructions: Some(1000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        BenchmarkResult {
+            command: String::from("sleep 1"),
+            mean: 1.0,
+            stddev: None,
+            min: 1.0,
+            max: 1.0,
+            ..Default::default()
+        },
+    ];
+
+    let output = String::from_utf8(
+        PrometheusExporter::default()
+            .serialize(&results, None, SortOrder::Command)
+            .unwrap(),
+    )
+    .unwrap();
+
+    let first = r#"command="grep \"x\\y\"\nfoo",parameter_file_name="a\"b""#;
+    let second = r#"command="sleep 1""#;
+    let expected = [
+        "# HELP hyperfine_mean_seconds Mean wall clock time".to_string(),
+        "# TYPE hyperfine_mean_seconds gauge".to_string(),
+        format!("hyperfine_mean_seconds{{{first}}} 0.5"),
+        format!("hyperfine_mean_seconds{{{second}}} 1"),
+        "# HELP hyperfine_stddev_seconds Standard deviation of the wall clock time".to_string(),
+        "# TYPE hyperfine_stddev_seconds gauge".to_string(),
+        format!("hyperfine_stddev_seconds{{{first}}} 0.1"),
+        "# HELP hyperfine_min_seconds Minimum wall clock time".to_string(),
+        "# TYPE hyperfine_min_seconds gauge".to_string(),
+        format!("hyperfine_min_seconds{{{first}}} 0.25"),
+        format!("hyperfine_min_seconds{{{second}}} 1"),
+        "# HELP hyperfine_max_seconds Maximum wall clock time".to_string(),
+        "# TYPE hyperfine_max_seconds gauge".to_string(),
+        format!("hyperfine_max_seconds{{{first}}} 1"),
+        format!("hyperfine_max_seconds{{{second}}} 1"),
+        "# HELP hyperfine_instructions Instructions per run, averaged over all runs".to_string(),
+        "# TYPE hyperfine_instructions gauge".to_string(),
+        format!("hyperfine_instructions{{{first}}} 1000"),
+    ];
+    assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+}
@@ -0,0 +1,273 @@
+use std::cell::Cell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::benchmark::benchmark_result::BenchmarkResult;
+use crate::poop_metrics::{MetricType, PoopMetrics};
+
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, Transaction};
+
+/// Version of the database schema, stored in the `user_version` pragma. This needs to be
+/// increased (and a migration added to `SqliteExporter::open`) whenever the schema changes.
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE benchmarks (
+        id INTEGER PRIMARY KEY,
+        started_at INTEGER NOT NULL,
+        command TEXT NOT NULL,
+        mean REAL NOT NULL,
+        stddev REAL,
+        median REAL NOT NULL,
+        user REAL NOT NULL,
+        system REAL NOT NULL,
+        min REAL NOT NULL,
+        max REAL NOT NULL
+    );
+    CREATE TABLE parameters (
+        benchmark_id INTEGER NOT NULL REFERENCES benchmarks(id),
+        name TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (benchmark_id, name)
+    );
+    CREATE TABLE runs (
+        id INTEGER PRIMARY KEY,
+        benchmark_id INTEGER NOT NULL REFERENCES benchmarks(id),
+        run_index INTEGER NOT NULL,
+        time_real REAL NOT NULL,
+        exit_code INTEGER,
+        memory_usage_byte INTEGER
+    );
+    CREATE TABLE run_metrics (
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        metric TEXT NOT NULL,
+        value INTEGER NOT NULL,
+        PRIMARY KEY (run_id, metric)
+    );
+";
+
+/// Appends benchmark results to a SQLite database. Unlike the other exporters, this does
+/// not replace the content of the file, such that a single database can be used to track
+/// results over many invocations of hyperfine.
+pub struct SqliteExporter {
+    connection: Connection,
+
+    /// Number of results that have already been inserted during this session
+    exported: Cell<usize>,
+
+    /// Start time of this session in seconds since the Unix epoch
+    started_at: i64,
+}
+
+impl SqliteExporter {
+    /// Open (or create) the database with the given file name
+    pub fn open(filename: &str) -> Result<Self> {
+        let connection = Connection::open(filename)
+            .with_context(|| format!("Could not open SQLite database '{filename}'"))?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+
+        let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        match version {
+            0 => {
+                connection.execute_batch(SCHEMA)?;
+                connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+            SCHEMA_VERSION => {}
+            _ => bail!(
+                "The SQLite database '{filename}' uses schema version {version}, which is not \
+                 supported by this version of hyperfine (expected {SCHEMA_VERSION})"
+            ),
+        }
+
+        Ok(SqliteExporter {
+            connection,
+            exported: Cell::new(0),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |t| t.as_secs() as i64),
+        })
+    }
+
+    /// Insert all results that have not been inserted before. Each call is a separate
+    /// transaction, such that a benchmark is either stored completely or not at all.
+    pub fn write(&self, results: &[BenchmarkResult]) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        for result in results.iter().skip(self.exported.get()) {
+            insert_benchmark(&transaction, self.started_at, result)?;
+        }
+        transaction.commit()?;
+
+        self.exported.set(results.len());
+        Ok(())
+    }
+}
+
+fn insert_benchmark(
+    transaction: &Transaction,
+    started_at: i64,
+    result: &BenchmarkResult,
+) -> Result<()> {
+    transaction.execute(
+        "INSERT INTO benchmarks (started_at, command, mean, stddev, median, user, system, min, max)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            started_at,
+            result.command,
+            result.mean,
+            result.stddev,
+            result.median,
+            result.user,
+            result.system,
+            result.min,
+            result.max,
+        ],
+    )?;
+    let benchmark_id = transaction.last_insert_rowid();
+
+    for (name, value) in &result.parameters {
+        transaction.execute(
+            "INSERT INTO parameters (benchmark_id, name, value) VALUES (?1, ?2, ?3)",
+            params![benchmark_id, name, value],
+        )?;
+    }
+
+    for (index, time) in result.times.iter().flatten().enumerate() {
+        let memory_usage = result
+            .memory_usage_byte
+            .as_ref()
+            .and_then(|m| m.get(index))
+            .map(|&bytes| bytes as i64);
+        transaction.execute(
+            "INSERT INTO runs (benchmark_id, run_index, time_real, exit_code, memory_usage_byte)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                benchmark_id,
+                index as i64,
+                time,
+                result.exit_codes.get(index).copied().flatten(),
+                memory_usage,
+            ],
+        )?;
+        let run_id = transaction.last_insert_rowid();
+
+        let metrics = result
+            .metrics_per_run
+            .as_ref()
+            .and_then(|m| m.get(index))
+            .and_then(Option::as_ref);
+        for (metric, value) in metrics.map(metric_values).unwrap_or_default() {
+            transaction.execute(
+                "INSERT INTO run_metrics (run_id, metric, value) VALUES (?1, ?2, ?3)",
+                params![run_id, metric, value as i64],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// All collected metrics of a single run, keyed by their command line names
+fn metric_values(metrics: &PoopMetrics) -> Vec<(String, u64)> {
+    MetricType::all()
+        .into_iter()
+        .filter_map(|metric| Some((metric.cli_name().to_string(), metrics.get(metric)?)))
+        .chain(
+            metrics
+                .raw
+                .iter()
+                .map(|(label, &value)| (label.clone(), value)),
+        )
+        .collect()
+}
+
+#[test]
+fn test_sqlite_export() {
+    use std::collections::BTreeMap;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.db");
+    let path = path.to_str().unwrap();
+
+    let mut parameters = BTreeMap::new();
+    parameters.insert("threads".to_string(), "4".to_string());
+    let metrics = |instructions| PoopMetrics {
+        instructions: Some(instructions),
+        ..Default::default()
+    };
+
+    let first = BenchmarkResult {
+        command: String::from("sort input"),
+        mean: 0.2,
+        times: Some(vec![0.1, 0.3]),
+        exit_codes: vec![Some(0), Some(1)],
+        memory_usage_byte: Some(vec![1024, 2048]),
+        parameters,
+        metrics_per_run: Some(vec![Some(metrics(100)), None]),
+        ..Default::default()
+    };
+    let second = BenchmarkResult {
+        command: String::from("sleep 1"),
+        mean: 1.0,
+        times: Some(vec![1.0]),
+        exit_codes: vec![None],
+        ..Default::default()
+    };
+
+    // Intermediate exports must not insert a result twice
+    let exporter = SqliteExporter::open(path).unwrap();
+    let results = vec![first, second.clone()];
+    exporter.write(&results[..1]).unwrap();
+    exporter.write(&results).unwrap();
+    drop(exporter);
+
+    // A second session appends to the existing database
+    SqliteExporter::open(path)
+        .unwrap()
+        .write(&[second])
+        .unwrap();
+
+    let connection = Connection::open(path).unwrap();
+    let count = |table: &str| -> i64 {
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    };
+    assert_eq!(count("benchmarks"), 3);
+    assert_eq!(count("parameters"), 1);
+    assert_eq!(count("runs"), 4);
+    assert_eq!(count("run_metrics"), 1);
+
+    let version: i64 = connection
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .unwrap();
+    assert_eq!(version, SCHEMA_VERSION);
+
+    let (exit_code, memory_usage, instructions): (Option<i32>, Option<i64>, i64) = connection
+        .query_row(
+            "SELECT runs.exit_code, runs.memory_usage_byte, run_metrics.value
+             FROM runs JOIN run_metrics ON run_metrics.run_id = runs.id
+             WHERE run_metrics.metric = 'instructions'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(exit_code, Some(0));
+    assert_eq!(memory_usage, Some(1024));
+    assert_eq!(instructions, 100);
+}
+
+#[test]
+fn test_sqlite_export_rejects_newer_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.db");
+    let path = path.to_str().unwrap();
+
+    Connection::open(path)
+        .unwrap()
+        .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+        .unwrap();
+
+    assert!(SqliteExporter::open(path).is_err());
+}
@@ -1,10 +1,9 @@
-use super::Exporter;
+use super::{version_description, write_to_file, Exporter, SCHEMA_VERSION};
 use crate::benchmark::benchmark_result::BenchmarkResult;
 use crate::export::asciidoc::AsciidocExporter;
 use crate::export::orgmode::OrgmodeExporter;
 use crate::util::units::Unit;
 use crate::{export::markdown::MarkdownExporter, options::SortOrder};
-use std::collections::BTreeMap;
 
 fn get_output<E: Exporter + Default>(
     results: &[BenchmarkResult],
@@ -35,11 +34,8 @@ fn test_markup_export_auto_ms() {
             min: 0.1023,
             max: 0.1080,
             times: Some(vec![0.1, 0.1, 0.1]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
         BenchmarkResult {
             command: String::from("sleep 2"),
@@ -52,11 +48,8 @@ fn test_markup_export_auto_ms() {
             min: 2.0020,
             max: 2.0080,
             times: Some(vec![2.0, 2.0, 2.0]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
     ];
 
@@ -114,11 +107,8 @@ fn test_markup_export_auto_s() {
             min: 2.0020,
             max: 2.0080,
             times: Some(vec![2.0, 2.0, 2.0]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
         BenchmarkResult {
             command: String::from("sleep 0.1"),
@@ -131,11 +121,8 @@ fn test_markup_export_auto_s() {
             min: 0.1023,
             max: 0.1080,
             times: Some(vec![0.1, 0.1, 0.1]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
     ];
 
@@ -193,11 +180,8 @@ fn test_markup_export_manual_ms() {
             min: 2.0020,
             max: 2.0080,
             times: Some(vec![2.0, 2.0, 2.0]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
         BenchmarkResult {
             command: String::from("sleep 0.1"),
@@ -210,11 +194,8 @@ fn test_markup_export_manual_ms() {
             min: 0.1023,
             max: 0.1080,
             times: Some(vec![0.1, 0.1, 0.1]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
     ];
 
@@ -271,11 +252,8 @@ fn test_markup_export_manual_s() {
             min: 2.0020,
             max: 2.0080,
             times: Some(vec![2.0, 2.0, 2.0]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
         BenchmarkResult {
             command: String::from("sleep 0.1"),
@@ -288,11 +266,8 @@ fn test_markup_export_manual_s() {
             min: 0.1023,
             max: 0.1080,
             times: Some(vec![0.1, 0.1, 0.1]),
-            memory_usage_byte: None,
             exit_codes: vec![Some(0), Some(0), Some(0)],
-            parameters: BTreeMap::new(),
-            poop_metrics: None,
-            poop_metrics_all: None,
+            ..Default::default()
         },
     ];
 
@@ -333,3 +308,85 @@ fn test_markup_export_manual_s() {
     |===
     "#);
 }
+
+/// Pipe characters in commands would otherwise start a new table cell
+#[test]
+fn test_markdown_export_escapes_pipes() {
+    let results = [BenchmarkResult {
+        command: String::from("seq 10 | sort"),
+        command_with_unused_parameters: String::from("seq 10 | sort"),
+        mean: 0.0105,
+        stddev: Some(0.0011),
+        min: 0.0092,
+        max: 0.0123,
+        exit_codes: vec![Some(0), Some(0)],
+        ..Default::default()
+    }];
+
+    insta::assert_snapshot!(get_output::<MarkdownExporter>(&results, None, SortOrder::Command), @r#"
+    | Command | Mean [ms] | Min [ms] | Max [ms] | Relative |
+    |:---|---:|---:|---:|---:|
+    | `seq 10 \| sort` | 10.5 ± 1.1 | 9.2 | 12.3 | 1.00 |
+    "#);
+}
+
+/// Ensure that the exports state the version of hyperfine and of the export schema
+#[test]
+fn test_version_comments() {
+    use crate::export::csv::CsvExporter;
+
+    let version = format!(
+        "Exported by hyperfine {} (schema version {})",
+        env!("CARGO_PKG_VERSION"),
+        SCHEMA_VERSION
+    );
+    assert_eq!(version_description(), version);
+
+    let comment = |exporter: &dyn Exporter| exporter.comment(&version_description());
+    assert_eq!(
+        comment(&CsvExporter::default()),
+        Some(format!("# {version}\n"))
+    );
+    assert_eq!(
+        comment(&MarkdownExporter::default()),
+        Some(format!("<!-- {version} -->\n"))
+    );
+    assert_eq!(comment(&AsciidocExporter::default()), None);
+}
+
+#[test]
+fn test_write_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.json");
+    let filename = path.to_str().unwrap();
+    let tmp_path = dir.path().join("results.json.tmp");
+
+    // New and existing regular files are replaced, without leaving the temporary file behind
+    write_to_file(filename, b"first").unwrap();
+    write_to_file(filename, b"second").unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"second");
+    assert!(!tmp_path.exists());
+
+    // Exports to missing directories fail
+    let missing = dir.path().join("missing").join("results.json");
+    assert!(write_to_file(missing.to_str().unwrap(), b"third").is_err());
+}
+
+/// Symbolic links (and other special files like `/dev/stdout`) are written to directly,
+/// instead of being replaced by a regular file
+#[cfg(unix)]
+#[test]
+fn test_write_to_file_through_symlink() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("target.json");
+    let link = dir.path().join("link.json");
+    std::fs::write(&target, b"old").unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    write_to_file(link.to_str().unwrap(), b"new").unwrap();
+    assert!(std::fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(std::fs::read(&target).unwrap(), b"new");
+}
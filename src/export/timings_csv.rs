@@ -0,0 +1,122 @@
+use std::collections::BTreeSet;
+
+use csv::WriterBuilder;
+
+use super::Exporter;
+use crate::benchmark::benchmark_result::BenchmarkResult;
+use crate::options::SortOrder;
+use crate::util::units::Unit;
+
+use anyhow::Result;
+
+/// Exports the measurements of every individual run as CSV, with one row per run
+#[derive(Default)]
+pub struct TimingsCsvExporter {}
+
+impl Exporter for TimingsCsvExporter {
+    fn serialize(
+        &self,
+        results: &[BenchmarkResult],
+        _unit: Option<Unit>,
+        _sort_order: SortOrder,
+    ) -> Result<Vec<u8>> {
+        let mut writer = WriterBuilder::new().from_writer(vec![]);
+
+        let param_names: BTreeSet<&String> = results
+            .iter()
+            .flat_map(|res| res.parameters.keys())
+            .collect();
+
+        let mut headers: Vec<String> = [
+            "command",
+            "run_index",
+            "time_real",
+            "time_user",
+            "time_system",
+            "memory_usage_byte",
+            "exit_code",
+        ]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+        for param_name in &param_names {
+            headers.push(format!("parameter_{param_name}"));
+        }
+        writer.write_record(headers)?;
+
+        for res in results {
+            for (index, time_real) in res.times.iter().flatten().enumerate() {
+                let mut fields = vec![
+                    res.command.clone(),
+                    index.to_string(),
+                    time_real.to_string(),
+                    cell(res.times_user.as_deref(), index),
+                    cell(res.times_system.as_deref(), index),
+                    cell(res.memory_usage_byte.as_deref(), index),
+                    res.exit_codes
+                        .get(index)
+                        .copied()
+                        .flatten()
+                        .map_or_else(String::new, |code| code.to_string()),
+                ];
+                for param_name in &param_names {
+                    fields.push(res.parameters.get(*param_name).cloned().unwrap_or_default());
+                }
+                writer.write_record(fields)?;
+            }
+        }
+
+        Ok(writer.into_inner()?)
+    }
+
+    fn comment(&self, text: &str) -> Option<String> {
+        Some(format!("# {text}\n"))
+    }
+}
+
+/// The value of the given run, or an empty cell if it is not available
+fn cell<T: ToString>(values: Option<&[T]>, index: usize) -> String {
+    values
+        .and_then(|values| values.get(index))
+        .map_or_else(String::new, ToString::to_string)
+}
+
+#[test]
+fn test_timings_csv() {
+    use std::collections::BTreeMap;
+
+    let mut parameters = BTreeMap::new();
+    parameters.insert("size".to_string(), "10".to_string());
+
+    let results = vec![
+        BenchmarkResult {
+            command: String::from("sort, then uniq"),
+            times: Some(vec![0.5, 0.25]),
+            times_user: Some(vec![0.125, 0.0625]),
+            times_system: Some(vec![0.0, 0.03125]),
+            memory_usage_byte: Some(vec![1024, 2048]),
+            exit_codes: vec![Some(0), None],
+            parameters,
+            ..Default::default()
+        },
+        BenchmarkResult {
+            command: String::from("sleep 1"),
+            times: Some(vec![1.0]),
+            exit_codes: vec![Some(1)],
+            ..Default::default()
+        },
+    ];
+
+    let exporter = TimingsCsvExporter::default();
+    let output = exporter
+        .serialize(&results, None, SortOrder::Command)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "command,run_index,time_real,time_user,time_system,memory_usage_byte,exit_code,parameter_size\n\
+         \"sort, then uniq\",0,0.5,0.125,0,1024,0,10\n\
+         \"sort, then uniq\",1,0.25,0.0625,0.03125,2048,,10\n\
+         sleep 1,0,1,,,,1,\n"
+    );
+}
@@ -4,14 +4,19 @@
 )]
 
 use std::env;
+use std::path::Path;
 
 use benchmark::scheduler::Scheduler;
 use cli::get_cli_arguments;
 use command::Commands;
-use export::ExportManager;
+use error::{CommandFailedError, OptionsError, ParameterScanError, PerformanceRegressionError};
+use export::{import_results, ExportManager};
 use options::Options;
+use output::metric_list::print_metric_list;
+#[cfg(target_os = "linux")]
+use util::cpu_affinity::CpuSet;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::*;
 
 pub mod benchmark;
@@ -27,36 +32,111 @@ mod poop_metrics;
 mod timer;
 mod util;
 
-fn run() -> Result<()> {
+/// Run hyperfine and return the exit code of the process
+fn run() -> Result<i32> {
     // Enabled ANSI colors on Windows 10
     #[cfg(windows)]
     colored::control::set_virtual_terminal(true).unwrap();
 
     let cli_arguments = get_cli_arguments(env::args_os());
+
+    if cli_arguments.get_flag("list-metrics") {
+        print_metric_list();
+        return Ok(0);
+    }
+
     let mut options = Options::from_cli_arguments(&cli_arguments)?;
     let commands = Commands::from_cli_arguments(&cli_arguments)?;
-    let export_manager = ExportManager::from_cli_arguments(
-        &cli_arguments,
-        options.time_unit,
-        options.sort_order_exports,
-    )?;
 
-    options.validate_against_command_list(&commands)?;
+    #[cfg(target_os = "linux")]
+    if let Some(cpus) = &options.cpu_affinity {
+        CpuSet::new(cpus)
+            .apply()
+            .context("Could not pin hyperfine to the CPUs given by '--cpu'")?;
+    }
+
+    #[cfg(unix)]
+    if !options.priority.is_inherited() && !options.priority.is_permitted() {
+        eprintln!(
+            "{}: The scheduling priority given by '--nice'/'--realtime' can not be set \
+             (insufficient privileges?). The commands will run at the priority of hyperfine.",
+            "Warning".yellow()
+        );
+        options.priority = Default::default();
+    }
+
+    let imported_results = cli_arguments
+        .get_one::<String>("from-json")
+        .map(|path| import_results(Path::new(path)))
+        .transpose()?;
+
+    match &imported_results {
+        Some(results) => options.validate_against_imported_results(results)?,
+        None => options.validate_against_command_list(&commands)?,
+    }
+
+    let baseline_results = cli_arguments
+        .get_one::<String>("baseline")
+        .map(|path| import_results(Path::new(path)))
+        .transpose()?;
+
+    let export_manager = ExportManager::from_cli_arguments(&cli_arguments, &options)?;
 
     let mut scheduler = Scheduler::new(&commands, &options, &export_manager);
-    scheduler.run_benchmarks()?;
+    match imported_results {
+        Some(results) => scheduler.import_results(results)?,
+        None => scheduler.run_benchmarks()?,
+    }
     scheduler.print_relative_speed_comparison();
     scheduler.final_export()?;
 
-    Ok(())
+    if let Some(baseline) = &baseline_results {
+        scheduler.compare_to_baseline(baseline)?;
+    }
+
+    // The exit code of the command is passed on unchanged (as documented), even if it is one of
+    // the exit codes that hyperfine uses itself
+    let failing_exit_code = scheduler
+        .first_failing_exit_code()
+        .filter(|_| options.error_exit_code);
+    Ok(failing_exit_code.unwrap_or(0))
+}
+
+/// Exit status for errors that are not covered by a more specific exit status
+const EXIT_ERROR: i32 = 1;
+
+/// Exit status for invalid command-line arguments (the same that is used by clap)
+const EXIT_USAGE_ERROR: i32 = 2;
+
+/// Exit status if a benchmarked command failed and the failure has not been ignored
+const EXIT_COMMAND_FAILED: i32 = 3;
+
+/// Exit status if a benchmark regressed compared to the '--baseline' results
+const EXIT_REGRESSION: i32 = 4;
+
+/// Select the exit status for the given error
+fn exit_status_of(error: &anyhow::Error) -> i32 {
+    if error.chain().any(|e| e.is::<CommandFailedError>()) {
+        EXIT_COMMAND_FAILED
+    } else if error.chain().any(|e| e.is::<PerformanceRegressionError>()) {
+        EXIT_REGRESSION
+    } else if error
+        .chain()
+        .any(|e| e.is::<OptionsError<'static>>() || e.is::<ParameterScanError>())
+    {
+        EXIT_USAGE_ERROR
+    } else {
+        EXIT_ERROR
+    }
 }
 
 fn main() {
     match run() {
-        Ok(_) => {}
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
         Err(e) => {
             eprintln!("{} {:#}", "Error:".red(), e);
-            std::process::exit(1);
+            std::process::exit(exit_status_of(&e));
         }
     }
 }
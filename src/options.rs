@@ -1,16 +1,21 @@
 use std::fs::File;
-use std::io::IsTerminal;
+use std::io::{IsTerminal, Write};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
-use std::{cmp, env, fmt, io};
+use std::time::Duration;
+use std::{cmp, env, fmt, io, thread};
 
-use anyhow::ensure;
 use clap::ArgMatches;
+use serde::Serialize;
 
-use crate::command::Commands;
+use crate::benchmark::benchmark_result::BenchmarkResult;
+use crate::command::{self, Commands};
 use crate::error::OptionsError;
-use crate::poop_metrics::MetricType;
-use crate::util::units::{Second, Unit};
+use crate::poop_metrics::{ComparisonMetric, MetricType, RawEvent};
+#[cfg(target_os = "linux")]
+use crate::util::cpu_affinity::{allowed_cpus, format_cpu_list, parse_cpu_list};
+use crate::util::priority::{SchedulingPriority, NICE_RANGE};
+use crate::util::units::{Scalar, Second, Unit};
 
 use anyhow::Result;
 
@@ -21,7 +26,7 @@ pub const DEFAULT_SHELL: &str = "sh";
 pub const DEFAULT_SHELL: &str = "cmd.exe";
 
 /// Shell to use for executing benchmarked commands
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Shell {
     /// Default shell command
     Default(&'static str),
@@ -65,8 +70,46 @@ impl Shell {
             }
         }
     }
+
+    /// Name or path of the shell executable
+    pub fn program(&self) -> &str {
+        match self {
+            Shell::Default(cmd) => cmd,
+            Shell::Custom(cmdline) => &cmdline[0],
+        }
+    }
+
+    /// The argument that needs to be passed before the command line to make the shell execute
+    /// it, e.g. "-c" for POSIX shells. This is `None` if the custom shell command line already
+    /// ends with such an argument, like in "bash --norc -c" or "pwsh -Command".
+    pub fn command_flag(&self) -> Option<&'static str> {
+        if let Shell::Custom(cmdline) = self {
+            if cmdline.len() > 1
+                && SHELL_COMMAND_FLAGS.contains(&cmdline[cmdline.len() - 1].as_str())
+            {
+                return None;
+            }
+        }
+
+        // Handle Windows paths independent of the current platform
+        let file_name = self
+            .program()
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        Some(match file_name.trim_end_matches(".exe") {
+            "cmd" => "/C",
+            "pwsh" | "powershell" => "-Command",
+            _ => "-c",
+        })
+    }
 }
 
+/// Arguments of common shells that make them execute the command line given as the next
+/// argument
+const SHELL_COMMAND_FLAGS: &[&str] = &["-c", "/C", "/c", "-Command", "-command"];
+
 /// Action to take when an executed command fails.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CmdFailureAction {
@@ -101,11 +144,18 @@ pub enum OutputStyleOption {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortOrder {
+    /// The order in which the commands were specified
     Command,
+
+    /// Lexicographic order of the command names
+    Name,
+
+    /// Ascending mean run time (fastest first)
     MeanTime,
 }
 
 /// Bounds for the number of benchmark runs
+#[derive(Clone)]
 pub struct RunBounds {
     /// Minimum number of benchmark runs
     pub min: u64,
@@ -120,6 +170,44 @@ impl Default for RunBounds {
     }
 }
 
+impl RunBounds {
+    /// Bounds that only allow exactly the given number of runs
+    pub fn exact(runs: u64) -> Self {
+        RunBounds {
+            min: runs,
+            max: Some(runs),
+        }
+    }
+
+    /// The number of runs, if the bounds leave no choice (e.g. with '--runs'). In this case,
+    /// the number of runs is not derived from the minimum benchmarking time.
+    pub fn exact_count(&self) -> Option<u64> {
+        self.max.filter(|&max| max == self.min)
+    }
+}
+
+/// An exact number of runs for all benchmarks in which a parameter has the given value
+/// (see `--runs-for`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RunCountOverride {
+    pub parameter: String,
+    pub value: String,
+    pub runs: u64,
+}
+
+/// Which processes are taken into account for the user and system CPU times
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CpuTimeAccounting {
+    /// The CPU time of the spawned process and all of its descendants that have been waited
+    /// for. For commands that are run through a shell, this includes the command itself.
+    #[default]
+    Children,
+
+    /// Like `Children`, but additionally include the CPU time that hyperfine itself spends
+    /// while the command is running (e.g. for reading its output). Only supported on Unix.
+    IncludeSelf,
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub enum CommandInputPolicy {
     /// Read from the null device
@@ -128,6 +216,9 @@ pub enum CommandInputPolicy {
 
     /// Read input from a file
     File(PathBuf),
+
+    /// Read the given text, which is written to a pipe
+    String(String),
 }
 
 impl CommandInputPolicy {
@@ -139,6 +230,18 @@ impl CommandInputPolicy {
                 let file: File = File::open(path)?;
                 Stdio::from(file)
             }
+
+            CommandInputPolicy::String(content) => {
+                let (reader, mut writer) = io::pipe()?;
+                let content = content.clone();
+                // Write from a separate thread, such that input larger than the pipe buffer
+                // does not block. Errors are ignored, as the command does not have to read
+                // all of its input.
+                thread::spawn(move || {
+                    let _ = writer.write_all(content.as_bytes());
+                });
+                Stdio::from(reader)
+            }
         };
 
         Ok(stream)
@@ -160,6 +263,9 @@ pub enum CommandOutputPolicy {
 
     /// Show command output on the terminal
     Inherit,
+
+    /// Keep the last N bytes of the combined stdout and stderr output in memory
+    Capture(usize),
 }
 
 impl CommandOutputPolicy {
@@ -176,13 +282,50 @@ impl CommandOutputPolicy {
             }
 
             CommandOutputPolicy::Inherit => (Stdio::inherit(), Stdio::inherit()),
+
+            // Both streams share a single pipe that is set up by the executor
+            CommandOutputPolicy::Capture(_) => {
+                return Err(io::Error::other("captured output needs a pipe"));
+            }
         };
 
         Ok(streams)
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Search path of commands that are run with a cleared environment
+#[cfg(not(windows))]
+const MINIMAL_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Environment of the benchmarked commands and of all intermediate commands
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandEnvironment {
+    /// Start from an empty environment (with a minimal `PATH`) instead of the one of hyperfine
+    pub clear: bool,
+
+    /// Variables that are set in addition
+    pub variables: Vec<(String, String)>,
+}
+
+impl CommandEnvironment {
+    pub fn apply(&self, command: &mut Command) {
+        if self.clear {
+            command.env_clear();
+            #[cfg(not(windows))]
+            command.env("PATH", MINIMAL_PATH);
+            // Windows programs commonly fail to start without these variables
+            #[cfg(windows)]
+            for name in ["PATH", "SYSTEMROOT"] {
+                if let Some(value) = env::var_os(name) {
+                    command.env(name, value);
+                }
+            }
+        }
+        command.envs(self.variables.iter().map(|(k, v)| (k, v)));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExecutorKind {
     Raw,
     Shell(Shell),
@@ -196,25 +339,51 @@ impl Default for ExecutorKind {
 }
 
 /// The main settings for a hyperfine benchmark session
+#[derive(Clone)]
 pub struct Options {
     /// Upper and lower bound for the number of benchmark runs
     pub run_bounds: RunBounds,
 
+    /// Numbers of runs that replace the run bounds for specific parameter values
+    pub run_count_overrides: Vec<RunCountOverride>,
+
     /// Number of warmup runs
     pub warmup_count: u64,
 
+    /// Keep performing warmup runs until their accumulated wall clock time exceeds this value
+    pub warmup_time: Option<Second>,
+
     /// Minimum benchmarking time
     pub min_benchmarking_time: Second,
 
     /// Whether or not to ignore non-zero exit codes
     pub command_failure_action: CmdFailureAction,
 
+    /// Whether to exit with the first non-zero exit code of the benchmarked commands
+    pub error_exit_code: bool,
+
+    /// Maximum duration of a single run, after which the command is killed
+    pub timeout: Option<Second>,
+
+    /// CPUs that hyperfine and the benchmarked commands are pinned to
+    pub cpu_affinity: Option<Vec<usize>>,
+
+    /// Nice value and scheduling policy of the benchmarked commands
+    pub priority: SchedulingPriority,
+
+    /// Shell spawning time to subtract from the wall clock time, instead of measuring it
+    pub shell_overhead: Option<Second>,
+
     // Command to use as a reference for relative speed comparison
     pub reference_command: Option<String>,
 
     // Name of the reference command
     pub reference_name: Option<String>,
 
+    /// Index of the benchmarked command that serves as the reference, if '--reference' refers
+    /// to one of them (by its number or name) instead of giving an additional command
+    pub reference_index: Option<usize>,
+
     /// Command(s) to run before each timing run
     pub preparation_command: Option<Vec<String>>,
 
@@ -242,28 +411,164 @@ pub struct Options {
     /// Where input to the benchmarked command comes from
     pub command_input_policy: CommandInputPolicy,
 
+    /// Environment variables of the benchmarked commands
+    pub command_environment: CommandEnvironment,
+
     /// What to do with the output of the benchmarked commands
     pub command_output_policies: Vec<CommandOutputPolicy>,
 
+    /// Minimum time between two updates of the progress bar (`None` updates after every run)
+    pub progress_update_interval: Option<Duration>,
+
     /// Which time unit to use when displaying results
     pub time_unit: Option<Unit>,
 
     /// Whether to collect poop performance metrics
     pub poop_metrics_enabled: bool,
 
-    /// Specific metrics to collect (empty means all)
+    /// Specific metrics to collect (empty means all, unless raw events are given)
     pub metrics_to_collect: Vec<MetricType>,
+
+    /// Raw, CPU-specific perf events to collect
+    pub raw_events: Vec<RawEvent>,
+
+    /// Keep running until the runs have executed this many instructions in total
+    pub target_instructions: Option<u64>,
+
+    /// Whether to only count the process that is started for every run, but not its
+    /// descendants and threads
+    pub metrics_leader_only: bool,
+
+    /// Whether to additionally report the poop metrics per second of run time
+    pub metrics_per_second: bool,
+
+    /// CPU frequency (in GHz) for which run times are estimated from the cycle counts
+    pub reference_frequency_ghz: Option<f64>,
+
+    /// Whether to warn if the run times vary although the CPU cycle counts are stable
+    pub external_variance_warning: bool,
+
+    /// Whether missing performance metrics are an error (instead of a warning)
+    pub require_metrics: bool,
+
+    /// Whether to dump the raw values of all perf counters to stderr after each run
+    pub debug_perf: bool,
+
+    /// Which processes are taken into account for the user and system CPU times
+    pub cpu_time_accounting: CpuTimeAccounting,
+
+    /// Metrics to compare across benchmarks in the summary
+    pub comparison_metrics: Vec<ComparisonMetric>,
+
+    /// Free-form notes describing this benchmark session
+    pub descriptions: Vec<String>,
+
+    /// Repeat the measurement phase if the relative standard deviation exceeds this value
+    pub max_relative_stddev: Option<Scalar>,
+
+    /// Maximum number of measurement attempts per benchmark when retrying on high variance
+    pub max_attempts: u64,
+
+    /// Keep performing runs until the relative margin of error of the mean is below this value
+    pub precision: Option<Scalar>,
+
+    /// Whether to exclude statistical outliers from the summary statistics
+    pub drop_outliers: bool,
+
+    /// Whether to omit the results of the individual benchmarks from the output
+    pub quiet: bool,
+
+    /// Whether to write progress events as JSON lines to stderr
+    pub progress_json: bool,
+
+    /// Whether to show the trimmed mean and the median absolute deviation of the run times
+    pub robust_stats: bool,
+
+    /// Whether to show a histogram of the run times after each benchmark
+    pub show_histogram: bool,
+
+    /// Number of bins of the histogram (`None` for the square root of the number of runs)
+    pub histogram_bins: Option<usize>,
+
+    /// Whether to show the mean run time of the preparation and conclusion commands
+    pub show_setup_time: bool,
+
+    /// Number of bootstrap resamples for the confidence interval of the mean
+    pub bootstrap_resamples: usize,
+
+    /// Confidence level of the confidence interval of the mean, e.g. 0.95
+    pub confidence_level: Scalar,
+
+    /// Trivial command whose startup overhead should be benchmarked and reported separately
+    pub overhead_command: Option<String>,
+
+    /// Maximum number of bytes of command output that is kept in memory when capturing output
+    pub output_capture_limit: usize,
+
+    /// Whether to show the (otherwise discarded) output of runs that fail
+    pub show_output_on_failure: bool,
+
+    /// Whether to show the summary as a before/after comparison of two commands
+    pub before_after: bool,
+
+    /// Whether to perform the runs of all commands in turns, instead of one benchmark after
+    /// the other
+    pub interleave: bool,
+
+    /// Seed for randomizing the order in which the benchmarks are performed. The benchmarks
+    /// are performed in the given order if this is not set.
+    pub shuffle_seed: Option<u64>,
+
+    /// File to save the results of completed benchmarks to
+    pub checkpoint_file: Option<PathBuf>,
+
+    /// Whether to skip the benchmarks that have been completed according to the checkpoint file
+    pub resume: bool,
+
+    /// File from which a weight for each benchmark run is read, after the run has finished
+    pub weight_file: Option<PathBuf>,
+
+    /// File to which each completed benchmark run is appended as a line of JSON
+    pub jsonl_file: Option<PathBuf>,
+
+    /// Whether to record the start time of every run (only exported to JSON)
+    pub record_start_times: bool,
+
+    /// Whether to write the JSON exports on a single line instead of indenting them
+    pub json_compact: bool,
+
+    /// Whether to report the size of the binary of each command
+    pub binary_size: bool,
+
+    /// Maximum relative increase of the mean run times, compared to the '--baseline' results
+    pub regression_threshold: Option<Scalar>,
+
+    /// Maximum relative increase of the mean values of individual poop metrics, compared to
+    /// the '--baseline' results
+    pub metric_regression_thresholds: Vec<(MetricType, Scalar)>,
+
+    /// Explicit paths of the binaries (once, or once per command). Resolved from the
+    /// command line if empty.
+    pub binary_paths: Vec<PathBuf>,
 }
 
 impl Default for Options {
     fn default() -> Options {
         Options {
             run_bounds: RunBounds::default(),
+            run_count_overrides: vec![],
             warmup_count: 0,
+            warmup_time: None,
             min_benchmarking_time: 3.0,
             command_failure_action: CmdFailureAction::RaiseError,
+            error_exit_code: false,
+            timeout: None,
+            cpu_affinity: None,
+            priority: SchedulingPriority::default(),
+            shell_overhead: None,
             reference_command: None,
             reference_name: None,
+            reference_index: None,
             preparation_command: None,
             conclusion_command: None,
             setup_command: None,
@@ -275,13 +580,348 @@ impl Default for Options {
             command_output_policies: vec![CommandOutputPolicy::Null],
             time_unit: None,
             command_input_policy: CommandInputPolicy::Null,
+            command_environment: CommandEnvironment::default(),
             poop_metrics_enabled: false,
             metrics_to_collect: vec![],
+            raw_events: vec![],
+            target_instructions: None,
+            progress_update_interval: None,
+            metrics_leader_only: false,
+            metrics_per_second: false,
+            reference_frequency_ghz: None,
+            external_variance_warning: true,
+            require_metrics: false,
+            debug_perf: false,
+            cpu_time_accounting: CpuTimeAccounting::default(),
+            comparison_metrics: vec![],
+            descriptions: vec![],
+            max_relative_stddev: None,
+            max_attempts: 1,
+            precision: None,
+            drop_outliers: false,
+            quiet: false,
+            progress_json: false,
+            robust_stats: false,
+            show_histogram: false,
+            histogram_bins: None,
+            show_setup_time: false,
+            bootstrap_resamples: 10_000,
+            confidence_level: 0.95,
+            overhead_command: None,
+            output_capture_limit: 64 << 10,
+            show_output_on_failure: false,
+            before_after: false,
+            interleave: false,
+            shuffle_seed: None,
+            checkpoint_file: None,
+            resume: false,
+            weight_file: None,
+            jsonl_file: None,
+            record_start_times: false,
+            json_compact: false,
+            binary_size: false,
+            regression_threshold: None,
+            metric_regression_thresholds: vec![],
+            binary_paths: vec![],
         }
     }
 }
 
+/// A serializable snapshot of the options that influence the benchmark results. It is
+/// stored in the metadata of the JSON export, such that a benchmark can be reproduced from
+/// its results file alone.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OptionsSnapshot {
+    pub min_runs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_runs: Option<u64>,
+    /// Exact numbers of runs for specific parameter values (see `--runs-for`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub runs_for: Vec<RunCountOverride>,
+    pub warmup_runs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warmup_time: Option<Second>,
+    pub min_benchmarking_time: Second,
+    pub failure_action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Second>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_affinity: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub realtime: bool,
+    pub shell: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell_overhead: Option<Second>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// Zero-based index of the benchmarked command that was used as the reference
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shuffle_seed: Option<u64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub interleave: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setup: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prepare: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclude: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleanup: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overhead_command: Option<String>,
+    pub input: String,
+    pub output: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub clear_env: bool,
+    /// Additional environment variables, as `NAME=VALUE`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight_file: Option<String>,
+    /// Minimum time between two updates of the progress bar, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_interval: Option<Second>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_unit: Option<String>,
+    /// The collected performance metrics (`None` if disabled, empty if all)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub metrics_leader_only: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub metrics_per_second: bool,
+    pub cpu_time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_instructions: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_relative_stddev: Option<Scalar>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<Scalar>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub drop_outliers: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub robust_stats: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub histogram: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub histogram_bins: Option<usize>,
+    pub bootstrap_resamples: usize,
+    pub confidence_level: Scalar,
+}
+
+impl OptionsSnapshot {
+    pub fn new(options: &Options) -> Self {
+        // All options are listed, such that every new option needs to be either recorded or
+        // explicitly excluded here
+        let Options {
+            run_bounds,
+            run_count_overrides,
+            warmup_count,
+            warmup_time,
+            min_benchmarking_time,
+            command_failure_action,
+            timeout,
+            cpu_affinity,
+            priority,
+            shell_overhead,
+            reference_command,
+            reference_index,
+            preparation_command,
+            conclusion_command,
+            setup_command,
+            cleanup_command,
+            executor_kind,
+            command_input_policy,
+            command_environment,
+            command_output_policies,
+            progress_update_interval,
+            time_unit,
+            poop_metrics_enabled,
+            metrics_to_collect,
+            raw_events,
+            target_instructions,
+            metrics_leader_only,
+            metrics_per_second,
+            cpu_time_accounting,
+            max_relative_stddev,
+            max_attempts,
+            precision,
+            drop_outliers,
+            robust_stats,
+            show_histogram,
+            histogram_bins,
+            bootstrap_resamples,
+            confidence_level,
+            overhead_command,
+            interleave,
+            shuffle_seed,
+            weight_file,
+            // These options only affect how the results are reported, not the results
+            error_exit_code: _,
+            reference_name: _,
+            output_style: _,
+            sort_order_speed_comparison: _,
+            sort_order_exports: _,
+            reference_frequency_ghz: _,
+            external_variance_warning: _,
+            require_metrics: _,
+            debug_perf: _,
+            comparison_metrics: _,
+            descriptions: _,
+            quiet: _,
+            progress_json: _,
+            show_setup_time: _,
+            output_capture_limit: _,
+            show_output_on_failure: _,
+            before_after: _,
+            checkpoint_file: _,
+            resume: _,
+            jsonl_file: _,
+            record_start_times: _,
+            json_compact: _,
+            binary_size: _,
+            regression_threshold: _,
+            metric_regression_thresholds: _,
+            binary_paths: _,
+        } = options;
+
+        OptionsSnapshot {
+            min_runs: run_bounds.min,
+            max_runs: run_bounds.max,
+            runs_for: run_count_overrides.clone(),
+            warmup_runs: *warmup_count,
+            warmup_time: *warmup_time,
+            min_benchmarking_time: *min_benchmarking_time,
+            failure_action: match command_failure_action {
+                CmdFailureAction::RaiseError => "raise-error".into(),
+                CmdFailureAction::IgnoreAllFailures => "ignore-all-non-zero".into(),
+                CmdFailureAction::IgnoreSpecificFailures(codes) => format!(
+                    "ignore-exit-codes:{}",
+                    codes
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+            },
+            timeout: *timeout,
+            cpu_affinity: cpu_affinity.clone(),
+            nice: priority.nice,
+            realtime: priority.realtime,
+            shell: match executor_kind {
+                ExecutorKind::Raw => "none".into(),
+                ExecutorKind::Shell(shell) => shell.to_string(),
+                ExecutorKind::Mock(shell) => {
+                    format!("mock:{}", shell.as_deref().unwrap_or("default"))
+                }
+            },
+            shell_overhead: *shell_overhead,
+            reference: reference_command.clone(),
+            reference_index: *reference_index,
+            shuffle_seed: *shuffle_seed,
+            interleave: *interleave,
+            setup: setup_command.clone(),
+            prepare: preparation_command.clone(),
+            conclude: conclusion_command.clone(),
+            cleanup: cleanup_command.clone(),
+            overhead_command: overhead_command.clone(),
+            input: match command_input_policy {
+                CommandInputPolicy::Null => "null".into(),
+                CommandInputPolicy::File(path) => path.display().to_string(),
+                CommandInputPolicy::String(text) => format!("string:{text}"),
+            },
+            output: command_output_policies
+                .iter()
+                .map(|policy| match policy {
+                    CommandOutputPolicy::Null => "null".into(),
+                    CommandOutputPolicy::Pipe => "pipe".into(),
+                    CommandOutputPolicy::Inherit => "inherit".into(),
+                    CommandOutputPolicy::File(path) => path.display().to_string(),
+                    CommandOutputPolicy::Capture(limit) => format!("capture:{limit}"),
+                })
+                .collect(),
+            clear_env: command_environment.clear,
+            env: command_environment
+                .variables
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect(),
+            weight_file: weight_file.as_ref().map(|path| path.display().to_string()),
+            progress_interval: progress_update_interval.map(|interval| interval.as_secs_f64()),
+            time_unit: time_unit.map(|unit| {
+                match unit {
+                    Unit::Hour => "hour",
+                    Unit::Minute => "minute",
+                    Unit::Second => "second",
+                    Unit::MilliSecond => "millisecond",
+                    Unit::MicroSecond => "microsecond",
+                    Unit::NanoSecond => "nanosecond",
+                }
+                .into()
+            }),
+            metrics: poop_metrics_enabled.then(|| {
+                metrics_to_collect
+                    .iter()
+                    .map(|m| m.cli_name().into())
+                    .chain(raw_events.iter().map(|e| e.label()))
+                    .collect()
+            }),
+            metrics_leader_only: *poop_metrics_enabled && *metrics_leader_only,
+            metrics_per_second: *poop_metrics_enabled && *metrics_per_second,
+            cpu_time: match cpu_time_accounting {
+                CpuTimeAccounting::Children => "children",
+                CpuTimeAccounting::IncludeSelf => "include-self",
+            }
+            .into(),
+            target_instructions: *target_instructions,
+            max_relative_stddev: *max_relative_stddev,
+            max_attempts: max_relative_stddev.map(|_| *max_attempts),
+            precision: *precision,
+            drop_outliers: *drop_outliers,
+            robust_stats: *robust_stats,
+            histogram_bins: show_histogram.then_some(*histogram_bins).flatten(),
+            histogram: *show_histogram,
+            bootstrap_resamples: *bootstrap_resamples,
+            confidence_level: *confidence_level,
+        }
+    }
+}
+
+/// Parse and validate the argument to '--cpu'
+#[cfg(target_os = "linux")]
+fn parse_cpu_affinity(list: &str) -> Result<Vec<usize>, OptionsError<'static>> {
+    let cpus = parse_cpu_list(list).ok_or_else(|| OptionsError::InvalidCpuList(list.into()))?;
+    // If the allowed CPUs can not be determined, pinning fails later with a proper error
+    let Ok(allowed) = allowed_cpus() else {
+        return Ok(cpus);
+    };
+    match cpus.iter().find(|cpu| !allowed.contains(cpu)) {
+        Some(&cpu) => Err(OptionsError::CpuNotAvailable(
+            cpu,
+            format_cpu_list(&allowed),
+        )),
+        None => Ok(cpus),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn parse_cpu_affinity(_list: &str) -> Result<Vec<usize>, OptionsError<'static>> {
+    Err(OptionsError::CpuAffinityNotSupported)
+}
+
 impl Options {
+    /// Whether to show the interactive progress bar, which is replaced by the event stream of
+    /// '--progress-json'
+    pub fn show_progress_bar(&self) -> bool {
+        self.output_style != OutputStyleOption::Disabled && !self.progress_json
+    }
+
     pub fn from_cli_arguments<'a>(matches: &ArgMatches) -> Result<Self, OptionsError<'a>> {
         let mut options = Self::default();
         let param_to_u64 = |param| {
@@ -296,6 +936,16 @@ impl Options {
 
         options.warmup_count = param_to_u64("warmup")?.unwrap_or(options.warmup_count);
 
+        if let Some(time) = matches.get_one::<String>("warmup-time") {
+            let time = time
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("warmup-time", e))?;
+            if !(time > 0.0 && time.is_finite()) {
+                return Err(OptionsError::NonPositiveArgument("warmup-time"));
+            }
+            options.warmup_time = Some(time);
+        }
+
         let mut min_runs = param_to_u64("min-runs")?;
         let mut max_runs = param_to_u64("max-runs")?;
 
@@ -314,7 +964,7 @@ impl Options {
                 options.run_bounds.max = Some(max);
             }
             (Some(min), Some(max)) if min > max => {
-                return Err(OptionsError::EmptyRunsRange);
+                return Err(OptionsError::EmptyRunsRange(min, max));
             }
             (Some(min), Some(max)) => {
                 options.run_bounds.min = min;
@@ -340,6 +990,67 @@ impl Options {
 
         options.cleanup_command = matches.get_one::<String>("cleanup").map(String::from);
 
+        options.before_after = matches.get_flag("before-after");
+
+        options.interleave = matches.get_flag("interleave");
+
+        if matches.get_flag("shuffle") {
+            options.shuffle_seed = Some(match matches.get_one::<String>("shuffle-seed") {
+                Some(seed) => seed
+                    .parse::<u64>()
+                    .map_err(|e| OptionsError::IntParsingError("shuffle-seed", e))?,
+                None => rand::random(),
+            });
+        }
+
+        let overrides: Vec<&String> = matches
+            .get_many::<String>("runs-for")
+            .unwrap_or_default()
+            .collect();
+        for pair in overrides.chunks(2) {
+            let [parameter_value, runs] = pair else {
+                return Err(OptionsError::MissingRunCountOverride(pair[0].clone()));
+            };
+            let (parameter, value) = parameter_value.split_once('=').ok_or_else(|| {
+                OptionsError::InvalidRunCountOverride(parameter_value.to_string())
+            })?;
+            let runs = runs
+                .parse::<u64>()
+                .map_err(|e| OptionsError::IntParsingError("runs-for", e))?;
+            if runs == 0 {
+                return Err(OptionsError::NonPositiveArgument("runs-for"));
+            }
+            options.run_count_overrides.push(RunCountOverride {
+                parameter: parameter.into(),
+                value: value.into(),
+                runs,
+            });
+        }
+
+        options.checkpoint_file = matches.get_one::<String>("checkpoint").map(PathBuf::from);
+        options.resume = matches.get_flag("resume");
+
+        options.weight_file = matches.get_one::<String>("weight-file").map(PathBuf::from);
+        options.jsonl_file = matches.get_one::<String>("export-jsonl").map(PathBuf::from);
+        options.record_start_times =
+            matches.contains_id("export-json") || matches.contains_id("export-json-binary");
+        options.json_compact = matches.get_flag("json-compact");
+
+        options.binary_paths = matches
+            .get_many::<String>("binary-path")
+            .map(|values| values.map(PathBuf::from).collect())
+            .unwrap_or_default();
+        options.binary_size = matches.get_flag("binary-size") || !options.binary_paths.is_empty();
+
+        options.overhead_command = matches
+            .get_one::<String>("measure-overhead")
+            .map(String::from);
+
+        options.descriptions = matches
+            .get_many::<String>("description")
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default();
+
         options.command_output_policies = if matches.get_flag("show-output") {
             vec![CommandOutputPolicy::Inherit]
         } else if let Some(output_values) = matches.get_many::<String>("output") {
@@ -401,12 +1112,16 @@ impl Options {
             OutputStyleOption::Disabled => {}
         };
 
+        options.quiet = matches.get_flag("quiet");
+        options.progress_json = matches.get_flag("progress-json");
+
         (
             options.sort_order_speed_comparison,
             options.sort_order_exports,
         ) = match matches.get_one::<String>("sort").map(|s| s.as_str()) {
             None | Some("auto") => (SortOrder::MeanTime, SortOrder::Command),
-            Some("command") => (SortOrder::Command, SortOrder::Command),
+            Some("command") | Some("none") => (SortOrder::Command, SortOrder::Command),
+            Some("name") => (SortOrder::Name, SortOrder::Name),
             Some("mean-time") => (SortOrder::MeanTime, SortOrder::MeanTime),
             Some(_) => unreachable!("Unknown sort order"),
         };
@@ -427,6 +1142,19 @@ impl Options {
             }
         };
 
+        if let Some(overhead) = matches.get_one::<String>("shell-overhead") {
+            let overhead = overhead
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("shell-overhead", e))?;
+            if !(overhead >= 0.0 && overhead.is_finite()) {
+                return Err(OptionsError::NegativeArgument("shell-overhead"));
+            }
+            if options.executor_kind == ExecutorKind::Raw {
+                return Err(OptionsError::ShellOverheadWithoutShell);
+            }
+            options.shell_overhead = Some(overhead);
+        }
+
         if let Some(mode) = matches.get_one::<String>("ignore-failure") {
             options.command_failure_action = match mode.as_str() {
                 "all-non-zero" | "" => CmdFailureAction::IgnoreAllFailures,
@@ -444,10 +1172,46 @@ impl Options {
             };
         }
 
+        options.error_exit_code = matches.get_flag("error-exit-code");
+
+        if let Some(timeout) = matches.get_one::<String>("timeout") {
+            let timeout = timeout
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("timeout", e))?;
+            if !(timeout > 0.0 && timeout.is_finite()) {
+                return Err(OptionsError::NonPositiveArgument("timeout"));
+            }
+            options.timeout = Some(timeout);
+        }
+
+        if let Some(list) = matches.get_one::<String>("cpu") {
+            options.cpu_affinity = Some(parse_cpu_affinity(list)?);
+        }
+
+        if let Some(nice) = matches.get_one::<String>("nice") {
+            let nice = nice
+                .parse::<i32>()
+                .map_err(|e| OptionsError::IntParsingError("nice", e))?;
+            if !NICE_RANGE.contains(&nice) {
+                return Err(OptionsError::NiceOutOfRange);
+            }
+            options.priority.nice = Some(nice);
+        }
+        options.priority.realtime = matches.get_flag("realtime");
+        if cfg!(not(unix)) && options.priority.nice.is_some() {
+            return Err(OptionsError::PriorityNotSupported("nice"));
+        }
+        if cfg!(not(target_os = "linux")) && options.priority.realtime {
+            return Err(OptionsError::PriorityNotSupported("realtime"));
+        }
+
         options.time_unit = match matches.get_one::<String>("time-unit").map(|s| s.as_str()) {
-            Some("microsecond") => Some(Unit::MicroSecond),
-            Some("millisecond") => Some(Unit::MilliSecond),
-            Some("second") => Some(Unit::Second),
+            Some("nanosecond" | "ns") => Some(Unit::NanoSecond),
+            Some("microsecond" | "us") => Some(Unit::MicroSecond),
+            Some("millisecond" | "ms") => Some(Unit::MilliSecond),
+            Some("second" | "s") => Some(Unit::Second),
+            Some("minute" | "min") => Some(Unit::Minute),
+            Some("hour" | "h") => Some(Unit::Hour),
             _ => None,
         };
 
@@ -457,6 +1221,60 @@ impl Options {
                 .map_err(|e| OptionsError::FloatParsingError("min-benchmarking-time", e))?;
         }
 
+        if let Some(limit) = param_to_u64("output-capture-limit")? {
+            options.output_capture_limit = limit as usize;
+        }
+        options.show_output_on_failure = matches.get_flag("show-output-on-failure");
+
+        if let Some(percent) = matches.get_one::<String>("retry-on-variance") {
+            let percent = percent
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("retry-on-variance", e))?;
+            if !(percent > 0.0 && percent.is_finite()) {
+                return Err(OptionsError::NonPositiveArgument("retry-on-variance"));
+            }
+            options.max_relative_stddev = Some(percent / 100.0);
+            options.max_attempts = param_to_u64("retry-attempts")?.unwrap_or(3).max(1);
+        }
+
+        if let Some(percent) = matches.get_one::<String>("precision") {
+            let percent = percent
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("precision", e))?;
+            if !(percent > 0.0 && percent.is_finite()) {
+                return Err(OptionsError::NonPositiveArgument("precision"));
+            }
+            options.precision = Some(percent / 100.0);
+        }
+
+        options.drop_outliers = matches.get_flag("drop-outliers");
+        options.robust_stats = matches.get_flag("robust-stats");
+        options.show_histogram = matches.get_flag("histogram");
+        options.show_setup_time = matches.get_flag("show-setup-time");
+        if let Some(bins) = param_to_u64("histogram-bins")? {
+            if bins == 0 {
+                return Err(OptionsError::NonPositiveArgument("histogram-bins"));
+            }
+            options.histogram_bins = Some(bins as usize);
+        }
+
+        if let Some(resamples) = param_to_u64("bootstrap-resamples")? {
+            if resamples == 0 {
+                return Err(OptionsError::NonPositiveArgument("bootstrap-resamples"));
+            }
+            options.bootstrap_resamples = resamples as usize;
+        }
+
+        if let Some(percent) = matches.get_one::<String>("confidence-level") {
+            let percent = percent
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("confidence-level", e))?;
+            if !(percent > 0.0 && percent < 100.0) {
+                return Err(OptionsError::ConfidenceLevelOutOfRange);
+            }
+            options.confidence_level = percent / 100.0;
+        }
+
         options.command_input_policy = if let Some(path_str) = matches.get_one::<String>("input") {
             if path_str == "null" {
                 CommandInputPolicy::Null
@@ -469,55 +1287,279 @@ impl Options {
                 }
                 CommandInputPolicy::File(path)
             }
+        } else if let Some(content) = matches.get_one::<String>("input-string") {
+            CommandInputPolicy::String(content.clone())
         } else {
             CommandInputPolicy::Null
         };
 
+        options.command_environment.clear = matches.get_flag("clear-env");
+        for variable in matches.get_many::<String>("env").unwrap_or_default() {
+            match variable.split_once('=') {
+                Some((name, value)) if !name.is_empty() => options
+                    .command_environment
+                    .variables
+                    .push((name.into(), value.into())),
+                _ => return Err(OptionsError::InvalidEnvironmentVariable(variable.clone())),
+            }
+        }
+
         // Parse poop metrics options
         options.poop_metrics_enabled = matches.get_flag("metrics");
 
         if let Some(metric_names) = matches.get_many::<String>("metric") {
-            options.metrics_to_collect = metric_names
-                .filter_map(|m| MetricType::from_str(m))
-                .collect();
+            for name in metric_names {
+                if let Some(event) = RawEvent::from_str(name) {
+                    options.raw_events.push(event);
+                } else {
+                    let metric = name.parse().map_err(OptionsError::UnknownMetric)?;
+                    options.metrics_to_collect.push(metric);
+                }
+            }
             // If specific metrics are requested, also enable metrics collection
-            if !options.metrics_to_collect.is_empty() {
+            if !options.collects_all_metrics() {
                 options.poop_metrics_enabled = true;
             }
         }
 
+        if let Some(metric_names) = matches.get_many::<String>("compare-metric") {
+            options.comparison_metrics = metric_names
+                .filter_map(|m| ComparisonMetric::from_str(m))
+                .collect();
+
+            // Make sure that all counters required for the comparison are collected
+            let required_metrics = options
+                .comparison_metrics
+                .iter()
+                .flat_map(|m| m.required_metrics());
+            if !options.poop_metrics_enabled {
+                options.poop_metrics_enabled = true;
+                options.metrics_to_collect = required_metrics.collect();
+            } else if !options.collects_all_metrics() {
+                for metric in required_metrics {
+                    if !options.metrics_to_collect.contains(&metric) {
+                        options.metrics_to_collect.push(metric);
+                    }
+                }
+            }
+        }
+
+        // A threshold is either given for the mean run time (PERCENT) or for the mean value
+        // of a poop metric (METRIC=PERCENT)
+        for value in matches
+            .get_many::<String>("regression-threshold")
+            .into_iter()
+            .flatten()
+        {
+            let (metric, percent) = match value.split_once('=') {
+                Some((name, percent)) => (
+                    Some(
+                        name.parse::<MetricType>()
+                            .map_err(OptionsError::UnknownRegressionMetric)?,
+                    ),
+                    percent,
+                ),
+                None => (None, value.as_str()),
+            };
+            let percent = percent
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("regression-threshold", e))?;
+            if percent < 0.0 {
+                return Err(OptionsError::NegativeArgument("regression-threshold"));
+            }
+            match metric {
+                Some(metric) => {
+                    options
+                        .metric_regression_thresholds
+                        .retain(|&(m, _)| m != metric);
+                    options
+                        .metric_regression_thresholds
+                        .push((metric, percent / 100.0));
+
+                    // Make sure that the metric is collected
+                    if !options.poop_metrics_enabled {
+                        options.poop_metrics_enabled = true;
+                        options.metrics_to_collect = vec![metric];
+                    } else if !options.collects_all_metrics()
+                        && !options.metrics_to_collect.contains(&metric)
+                    {
+                        options.metrics_to_collect.push(metric);
+                    }
+                }
+                None => options.regression_threshold = Some(percent / 100.0),
+            }
+        }
+
+        options.progress_update_interval =
+            param_to_u64("progress-interval")?.map(Duration::from_millis);
+
+        if let Some(target) = param_to_u64("target-instructions")? {
+            if target == 0 {
+                return Err(OptionsError::NonPositiveArgument("target-instructions"));
+            }
+            options.target_instructions = Some(target);
+            options.poop_metrics_enabled = true;
+            if !options.collects_all_metrics()
+                && !options
+                    .metrics_to_collect
+                    .contains(&MetricType::Instructions)
+            {
+                options.metrics_to_collect.push(MetricType::Instructions);
+            }
+        }
+
+        options.metrics_per_second = matches.get_flag("metrics-per-second");
+
+        if let Some(frequency) = matches.get_one::<String>("reference-frequency") {
+            let frequency = frequency
+                .parse::<f64>()
+                .map_err(|e| OptionsError::FloatParsingError("reference-frequency", e))?;
+            if frequency <= 0.0 {
+                return Err(OptionsError::NonPositiveArgument("reference-frequency"));
+            }
+            options.reference_frequency_ghz = Some(frequency);
+        }
+
+        options.external_variance_warning = !matches.get_flag("no-external-variance-warning");
+
+        options.require_metrics = matches.get_flag("require-metrics");
+        options.metrics_leader_only = matches.get_flag("metrics-leader-only");
+
+        options.cpu_time_accounting =
+            match matches.get_one::<String>("cpu-time").map(|s| s.as_str()) {
+                Some("include-self") => CpuTimeAccounting::IncludeSelf,
+                _ => CpuTimeAccounting::Children,
+            };
+        if cfg!(windows) && options.cpu_time_accounting == CpuTimeAccounting::IncludeSelf {
+            return Err(OptionsError::CpuTimeIncludeSelfNotSupported);
+        }
+
+        options.debug_perf = matches.get_flag("debug-perf");
+        if options.debug_perf {
+            options.poop_metrics_enabled = true;
+        }
+
         Ok(options)
     }
 
+    /// Whether all available metrics are collected, because no specific metric (or raw
+    /// event) has been selected
+    pub fn collects_all_metrics(&self) -> bool {
+        self.metrics_to_collect.is_empty() && self.raw_events.is_empty()
+    }
+
     pub fn validate_against_command_list(&mut self, commands: &Commands) -> Result<()> {
+        if let Some(reference) = &self.reference_command {
+            let index = reference
+                .parse::<usize>()
+                .ok()
+                .filter(|number| (1..=commands.num_commands(false)).contains(number))
+                .map(|number| number - 1)
+                .or_else(|| {
+                    commands
+                        .iter()
+                        .position(|cmd| cmd.has_name() && cmd.get_name() == *reference)
+                });
+
+            if let Some(index) = index {
+                if self.reference_name.is_some() {
+                    return Err(OptionsError::ReferenceNameForBenchmarkedCommand.into());
+                }
+                self.reference_index = Some(index);
+                self.reference_command = None;
+            }
+        }
+
         let has_reference_command = self.reference_command.is_some();
         let num_commands = commands.num_commands(has_reference_command);
 
-        if let Some(preparation_command) = &self.preparation_command {
-            ensure!(
-                preparation_command.len() <= 1 || num_commands == preparation_command.len(),
-                "The '--prepare' option has to be provided just once or N times, where N={num_commands} is the \
-                 number of benchmark commands (including a potential reference)."
-            );
+        if self.before_after && num_commands != 2 {
+            return Err(OptionsError::BeforeAfterCommandCount(num_commands).into());
         }
 
-        if let Some(conclusion_command) = &self.conclusion_command {
-            ensure!(
-                conclusion_command.len() <= 1 || num_commands == conclusion_command.len(),
-                "The '--conclude' option has to be provided just once or N times, where N={num_commands} is the \
-                 number of benchmark commands (including a potential reference)."
-            );
-        }
+        let check_count = |option, count: usize| {
+            if count <= 1 || count == num_commands {
+                Ok(())
+            } else {
+                Err(OptionsError::UnexpectedOptionCount(option, num_commands))
+            }
+        };
+        check_count("binary-path", self.binary_paths.len())?;
+        check_count(
+            "prepare",
+            self.preparation_command.as_ref().map_or(0, Vec::len),
+        )?;
+        check_count(
+            "conclude",
+            self.conclusion_command.as_ref().map_or(0, Vec::len),
+        )?;
+        check_count("output", self.command_output_policies.len())?;
 
         if self.command_output_policies.len() == 1 {
             self.command_output_policies =
                 vec![self.command_output_policies[0].clone(); num_commands];
-        } else {
-            ensure!(
-                self.command_output_policies.len() == num_commands,
-                "The '--output' option has to be provided just once or N times, where N={num_commands} is the \
-                 number of benchmark commands (including a potential reference)."
-            );
+        }
+
+        if matches!(self.executor_kind, ExecutorKind::Raw) {
+            self.check_commands_without_shell(commands)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check all benchmarked and intermediate commands for shell operators before any of them
+    /// is run, since they can not be executed without a shell
+    fn check_commands_without_shell(&self, commands: &Commands) -> Result<()> {
+        let intermediate_commands: Vec<&String> = self
+            .setup_command
+            .iter()
+            .chain(self.preparation_command.iter().flatten())
+            .chain(self.conclusion_command.iter().flatten())
+            .chain(self.cleanup_command.iter())
+            .collect();
+
+        let reference = self
+            .reference_command
+            .as_ref()
+            .map(|reference| command::Command::new(None, reference));
+        for cmd in reference.iter().chain(commands.iter()) {
+            cmd.check_without_shell()?;
+            for intermediate_command in &intermediate_commands {
+                command::Command::new_parametrized(
+                    None,
+                    intermediate_command,
+                    cmd.get_parameters().iter().cloned(),
+                )
+                .check_without_shell()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the options against results that have been imported with '--from-json' instead
+    /// of running benchmarks. A reference has to be one of the imported results, given by its
+    /// number or command.
+    pub fn validate_against_imported_results(&mut self, results: &[BenchmarkResult]) -> Result<()> {
+        if let Some(reference) = self.reference_command.take() {
+            let index = reference
+                .parse::<usize>()
+                .ok()
+                .filter(|number| (1..=results.len()).contains(number))
+                .map(|number| number - 1)
+                .or_else(|| results.iter().position(|r| r.command == reference));
+
+            if self.reference_name.is_some() {
+                return Err(OptionsError::ReferenceNameWithImportedResults.into());
+            }
+            match index {
+                Some(index) => self.reference_index = Some(index),
+                None => return Err(OptionsError::UnknownImportedReference(reference).into()),
+            }
+        }
+
+        if self.before_after && results.len() != 2 {
+            return Err(OptionsError::BeforeAfterResultCount(results.len()).into());
         }
 
         Ok(())
@@ -567,3 +1609,143 @@ fn test_can_parse_shell_command_line_from_str() {
         OptionsError::EmptyShell
     ));
 }
+
+#[test]
+fn test_shell_command_flag() {
+    let flag = |shell: &str| Shell::parse_from_str(shell).unwrap().command_flag();
+
+    assert_eq!(Shell::Default("sh").command_flag(), Some("-c"));
+    assert_eq!(Shell::Default("cmd.exe").command_flag(), Some("/C"));
+    assert_eq!(flag("zsh"), Some("-c"));
+    assert_eq!(flag("bash --norc"), Some("-c"));
+    assert_eq!(flag("/usr/bin/pwsh -NoProfile"), Some("-Command"));
+    assert_eq!(flag("'C:\\Windows\\System32\\cmd.exe'"), Some("/C"));
+
+    // The flag is already part of the shell command line
+    assert_eq!(flag("zsh -c"), None);
+    assert_eq!(flag("bash --norc -c"), None);
+    assert_eq!(flag("pwsh -Command"), None);
+    assert_eq!(flag("cmd.exe /c"), None);
+
+    // A program that happens to be called like a flag is no shell command line
+    assert_eq!(flag("-c"), Some("-c"));
+}
+
+#[test]
+fn test_options_snapshot_records_options() {
+    type Modifier = fn(&mut Options);
+
+    let modifiers: &[(&str, Modifier)] = &[
+        ("run_bounds", |o| o.run_bounds.max = Some(20)),
+        ("run_count_overrides", |o| {
+            o.run_count_overrides.push(RunCountOverride {
+                parameter: "n".into(),
+                value: "2".into(),
+                runs: 5,
+            })
+        }),
+        ("warmup_count", |o| o.warmup_count = 3),
+        ("warmup_time", |o| o.warmup_time = Some(1.0)),
+        ("min_benchmarking_time", |o| o.min_benchmarking_time = 1.0),
+        ("command_failure_action", |o| {
+            o.command_failure_action = CmdFailureAction::IgnoreAllFailures
+        }),
+        ("timeout", |o| o.timeout = Some(1.0)),
+        ("cpu_affinity", |o| o.cpu_affinity = Some(vec![1])),
+        ("priority", |o| o.priority.nice = Some(5)),
+        ("shell_overhead", |o| o.shell_overhead = Some(0.001)),
+        ("reference_command", |o| {
+            o.reference_command = Some("true".into())
+        }),
+        ("reference_index", |o| o.reference_index = Some(1)),
+        ("preparation_command", |o| {
+            o.preparation_command = Some(vec!["true".into()])
+        }),
+        ("conclusion_command", |o| {
+            o.conclusion_command = Some(vec!["true".into()])
+        }),
+        ("setup_command", |o| o.setup_command = Some("true".into())),
+        ("cleanup_command", |o| {
+            o.cleanup_command = Some("true".into())
+        }),
+        ("executor_kind", |o| o.executor_kind = ExecutorKind::Raw),
+        ("command_input_policy", |o| {
+            o.command_input_policy = CommandInputPolicy::String("input".into())
+        }),
+        ("command_environment", |o| {
+            o.command_environment
+                .variables
+                .push(("KEY".into(), "value".into()))
+        }),
+        ("command_environment.clear", |o| {
+            o.command_environment.clear = true
+        }),
+        ("command_output_policies", |o| {
+            o.command_output_policies = vec![CommandOutputPolicy::Inherit]
+        }),
+        ("progress_update_interval", |o| {
+            o.progress_update_interval = Some(Duration::from_millis(500))
+        }),
+        ("time_unit", |o| o.time_unit = Some(Unit::Second)),
+        ("poop_metrics_enabled", |o| o.poop_metrics_enabled = true),
+        ("target_instructions", |o| {
+            o.target_instructions = Some(1000)
+        }),
+        ("cpu_time_accounting", |o| {
+            o.cpu_time_accounting = CpuTimeAccounting::IncludeSelf
+        }),
+        ("max_relative_stddev", |o| {
+            o.max_relative_stddev = Some(0.05)
+        }),
+        ("precision", |o| o.precision = Some(0.01)),
+        ("drop_outliers", |o| o.drop_outliers = true),
+        ("robust_stats", |o| o.robust_stats = true),
+        ("show_histogram", |o| o.show_histogram = true),
+        ("bootstrap_resamples", |o| o.bootstrap_resamples = 100),
+        ("confidence_level", |o| o.confidence_level = 0.9),
+        ("overhead_command", |o| {
+            o.overhead_command = Some("true".into())
+        }),
+        ("interleave", |o| o.interleave = true),
+        ("shuffle_seed", |o| o.shuffle_seed = Some(42)),
+        ("weight_file", |o| {
+            o.weight_file = Some("weights.json".into())
+        }),
+    ];
+
+    let default_snapshot = OptionsSnapshot::new(&Options::default());
+    for (name, modify) in modifiers {
+        let mut options = Options::default();
+        modify(&mut options);
+        assert_ne!(
+            OptionsSnapshot::new(&options),
+            default_snapshot,
+            "option '{}' is not recorded",
+            name
+        );
+    }
+
+    // Options that only have an effect in combination with others
+    let metrics_enabled = |modify: Modifier| {
+        let mut options = Options {
+            poop_metrics_enabled: true,
+            ..Options::default()
+        };
+        let snapshot = OptionsSnapshot::new(&options);
+        modify(&mut options);
+        OptionsSnapshot::new(&options) != snapshot
+    };
+    assert!(metrics_enabled(|o| o.metrics_leader_only = true));
+    assert!(metrics_enabled(|o| o.metrics_per_second = true));
+    assert!(metrics_enabled(
+        |o| o.metrics_to_collect = vec![MetricType::CpuCycles]
+    ));
+
+    let mut options = Options {
+        show_histogram: true,
+        ..Options::default()
+    };
+    let snapshot = OptionsSnapshot::new(&options);
+    options.histogram_bins = Some(10);
+    assert_ne!(OptionsSnapshot::new(&options), snapshot);
+}
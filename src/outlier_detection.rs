@@ -5,13 +5,24 @@
 //!   The ASQC Basic References in Quality Control: Statistical Techniques, Edward F. Mykytka,
 //!   Ph.D., Editor.
 
-use statistical::median;
+use std::cmp;
+
+use statistical::{mean, median};
+
+use crate::util::student_t::two_sided_p_value;
 
 /// Minimum modified Z-score for a datapoint to be an outlier. Here, 1.4826 is a factor that
 /// converts the MAD to an estimator for the standard deviation. The second factor is the number
 /// of standard deviations.
 pub const OUTLIER_THRESHOLD: f64 = 1.4826 * 10.0;
 
+/// Compute the median absolute deviation (MAD) of a sample with the given median, i.e. the
+/// median of the absolute deviations from the median
+pub fn median_absolute_deviation(xs: &[f64], x_median: f64) -> f64 {
+    let deviations: Vec<f64> = xs.iter().map(|x| (x - x_median).abs()).collect();
+    median(&deviations)
+}
+
 /// Compute modifized Z-scores for a given sample. A (unmodified) Z-score is defined by
 /// `(x_i - x_mean)/x_stddev` whereas the modified Z-score is defined by `(x_i - x_median)/MAD`
 /// where MAD is the median absolute deviation.
@@ -24,11 +35,8 @@ pub fn modified_zscores(xs: &[f64]) -> Vec<f64> {
     // Compute sample median:
     let x_median = median(xs);
 
-    // Compute the absolute deviations from the median:
-    let deviations: Vec<f64> = xs.iter().map(|x| (x - x_median).abs()).collect();
-
     // Compute median absolute deviation:
-    let mad = median(&deviations);
+    let mad = median_absolute_deviation(xs, x_median);
 
     // Handle MAD == 0 case
     let mad = if mad > 0.0 { mad } else { f64::EPSILON };
@@ -37,6 +45,124 @@ pub fn modified_zscores(xs: &[f64]) -> Vec<f64> {
     xs.iter().map(|&x| (x - x_median) / mad).collect()
 }
 
+/// Minimum fraction of the variance of a sample that has to be explained by splitting it into
+/// two clusters for the sample to be considered bimodal. Even for a uniform distribution, the
+/// best split explains 75% of the variance (64% for a normal distribution).
+pub const BIMODAL_EXPLAINED_VARIANCE: f64 = 0.9;
+
+/// Minimum fraction of the datapoints in each of the two clusters of a bimodal sample. This
+/// rules out single outliers, which are handled by the outlier detection.
+pub const BIMODAL_MIN_CLUSTER_FRACTION: f64 = 0.1;
+
+/// Minimum relative difference between the means of the two clusters of a bimodal sample.
+/// Closer clusters are of no practical relevance (and can be caused by rounding errors).
+pub const BIMODAL_MIN_RELATIVE_GAP: f64 = 0.05;
+
+/// Check whether the sample consists of two clusters of values, and return the means of the
+/// lower and the upper cluster if so. This fits a two-cluster k-means model (which is exact in
+/// one dimension, as the clusters are contiguous in the sorted sample) and compares its
+/// within-cluster variance to the variance of the whole sample.
+pub fn bimodal_cluster_means(xs: &[f64]) -> Option<(f64, f64)> {
+    let mut sorted = xs.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let n = sorted.len();
+    let min_cluster_size = cmp::max(2, (BIMODAL_MIN_CLUSTER_FRACTION * n as f64).ceil() as usize);
+    if n < 2 * min_cluster_size {
+        return None;
+    }
+
+    // Prefix sums of the (centered, for numerical stability) values and their squares, such
+    // that the sum of squared deviations of every cluster can be computed in constant time
+    let center = mean(&sorted);
+    let mut sums = vec![(0.0, 0.0); n + 1];
+    for (i, x) in sorted.iter().enumerate() {
+        let x = x - center;
+        sums[i + 1] = (sums[i].0 + x, sums[i].1 + x * x);
+    }
+    let sum_of_squares = |start: usize, end: usize| {
+        let (sum, sum_squared) = (sums[end].0 - sums[start].0, sums[end].1 - sums[start].1);
+        (sum_squared - sum * sum / (end - start) as f64).max(0.0)
+    };
+    let total = sum_of_squares(0, n);
+    if total == 0.0 {
+        return None;
+    }
+
+    let (split, within) = (min_cluster_size..=n - min_cluster_size)
+        .map(|split| (split, sum_of_squares(0, split) + sum_of_squares(split, n)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    let (lower, upper) = (mean(&sorted[..split]), mean(&sorted[split..]));
+    if 1.0 - within / total < BIMODAL_EXPLAINED_VARIANCE
+        || upper - lower < BIMODAL_MIN_RELATIVE_GAP * upper.abs()
+    {
+        return None;
+    }
+    Some((lower, upper))
+}
+
+/// Ranks of the given values, starting at 1. Tied values get the average of their ranks.
+fn ranks(xs: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..xs.len()).collect();
+    order.sort_by(|&a, &b| xs[a].total_cmp(&xs[b]));
+
+    let mut ranks = vec![0.0; xs.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && xs[order[end]] == xs[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Compute the Spearman rank correlation between the position of each datapoint in the sample
+/// and its value. This is close to 1 if the values tend to increase over the course of the
+/// sample, close to -1 if they tend to decrease, and `None` if all values are equal.
+///
+/// References:
+/// - <https://en.wikipedia.org/wiki/Spearman%27s_rank_correlation_coefficient>
+pub fn trend_correlation(xs: &[f64]) -> Option<f64> {
+    let indices: Vec<f64> = (1..=xs.len()).map(|i| i as f64).collect();
+    let ranks = ranks(xs);
+
+    // Pearson correlation of the ranks
+    let mean = (xs.len() + 1) as f64 / 2.0;
+    let (mut covariance, mut variance_indices, mut variance_ranks) = (0.0, 0.0, 0.0);
+    for (i, r) in indices.iter().zip(&ranks) {
+        covariance += (i - mean) * (r - mean);
+        variance_indices += (i - mean).powi(2);
+        variance_ranks += (r - mean).powi(2);
+    }
+
+    if variance_ranks == 0.0 {
+        return None;
+    }
+    Some(covariance / (variance_indices * variance_ranks).sqrt())
+}
+
+/// Two-sided p-value of the rank correlation `correlation` of a sample of size `n` under the
+/// hypothesis that there is no trend, based on the t-distribution with `n - 2` degrees of
+/// freedom. Since this approximation is too optimistic for small samples, the p-value is never
+/// smaller than `2 / n!`, the probability that `n` values are sorted in either direction by chance.
+pub fn trend_p_value(correlation: f64, n: usize) -> f64 {
+    if n < 3 {
+        return 1.0;
+    }
+
+    let df = (n - 2) as f64;
+    let t = correlation * (df / (1.0 - correlation * correlation)).sqrt();
+    let min_p_value = (1..=n).fold(2.0, |p, k| p / k as f64);
+    two_sided_p_value(t, df).clamp(min_p_value, 1.0)
+}
+
 /// Return the number of outliers in a given sample. Outliers are defined as data points with a
 /// modified Z-score that is larger than `OUTLIER_THRESHOLD`.
 #[cfg(test)]
@@ -113,3 +239,79 @@ fn test_detect_outliers_if_mad_becomes_0() {
     let xs = [10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 100.0, 100.0];
     assert_eq!(2, num_outliers(&xs));
 }
+
+#[test]
+fn test_trend_correlation() {
+    use approx::assert_relative_eq;
+
+    assert_eq!(trend_correlation(&[]), None);
+    assert_eq!(trend_correlation(&[1.0, 1.0, 1.0]), None);
+
+    assert_relative_eq!(trend_correlation(&[1.0, 2.0, 5.0, 10.0]).unwrap(), 1.0);
+    assert_relative_eq!(trend_correlation(&[3.0, 2.0, 1.0]).unwrap(), -1.0);
+
+    // Ties get the average rank: ranks 1.5, 1.5, 3, 4
+    assert_relative_eq!(
+        trend_correlation(&[1.0, 1.0, 2.0, 3.0]).unwrap(),
+        0.9486832980505138
+    );
+
+    // Only the order matters, not the magnitude
+    assert_relative_eq!(
+        trend_correlation(&[0.1, 0.3, 0.2, 100.0]).unwrap(),
+        trend_correlation(&[1.0, 3.0, 2.0, 4.0]).unwrap()
+    );
+    assert_relative_eq!(trend_correlation(&[1.0, 3.0, 2.0, 4.0]).unwrap(), 0.8);
+}
+
+#[test]
+fn test_trend_p_value() {
+    // The same correlation is significant for large samples, but not for small ones
+    assert!(trend_p_value(0.6, 10) > 0.05);
+    assert!(trend_p_value(0.4, 100) < 0.001);
+
+    // A perfect trend in a small sample can easily be a coincidence
+    assert_eq!(trend_p_value(1.0, 5), 2.0 / 120.0);
+    assert!(trend_p_value(-1.0, 10) < 0.001);
+
+    assert_eq!(trend_p_value(1.0, 2), 1.0);
+    assert_eq!(trend_p_value(0.0, 10), 1.0);
+}
+
+#[test]
+fn test_bimodal_cluster_means() {
+    use approx::assert_relative_eq;
+
+    // Two clearly separated regimes
+    let xs = [1.0, 1.1, 0.9, 3.0, 1.0, 3.1, 2.9, 1.05, 3.0, 0.95];
+    let (lower, upper) = bimodal_cluster_means(&xs).unwrap();
+    assert_relative_eq!(lower, 1.0);
+    assert_relative_eq!(upper, 3.0);
+
+    // Sample drawn from a normal distribution
+    let xs = [
+        2.33269488,
+        1.42195907,
+        -0.57527698,
+        -0.31293437,
+        2.2948158,
+        0.75813273,
+        -1.0712388,
+        -0.96394741,
+        -1.15897446,
+        1.10976285,
+    ];
+    assert_eq!(bimodal_cluster_means(&xs), None);
+
+    // A single outlier is not a second mode
+    let xs = [1.0, 1.1, 0.9, 1.0, 1.05, 0.95, 1.0, 1.1, 0.9, 10.0];
+    assert_eq!(bimodal_cluster_means(&xs), None);
+
+    assert_eq!(bimodal_cluster_means(&[1.0, 1.0, 1.0, 1.0]), None);
+    assert_eq!(bimodal_cluster_means(&[0.1; 10]), None);
+    assert_eq!(
+        bimodal_cluster_means(&[1.0, 1.0, 1.0, 1.01, 1.01, 1.01]),
+        None
+    );
+    assert_eq!(bimodal_cluster_means(&[1.0, 3.0]), None);
+}
@@ -1,4 +1,11 @@
-use crate::util::units::{Second, Unit};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::options::OutputStyleOption;
+use crate::poop_metrics::{MetricType, PoopMetricRates, PoopMetrics};
+use crate::util::units::{ByteUnit, Second, Unit};
+
+use colored::{Color, Colorize};
 
 /// Format the given duration as a string. The output-unit can be enforced by setting `unit` to
 /// `Some(target_unit)`. If `unit` is `None`, it will be determined automatically.
@@ -16,15 +23,370 @@ pub fn format_duration_unit(duration: Second, unit: Option<Unit>) -> (String, Un
 
 /// Like `format_duration`, but returns the target unit as well.
 pub fn format_duration_value(duration: Second, unit: Option<Unit>) -> (String, Unit) {
-    if (duration < 0.001 && unit.is_none()) || unit == Some(Unit::MicroSecond) {
-        (Unit::MicroSecond.format(duration), Unit::MicroSecond)
-    } else if (duration < 1.0 && unit.is_none()) || unit == Some(Unit::MilliSecond) {
-        (Unit::MilliSecond.format(duration), Unit::MilliSecond)
+    let unit = unit.unwrap_or_else(|| Unit::for_value(duration));
+    (unit.format(duration), unit)
+}
+
+/// Format the given number of bytes using binary prefixes, e.g. "1.5 MiB"
+pub fn format_bytes(bytes: u64) -> String {
+    let (bytes_fmt, _) = format_bytes_unit(bytes, None);
+    bytes_fmt
+}
+
+/// Like `format_bytes`, but returns the target unit as well. The unit can be enforced by
+/// setting `unit` to `Some(target_unit)`, e.g. to format a set of values consistently.
+pub fn format_bytes_unit(bytes: u64, unit: Option<ByteUnit>) -> (String, ByteUnit) {
+    let unit = unit.unwrap_or_else(|| ByteUnit::for_value(bytes));
+    (
+        format!("{} {}", unit.format(bytes), unit.short_name()),
+        unit,
+    )
+}
+
+/// Scale the given value to a decimal prefix. Values below 1000 are returned unchanged with an
+/// empty prefix.
+fn scale_decimal(value: f64) -> (f64, &'static str) {
+    const UNITS: [&str; 4] = ["k", "M", "G", "T"];
+
+    if value.abs() < 1000.0 {
+        return (value, "");
+    }
+
+    let mut value = value / 1000.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if value.abs() < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next_unit;
+    }
+
+    (value, unit)
+}
+
+/// Format the given rate using decimal prefixes, e.g. "1.50 G/s"
+pub fn format_rate(per_second: f64) -> String {
+    let (value, unit) = scale_decimal(per_second);
+    format!("{value:.2} {unit}/s")
+}
+
+/// Format the given (fractional) count using decimal prefixes, e.g. "4.50 k"
+pub fn format_count(count: f64) -> String {
+    match scale_decimal(count) {
+        (value, "") => format!("{value:.2}"),
+        (value, unit) => format!("{value:.2} {unit}"),
+    }
+}
+
+/// Format the number of occurrences of each exit code, e.g. "0 (18×), 1 (2×)"
+pub fn format_exit_code_counts(counts: &BTreeMap<i32, usize>) -> String {
+    counts
+        .iter()
+        .map(|(code, count)| format!("{code} ({count}×)"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Additional information that is shown next to the poop metrics of a benchmark
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PoopMetricsDetails<'a> {
+    /// Standard deviation of each metric over all runs
+    pub stddev: Option<&'a PoopMetricRates>,
+
+    /// Mean wall clock time. If set, the rate of every metric is shown as well.
+    pub per_second_of: Option<Second>,
+
+    /// Reference frequency (in GHz) and time unit for the time estimated from the CPU cycles
+    pub reference_frequency: Option<(f64, Unit)>,
+}
+
+/// Format the poop metrics of a benchmark as shown below the wall clock time, with one line per
+/// metric. Derived ratios such as the IPC or the miss rates are appended to the line of the
+/// respective metric. An empty string is returned if no metric has been collected.
+pub fn format_poop_metrics(
+    metrics: &PoopMetrics,
+    details: &PoopMetricsDetails,
+    style: OutputStyleOption,
+) -> String {
+    let paint = |text: String, color: Color| match style {
+        OutputStyleOption::Full | OutputStyleOption::Color => text.color(color).to_string(),
+        _ => text,
+    };
+    let cyan = |text: String| paint(text, Color::Cyan);
+    let yellow = |text: String| paint(text, Color::Yellow);
+
+    // Mean value of a counter, followed by its standard deviation (if available)
+    let count = |metric: MetricType, value: u64| {
+        cyan(match details.stddev.and_then(|stddev| stddev.get(metric)) {
+            Some(stddev) => format!("{} ± {}", format_count(value as f64), format_count(stddev)),
+            None => value.to_string(),
+        })
+    };
+    let percentage = |label: &str, value: Option<f64>| match value {
+        Some(value) => format!("\t({label}: {}%)", yellow(format!("{value:.1}"))),
+        None => String::new(),
+    };
+
+    let mut output = String::new();
+
+    if let Some(cycles) = metrics.cpu_cycles {
+        let ipc = metrics
+            .instructions_per_cycle()
+            .map(|ipc| format!("\t(IPC: {})", cyan(format!("{ipc:.2}"))))
+            .unwrap_or_default();
+        push_line(
+            &mut output,
+            "CPU cycles",
+            count(MetricType::CpuCycles, cycles),
+            ipc,
+        );
+    }
+    let stalls = [
+        (
+            MetricType::StalledCyclesFrontend,
+            "Frontend stall cycles",
+            "frontend bound",
+            metrics.frontend_bound(),
+        ),
+        (
+            MetricType::StalledCyclesBackend,
+            "Backend stall cycles",
+            "backend bound",
+            metrics.backend_bound(),
+        ),
+    ];
+    for (metric, label, bound_label, bound) in stalls {
+        if let Some(stalled) = metrics.get(metric) {
+            push_line(
+                &mut output,
+                label,
+                count(metric, stalled),
+                percentage(bound_label, bound),
+            );
+        }
+    }
+    if let Some(ref_cycles) = metrics.ref_cycles {
+        let ratio = metrics
+            .frequency_ratio()
+            .map(|ratio| {
+                format!(
+                    "\t(effective frequency ratio: {})",
+                    cyan(format!("{ratio:.2}"))
+                )
+            })
+            .unwrap_or_default();
+        push_line(
+            &mut output,
+            "Reference cycles",
+            count(MetricType::RefCycles, ref_cycles),
+            ratio,
+        );
+    }
+    if let Some(bus_cycles) = metrics.bus_cycles {
+        push_line(
+            &mut output,
+            "Bus cycles",
+            count(MetricType::BusCycles, bus_cycles),
+            String::new(),
+        );
+    }
+    if let Some((frequency, time_unit)) = details.reference_frequency {
+        if let Some(time) = metrics.estimated_time_at(frequency) {
+            let _ = writeln!(
+                output,
+                "  Estimated time @{frequency} GHz: {}\t(from CPU cycles)",
+                cyan(format_duration(time, Some(time_unit)))
+            );
+        }
+    }
+    if let Some(instructions) = metrics.instructions {
+        push_line(
+            &mut output,
+            "Instructions",
+            count(MetricType::Instructions, instructions),
+            String::new(),
+        );
+    }
+    if let Some(cache_refs) = metrics.cache_references {
+        push_line(
+            &mut output,
+            "Cache references",
+            count(MetricType::CacheReferences, cache_refs),
+            percentage("miss rate", metrics.cache_miss_rate()),
+        );
+    } else if let Some(cache_misses) = metrics.cache_misses {
+        push_line(
+            &mut output,
+            "Cache misses",
+            count(MetricType::CacheMisses, cache_misses),
+            String::new(),
+        );
+    }
+    if let Some(loads) = metrics.l1_dcache_loads {
+        push_line(
+            &mut output,
+            "L1 dcache loads",
+            count(MetricType::L1DcacheLoads, loads),
+            percentage("miss rate", metrics.l1_dcache_miss_rate()),
+        );
+    } else if let Some(misses) = metrics.l1_dcache_load_misses {
+        push_line(
+            &mut output,
+            "L1 dcache misses",
+            count(MetricType::L1DcacheLoadMisses, misses),
+            String::new(),
+        );
+    }
+    if let Some(loads) = metrics.llc_loads {
+        push_line(
+            &mut output,
+            "LLC loads",
+            count(MetricType::LlcLoads, loads),
+            percentage("miss rate", metrics.llc_miss_rate()),
+        );
+    } else if let Some(misses) = metrics.llc_load_misses {
+        push_line(
+            &mut output,
+            "LLC misses",
+            count(MetricType::LlcLoadMisses, misses),
+            String::new(),
+        );
+    }
+    if let Some(misses) = metrics.dtlb_load_misses {
+        push_line(
+            &mut output,
+            "dTLB misses",
+            count(MetricType::DtlbLoadMisses, misses),
+            String::new(),
+        );
+    }
+    if let Some(misses) = metrics.itlb_load_misses {
+        push_line(
+            &mut output,
+            "iTLB misses",
+            count(MetricType::ItlbLoadMisses, misses),
+            String::new(),
+        );
+    }
+    if let Some(branches) = metrics.branches {
+        push_line(
+            &mut output,
+            "Branch instructions",
+            count(MetricType::Branches, branches),
+            percentage("miss rate", metrics.branch_miss_rate()),
+        );
+    } else if let Some(branch_misses) = metrics.branch_misses {
+        push_line(
+            &mut output,
+            "Branch misses",
+            count(MetricType::BranchMisses, branch_misses),
+            String::new(),
+        );
+    }
+    for (metric, label) in [
+        (MetricType::PageFaults, "Page faults"),
+        (MetricType::ContextSwitches, "Context switches"),
+        (MetricType::CpuMigrations, "CPU migrations"),
+        (MetricType::Syscalls, "Syscalls"),
+    ] {
+        if let Some(value) = metrics.get(metric) {
+            push_line(&mut output, label, count(metric, value), String::new());
+        }
+    }
+    for (label, &value) in &metrics.raw {
+        let value = match details.stddev.and_then(|stddev| stddev.raw.get(label)) {
+            Some(&stddev) => format!("{} ± {}", format_count(value as f64), format_count(stddev)),
+            None => value.to_string(),
+        };
+        push_line(&mut output, label, cyan(value), String::new());
+    }
+    if let Some(mean_time) = details.per_second_of {
+        for metric in MetricType::all() {
+            if let Some(value) = metrics.get(metric) {
+                let rate = cyan(format_rate(value as f64 / mean_time));
+                push_line(&mut output, metric.rate_label(), rate, String::new());
+            }
+        }
+        for (label, &value) in &metrics.raw {
+            let rate = cyan(format_rate(value as f64 / mean_time));
+            push_line(&mut output, &format!("{label}/s"), rate, String::new());
+        }
+    }
+
+    if output.is_empty() {
+        output
     } else {
-        (Unit::Second.format(duration), Unit::Second)
+        format!("\n{output}")
     }
 }
 
+/// Append a line with an aligned label, a value and an optional annotation
+fn push_line(output: &mut String, label: &str, value: String, annotation: String) {
+    let _ = writeln!(
+        output,
+        "  {:<24} {}{}",
+        format!("{label}:"),
+        value,
+        annotation
+    );
+}
+
+#[test]
+fn test_format_rate() {
+    assert_eq!("0.00 /s", format_rate(0.0));
+    assert_eq!("999.50 /s", format_rate(999.5));
+    assert_eq!("1.00 k/s", format_rate(1000.0));
+    assert_eq!("2.50 G/s", format_rate(2.5e9));
+    assert_eq!("4000.00 T/s", format_rate(4e15));
+}
+
+#[test]
+fn test_format_count() {
+    assert_eq!("0.00", format_count(0.0));
+    assert_eq!("12.50", format_count(12.5));
+    assert_eq!("4.50 k", format_count(4500.0));
+    assert_eq!("1.23 G", format_count(1.234e9));
+}
+
+#[test]
+fn test_format_bytes() {
+    assert_eq!("0 B", format_bytes(0));
+    assert_eq!("1023 B", format_bytes(1023));
+    assert_eq!("1.0 KiB", format_bytes(1024));
+    assert_eq!("1.5 MiB", format_bytes(3 << 19));
+    assert_eq!("2.0 GiB", format_bytes(2 << 30));
+    assert_eq!("4096.0 TiB", format_bytes(1 << 52));
+}
+
+#[test]
+fn test_format_bytes_unit() {
+    assert_eq!(
+        ("0 B".to_string(), ByteUnit::Byte),
+        format_bytes_unit(0, None)
+    );
+    assert_eq!(
+        ("1023 B".to_string(), ByteUnit::Byte),
+        format_bytes_unit(1023, None)
+    );
+    assert_eq!(
+        ("1.0 KiB".to_string(), ByteUnit::KibiByte),
+        format_bytes_unit(1024, None)
+    );
+    assert_eq!(
+        ("1.0 MiB".to_string(), ByteUnit::MebiByte),
+        format_bytes_unit(1048576, None)
+    );
+
+    // A given unit is used even if another one would be chosen automatically
+    let (_, unit) = format_bytes_unit(3 << 20, None);
+    assert_eq!(
+        ("1024.0 KiB".to_string(), ByteUnit::KibiByte),
+        format_bytes_unit(1048576, Some(ByteUnit::KibiByte))
+    );
+    assert_eq!("0.0 MiB", format_bytes_unit(1023, Some(unit)).0);
+}
+
 #[test]
 fn test_format_duration_unit_basic() {
     let (out_str, out_unit) = format_duration_unit(1.3, None);
@@ -49,13 +411,30 @@ fn test_format_duration_unit_basic() {
 
     let (out_str, out_unit) = format_duration_unit(0.0, None);
 
-    assert_eq!("0.0 µs", out_str);
-    assert_eq!(Unit::MicroSecond, out_unit);
+    assert_eq!("0.0 ns", out_str);
+    assert_eq!(Unit::NanoSecond, out_unit);
 
-    let (out_str, out_unit) = format_duration_unit(1000.0, None);
+    let (out_str, out_unit) = format_duration_unit(59.5, None);
 
-    assert_eq!("1000.000 s", out_str);
+    assert_eq!("59.500 s", out_str);
     assert_eq!(Unit::Second, out_unit);
+
+    let (out_str, out_unit) = format_duration_unit(1000.0, None);
+
+    assert_eq!("16.67 min", out_str);
+    assert_eq!(Unit::Minute, out_unit);
+}
+
+#[test]
+fn test_format_duration_unit_boundaries() {
+    assert_eq!("999.0 ns", format_duration(0.000000999, None));
+    assert_eq!("1.0 µs", format_duration(0.000001, None));
+    assert_eq!("999.0 µs", format_duration(0.000999, None));
+    assert_eq!("1.0 ms", format_duration(0.001, None));
+    assert_eq!("1.00 min", format_duration(60.0, None));
+    assert_eq!("59.98 min", format_duration(3599.0, None));
+    assert_eq!("1.00 h", format_duration(3600.0, None));
+    assert_eq!("25.00 h", format_duration(90000.0, None));
 }
 
 #[test]
@@ -74,4 +453,85 @@ fn test_format_duration_unit_with_unit() {
 
     assert_eq!("1300000.0 µs", out_str);
     assert_eq!(Unit::MicroSecond, out_unit);
+
+    let (out_str, out_unit) = format_duration_unit(1.3, Some(Unit::NanoSecond));
+
+    assert_eq!("1300000000.0 ns", out_str);
+    assert_eq!(Unit::NanoSecond, out_unit);
+
+    // The given unit is kept for values that are too small for it
+    let (out_str, out_unit) = format_duration_unit(0.00002, Some(Unit::Second));
+
+    assert_eq!("0.000 s", out_str);
+    assert_eq!(Unit::Second, out_unit);
+
+    let (out_str, out_unit) = format_duration_unit(0.0000005, Some(Unit::MilliSecond));
+
+    assert_eq!("0.0 ms", out_str);
+    assert_eq!(Unit::MilliSecond, out_unit);
+}
+
+#[test]
+fn test_format_exit_code_counts() {
+    use crate::util::exit_code::count_exit_codes;
+
+    let counts = count_exit_codes(&[Some(1), Some(0), None, Some(0), Some(130)]);
+    assert_eq!("0 (2×), 1 (1×), 130 (1×)", format_exit_code_counts(&counts));
+    assert_eq!("", format_exit_code_counts(&BTreeMap::new()));
+}
+
+#[test]
+fn test_format_poop_metrics() {
+    let format = |metrics: PoopMetrics, details: PoopMetricsDetails| {
+        format_poop_metrics(&metrics, &details, OutputStyleOption::Basic)
+    };
+
+    let stddev = PoopMetricRates {
+        cpu_cycles: Some(25.0),
+        raw: BTreeMap::from([("r003c".to_string(), 30.0)]),
+        ..Default::default()
+    };
+    let cycles_and_instructions = PoopMetrics {
+        cpu_cycles: Some(2000),
+        instructions: Some(3000),
+        raw: BTreeMap::from([("r003c".to_string(), 1500)]),
+        ..Default::default()
+    };
+    insta::assert_snapshot!(format(cycles_and_instructions, PoopMetricsDetails {
+        stddev: Some(&stddev),
+        ..Default::default()
+    }), @r"
+      CPU cycles:              2.00 k ± 25.00	(IPC: 1.50)
+      Instructions:            3000
+      r003c:                   1.50 k ± 30.00
+    ");
+
+    let cache_references_and_misses = PoopMetrics {
+        cache_references: Some(1000),
+        cache_misses: Some(50),
+        ..Default::default()
+    };
+    insta::assert_snapshot!(format(cache_references_and_misses, PoopMetricsDetails {
+        per_second_of: Some(2.0),
+        ..Default::default()
+    }), @r"
+      Cache references:        1000	(miss rate: 5.0%)
+      Cache references/s:      500.00 /s
+      Cache misses/s:          25.00 /s
+    ");
+
+    // The miss rates are already percentages
+    let branches_and_misses = PoopMetrics {
+        branches: Some(400),
+        branch_misses: Some(10),
+        ..Default::default()
+    };
+    insta::assert_snapshot!(format(branches_and_misses, PoopMetricsDetails::default()), @r"
+      Branch instructions:     400	(miss rate: 2.5%)
+    ");
+
+    assert_eq!(
+        format(PoopMetrics::default(), PoopMetricsDetails::default()),
+        ""
+    );
 }
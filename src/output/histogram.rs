@@ -0,0 +1,120 @@
+use crate::output::format::{format_duration, format_duration_unit};
+use crate::util::units::{Second, Unit};
+
+/// Maximum width of the bars, in characters
+const MAX_BAR_WIDTH: usize = 40;
+
+/// Characters for partially filled bar cells, in steps of 1/8
+const PARTIAL_BLOCKS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Default number of bins for the given number of values, `ceil(sqrt(n))`
+pub fn default_bin_count(num_values: usize) -> usize {
+    (num_values as f64).sqrt().ceil().max(1.0) as usize
+}
+
+/// Count how many of the `times` fall into each of `bins` equally wide bins between the
+/// minimum and the maximum time. Returns the lower bound of the first bin, the bin width and
+/// the counts. If all times are equal, a single bin is used.
+fn bin_counts(times: &[Second], bins: usize) -> (Second, Second, Vec<usize>) {
+    let min = times.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = times.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    let bins = if max > min { bins.max(1) } else { 1 };
+    let width = (max - min) / bins as Second;
+
+    let mut counts = vec![0; bins];
+    for &time in times {
+        let index = if width > 0.0 {
+            ((time - min) / width) as usize
+        } else {
+            0
+        };
+        counts[index.min(bins - 1)] += 1;
+    }
+
+    (min, width, counts)
+}
+
+/// A horizontal bar of the given length (in units of full cells), using Unicode block
+/// characters with a resolution of 1/8 of a cell
+fn bar(length: f64) -> String {
+    let eighths = (length * 8.0).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    let partial = eighths % 8;
+    if partial > 0 {
+        bar.push(PARTIAL_BLOCKS[partial]);
+    }
+    bar
+}
+
+/// Render a histogram of the given run times, one line per bin. The bin boundaries are shown
+/// in a common time unit, which is determined from the maximum time if `unit` is `None`.
+pub fn format_histogram(times: &[Second], bins: usize, unit: Option<Unit>) -> Vec<String> {
+    if times.is_empty() {
+        return vec![];
+    }
+
+    let (min, width, counts) = bin_counts(times, bins);
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    let (_, unit) = format_duration_unit(min + width * counts.len() as Second, unit);
+
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            let lower = min + width * i as Second;
+            format!(
+                "{:>10} … {:>10} │{:<bar_width$} {}",
+                format_duration(lower, Some(unit)),
+                format_duration(lower + width, Some(unit)),
+                bar(count as f64 / max_count as f64 * MAX_BAR_WIDTH as f64),
+                count,
+                bar_width = MAX_BAR_WIDTH
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_default_bin_count() {
+    assert_eq!(default_bin_count(0), 1);
+    assert_eq!(default_bin_count(1), 1);
+    assert_eq!(default_bin_count(10), 4);
+    assert_eq!(default_bin_count(100), 10);
+}
+
+#[test]
+fn test_bin_counts() {
+    let times = [1.0, 1.1, 1.9, 2.0, 3.0];
+    let (min, width, counts) = bin_counts(&times, 2);
+    assert_eq!(min, 1.0);
+    assert_eq!(width, 1.0);
+    assert_eq!(counts, vec![3, 2]);
+
+    // All times are equal
+    let (_, width, counts) = bin_counts(&[0.5, 0.5, 0.5], 3);
+    assert_eq!(width, 0.0);
+    assert_eq!(counts, vec![3]);
+}
+
+#[test]
+fn test_bar() {
+    assert_eq!(bar(0.0), "");
+    assert_eq!(bar(2.0), "██");
+    assert_eq!(bar(1.5), "█▌");
+    assert_eq!(bar(0.125), "▏");
+}
+
+#[test]
+fn test_format_histogram() {
+    let times = [0.010, 0.011, 0.012, 0.019, 0.020];
+    let lines = format_histogram(&times, 2, None);
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("   10.0 ms …    15.0 ms │"));
+    assert!(lines[0].ends_with(&format!("{} 3", "█".repeat(MAX_BAR_WIDTH))));
+    assert!(lines[1].starts_with("   15.0 ms …    20.0 ms │"));
+    assert!(lines[1].ends_with(" 2"));
+
+    assert!(format_histogram(&[], 4, None).is_empty());
+}
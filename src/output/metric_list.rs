@@ -0,0 +1,24 @@
+use crate::poop_metrics::{probe_metric, MetricType};
+
+/// Print all metric types with their accepted names and whether they can be collected on
+/// this system (see '--list-metrics')
+pub fn print_metric_list() {
+    println!("{:<18} {:<32} Status", "Metric", "Names");
+
+    for metric in MetricType::all() {
+        let status = match probe_metric(metric) {
+            Ok(()) => "available".to_string(),
+            Err(e) => format!("not available ({e})"),
+        };
+
+        println!(
+            "{:<18} {:<32} {}",
+            metric.display_name(),
+            metric.cli_names().join(", "),
+            status
+        );
+    }
+
+    println!();
+    println!("Raw, CPU-specific events can be selected with '--metric r<hex>' (e.g. 'r003c').");
+}
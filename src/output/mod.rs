@@ -1,3 +1,6 @@
 pub mod format;
+pub mod histogram;
+pub mod metric_list;
 pub mod progress_bar;
+pub mod progress_events;
 pub mod warnings;
@@ -1,5 +1,9 @@
-use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
+use indicatif::{FormattedDuration, ProgressBar, ProgressState, ProgressStyle};
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::options::OutputStyleOption;
 
@@ -9,14 +13,135 @@ const TICK_SETTINGS: (&str, u64) = ("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ", 80);
 #[cfg(windows)]
 const TICK_SETTINGS: (&str, u64) = (r"+-x| ", 200);
 
+/// A progress bar that is either updated on every call or, if an update interval is given,
+/// at most once per interval. In the latter case, increments and messages are coalesced until
+/// the next update, which reduces the overhead of redrawing the bar for very fast commands.
+pub struct BenchmarkProgressBar {
+    bar: ProgressBar,
+    update_interval: Option<Duration>,
+    last_update: Cell<Instant>,
+    pending_increment: Cell<u64>,
+    pending_message: RefCell<Option<String>>,
+    pending_session_eta: Cell<Option<Duration>>,
+    /// The remaining time of the whole session, as shown by the `{session_eta}` template key
+    session_eta: Arc<Mutex<Option<Duration>>>,
+}
+
+/// Progress of the whole benchmarking session, used to estimate the time until all
+/// benchmarks have finished
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SessionProgress {
+    /// Number of benchmarks that still need to be performed after the current one
+    pub remaining_benchmarks: usize,
+
+    /// Average duration of the benchmarks that have already finished
+    pub mean_benchmark_duration: Option<Duration>,
+}
+
+impl SessionProgress {
+    /// Estimate the remaining time of the session from the remaining and the expected total
+    /// time of the current benchmark. Without any finished benchmarks, the remaining ones are
+    /// assumed to take as long as the current one.
+    pub fn remaining_time(&self, current_remaining: Duration, current_total: Duration) -> Duration {
+        let per_benchmark = self.mean_benchmark_duration.unwrap_or(current_total);
+        current_remaining + per_benchmark * self.remaining_benchmarks as u32
+    }
+}
+
+impl BenchmarkProgressBar {
+    pub fn inc(&self, delta: u64) {
+        self.pending_increment
+            .set(self.pending_increment.get() + delta);
+        self.update_if_due();
+    }
+
+    pub fn set_message(&self, msg: String) {
+        *self.pending_message.borrow_mut() = Some(msg);
+        self.update_if_due();
+    }
+
+    /// Show the estimated remaining time of the whole session next to the ETA of the
+    /// current benchmark
+    pub fn set_session_eta(&self, eta: Duration) {
+        self.pending_session_eta.set(Some(eta));
+        self.update_if_due();
+    }
+
+    pub fn set_length(&self, length: u64) {
+        self.bar.set_length(length)
+    }
+
+    pub fn inc_length(&self, delta: u64) {
+        self.bar.inc_length(delta)
+    }
+
+    pub fn finish_and_clear(&self) {
+        self.flush();
+        self.bar.finish_and_clear()
+    }
+
+    fn update_if_due(&self) {
+        self.update_if_due_at(Instant::now())
+    }
+
+    fn update_if_due_at(&self, now: Instant) {
+        if let Some(interval) = self.update_interval {
+            if now.saturating_duration_since(self.last_update.get()) < interval {
+                return;
+            }
+            self.last_update.set(now);
+        }
+
+        self.flush();
+    }
+
+    /// Apply all coalesced updates to the bar
+    fn flush(&self) {
+        let delta = self.pending_increment.replace(0);
+        if delta > 0 {
+            self.bar.inc(delta);
+        }
+        if let Some(msg) = self.pending_message.take() {
+            self.bar.set_message(msg);
+        }
+        if let Some(eta) = self.pending_session_eta.take() {
+            *self.session_eta.lock().unwrap() = Some(eta);
+        }
+    }
+}
+
+/// Render the `{session_eta}` template key
+fn write_session_eta(session_eta: Option<Duration>, w: &mut dyn Write) {
+    if let Some(eta) = session_eta {
+        let _ = write!(w, " (total {})", FormattedDuration(eta));
+    }
+}
+
 /// Return a pre-configured progress bar
-pub fn get_progress_bar(length: u64, msg: &str, option: OutputStyleOption) -> ProgressBar {
+pub fn get_progress_bar(
+    length: u64,
+    msg: &str,
+    option: OutputStyleOption,
+    update_interval: Option<Duration>,
+) -> BenchmarkProgressBar {
+    let session_eta = Arc::new(Mutex::new(None));
     let progressbar_style = match option {
         OutputStyleOption::Basic | OutputStyleOption::Color => ProgressStyle::default_bar(),
-        _ => ProgressStyle::default_spinner()
-            .tick_chars(TICK_SETTINGS.0)
-            .template(" {spinner} {msg:<30} {wide_bar} ETA {eta_precise} ")
-            .expect("no template error"),
+        _ => {
+            let session_eta = session_eta.clone();
+            ProgressStyle::default_spinner()
+                .tick_chars(TICK_SETTINGS.0)
+                .template(
+                    " {spinner} {msg:<30} {wide_bar} {per_sec} ETA {eta_precise}{session_eta} ",
+                )
+                .expect("no template error")
+                .with_key(
+                    "session_eta",
+                    move |_: &ProgressState, w: &mut dyn Write| {
+                        write_session_eta(*session_eta.lock().unwrap(), w)
+                    },
+                )
+        }
     };
 
     let progress_bar = match option {
@@ -24,8 +149,133 @@ pub fn get_progress_bar(length: u64, msg: &str, option: OutputStyleOption) -> Pr
         _ => ProgressBar::new(length),
     };
     progress_bar.set_style(progressbar_style);
-    progress_bar.enable_steady_tick(Duration::from_millis(TICK_SETTINGS.1));
+    progress_bar.enable_steady_tick(cmp::max(
+        Duration::from_millis(TICK_SETTINGS.1),
+        update_interval.unwrap_or_default(),
+    ));
     progress_bar.set_message(msg.to_owned());
 
+    BenchmarkProgressBar {
+        bar: progress_bar,
+        update_interval,
+        last_update: Cell::new(Instant::now()),
+        pending_increment: Cell::new(0),
+        pending_message: RefCell::new(None),
+        pending_session_eta: Cell::new(None),
+        session_eta,
+    }
+}
+
+#[test]
+fn test_progress_bar_updates() {
+    // By default, every increment is applied immediately
+    let progress_bar = get_progress_bar(10, "", OutputStyleOption::Basic, None);
+    progress_bar.inc(1);
+    progress_bar.inc(1);
+    assert_eq!(progress_bar.bar.position(), 2);
+
+    // With an update interval, increments are coalesced until the interval has passed
+    let interval = Duration::from_secs(3600);
+    let progress_bar = get_progress_bar(10, "", OutputStyleOption::Basic, Some(interval));
+    progress_bar.inc(1);
+    progress_bar.inc(1);
+    assert_eq!(progress_bar.bar.position(), 0);
+
+    let start = progress_bar.last_update.get();
+    progress_bar.update_if_due_at(start + interval / 2);
+    assert_eq!(progress_bar.bar.position(), 0);
+
+    progress_bar.update_if_due_at(start + interval);
+    assert_eq!(progress_bar.bar.position(), 2);
+
+    // Pending updates are not lost when the bar is finished
+    progress_bar.inc(1);
+    assert_eq!(progress_bar.bar.position(), 2);
+    progress_bar.finish_and_clear();
+    assert_eq!(progress_bar.bar.position(), 3);
+}
+
+#[test]
+fn test_session_remaining_time() {
+    let secs = Duration::from_secs;
+
+    // The remaining benchmarks are assumed to take as long as the current one
+    let session = SessionProgress {
+        remaining_benchmarks: 2,
+        mean_benchmark_duration: None,
+    };
+    assert_eq!(session.remaining_time(secs(3), secs(10)), secs(23));
+
+    // ... unless there are finished benchmarks
+    let session = SessionProgress {
+        remaining_benchmarks: 2,
+        mean_benchmark_duration: Some(secs(5)),
+    };
+    assert_eq!(session.remaining_time(secs(3), secs(10)), secs(13));
+
+    assert_eq!(
+        SessionProgress::default().remaining_time(secs(3), secs(10)),
+        secs(3)
+    );
+}
+
+#[test]
+fn test_session_eta_is_shown() {
+    use indicatif::{ProgressDrawTarget, TermLike};
+    use std::io;
+
+    /// A terminal that records everything that is drawn to it
+    #[derive(Debug, Clone, Default)]
+    struct RecordingTerm(Arc<Mutex<String>>);
+
+    impl TermLike for RecordingTerm {
+        fn width(&self) -> u16 {
+            200
+        }
+        fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, s: &str) -> io::Result<()> {
+            self.write_str(s)
+        }
+        fn write_str(&self, s: &str) -> io::Result<()> {
+            self.0.lock().unwrap().push_str(s);
+            Ok(())
+        }
+        fn clear_line(&self) -> io::Result<()> {
+            Ok(())
+        }
+        fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let interval = Duration::from_secs(3600);
+    let progress_bar = get_progress_bar(10, "", OutputStyleOption::Full, Some(interval));
+    let term = RecordingTerm::default();
     progress_bar
+        .bar
+        .set_draw_target(ProgressDrawTarget::term_like(Box::new(term.clone())));
+    progress_bar.bar.disable_steady_tick();
+
+    progress_bar.bar.tick();
+    assert!(!term.0.lock().unwrap().contains("total"));
+
+    // The session ETA is coalesced like all other updates ...
+    progress_bar.set_session_eta(Duration::from_secs(65));
+    assert_eq!(*progress_bar.session_eta.lock().unwrap(), None);
+
+    // ... and shown once it has been applied
+    progress_bar.update_if_due_at(progress_bar.last_update.get() + interval);
+    progress_bar.bar.tick();
+    assert!(term.0.lock().unwrap().contains(" (total 00:01:05)"));
 }
@@ -0,0 +1,62 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::util::units::Second;
+
+/// A progress update that is written to stderr as a single line of JSON with '--progress-json'
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent<'a> {
+    /// A warmup run of the given benchmark has finished
+    Warmup { benchmark: usize, run: u64 },
+
+    /// A timing run of the given benchmark has finished
+    Measure { benchmark: usize, run: usize },
+
+    /// All runs of the given benchmark have finished
+    BenchmarkDone {
+        index: usize,
+        command: &'a str,
+        mean: Second,
+    },
+}
+
+impl ProgressEvent<'_> {
+    /// Write the event to stderr. The whole line is written at once, such that events are not
+    /// interleaved with other output.
+    pub fn emit(&self) {
+        if let Ok(mut line) = serde_json::to_vec(self) {
+            line.push(b'\n');
+            let _ = io::stderr().lock().write_all(&line);
+        }
+    }
+}
+
+#[test]
+fn test_progress_event_serialization() {
+    let to_json = |event: &ProgressEvent| serde_json::to_string(event).unwrap();
+
+    assert_eq!(
+        to_json(&ProgressEvent::Warmup {
+            benchmark: 0,
+            run: 3
+        }),
+        r#"{"event":"warmup","benchmark":0,"run":3}"#
+    );
+    assert_eq!(
+        to_json(&ProgressEvent::Measure {
+            benchmark: 1,
+            run: 0
+        }),
+        r#"{"event":"measure","benchmark":1,"run":0}"#
+    );
+    assert_eq!(
+        to_json(&ProgressEvent::BenchmarkDone {
+            index: 1,
+            command: "sleep 0.5",
+            mean: 0.5
+        }),
+        r#"{"event":"benchmark_done","index":1,"command":"sleep 0.5","mean":0.5}"#
+    );
+}
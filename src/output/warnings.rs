@@ -1,21 +1,41 @@
+use std::collections::BTreeMap;
 use std::fmt;
 
-use crate::benchmark::MIN_EXECUTION_TIME;
+use crate::benchmark::{MIN_EXECUTION_TIME, MIN_RUNS_FOR_STATISTICS};
 use crate::output::format::format_duration;
-use crate::util::units::Second;
+use crate::util::units::{Scalar, Second, Unit};
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct OutlierWarningOptions {
     pub warmup_in_use: bool,
     pub prepare_in_use: bool,
 }
 
 /// A list of all possible warnings
+#[derive(Debug, Clone, PartialEq)]
 pub enum Warnings {
     FastExecutionTime,
     NonZeroExitCode,
-    SlowInitialRun(Second, OutlierWarningOptions),
+    /// Time of the first run, the unit it is shown in and the options in use
+    SlowInitialRun(Second, Option<Unit>, OutlierWarningOptions),
     OutliersDetected(OutlierWarningOptions),
-    PoopMetricsUnavailable,
+    /// Whether opening the counters has been denied due to insufficient permissions
+    PoopMetricsUnavailable(bool),
+    UnavailableMetrics(BTreeMap<String, String>),
+    VarianceTargetNotMet(Scalar, Scalar, u64),
+    ExternalVariance(Scalar, Scalar),
+    MultiplexedCounters(Scalar),
+    FrequentCpuMigrations(u64),
+    /// Number of killed runs, the timeout and the unit it is shown in
+    TimedOut(usize, Second, Option<Unit>),
+    PrecisionNotReached(Scalar, Scalar, usize),
+    PreparationOverhead(Scalar),
+    TooFewRuns(usize),
+    RunTimeTrend(Scalar),
+    /// Means of the two clusters and the unit they are shown in
+    BimodalDistribution(Second, Second, Option<Unit>),
+    /// Number of discarded bytes and the capture limit
+    OutputTruncated(u64, usize),
 }
 
 impl fmt::Display for Warnings {
@@ -30,12 +50,12 @@ impl fmt::Display for Warnings {
                 MIN_EXECUTION_TIME * 1e3
             ),
             Warnings::NonZeroExitCode => write!(f, "Ignoring non-zero exit code."),
-            Warnings::SlowInitialRun(time_first_run, ref options) => write!(
+            Warnings::SlowInitialRun(time_first_run, unit, ref options) => write!(
                 f,
                 "The first benchmarking run for this command was significantly slower than the \
                  rest ({time}). This could be caused by (filesystem) caches that were not filled until \
                  after the first run. {hints}",
-                time=format_duration(time_first_run, None),
+                time=format_duration(time_first_run, unit),
                 hints=match (options.warmup_in_use, options.prepare_in_use) {
                     (true, true) => "You are already using both the '--warmup' option as well \
                     as the '--prepare' option. Consider re-running the benchmark on a quiet system. \
@@ -65,12 +85,123 @@ impl fmt::Display for Warnings {
                     " It might help to use the '--warmup' or '--prepare' options."
                 }
             ),
-            Warnings::PoopMetricsUnavailable => write!(
+            Warnings::PoopMetricsUnavailable(true) => write!(
                 f,
-                "Hardware performance metrics were requested but could not be collected. \
-                 This is likely due to insufficient permissions. On Linux, you may need to adjust \
-                 /proc/sys/kernel/perf_event_paranoid or run as root. See the hyperfine documentation \
-                 for more details."
+                "Hardware performance metrics were requested but could not be collected, because \
+                 access to the performance counters was denied. Allow unprivileged access with \
+                 'sudo sysctl kernel.perf_event_paranoid=1', or grant the CAP_PERFMON capability \
+                 to hyperfine (e.g. 'sudo setcap cap_perfmon+ep \"$(command -v hyperfine)\"')."
+            ),
+            Warnings::PoopMetricsUnavailable(false) => write!(
+                f,
+                "Hardware performance metrics were requested but could not be collected, because \
+                 the performance counters are not supported by the kernel, the CPU or the virtual \
+                 machine that hyperfine is running on."
+            ),
+            Warnings::UnavailableMetrics(ref unavailable) => write!(
+                f,
+                "The following metrics could not be collected: {}.",
+                unavailable
+                    .iter()
+                    .map(|(name, reason)| format!("{name} ({reason})"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Warnings::ExternalVariance(cv_cycles, cv_times) => write!(
+                f,
+                "The number of CPU cycles is stable across runs (±{:.1}%), but the wall clock \
+                 times vary a lot (±{:.1}%). This indicates that the variance is not caused by \
+                 the command itself, but by I/O, process scheduling, or CPU frequency scaling. \
+                 Use '--no-external-variance-warning' to disable this warning.",
+                cv_cycles * 1e2,
+                cv_times * 1e2
+            ),
+            Warnings::MultiplexedCounters(factor) => write!(
+                f,
+                "The performance counters could only be active for 1/{:.1} of the run time \
+                 because there are not enough hardware counters to collect all metrics at \
+                 once. The values have been extrapolated and are unreliable. Consider collecting \
+                 fewer metrics with '--metric'.",
+                factor
+            ),
+            Warnings::FrequentCpuMigrations(migrations) => write!(
+                f,
+                "The command has been migrated to a different CPU {} times per run on average. \
+                 Migrations are a common source of measurement noise. Consider pinning the \
+                 benchmark to a set of CPUs (e.g. with 'taskset').",
+                migrations
+            ),
+            Warnings::TimedOut(runs, timeout, unit) => write!(
+                f,
+                "{} run(s) exceeded the timeout of {} and have been killed. The time until \
+                 they were killed is included in the statistics, so the results are not \
+                 representative.",
+                runs,
+                format_duration(timeout, unit)
+            ),
+            Warnings::PrecisionNotReached(margin, precision, runs) => write!(
+                f,
+                "The mean is only known to within ±{:.1}% after {} runs, which is above the \
+                 requested precision of ±{:.1}%. Consider increasing '--max-runs'.",
+                margin * 1e2,
+                runs,
+                precision * 1e2
+            ),
+            Warnings::OutputTruncated(bytes_truncated, limit) => write!(
+                f,
+                "The output of the command exceeded the capture limit of {limit} bytes, so the \
+                 first {bytes_truncated} bytes have been discarded. Use '--output-capture-limit' \
+                 to keep more of the output."
+            ),
+            Warnings::TooFewRuns(runs) => write!(
+                f,
+                "Only {} run(s) have been performed, so the standard deviation and the other \
+                 statistics are not reliable. Consider using '--runs' or '--min-runs' to \
+                 perform at least {} runs.",
+                runs, MIN_RUNS_FOR_STATISTICS
+            ),
+            Warnings::RunTimeTrend(correlation) => write!(
+                f,
+                "The run times {} over the course of the benchmark (rank correlation between \
+                 run index and run time: {:.2}). {}",
+                if correlation > 0.0 { "increased" } else { "decreased" },
+                correlation,
+                if correlation > 0.0 {
+                    "The machine might be slowing down, e.g. due to thermal throttling. \
+                     Consider re-running the benchmark on a quiet, cooled-down system."
+                } else {
+                    "The command might still be warming up (e.g. filling caches). Consider \
+                     increasing the number of warmup runs with '--warmup'."
+                }
+            ),
+            Warnings::BimodalDistribution(lower, upper, unit) => write!(
+                f,
+                "The run times form two separate clusters with means of {} and {}, so the \
+                 mean and the standard deviation do not describe them well. This can be \
+                 caused by garbage collection pauses, CPU frequency scaling, or by different \
+                 code paths being taken. Consider looking at the individual run times, e.g. \
+                 with '--export-json'.",
+                format_duration(lower, unit),
+                format_duration(upper, unit)
+            ),
+            Warnings::PreparationOverhead(ratio) => write!(
+                f,
+                "The preparation command took {:.0}% of the time of the benchmarked command on \
+                 average. Since it is executed before every run, it dominates the total \
+                 benchmarking time and reduces the number of runs that fit into the minimum \
+                 benchmarking time. Consider using a lighter '--prepare' command, or the \
+                 '--warmup' option if the preparation is only needed to fill caches.",
+                ratio * 1e2
+            ),
+            Warnings::VarianceTargetNotMet(relative_stddev, target, attempts) => write!(
+                f,
+                "The relative standard deviation of the run times ({:.1}%) is above the requested \
+                 limit of {:.1}%, even after {} measurement attempts. The results of the least \
+                 noisy attempt are shown. Consider closing other programs or increasing the \
+                 number of runs.",
+                relative_stddev * 1e2,
+                target * 1e2,
+                attempts
             ),
         }
     }
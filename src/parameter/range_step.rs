@@ -30,7 +30,7 @@ impl<
 {
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RangeStep<T> {
     state: T,
     end: T,
@@ -30,6 +30,20 @@ pub fn tokenize(values: &str) -> Vec<String> {
     tokens
 }
 
+/// Split the content of a parameter values file into one value per line. Trailing whitespace
+/// is removed, and blank lines as well as lines starting with '#' are skipped.
+pub fn tokenize_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| {
+            let line = line.trim_start();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(String::from)
+        .collect()
+}
+
 #[test]
 fn test_tokenize_single_value() {
     assert_eq!(tokenize(r""), vec![""]);
@@ -58,3 +72,16 @@ fn test_tokenize_empty_values() {
     assert_eq!(tokenize(r"bar,"), vec!["bar", ""]);
     assert_eq!(tokenize(r",,"), vec!["", "", ""]);
 }
+
+#[test]
+fn test_tokenize_lines() {
+    assert_eq!(tokenize_lines(""), Vec::<String>::new());
+    assert_eq!(
+        tokenize_lines("# only a comment\n\n  \n"),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        tokenize_lines("gcc\r\n  # compilers\nclang -O2  \n\n  tcc\na,b #1"),
+        vec!["gcc", "clang -O2", "  tcc", "a,b #1"]
+    );
+}
@@ -0,0 +1,26 @@
+// The accepted spellings of all metric types on the command line, in the order of
+// `MetricType::all()`. The first name is the canonical one. This file is included by
+// `src/cli.rs`, which is also compiled as part of the build script, so it must not refer to
+// anything else in this crate.
+&[
+    &["cpu-cycles", "cycles"],
+    &["instructions"],
+    &["cache-references", "cache-refs"],
+    &["cache-misses"],
+    &["branches"],
+    &["branch-misses"],
+    &["stalled-cycles-frontend", "frontend-stalls"],
+    &["stalled-cycles-backend", "backend-stalls"],
+    &["ref-cycles", "ref-cpu-cycles"],
+    &["bus-cycles"],
+    &["l1-dcache-loads"],
+    &["l1-dcache-load-misses"],
+    &["llc-loads"],
+    &["llc-load-misses"],
+    &["dtlb-load-misses"],
+    &["itlb-load-misses"],
+    &["page-faults", "faults"],
+    &["context-switches", "cs"],
+    &["cpu-migrations", "migrations"],
+    &["syscalls", "system-calls"],
+]
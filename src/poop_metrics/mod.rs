@@ -3,7 +3,9 @@ pub mod types;
 #[cfg(target_os = "linux")]
 pub mod perf_events;
 
-pub use types::{MetricType, PoopMetrics};
+pub use types::{
+    ComparisonMetric, DerivedRatio, MetricType, PoopMetricRates, PoopMetrics, RawEvent,
+};
 
 #[cfg(target_os = "linux")]
 pub use perf_events::PerfEventsCollector;
@@ -13,8 +15,14 @@ use std::io;
 #[allow(unused)]
 /// Trait for collecting poop metrics
 pub trait MetricsCollector {
-    /// Create a new collector for the given process ID
-    fn new(pid: i32, metrics: &[MetricType]) -> io::Result<Self>
+    /// Create a new collector for the given process ID. With `inherit`, all child processes
+    /// and threads that are created afterwards are counted as well.
+    fn new(
+        pid: i32,
+        metrics: &[MetricType],
+        raw_events: &[RawEvent],
+        inherit: bool,
+    ) -> io::Result<Self>
     where
         Self: Sized;
 
@@ -30,8 +38,13 @@ pub trait MetricsCollector {
 
 #[cfg(target_os = "linux")]
 impl MetricsCollector for PerfEventsCollector {
-    fn new(pid: i32, metrics: &[MetricType]) -> io::Result<Self> {
-        PerfEventsCollector::new(pid, metrics)
+    fn new(
+        pid: i32,
+        metrics: &[MetricType],
+        raw_events: &[RawEvent],
+        inherit: bool,
+    ) -> io::Result<Self> {
+        PerfEventsCollector::new(pid, metrics, raw_events, inherit)
     }
 
     fn enable(&self) -> io::Result<()> {
@@ -49,13 +62,38 @@ impl MetricsCollector for PerfEventsCollector {
 
 /// Create a metrics collector for the current platform
 #[cfg(target_os = "linux")]
-pub fn create_collector(pid: i32, metrics: &[MetricType]) -> io::Result<PerfEventsCollector> {
-    PerfEventsCollector::new(pid, metrics)
+pub fn create_collector(
+    pid: i32,
+    metrics: &[MetricType],
+    raw_events: &[RawEvent],
+    inherit: bool,
+) -> io::Result<PerfEventsCollector> {
+    PerfEventsCollector::new(pid, metrics, raw_events, inherit)
 }
 
 /// Create a metrics collector for the current platform (stub for non-Linux)
 #[cfg(not(target_os = "linux"))]
-pub fn create_collector(_pid: i32, _metrics: &[MetricType]) -> io::Result<()> {
+pub fn create_collector(
+    _pid: i32,
+    _metrics: &[MetricType],
+    _raw_events: &[RawEvent],
+    _inherit: bool,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "poop metrics collection is only supported on Linux",
+    ))
+}
+
+/// Check whether the given metric can be collected on this system
+#[cfg(target_os = "linux")]
+pub fn probe_metric(metric: MetricType) -> io::Result<()> {
+    perf_events::probe(metric)
+}
+
+/// Check whether the given metric can be collected on this system (stub for non-Linux)
+#[cfg(not(target_os = "linux"))]
+pub fn probe_metric(_metric: MetricType) -> io::Result<()> {
     Err(io::Error::new(
         io::ErrorKind::Unsupported,
         "poop metrics collection is only supported on Linux",
@@ -1,9 +1,12 @@
+use std::convert::TryInto;
+use std::fs;
 use std::io;
 use std::os::unix::io::RawFd;
 
-use super::types::{MetricType, PoopMetrics};
+use super::types::{MetricType, PoopMetrics, RawEvent};
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 struct perf_event_attr {
     type_: u32,
     size: u32,
@@ -40,7 +43,21 @@ impl perf_event_attr {
             type_: perf_type_poop,
             size: std::mem::size_of::<perf_event_attr>() as u32,
             config,
-            flags: 1 << 0 | 1 << 1, // disabled | inherit
+            flags: PERF_ATTR_FLAG_DISABLED | PERF_ATTR_FLAG_INHERIT,
+            ..Default::default()
+        }
+    }
+
+    /// Counter for a generalized cache event. The config is packed from the cache
+    /// (`PERF_COUNT_HW_CACHE_L1D`, ...), the operation (`PERF_COUNT_HW_CACHE_OP_READ`, ...)
+    /// and the result (`PERF_COUNT_HW_CACHE_RESULT_ACCESS` or `..._MISS`).
+    fn new_hw_cache(cache: u64, op: u64, result: u64) -> Self {
+        let perf_type_hw_cache = 3;
+        Self {
+            type_: perf_type_hw_cache,
+            size: std::mem::size_of::<perf_event_attr>() as u32,
+            config: cache | (op << 8) | (result << 16),
+            flags: PERF_ATTR_FLAG_DISABLED | PERF_ATTR_FLAG_INHERIT,
             ..Default::default()
         }
     }
@@ -51,12 +68,65 @@ impl perf_event_attr {
             type_: perf_type_software,
             size: std::mem::size_of::<perf_event_attr>() as u32,
             config,
-            flags: 1 << 0 | 1 << 1, // disabled | inherit
+            flags: PERF_ATTR_FLAG_DISABLED | PERF_ATTR_FLAG_INHERIT,
+            ..Default::default()
+        }
+    }
+
+    fn new_raw(config: u64) -> Self {
+        let perf_type_raw = 4;
+        Self {
+            type_: perf_type_raw,
+            size: std::mem::size_of::<perf_event_attr>() as u32,
+            config,
+            flags: PERF_ATTR_FLAG_DISABLED | PERF_ATTR_FLAG_INHERIT,
+            ..Default::default()
+        }
+    }
+
+    fn new_tracepoint(config: u64) -> Self {
+        let perf_type_tracepoint = 2;
+        Self {
+            type_: perf_type_tracepoint,
+            size: std::mem::size_of::<perf_event_attr>() as u32,
+            config,
+            flags: PERF_ATTR_FLAG_DISABLED | PERF_ATTR_FLAG_INHERIT,
             ..Default::default()
         }
     }
 }
 
+// Bits of `perf_event_attr::flags`
+const PERF_ATTR_FLAG_DISABLED: u64 = 1 << 0;
+const PERF_ATTR_FLAG_INHERIT: u64 = 1 << 1;
+const PERF_ATTR_FLAG_ENABLE_ON_EXEC: u64 = 1 << 12;
+
+/// Locations where tracefs is typically mounted
+const TRACEFS_MOUNT_POINTS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+
+/// Read the numeric id of the given tracepoint (e.g. "raw_syscalls/sys_enter") from tracefs.
+/// This fails if tracefs is not mounted or not accessible to the current user.
+fn tracepoint_id(tracepoint: &str) -> io::Result<u64> {
+    tracepoint_id_in(&TRACEFS_MOUNT_POINTS, tracepoint)
+}
+
+/// Read the id of the tracepoint from the first of the given tracefs mount points that has it
+fn tracepoint_id_in(mount_points: &[&str], tracepoint: &str) -> io::Result<u64> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "tracefs is not mounted");
+    for mount_point in mount_points {
+        match fs::read_to_string(format!("{mount_point}/events/{tracepoint}/id")) {
+            Ok(content) => {
+                return content
+                    .trim()
+                    .parse()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
 fn perf_event_open(
     attr: &perf_event_attr,
     pid: i32,
@@ -64,12 +134,9 @@ fn perf_event_open(
     group_fd: i32,
     flags: u64,
 ) -> io::Result<RawFd> {
-    // perf_event_open syscall number for x86_64
-    // FIXME: ?
-    let perf_event_open = 298;
     let fd = unsafe {
         libc::syscall(
-            perf_event_open,
+            libc::SYS_perf_event_open,
             attr as *const perf_event_attr,
             pid,
             cpu,
@@ -88,18 +155,29 @@ fn perf_event_open(
 /// Performance event counter
 struct PerfCounter {
     fd: RawFd,
+    type_: u32,
+    config: u64,
 }
 
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+
+/// ioctl flag to apply an operation to all counters in the group of the given counter
+const PERF_IOC_FLAG_GROUP: libc::c_ulong = 1;
+
 impl PerfCounter {
-    fn new(attr: perf_event_attr, pid: i32) -> io::Result<Self> {
+    fn new(attr: perf_event_attr, pid: i32, group_fd: Option<RawFd>) -> io::Result<Self> {
         let perf_flag_fd_cloexec = 1 << 3;
-        let fd = perf_event_open(&attr, pid, -1, -1, perf_flag_fd_cloexec)?;
-        Ok(Self { fd })
+        let fd = perf_event_open(&attr, pid, -1, group_fd.unwrap_or(-1), perf_flag_fd_cloexec)?;
+        Ok(Self {
+            fd,
+            type_: attr.type_,
+            config: attr.config,
+        })
     }
 
-    fn enable(&self) -> io::Result<()> {
-        let perf_event_ioc_enable = 0x2400;
-        let ret = unsafe { libc::ioctl(self.fd, perf_event_ioc_enable, 0) };
+    fn ioctl(&self, request: libc::c_ulong, arg: libc::c_ulong) -> io::Result<()> {
+        let ret = unsafe { libc::ioctl(self.fd, request as _, arg) };
         if ret < 0 {
             Err(io::Error::last_os_error())
         } else {
@@ -107,31 +185,22 @@ impl PerfCounter {
         }
     }
 
-    fn disable(&self) -> io::Result<()> {
-        let perf_event_ioc_disable = 0x2401;
-        let ret = unsafe { libc::ioctl(self.fd, perf_event_ioc_disable, 0) };
+    /// Read the bytes returned by the kernel into `buf`, which has to match the size of the
+    /// layout given by the `read_format` of the counter (without any flags, this is a single
+    /// u64 value in native byte order)
+    fn read_raw(&self, buf: &mut [u8]) -> io::Result<()> {
+        let ret = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
         if ret < 0 {
             Err(io::Error::last_os_error())
+        } else if ret as usize != buf.len() {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("short read of {ret} bytes"),
+            ))
         } else {
             Ok(())
         }
     }
-
-    fn read_value(&self) -> io::Result<u64> {
-        let mut value: u64 = 0;
-        let ret = unsafe {
-            libc::read(
-                self.fd,
-                &mut value as *mut u64 as *mut libc::c_void,
-                std::mem::size_of::<u64>(),
-            )
-        };
-        if ret < 0 {
-            Err(io::Error::last_os_error())
-        } else {
-            Ok(value)
-        }
-    }
 }
 
 impl Drop for PerfCounter {
@@ -140,166 +209,594 @@ impl Drop for PerfCounter {
     }
 }
 
-/// Collector for poop performance metrics
+// Generalized cache events (see `perf_event_attr::new_hw_cache`)
+const HW_CACHE_L1D: u64 = 0;
+const HW_CACHE_LL: u64 = 2;
+const HW_CACHE_DTLB: u64 = 3;
+const HW_CACHE_ITLB: u64 = 4;
+const HW_CACHE_OP_READ: u64 = 0;
+const HW_CACHE_RESULT_ACCESS: u64 = 0;
+const HW_CACHE_RESULT_MISS: u64 = 1;
+
+/// The attributes of the counter for the given metric
+fn metric_attr(metric: MetricType) -> io::Result<perf_event_attr> {
+    Ok(match metric {
+        MetricType::CpuCycles => {
+            let perf_count_hw_cpu_cycles = 0;
+            perf_event_attr::new_poop(perf_count_hw_cpu_cycles)
+        }
+        MetricType::Instructions => {
+            let perf_count_hw_instructions = 1;
+            perf_event_attr::new_poop(perf_count_hw_instructions)
+        }
+        MetricType::CacheReferences => {
+            let perf_count_hw_cache_references = 2;
+            perf_event_attr::new_poop(perf_count_hw_cache_references)
+        }
+        MetricType::CacheMisses => {
+            let perf_count_hw_cache_misses = 3;
+            perf_event_attr::new_poop(perf_count_hw_cache_misses)
+        }
+        MetricType::Branches => {
+            let perf_count_hw_branch_instructions = 4;
+            perf_event_attr::new_poop(perf_count_hw_branch_instructions)
+        }
+        MetricType::BranchMisses => {
+            let perf_count_hw_branch_misses = 5;
+            perf_event_attr::new_poop(perf_count_hw_branch_misses)
+        }
+        MetricType::StalledCyclesFrontend => {
+            let perf_count_hw_stalled_cycles_frontend = 7;
+            perf_event_attr::new_poop(perf_count_hw_stalled_cycles_frontend)
+        }
+        MetricType::StalledCyclesBackend => {
+            let perf_count_hw_stalled_cycles_backend = 8;
+            perf_event_attr::new_poop(perf_count_hw_stalled_cycles_backend)
+        }
+        MetricType::RefCycles => {
+            let perf_count_hw_ref_cpu_cycles = 9;
+            perf_event_attr::new_poop(perf_count_hw_ref_cpu_cycles)
+        }
+        MetricType::BusCycles => {
+            let perf_count_hw_bus_cycles = 6;
+            perf_event_attr::new_poop(perf_count_hw_bus_cycles)
+        }
+        MetricType::L1DcacheLoads => {
+            perf_event_attr::new_hw_cache(HW_CACHE_L1D, HW_CACHE_OP_READ, HW_CACHE_RESULT_ACCESS)
+        }
+        MetricType::L1DcacheLoadMisses => {
+            perf_event_attr::new_hw_cache(HW_CACHE_L1D, HW_CACHE_OP_READ, HW_CACHE_RESULT_MISS)
+        }
+        MetricType::LlcLoads => {
+            perf_event_attr::new_hw_cache(HW_CACHE_LL, HW_CACHE_OP_READ, HW_CACHE_RESULT_ACCESS)
+        }
+        MetricType::LlcLoadMisses => {
+            perf_event_attr::new_hw_cache(HW_CACHE_LL, HW_CACHE_OP_READ, HW_CACHE_RESULT_MISS)
+        }
+        MetricType::DtlbLoadMisses => {
+            perf_event_attr::new_hw_cache(HW_CACHE_DTLB, HW_CACHE_OP_READ, HW_CACHE_RESULT_MISS)
+        }
+        MetricType::ItlbLoadMisses => {
+            perf_event_attr::new_hw_cache(HW_CACHE_ITLB, HW_CACHE_OP_READ, HW_CACHE_RESULT_MISS)
+        }
+        MetricType::PageFaults => {
+            let perf_count_sw_page_faults = 2;
+            perf_event_attr::new_software(perf_count_sw_page_faults)
+        }
+        MetricType::ContextSwitches => {
+            let perf_count_sw_context_switches = 3;
+            perf_event_attr::new_software(perf_count_sw_context_switches)
+        }
+        MetricType::CpuMigrations => {
+            let perf_count_sw_cpu_migrations = 4;
+            perf_event_attr::new_software(perf_count_sw_cpu_migrations)
+        }
+        MetricType::Syscalls => {
+            perf_event_attr::new_tracepoint(tracepoint_id("raw_syscalls/sys_enter")?)
+        }
+    })
+}
+
+/// Open a standalone counter for the given metric and process ID
+fn open_counter(metric: MetricType, pid: i32) -> io::Result<PerfCounter> {
+    PerfCounter::new(metric_attr(metric)?, pid, None)
+}
+
+/// Check whether the counter for the given metric can be opened on this system (for the
+/// current process). The counter is closed again immediately.
+pub fn probe(metric: MetricType) -> io::Result<()> {
+    open_counter(metric, 0).map(drop)
+}
+
+/// `read_format` flags for the group leader. A single read returns the values of all
+/// counters in the group, which have been enabled and disabled at the same time:
+///
+///     struct read_format {
+///         u64 nr;            /* number of counters */
+///         u64 time_enabled;  /* PERF_FORMAT_TOTAL_TIME_ENABLED */
+///         u64 time_running;  /* PERF_FORMAT_TOTAL_TIME_RUNNING */
+///         u64 values[nr];    /* in the order in which the counters have been opened */
+///     };
+const GROUP_READ_FORMAT: u64 = {
+    let perf_format_total_time_enabled = 1 << 0;
+    let perf_format_total_time_running = 1 << 1;
+    let perf_format_group = 1 << 3;
+    perf_format_total_time_enabled | perf_format_total_time_running | perf_format_group
+};
+
+/// The parsed result of a grouped read (see `GROUP_READ_FORMAT`)
+#[derive(Debug, PartialEq)]
+struct GroupReading {
+    time_enabled: u64,
+    time_running: u64,
+    values: Vec<u64>,
+}
+
+impl GroupReading {
+    /// The counter values, extrapolated to the full time in which the counters have been
+    /// enabled (`value * time_enabled / time_running`), together with the scaling factor, if
+    /// the counters have been multiplexed. Returns `None` if the counters have never been
+    /// scheduled.
+    fn scaled_values(&self) -> Option<(Vec<u64>, Option<f64>)> {
+        if self.time_running == 0 {
+            return None;
+        }
+        if self.time_running >= self.time_enabled {
+            return Some((self.values.clone(), None));
+        }
+
+        let values = self
+            .values
+            .iter()
+            .map(|&value| {
+                (value as u128 * self.time_enabled as u128 / self.time_running as u128) as u64
+            })
+            .collect();
+        let factor = self.time_enabled as f64 / self.time_running as f64;
+        Some((values, Some(factor)))
+    }
+
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
+        let words: Vec<u64> = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_ne_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        match words.as_slice() {
+            [nr, time_enabled, time_running, values @ ..] if values.len() as u64 == *nr => {
+                Ok(Self {
+                    time_enabled: *time_enabled,
+                    time_running: *time_running,
+                    values: values.to_vec(),
+                })
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected group read of {} bytes", bytes.len()),
+            )),
+        }
+    }
+}
+
+/// The quantity that is measured by a counter
+enum Counted {
+    Metric(MetricType),
+    /// A raw event, by its label
+    Raw(String),
+}
+
+impl Counted {
+    fn name(&self) -> String {
+        match self {
+            Counted::Metric(metric) => metric.cli_name().to_string(),
+            Counted::Raw(label) => label.clone(),
+        }
+    }
+}
+
+/// Counters that are scheduled (and read) together. The first counter is the group leader.
+struct CounterGroup {
+    counters: Vec<(Counted, PerfCounter)>,
+}
+
+impl CounterGroup {
+    fn leader(&self) -> &PerfCounter {
+        &self.counters[0].1
+    }
+
+    /// Read the raw bytes of a grouped read of all counters in the group
+    fn read_raw(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; 8 * (3 + self.counters.len())];
+        self.leader().read_raw(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Collector for poop performance metrics. The counters are opened in as few groups as
+/// possible. A new group is started whenever a counter does not fit into the current one,
+/// e.g. because the PMU has no free hardware counter left. If there is more than one group,
+/// the kernel rotates between the groups, and the values are extrapolated accordingly.
 pub struct PerfEventsCollector {
-    cpu_cycles: Option<PerfCounter>,
-    instructions: Option<PerfCounter>,
-    cache_references: Option<PerfCounter>,
-    cache_misses: Option<PerfCounter>,
-    branches: Option<PerfCounter>,
-    branch_misses: Option<PerfCounter>,
-    page_faults: Option<PerfCounter>,
+    groups: Vec<CounterGroup>,
+
+    /// The counters that could not be opened, by their name (see `MetricType::cli_name` and
+    /// `RawEvent::label`), with the reason
+    unavailable: Vec<(String, io::Error)>,
+}
+
+/// Whether the error is caused by restricted access to the performance counters, which is
+/// controlled by `kernel.perf_event_paranoid` and the CAP_PERFMON capability
+fn is_permission_error(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::EACCES) | Some(libc::EPERM))
 }
 
 impl PerfEventsCollector {
     /// Create a new collector for the given process ID
-    /// If metrics is empty, collect all available metrics
-    pub fn new(pid: i32, metrics: &[MetricType]) -> io::Result<Self> {
-        let collect_all = metrics.is_empty();
+    /// If neither metrics nor raw events are given, collect all available metrics
+    /// With `inherit`, child processes and threads that are created afterwards are counted
+    /// as well. The counters are started when the process calls `exec`, or with `enable`.
+    pub fn new(
+        pid: i32,
+        metrics: &[MetricType],
+        raw_events: &[RawEvent],
+        inherit: bool,
+    ) -> io::Result<Self> {
+        let collect_all = metrics.is_empty() && raw_events.is_empty();
 
         let should_collect =
             |metric: MetricType| -> bool { collect_all || metrics.contains(&metric) };
 
-        let cpu_cycles = if should_collect(MetricType::CpuCycles) {
-            let perf_count_hw_cpu_cycles = 0;
-            PerfCounter::new(perf_event_attr::new_poop(perf_count_hw_cpu_cycles), pid).ok()
-        } else {
-            None
+        let mut collector = Self {
+            groups: vec![],
+            unavailable: vec![],
         };
+        for metric in MetricType::all() {
+            if !should_collect(metric) {
+                continue;
+            }
 
-        let instructions = if should_collect(MetricType::Instructions) {
-            let perf_count_hw_instructions = 1;
-            PerfCounter::new(perf_event_attr::new_poop(perf_count_hw_instructions), pid).ok()
-        } else {
-            None
-        };
-
-        let cache_references = if should_collect(MetricType::CacheReferences) {
-            let perf_count_hw_cache_references = 2;
-            PerfCounter::new(
-                perf_event_attr::new_poop(perf_count_hw_cache_references),
+            match metric_attr(metric) {
+                Ok(attr) => collector.add(Counted::Metric(metric), attr, pid, inherit),
+                Err(e) => collector
+                    .unavailable
+                    .push((metric.cli_name().to_string(), e)),
+            }
+        }
+        for event in raw_events {
+            collector.add(
+                Counted::Raw(event.label()),
+                perf_event_attr::new_raw(event.config),
                 pid,
-            )
-            .ok()
-        } else {
-            None
-        };
+                inherit,
+            );
+        }
 
-        let cache_misses = if should_collect(MetricType::CacheMisses) {
-            let perf_count_hw_cache_misses = 3;
-            PerfCounter::new(perf_event_attr::new_poop(perf_count_hw_cache_misses), pid).ok()
-        } else {
-            None
-        };
+        Ok(collector)
+    }
 
-        let branches = if should_collect(MetricType::Branches) {
-            let perf_count_hw_branch_instructions = 4;
-            PerfCounter::new(
-                perf_event_attr::new_poop(perf_count_hw_branch_instructions),
-                pid,
-            )
-            .ok()
-        } else {
-            None
-        };
+    /// Open the counter as a member of the last group, or as the leader of a new group if it
+    /// can not be added to the last one
+    fn add(&mut self, counted: Counted, mut attr: perf_event_attr, pid: i32, inherit: bool) {
+        attr.read_format = GROUP_READ_FORMAT;
+        attr.flags |= PERF_ATTR_FLAG_ENABLE_ON_EXEC;
+        if !inherit {
+            attr.flags &= !PERF_ATTR_FLAG_INHERIT;
+        }
 
-        let branch_misses = if should_collect(MetricType::BranchMisses) {
-            let perf_count_hw_branch_misses = 5;
-            PerfCounter::new(perf_event_attr::new_poop(perf_count_hw_branch_misses), pid).ok()
-        } else {
-            None
-        };
+        if let Some(group) = self.groups.last_mut() {
+            // Only the leader starts disabled, the other counters follow its state
+            let mut member_attr = attr;
+            member_attr.flags &= !(PERF_ATTR_FLAG_DISABLED | PERF_ATTR_FLAG_ENABLE_ON_EXEC);
 
-        let page_faults = if should_collect(MetricType::PageFaults) {
-            let perf_count_sw_page_faults = 2;
-            PerfCounter::new(
-                perf_event_attr::new_software(perf_count_sw_page_faults),
-                pid,
-            )
-            .ok()
-        } else {
-            None
-        };
+            if let Ok(counter) = PerfCounter::new(member_attr, pid, Some(group.leader().fd)) {
+                group.counters.push((counted, counter));
+                return;
+            }
+        }
 
-        Ok(Self {
-            cpu_cycles,
-            instructions,
-            cache_references,
-            cache_misses,
-            branches,
-            branch_misses,
-            page_faults,
-        })
+        match PerfCounter::new(attr, pid, None) {
+            Ok(counter) => self.groups.push(CounterGroup {
+                counters: vec![(counted, counter)],
+            }),
+            Err(e) => self.unavailable.push((counted.name(), e)),
+        }
+    }
+
+    /// Apply the given ioctl to all groups. All groups are tried, even if one of them fails.
+    fn ioctl_groups(&self, request: libc::c_ulong) -> io::Result<()> {
+        let mut result = Ok(());
+        for group in &self.groups {
+            let group_result = group.leader().ioctl(request, PERF_IOC_FLAG_GROUP);
+            if result.is_ok() {
+                result = group_result;
+            }
+        }
+        result
     }
 
     /// Enable all counters
     pub fn enable(&self) -> io::Result<()> {
-        if let Some(ref c) = self.cpu_cycles {
-            c.enable()?;
-        }
-        if let Some(ref c) = self.instructions {
-            c.enable()?;
-        }
-        if let Some(ref c) = self.cache_references {
-            c.enable()?;
-        }
-        if let Some(ref c) = self.cache_misses {
-            c.enable()?;
-        }
-        if let Some(ref c) = self.branches {
-            c.enable()?;
-        }
-        if let Some(ref c) = self.branch_misses {
-            c.enable()?;
-        }
-        if let Some(ref c) = self.page_faults {
-            c.enable()?;
-        }
-        Ok(())
+        self.ioctl_groups(PERF_EVENT_IOC_ENABLE)
     }
 
     /// Disable all counters
     pub fn disable(&self) -> io::Result<()> {
-        if let Some(ref c) = self.cpu_cycles {
-            c.disable()?;
-        }
-        if let Some(ref c) = self.instructions {
-            c.disable()?;
-        }
-        if let Some(ref c) = self.cache_references {
-            c.disable()?;
+        self.ioctl_groups(PERF_EVENT_IOC_DISABLE)
+    }
+
+    /// Print the raw values read from each counter to stderr (see `--debug-perf`)
+    pub fn dump_raw(&self, pid: i32) {
+        for (index, group) in self.groups.iter().enumerate() {
+            let reading = match group.read_raw() {
+                Ok(bytes) => {
+                    let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+                    eprintln!(
+                        "[debug-perf] pid {pid}: group {index} read bytes [{}]",
+                        hex.join(" ")
+                    );
+                    GroupReading::parse(&bytes)
+                }
+                Err(e) => Err(e),
+            };
+
+            let reading = match reading {
+                Ok(reading) => {
+                    eprintln!(
+                        "[debug-perf] pid {pid}: group {index} time enabled {} ns, time running {} ns",
+                        reading.time_enabled, reading.time_running
+                    );
+                    Some(reading)
+                }
+                Err(e) => {
+                    eprintln!("[debug-perf] pid {pid}: group {index} read failed: {e}");
+                    None
+                }
+            };
+
+            for (position, (counted, counter)) in group.counters.iter().enumerate() {
+                let value = reading
+                    .as_ref()
+                    .map_or("?".into(), |r| r.values[position].to_string());
+                eprintln!(
+                    "[debug-perf] pid {pid}: {:<16} fd {} type {} config {:#x}: {value}",
+                    counted.name(),
+                    counter.fd,
+                    counter.type_,
+                    counter.config
+                );
+            }
         }
-        if let Some(ref c) = self.cache_misses {
-            c.disable()?;
+
+        for (name, e) in &self.unavailable {
+            eprintln!("[debug-perf] pid {pid}: {name:<16} not opened: {e}");
         }
-        if let Some(ref c) = self.branches {
-            c.disable()?;
+    }
+
+    /// Read all counter values and return as PoopMetrics
+    pub fn read(&self) -> io::Result<PoopMetrics> {
+        let mut metrics = PoopMetrics::new();
+        for (name, e) in &self.unavailable {
+            metrics.unavailable.insert(name.clone(), e.to_string());
         }
-        if let Some(ref c) = self.branch_misses {
-            c.disable()?;
+        metrics.permission_denied = self.unavailable.iter().any(|(_, e)| is_permission_error(e));
+
+        for group in &self.groups {
+            let reading = GroupReading::parse(&group.read_raw()?)?;
+            let Some((values, scaling_factor)) = reading.scaled_values() else {
+                for (counted, _) in &group.counters {
+                    metrics
+                        .unavailable
+                        .insert(counted.name(), "the counter was never scheduled".into());
+                }
+                continue;
+            };
+
+            for ((counted, _), value) in group.counters.iter().zip(values) {
+                match counted {
+                    Counted::Metric(metric) => metrics.set(*metric, value),
+                    Counted::Raw(label) => {
+                        metrics.raw.insert(label.clone(), value);
+                    }
+                }
+            }
+            // Keep the worst scaling factor of all groups
+            metrics.scaling_factor = match (metrics.scaling_factor, scaling_factor) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            };
         }
-        if let Some(ref c) = self.page_faults {
-            c.disable()?;
+
+        Ok(metrics)
+    }
+}
+
+#[test]
+fn test_perf_event_open_syscall_number() {
+    #[cfg(target_arch = "x86_64")]
+    assert_eq!(libc::SYS_perf_event_open, 298);
+    #[cfg(target_arch = "x86")]
+    assert_eq!(libc::SYS_perf_event_open, 336);
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    assert_eq!(libc::SYS_perf_event_open, 241);
+    #[cfg(target_arch = "arm")]
+    assert_eq!(libc::SYS_perf_event_open, 364);
+}
+
+#[test]
+fn test_read_software_counter() {
+    let counter = match open_counter(MetricType::PageFaults, 0) {
+        Ok(counter) => counter,
+        // Counters for the own process are not allowed with perf_event_paranoid > 2
+        Err(e) if e.raw_os_error() == Some(libc::EACCES) => return,
+        Err(e) => panic!("could not open page fault counter: {}", e),
+    };
+
+    counter.ioctl(PERF_EVENT_IOC_ENABLE, 0).unwrap();
+    let memory = vec![1u8; 16 << 20];
+    assert_eq!(memory.iter().map(|&b| b as u64).sum::<u64>(), 16 << 20);
+    counter.ioctl(PERF_EVENT_IOC_DISABLE, 0).unwrap();
+
+    let mut buf = [0u8; 8];
+    counter.read_raw(&mut buf).unwrap();
+    assert!(u64::from_ne_bytes(buf) > 0);
+}
+
+#[test]
+fn test_parse_group_reading() {
+    let words: [u64; 5] = [2, 1000, 500, 42, 7];
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_ne_bytes()).collect();
+
+    assert_eq!(
+        GroupReading::parse(&bytes).unwrap(),
+        GroupReading {
+            time_enabled: 1000,
+            time_running: 500,
+            values: vec![42, 7],
         }
-        Ok(())
+    );
+
+    // The number of values does not match the header
+    assert!(GroupReading::parse(&bytes[..32]).is_err());
+    assert!(GroupReading::parse(&[]).is_err());
+}
+
+#[test]
+fn test_scale_multiplexed_counters() {
+    let reading = |time_enabled, time_running| GroupReading {
+        time_enabled,
+        time_running,
+        values: vec![100, u64::MAX / 4],
+    };
+
+    assert_eq!(
+        reading(1000, 1000).scaled_values(),
+        Some((vec![100, u64::MAX / 4], None))
+    );
+    assert_eq!(
+        reading(1000, 250).scaled_values(),
+        Some((vec![400, u64::MAX / 4 * 4], Some(4.0)))
+    );
+    assert_eq!(reading(1000, 0).scaled_values(), None);
+}
+
+#[test]
+fn test_grouped_read_of_software_counters() {
+    let collector = PerfEventsCollector::new(0, &[MetricType::PageFaults], &[], true).unwrap();
+    if collector.groups.is_empty() {
+        // Counters are not available with perf_event_paranoid > 2
+        return;
     }
 
-    /// Read all counter values and return as PoopMetrics
-    pub fn read(&self) -> io::Result<PoopMetrics> {
-        Ok(PoopMetrics {
-            cpu_cycles: self.cpu_cycles.as_ref().and_then(|c| c.read_value().ok()),
-            instructions: self.instructions.as_ref().and_then(|c| c.read_value().ok()),
-            cache_references: self
-                .cache_references
-                .as_ref()
-                .and_then(|c| c.read_value().ok()),
-            cache_misses: self.cache_misses.as_ref().and_then(|c| c.read_value().ok()),
-            branches: self.branches.as_ref().and_then(|c| c.read_value().ok()),
-            branch_misses: self
-                .branch_misses
-                .as_ref()
-                .and_then(|c| c.read_value().ok()),
-            page_faults: self.page_faults.as_ref().and_then(|c| c.read_value().ok()),
-        })
+    collector.enable().unwrap();
+    let memory = vec![1u8; 16 << 20];
+    assert_eq!(memory.iter().map(|&b| b as u64).sum::<u64>(), 16 << 20);
+    collector.disable().unwrap();
+
+    assert!(collector.read().unwrap().page_faults.unwrap() > 0);
+}
+
+#[test]
+fn test_hw_cache_config() {
+    let attr = metric_attr(MetricType::L1DcacheLoadMisses).unwrap();
+    assert_eq!((attr.type_, attr.config), (3, 0x10000));
+
+    let attr = metric_attr(MetricType::LlcLoads).unwrap();
+    assert_eq!((attr.type_, attr.config), (3, 0x2));
+
+    let attr = metric_attr(MetricType::ItlbLoadMisses).unwrap();
+    assert_eq!((attr.type_, attr.config), (3, 0x10004));
+}
+
+#[test]
+fn test_unavailable_counters_are_reported() {
+    // There is no process with this ID
+    let collector =
+        PerfEventsCollector::new(i32::MAX, &[MetricType::PageFaults], &[], true).unwrap();
+    assert!(collector.groups.is_empty());
+
+    let metrics = collector.read().unwrap();
+    assert!(!metrics.has_data());
+    assert!(metrics.unavailable.contains_key("page-faults"));
+}
+
+#[test]
+fn test_is_permission_error() {
+    assert!(is_permission_error(&io::Error::from_raw_os_error(
+        libc::EACCES
+    )));
+    assert!(is_permission_error(&io::Error::from_raw_os_error(
+        libc::EPERM
+    )));
+    assert!(!is_permission_error(&io::Error::from_raw_os_error(
+        libc::ENOENT
+    )));
+    assert!(!is_permission_error(&io::Error::other(
+        "tracefs is not mounted"
+    )));
+}
+
+#[test]
+fn test_tracepoint_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let mount_point = dir.path().to_str().unwrap();
+    let event_dir = dir.path().join("events/raw_syscalls/sys_enter");
+    fs::create_dir_all(&event_dir).unwrap();
+
+    // The tracepoint does not exist
+    let missing = dir.path().join("missing");
+    let error = tracepoint_id_in(&[missing.to_str().unwrap()], "raw_syscalls/sys_enter");
+    assert_eq!(error.unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    fs::write(event_dir.join("id"), "not a number\n").unwrap();
+    let error = tracepoint_id_in(&[mount_point], "raw_syscalls/sys_enter");
+    assert_eq!(error.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    fs::write(event_dir.join("id"), "342\n").unwrap();
+    assert_eq!(
+        tracepoint_id_in(
+            &[missing.to_str().unwrap(), mount_point],
+            "raw_syscalls/sys_enter"
+        )
+        .unwrap(),
+        342
+    );
+}
+
+#[test]
+fn test_unavailable_tracepoint_is_reported() {
+    let collector = PerfEventsCollector::new(0, &[MetricType::Syscalls], &[], true).unwrap();
+    if tracepoint_id("raw_syscalls/sys_enter").is_ok() {
+        // tracefs is accessible, so the counter is either opened or fails for another reason
+        return;
     }
+
+    let metrics = collector.read().unwrap();
+    assert_eq!(metrics.syscalls, None);
+    assert!(metrics.unavailable.contains_key("syscalls"));
+}
+
+#[test]
+fn test_stall_metrics_from_the_command_line() {
+    use crate::cli::get_cli_arguments;
+    use crate::options::Options;
+
+    let matches = get_cli_arguments(vec![
+        "hyperfine",
+        "--metric",
+        "frontend-stalls",
+        "--metric",
+        "stalled-cycles-backend",
+        "echo",
+    ]);
+    let options = Options::from_cli_arguments(&matches).unwrap();
+
+    let configs: Vec<_> = options
+        .metrics_to_collect
+        .iter()
+        .map(|&metric| {
+            let attr = metric_attr(metric).unwrap();
+            (attr.type_, attr.config)
+        })
+        .collect();
+    assert_eq!(configs, [(0, 7), (0, 8)]);
 }
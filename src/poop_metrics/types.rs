@@ -1,7 +1,12 @@
-use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::units::Second;
 
 /// poop performance metrics collected during benchmark execution
-#[derive(Debug, Default, Clone, Copy, Serialize, PartialEq)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PoopMetrics {
     /// CPU cycles consumed
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -27,9 +32,144 @@ pub struct PoopMetrics {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub branch_misses: Option<u64>,
 
+    /// CPU cycles in which the frontend did not deliver instructions (e.g. i-cache misses)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stalled_cycles_frontend: Option<u64>,
+
+    /// CPU cycles in which the backend could not execute instructions (e.g. waiting for memory)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stalled_cycles_backend: Option<u64>,
+
+    /// CPU cycles at a constant reference frequency, independent of frequency scaling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ref_cycles: Option<u64>,
+
+    /// Bus cycles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bus_cycles: Option<u64>,
+
+    /// L1 data cache loads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_dcache_loads: Option<u64>,
+
+    /// L1 data cache load misses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_dcache_load_misses: Option<u64>,
+
+    /// Last level cache loads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llc_loads: Option<u64>,
+
+    /// Last level cache load misses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llc_load_misses: Option<u64>,
+
+    /// Data TLB load misses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dtlb_load_misses: Option<u64>,
+
+    /// Instruction TLB load misses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub itlb_load_misses: Option<u64>,
+
     /// Page faults
     #[serde(skip_serializing_if = "Option::is_none")]
     pub page_faults: Option<u64>,
+
+    /// Context switches (voluntary and involuntary)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_switches: Option<u64>,
+
+    /// Migrations of the process to a different CPU
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_migrations: Option<u64>,
+
+    /// System calls (entries via the raw_syscalls:sys_enter tracepoint)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syscalls: Option<u64>,
+
+    /// Raw, CPU-specific events (see `RawEvent`), by their label
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub raw: BTreeMap<String, u64>,
+
+    /// Factor by which the counter values have been extrapolated, because the kernel had to
+    /// multiplex the counters and could not keep them active during the complete run
+    /// (`time_enabled / time_running`). `None` if no scaling was necessary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scaling_factor: Option<f64>,
+
+    /// Derived ratios, averaged over the individual runs. This is only set for metrics that
+    /// have been aggregated over several runs, where the ratio of the averaged counters
+    /// would differ from the average ratio.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mean_ratios: BTreeMap<DerivedRatio, f64>,
+
+    /// Counters that have been requested but could not be collected, by their name (see
+    /// `MetricType::cli_name` and `RawEvent::label`), with the reason. For aggregated metrics,
+    /// these are the counters that have not been collected in any of the runs.
+    #[serde(skip)]
+    pub unavailable: BTreeMap<String, String>,
+
+    /// Whether any counter could not be opened because access to the performance counters
+    /// is restricted (see `kernel.perf_event_paranoid`)
+    #[serde(skip)]
+    pub permission_denied: bool,
+}
+
+/// A ratio of two counters, e.g. the number of instructions per cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivedRatio {
+    InstructionsPerCycle,
+    CacheMissRate,
+    BranchMissRate,
+    FrontendBound,
+    BackendBound,
+    L1DcacheMissRate,
+    LlcMissRate,
+    FrequencyRatio,
+}
+
+impl DerivedRatio {
+    /// Get all derived ratios
+    pub fn all() -> Vec<Self> {
+        vec![
+            DerivedRatio::InstructionsPerCycle,
+            DerivedRatio::CacheMissRate,
+            DerivedRatio::BranchMissRate,
+            DerivedRatio::FrontendBound,
+            DerivedRatio::BackendBound,
+            DerivedRatio::L1DcacheMissRate,
+            DerivedRatio::LlcMissRate,
+            DerivedRatio::FrequencyRatio,
+        ]
+    }
+
+    /// The numerator and denominator of this ratio
+    fn counters(self) -> (MetricType, MetricType) {
+        match self {
+            DerivedRatio::InstructionsPerCycle => (MetricType::Instructions, MetricType::CpuCycles),
+            DerivedRatio::CacheMissRate => (MetricType::CacheMisses, MetricType::CacheReferences),
+            DerivedRatio::BranchMissRate => (MetricType::BranchMisses, MetricType::Branches),
+            DerivedRatio::FrontendBound => {
+                (MetricType::StalledCyclesFrontend, MetricType::CpuCycles)
+            }
+            DerivedRatio::BackendBound => (MetricType::StalledCyclesBackend, MetricType::CpuCycles),
+            DerivedRatio::L1DcacheMissRate => {
+                (MetricType::L1DcacheLoadMisses, MetricType::L1DcacheLoads)
+            }
+            DerivedRatio::LlcMissRate => (MetricType::LlcLoadMisses, MetricType::LlcLoads),
+            DerivedRatio::FrequencyRatio => (MetricType::CpuCycles, MetricType::RefCycles),
+        }
+    }
+
+    /// Factor by which the ratio is multiplied, e.g. 100 for percentages
+    fn scale(self) -> f64 {
+        match self {
+            DerivedRatio::InstructionsPerCycle | DerivedRatio::FrequencyRatio => 1.0,
+            _ => 100.0,
+        }
+    }
 }
 
 impl PoopMetrics {
@@ -46,36 +186,288 @@ impl PoopMetrics {
             || self.cache_misses.is_some()
             || self.branches.is_some()
             || self.branch_misses.is_some()
+            || self.stalled_cycles_frontend.is_some()
+            || self.stalled_cycles_backend.is_some()
+            || self.ref_cycles.is_some()
+            || self.bus_cycles.is_some()
+            || self.l1_dcache_loads.is_some()
+            || self.l1_dcache_load_misses.is_some()
+            || self.llc_loads.is_some()
+            || self.llc_load_misses.is_some()
+            || self.dtlb_load_misses.is_some()
+            || self.itlb_load_misses.is_some()
             || self.page_faults.is_some()
+            || self.context_switches.is_some()
+            || self.cpu_migrations.is_some()
+            || self.syscalls.is_some()
+            || !self.raw.is_empty()
     }
 
-    /// Calculate cache miss rate as a percentage
-    pub fn cache_miss_rate(&self) -> Option<f64> {
-        match (self.cache_references, self.cache_misses) {
-            (Some(refs), Some(misses)) if refs > 0 => Some((misses as f64 / refs as f64) * 100.0),
+    /// Calculate the given derived ratio. For aggregated metrics, this is the mean of the
+    /// ratios of the individual runs (see `mean_ratios`).
+    pub fn ratio(&self, ratio: DerivedRatio) -> Option<f64> {
+        if let Some(&mean) = self.mean_ratios.get(&ratio) {
+            return Some(mean);
+        }
+
+        let (numerator, denominator) = ratio.counters();
+        match (self.get(numerator), self.get(denominator)) {
+            (Some(numerator), Some(denominator)) if denominator > 0 => {
+                Some(numerator as f64 / denominator as f64 * ratio.scale())
+            }
             _ => None,
         }
     }
 
+    /// Calculate cache miss rate as a percentage
+    pub fn cache_miss_rate(&self) -> Option<f64> {
+        self.ratio(DerivedRatio::CacheMissRate)
+    }
+
     /// Calculate branch miss rate as a percentage
     pub fn branch_miss_rate(&self) -> Option<f64> {
-        match (self.branches, self.branch_misses) {
-            (Some(branches), Some(misses)) if branches > 0 => {
-                Some((misses as f64 / branches as f64) * 100.0)
-            }
-            _ => None,
-        }
+        self.ratio(DerivedRatio::BranchMissRate)
+    }
+
+    /// Percentage of CPU cycles in which the frontend stalled
+    pub fn frontend_bound(&self) -> Option<f64> {
+        self.ratio(DerivedRatio::FrontendBound)
+    }
+
+    /// Percentage of CPU cycles in which the backend stalled
+    pub fn backend_bound(&self) -> Option<f64> {
+        self.ratio(DerivedRatio::BackendBound)
+    }
+
+    /// Calculate the L1 data cache load miss rate as a percentage
+    pub fn l1_dcache_miss_rate(&self) -> Option<f64> {
+        self.ratio(DerivedRatio::L1DcacheMissRate)
+    }
+
+    /// Calculate the last level cache load miss rate as a percentage
+    pub fn llc_miss_rate(&self) -> Option<f64> {
+        self.ratio(DerivedRatio::LlcMissRate)
     }
 
     /// Calculate instructions per cycle (IPC)
     pub fn instructions_per_cycle(&self) -> Option<f64> {
-        match (self.instructions, self.cpu_cycles) {
-            (Some(inst), Some(cycles)) if cycles > 0 => Some(inst as f64 / cycles as f64),
+        self.ratio(DerivedRatio::InstructionsPerCycle)
+    }
+
+    /// Ratio of the CPU cycles to the reference cycles, i.e. the mean CPU frequency relative
+    /// to the reference (base) frequency. Values below one indicate that the CPU has been
+    /// throttled, values above one that it has been boosted.
+    pub fn frequency_ratio(&self) -> Option<f64> {
+        self.ratio(DerivedRatio::FrequencyRatio)
+    }
+
+    /// Get the value of the given counter, if it has been collected
+    pub fn get(&self, metric: MetricType) -> Option<u64> {
+        match metric {
+            MetricType::CpuCycles => self.cpu_cycles,
+            MetricType::Instructions => self.instructions,
+            MetricType::CacheReferences => self.cache_references,
+            MetricType::CacheMisses => self.cache_misses,
+            MetricType::Branches => self.branches,
+            MetricType::BranchMisses => self.branch_misses,
+            MetricType::StalledCyclesFrontend => self.stalled_cycles_frontend,
+            MetricType::StalledCyclesBackend => self.stalled_cycles_backend,
+            MetricType::RefCycles => self.ref_cycles,
+            MetricType::BusCycles => self.bus_cycles,
+            MetricType::L1DcacheLoads => self.l1_dcache_loads,
+            MetricType::L1DcacheLoadMisses => self.l1_dcache_load_misses,
+            MetricType::LlcLoads => self.llc_loads,
+            MetricType::LlcLoadMisses => self.llc_load_misses,
+            MetricType::DtlbLoadMisses => self.dtlb_load_misses,
+            MetricType::ItlbLoadMisses => self.itlb_load_misses,
+            MetricType::PageFaults => self.page_faults,
+            MetricType::ContextSwitches => self.context_switches,
+            MetricType::CpuMigrations => self.cpu_migrations,
+            MetricType::Syscalls => self.syscalls,
+        }
+    }
+
+    /// Set the value of the given counter
+    pub fn set(&mut self, metric: MetricType, value: u64) {
+        let field = match metric {
+            MetricType::CpuCycles => &mut self.cpu_cycles,
+            MetricType::Instructions => &mut self.instructions,
+            MetricType::CacheReferences => &mut self.cache_references,
+            MetricType::CacheMisses => &mut self.cache_misses,
+            MetricType::Branches => &mut self.branches,
+            MetricType::BranchMisses => &mut self.branch_misses,
+            MetricType::StalledCyclesFrontend => &mut self.stalled_cycles_frontend,
+            MetricType::StalledCyclesBackend => &mut self.stalled_cycles_backend,
+            MetricType::RefCycles => &mut self.ref_cycles,
+            MetricType::BusCycles => &mut self.bus_cycles,
+            MetricType::L1DcacheLoads => &mut self.l1_dcache_loads,
+            MetricType::L1DcacheLoadMisses => &mut self.l1_dcache_load_misses,
+            MetricType::LlcLoads => &mut self.llc_loads,
+            MetricType::LlcLoadMisses => &mut self.llc_load_misses,
+            MetricType::DtlbLoadMisses => &mut self.dtlb_load_misses,
+            MetricType::ItlbLoadMisses => &mut self.itlb_load_misses,
+            MetricType::PageFaults => &mut self.page_faults,
+            MetricType::ContextSwitches => &mut self.context_switches,
+            MetricType::CpuMigrations => &mut self.cpu_migrations,
+            MetricType::Syscalls => &mut self.syscalls,
+        };
+        *field = Some(value);
+    }
+
+    /// Normalize all collected counters by the given (mean) run time. This allows to compare
+    /// commands with different run times, e.g. in terms of cache misses per second. Returns
+    /// `None` if the time is not positive or if no counter has been collected.
+    pub fn per_second(&self, time: Second) -> Option<PoopMetricRates> {
+        if time <= 0.0 || !self.has_data() {
+            return None;
+        }
+
+        let rate = |metric| self.get(metric).map(|value| value as f64 / time);
+        Some(PoopMetricRates {
+            cpu_cycles: rate(MetricType::CpuCycles),
+            instructions: rate(MetricType::Instructions),
+            cache_references: rate(MetricType::CacheReferences),
+            cache_misses: rate(MetricType::CacheMisses),
+            branches: rate(MetricType::Branches),
+            branch_misses: rate(MetricType::BranchMisses),
+            stalled_cycles_frontend: rate(MetricType::StalledCyclesFrontend),
+            stalled_cycles_backend: rate(MetricType::StalledCyclesBackend),
+            ref_cycles: rate(MetricType::RefCycles),
+            bus_cycles: rate(MetricType::BusCycles),
+            l1_dcache_loads: rate(MetricType::L1DcacheLoads),
+            l1_dcache_load_misses: rate(MetricType::L1DcacheLoadMisses),
+            llc_loads: rate(MetricType::LlcLoads),
+            llc_load_misses: rate(MetricType::LlcLoadMisses),
+            dtlb_load_misses: rate(MetricType::DtlbLoadMisses),
+            itlb_load_misses: rate(MetricType::ItlbLoadMisses),
+            page_faults: rate(MetricType::PageFaults),
+            context_switches: rate(MetricType::ContextSwitches),
+            cpu_migrations: rate(MetricType::CpuMigrations),
+            syscalls: rate(MetricType::Syscalls),
+            raw: self
+                .raw
+                .iter()
+                .map(|(label, &value)| (label.clone(), value as f64 / time))
+                .collect(),
+        })
+    }
+
+    /// Estimate the run time (in seconds) on a CPU that runs at a fixed frequency of
+    /// `frequency_ghz`, purely based on the number of CPU cycles. This assumes that the cycle
+    /// count does not depend on the frequency, which ignores that memory stalls take a
+    /// different number of cycles at different frequencies. Time spent waiting for I/O or
+    /// other processes is not included at all.
+    pub fn estimated_time_at(&self, frequency_ghz: f64) -> Option<f64> {
+        match self.cpu_cycles {
+            Some(cycles) if frequency_ghz > 0.0 => Some(cycles as f64 / (frequency_ghz * 1e9)),
             _ => None,
         }
     }
 }
 
+/// poop performance metrics, normalized per second of run time (see `PoopMetrics::per_second`).
+/// This is also used for the standard deviation of each metric across runs.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PoopMetricRates {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_cycles: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_references: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_misses: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branches: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_misses: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stalled_cycles_frontend: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stalled_cycles_backend: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ref_cycles: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bus_cycles: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_dcache_loads: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_dcache_load_misses: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llc_loads: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llc_load_misses: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dtlb_load_misses: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub itlb_load_misses: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_faults: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_switches: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_migrations: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syscalls: Option<f64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub raw: BTreeMap<String, f64>,
+}
+
+impl PoopMetricRates {
+    /// Get the value of the given metric
+    pub fn get(&self, metric: MetricType) -> Option<f64> {
+        match metric {
+            MetricType::CpuCycles => self.cpu_cycles,
+            MetricType::Instructions => self.instructions,
+            MetricType::CacheReferences => self.cache_references,
+            MetricType::CacheMisses => self.cache_misses,
+            MetricType::Branches => self.branches,
+            MetricType::BranchMisses => self.branch_misses,
+            MetricType::StalledCyclesFrontend => self.stalled_cycles_frontend,
+            MetricType::StalledCyclesBackend => self.stalled_cycles_backend,
+            MetricType::RefCycles => self.ref_cycles,
+            MetricType::BusCycles => self.bus_cycles,
+            MetricType::L1DcacheLoads => self.l1_dcache_loads,
+            MetricType::L1DcacheLoadMisses => self.l1_dcache_load_misses,
+            MetricType::LlcLoads => self.llc_loads,
+            MetricType::LlcLoadMisses => self.llc_load_misses,
+            MetricType::DtlbLoadMisses => self.dtlb_load_misses,
+            MetricType::ItlbLoadMisses => self.itlb_load_misses,
+            MetricType::PageFaults => self.page_faults,
+            MetricType::ContextSwitches => self.context_switches,
+            MetricType::CpuMigrations => self.cpu_migrations,
+            MetricType::Syscalls => self.syscalls,
+        }
+    }
+
+    /// Set the value of the given metric
+    pub fn set(&mut self, metric: MetricType, value: f64) {
+        let field = match metric {
+            MetricType::CpuCycles => &mut self.cpu_cycles,
+            MetricType::Instructions => &mut self.instructions,
+            MetricType::CacheReferences => &mut self.cache_references,
+            MetricType::CacheMisses => &mut self.cache_misses,
+            MetricType::Branches => &mut self.branches,
+            MetricType::BranchMisses => &mut self.branch_misses,
+            MetricType::StalledCyclesFrontend => &mut self.stalled_cycles_frontend,
+            MetricType::StalledCyclesBackend => &mut self.stalled_cycles_backend,
+            MetricType::RefCycles => &mut self.ref_cycles,
+            MetricType::BusCycles => &mut self.bus_cycles,
+            MetricType::L1DcacheLoads => &mut self.l1_dcache_loads,
+            MetricType::L1DcacheLoadMisses => &mut self.l1_dcache_load_misses,
+            MetricType::LlcLoads => &mut self.llc_loads,
+            MetricType::LlcLoadMisses => &mut self.llc_load_misses,
+            MetricType::DtlbLoadMisses => &mut self.dtlb_load_misses,
+            MetricType::ItlbLoadMisses => &mut self.itlb_load_misses,
+            MetricType::PageFaults => &mut self.page_faults,
+            MetricType::ContextSwitches => &mut self.context_switches,
+            MetricType::CpuMigrations => &mut self.cpu_migrations,
+            MetricType::Syscalls => &mut self.syscalls,
+        };
+        *field = Some(value);
+    }
+}
+
 /// Types of poop metrics that can be collected
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MetricType {
@@ -85,22 +477,86 @@ pub enum MetricType {
     CacheMisses,
     Branches,
     BranchMisses,
+    StalledCyclesFrontend,
+    StalledCyclesBackend,
+    RefCycles,
+    BusCycles,
+    L1DcacheLoads,
+    L1DcacheLoadMisses,
+    LlcLoads,
+    LlcLoadMisses,
+    DtlbLoadMisses,
+    ItlbLoadMisses,
     PageFaults,
+    ContextSwitches,
+    CpuMigrations,
+    Syscalls,
 }
 
-impl MetricType {
-    /// Parse a metric type from a string
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s.to_lowercase().as_str() {
-            "cpu-cycles" | "cycles" => Some(MetricType::CpuCycles),
-            "instructions" => Some(MetricType::Instructions),
-            "cache-references" | "cache-refs" => Some(MetricType::CacheReferences),
-            "cache-misses" => Some(MetricType::CacheMisses),
-            "branches" => Some(MetricType::Branches),
-            "branch-misses" => Some(MetricType::BranchMisses),
-            "page-faults" | "faults" => Some(MetricType::PageFaults),
-            _ => None,
+impl FromStr for MetricType {
+    type Err = String;
+
+    /// Parse a metric type from one of its (case-insensitive) names. The error message
+    /// suggests the closest known name and lists all accepted names.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        if let Some(metric) = Self::all()
+            .into_iter()
+            .find(|metric| metric.cli_names().contains(&name.as_str()))
+        {
+            return Ok(metric);
+        }
+
+        let all_names: Vec<&str> = Self::all()
+            .iter()
+            .flat_map(|metric| metric.cli_names().iter().copied())
+            .collect();
+        let suggestion = all_names
+            .iter()
+            .map(|candidate| (levenshtein_distance(&name, candidate), candidate))
+            .min()
+            .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE);
+
+        let mut message = format!("unknown metric '{s}'.");
+        if let Some((_, candidate)) = suggestion {
+            message.push_str(&format!(" Did you mean '{candidate}'?"));
+        }
+        message.push_str(&format!(" Accepted names: {}", all_names.join(", ")));
+        Err(message)
+    }
+}
+
+/// Maximum edit distance for which a metric name is suggested for a misspelled one
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+/// Number of single-character insertions, deletions or substitutions needed to turn `a`
+/// into `b`
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
         }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+impl MetricType {
+    /// All accepted spellings of this metric type on the command line
+    pub fn cli_names(&self) -> &'static [&'static str] {
+        const CLI_NAMES: &[&[&str]] = include!("cli_names.rs");
+        CLI_NAMES[*self as usize]
+    }
+
+    /// The canonical name of this metric type on the command line
+    pub fn cli_name(&self) -> &'static str {
+        self.cli_names()[0]
     }
 
     /// Get the display name for this metric type
@@ -112,7 +568,46 @@ impl MetricType {
             MetricType::CacheMisses => "Cache Misses",
             MetricType::Branches => "Branches",
             MetricType::BranchMisses => "Branch Misses",
+            MetricType::StalledCyclesFrontend => "Stalled Cycles Frontend",
+            MetricType::StalledCyclesBackend => "Stalled Cycles Backend",
+            MetricType::RefCycles => "Reference Cycles",
+            MetricType::BusCycles => "Bus Cycles",
+            MetricType::L1DcacheLoads => "L1 Dcache Loads",
+            MetricType::L1DcacheLoadMisses => "L1 Dcache Load Misses",
+            MetricType::LlcLoads => "LLC Loads",
+            MetricType::LlcLoadMisses => "LLC Load Misses",
+            MetricType::DtlbLoadMisses => "dTLB Load Misses",
+            MetricType::ItlbLoadMisses => "iTLB Load Misses",
             MetricType::PageFaults => "Page Faults",
+            MetricType::ContextSwitches => "Context Switches",
+            MetricType::CpuMigrations => "CPU Migrations",
+            MetricType::Syscalls => "Syscalls",
+        }
+    }
+
+    /// Label for the normalized rate of this metric in the console output
+    pub fn rate_label(&self) -> &'static str {
+        match self {
+            MetricType::CpuCycles => "CPU cycles/s",
+            MetricType::Instructions => "Instructions/s",
+            MetricType::CacheReferences => "Cache references/s",
+            MetricType::CacheMisses => "Cache misses/s",
+            MetricType::Branches => "Branch instructions/s",
+            MetricType::BranchMisses => "Branch misses/s",
+            MetricType::StalledCyclesFrontend => "Frontend stalls/s",
+            MetricType::StalledCyclesBackend => "Backend stalls/s",
+            MetricType::RefCycles => "Reference cycles/s",
+            MetricType::BusCycles => "Bus cycles/s",
+            MetricType::L1DcacheLoads => "L1 dcache loads/s",
+            MetricType::L1DcacheLoadMisses => "L1 dcache misses/s",
+            MetricType::LlcLoads => "LLC loads/s",
+            MetricType::LlcLoadMisses => "LLC misses/s",
+            MetricType::DtlbLoadMisses => "dTLB misses/s",
+            MetricType::ItlbLoadMisses => "iTLB misses/s",
+            MetricType::PageFaults => "Page faults/s",
+            MetricType::ContextSwitches => "Context switches/s",
+            MetricType::CpuMigrations => "CPU migrations/s",
+            MetricType::Syscalls => "Syscalls/s",
         }
     }
 
@@ -125,7 +620,274 @@ impl MetricType {
             MetricType::CacheMisses,
             MetricType::Branches,
             MetricType::BranchMisses,
+            MetricType::StalledCyclesFrontend,
+            MetricType::StalledCyclesBackend,
+            MetricType::RefCycles,
+            MetricType::BusCycles,
+            MetricType::L1DcacheLoads,
+            MetricType::L1DcacheLoadMisses,
+            MetricType::LlcLoads,
+            MetricType::LlcLoadMisses,
+            MetricType::DtlbLoadMisses,
+            MetricType::ItlbLoadMisses,
             MetricType::PageFaults,
+            MetricType::ContextSwitches,
+            MetricType::CpuMigrations,
+            MetricType::Syscalls,
         ]
     }
 }
+
+/// A raw, CPU-specific perf event (`PERF_TYPE_RAW`), e.g. an uncore or vendor-specific event
+/// that is not covered by `MetricType`. On the command line, it is given as `r<hex>` (like
+/// with `perf stat`), where the hex code is the event selector and umask in the format expected
+/// by the CPU (see the vendor's performance monitoring documentation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawEvent {
+    pub config: u64,
+}
+
+impl RawEvent {
+    /// Parse a raw event of the form `r<hex>`, e.g. `r003c`
+    pub fn from_str(s: &str) -> Option<Self> {
+        let hex = s.strip_prefix('r')?;
+        if hex.is_empty() || hex.len() > 16 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        u64::from_str_radix(hex, 16)
+            .ok()
+            .map(|config| Self { config })
+    }
+
+    /// The label of this event in the output, e.g. "r003c"
+    pub fn label(&self) -> String {
+        format!("r{:04x}", self.config)
+    }
+}
+
+/// A (derived) metric that can be compared across benchmarks in the summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonMetric {
+    /// Instructions per cycle
+    Ipc,
+    CacheMissRate,
+    BranchMissRate,
+    /// A raw counter value
+    Counter(MetricType),
+}
+
+impl ComparisonMetric {
+    /// Parse a comparison metric from a string. Apart from the derived rates, all
+    /// spellings of `MetricType` are accepted.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "ipc" | "instructions-per-cycle" => Some(ComparisonMetric::Ipc),
+            "cache-miss-rate" => Some(ComparisonMetric::CacheMissRate),
+            "branch-miss-rate" => Some(ComparisonMetric::BranchMissRate),
+            other => other.parse().ok().map(ComparisonMetric::Counter),
+        }
+    }
+
+    /// Short label for the console output
+    pub fn label(&self) -> &'static str {
+        match self {
+            ComparisonMetric::Ipc => "IPC",
+            ComparisonMetric::CacheMissRate => "cache miss rate",
+            ComparisonMetric::BranchMissRate => "branch miss rate",
+            ComparisonMetric::Counter(MetricType::CpuCycles) => "CPU cycles",
+            ComparisonMetric::Counter(MetricType::Instructions) => "instructions",
+            ComparisonMetric::Counter(MetricType::CacheReferences) => "cache references",
+            ComparisonMetric::Counter(MetricType::CacheMisses) => "cache misses",
+            ComparisonMetric::Counter(MetricType::Branches) => "branches",
+            ComparisonMetric::Counter(MetricType::BranchMisses) => "branch misses",
+            ComparisonMetric::Counter(MetricType::StalledCyclesFrontend) => "frontend stalls",
+            ComparisonMetric::Counter(MetricType::StalledCyclesBackend) => "backend stalls",
+            ComparisonMetric::Counter(MetricType::RefCycles) => "reference cycles",
+            ComparisonMetric::Counter(MetricType::BusCycles) => "bus cycles",
+            ComparisonMetric::Counter(MetricType::L1DcacheLoads) => "L1 dcache loads",
+            ComparisonMetric::Counter(MetricType::L1DcacheLoadMisses) => "L1 dcache misses",
+            ComparisonMetric::Counter(MetricType::LlcLoads) => "LLC loads",
+            ComparisonMetric::Counter(MetricType::LlcLoadMisses) => "LLC misses",
+            ComparisonMetric::Counter(MetricType::DtlbLoadMisses) => "dTLB misses",
+            ComparisonMetric::Counter(MetricType::ItlbLoadMisses) => "iTLB misses",
+            ComparisonMetric::Counter(MetricType::PageFaults) => "page faults",
+            ComparisonMetric::Counter(MetricType::ContextSwitches) => "context switches",
+            ComparisonMetric::Counter(MetricType::CpuMigrations) => "CPU migrations",
+            ComparisonMetric::Counter(MetricType::Syscalls) => "syscalls",
+        }
+    }
+
+    /// Whether larger values of this metric are an improvement
+    pub fn higher_is_better(&self) -> bool {
+        matches!(self, ComparisonMetric::Ipc)
+    }
+
+    /// The counters that need to be collected in order to compute this metric
+    pub fn required_metrics(&self) -> Vec<MetricType> {
+        match self {
+            ComparisonMetric::Ipc => vec![MetricType::Instructions, MetricType::CpuCycles],
+            ComparisonMetric::CacheMissRate => {
+                vec![MetricType::CacheReferences, MetricType::CacheMisses]
+            }
+            ComparisonMetric::BranchMissRate => {
+                vec![MetricType::Branches, MetricType::BranchMisses]
+            }
+            ComparisonMetric::Counter(metric) => vec![*metric],
+        }
+    }
+
+    /// Extract the value of this metric, if it has been collected
+    pub fn value(&self, metrics: &PoopMetrics) -> Option<f64> {
+        match self {
+            ComparisonMetric::Ipc => metrics.instructions_per_cycle(),
+            ComparisonMetric::CacheMissRate => metrics.cache_miss_rate(),
+            ComparisonMetric::BranchMissRate => metrics.branch_miss_rate(),
+            ComparisonMetric::Counter(metric) => metrics.get(*metric).map(|v| v as f64),
+        }
+    }
+}
+
+#[test]
+fn test_estimated_time_at() {
+    let metrics = PoopMetrics {
+        cpu_cycles: Some(3_000_000),
+        ..Default::default()
+    };
+    assert_eq!(metrics.estimated_time_at(3.0), Some(0.001));
+    assert_eq!(metrics.estimated_time_at(0.0), None);
+    assert_eq!(PoopMetrics::new().estimated_time_at(3.0), None);
+}
+
+#[test]
+fn test_metric_type_names() {
+    // The names are looked up by the position of the metric type in `MetricType::all()`
+    for (index, metric) in MetricType::all().into_iter().enumerate() {
+        assert_eq!(metric as usize, index);
+    }
+    assert_eq!(
+        MetricType::Syscalls.cli_names(),
+        ["syscalls", "system-calls"]
+    );
+
+    for metric in MetricType::all() {
+        for name in metric.cli_names() {
+            assert_eq!(name.parse(), Ok(metric));
+        }
+    }
+
+    assert_eq!("Cycles".parse(), Ok(MetricType::CpuCycles));
+    assert!("unknown".parse::<MetricType>().is_err());
+}
+
+#[test]
+fn test_metric_type_suggestions() {
+    let error = "cpu-cyles".parse::<MetricType>().unwrap_err();
+    assert!(error.starts_with("unknown metric 'cpu-cyles'. Did you mean 'cpu-cycles'?"));
+    assert!(error.contains("Accepted names: cpu-cycles, cycles, instructions,"));
+
+    let error = "Page-Fault".parse::<MetricType>().unwrap_err();
+    assert!(error.contains("Did you mean 'page-faults'?"));
+
+    let error = "bogus".parse::<MetricType>().unwrap_err();
+    assert!(!error.contains("Did you mean"));
+
+    assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+    assert_eq!(levenshtein_distance("", "abc"), 3);
+    assert_eq!(levenshtein_distance("same", "same"), 0);
+}
+
+#[test]
+fn test_per_second() {
+    let metrics = PoopMetrics {
+        instructions: Some(2_000_000),
+        cache_misses: Some(500),
+        ..Default::default()
+    };
+
+    let rates = metrics.per_second(0.5).unwrap();
+    assert_eq!(rates.instructions, Some(4_000_000.0));
+    assert_eq!(rates.cache_misses, Some(1000.0));
+    assert_eq!(rates.cpu_cycles, None);
+
+    assert_eq!(metrics.per_second(0.0), None);
+    assert_eq!(PoopMetrics::default().per_second(1.0), None);
+}
+
+#[test]
+fn test_stall_percentages() {
+    let metrics = PoopMetrics {
+        cpu_cycles: Some(1000),
+        stalled_cycles_frontend: Some(250),
+        stalled_cycles_backend: Some(100),
+        ..Default::default()
+    };
+    assert_eq!(metrics.frontend_bound(), Some(25.0));
+    assert_eq!(metrics.backend_bound(), Some(10.0));
+
+    let metrics = PoopMetrics {
+        stalled_cycles_frontend: Some(250),
+        ..Default::default()
+    };
+    assert_eq!(metrics.frontend_bound(), None);
+    assert_eq!(
+        "frontend-stalls".parse(),
+        Ok(MetricType::StalledCyclesFrontend)
+    );
+}
+
+#[test]
+fn test_raw_event() {
+    assert_eq!(RawEvent::from_str("r003c"), Some(RawEvent { config: 0x3c }));
+    assert_eq!(
+        RawEvent::from_str("r1A2b"),
+        Some(RawEvent { config: 0x1a2b })
+    );
+    assert_eq!(RawEvent::from_str("r3c").unwrap().label(), "r003c");
+    assert_eq!(RawEvent::from_str("r53010e").unwrap().label(), "r53010e");
+
+    assert_eq!(RawEvent::from_str("r"), None);
+    assert_eq!(RawEvent::from_str("r+3c"), None);
+    assert_eq!(RawEvent::from_str("rxyz"), None);
+    assert_eq!(RawEvent::from_str("003c"), None);
+    assert_eq!(RawEvent::from_str("r10000000000000000"), None);
+}
+
+#[test]
+fn test_cache_hierarchy_miss_rates() {
+    let metrics = PoopMetrics {
+        l1_dcache_loads: Some(2000),
+        l1_dcache_load_misses: Some(50),
+        llc_loads: Some(40),
+        llc_load_misses: Some(10),
+        ..Default::default()
+    };
+    assert_eq!(metrics.l1_dcache_miss_rate(), Some(2.5));
+    assert_eq!(metrics.llc_miss_rate(), Some(25.0));
+
+    let metrics = PoopMetrics {
+        llc_loads: Some(0),
+        llc_load_misses: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(metrics.l1_dcache_miss_rate(), None);
+    assert_eq!(metrics.llc_miss_rate(), None);
+}
+
+#[test]
+fn test_frequency_ratio() {
+    let metrics = PoopMetrics {
+        cpu_cycles: Some(3000),
+        ref_cycles: Some(2000),
+        ..Default::default()
+    };
+    assert_eq!(metrics.frequency_ratio(), Some(1.5));
+
+    let metrics = PoopMetrics {
+        cpu_cycles: Some(3000),
+        bus_cycles: Some(100),
+        ..Default::default()
+    };
+    assert_eq!(metrics.frequency_ratio(), None);
+    assert_eq!("ref-cycles".parse(), Ok(MetricType::RefCycles));
+    assert_eq!("bus-cycles".parse(), Ok(MetricType::BusCycles));
+}
@@ -1,4 +1,5 @@
 mod wall_clock_timer;
+mod watchdog;
 
 #[cfg(windows)]
 mod windows_timer;
@@ -16,12 +17,23 @@ use std::os::fd::AsFd;
 #[cfg(target_os = "windows")]
 use windows_sys::Win32::System::Threading::CREATE_SUSPENDED;
 
-use crate::poop_metrics::{create_collector, MetricType, PoopMetrics};
+use crate::options::CpuTimeAccounting;
+#[cfg(target_os = "linux")]
+use crate::poop_metrics::{create_collector, PerfEventsCollector};
+use crate::poop_metrics::{MetricType, PoopMetrics, RawEvent};
 use crate::util::units::Second;
 use wall_clock_timer::WallClockTimer;
+use watchdog::Watchdog;
 
-use std::io::Read;
+use std::collections::VecDeque;
+use std::fmt;
+#[cfg(target_os = "linux")]
+use std::io::{self, PipeWriter};
+use std::io::{PipeReader, Read};
 use std::process::{ChildStdout, Command, ExitStatus};
+#[cfg(target_os = "linux")]
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use anyhow::Result;
 
@@ -33,9 +45,6 @@ struct CPUTimes {
 
     /// Total amount of time spent executing in kernel mode
     pub system_usec: i64,
-
-    /// Maximum amount of memory used by the process, in bytes
-    pub memory_usage_byte: u64,
 }
 
 /// Used to indicate the result of running a command
@@ -49,12 +58,64 @@ pub struct TimerResult {
     pub status: ExitStatus,
     /// poop performance metrics (if collected)
     pub poop_metrics: Option<PoopMetrics>,
+    /// The tail of the output (if captured)
+    pub captured_output: Option<CapturedOutput>,
+    /// Whether the process has been killed because it exceeded the timeout
+    pub timed_out: bool,
+}
+
+const CHUNK_SIZE: usize = 64 << 10;
+
+/// The last bytes of the (combined stdout and stderr) output of a child process
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapturedOutput {
+    pub bytes: Vec<u8>,
+    /// Number of bytes that have been dropped because the capture limit was exceeded
+    pub bytes_truncated: u64,
+}
+
+impl fmt::Display for CapturedOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.bytes_truncated > 0 {
+            writeln!(
+                f,
+                "[... {} bytes truncated, showing the last {} bytes of output]",
+                self.bytes_truncated,
+                self.bytes.len()
+            )?;
+        }
+        write!(f, "{}", String::from_utf8_lossy(&self.bytes).trim_end())
+    }
+}
+
+/// Read the output of a child process until it is closed. At most `limit` bytes are kept
+/// in memory: older output is dropped, so only the tail of the output is available.
+fn capture(mut output: impl Read, limit: usize) -> CapturedOutput {
+    let mut tail = VecDeque::with_capacity(limit.min(CHUNK_SIZE));
+    let mut bytes_truncated = 0;
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    while let Ok(bytes) = output.read(&mut buf) {
+        if bytes == 0 {
+            break;
+        }
+
+        let chunk = &buf[bytes.saturating_sub(limit)..bytes];
+        let overflow = (tail.len() + chunk.len()).saturating_sub(limit);
+        tail.drain(..overflow);
+        tail.extend(chunk);
+
+        bytes_truncated += (bytes - chunk.len() + overflow) as u64;
+    }
+
+    CapturedOutput {
+        bytes: tail.into(),
+        bytes_truncated,
+    }
 }
 
 /// Discard the output of a child process.
 fn discard(output: ChildStdout) {
-    const CHUNK_SIZE: usize = 64 << 10;
-
     #[cfg(target_os = "linux")]
     {
         if let Ok(file) = File::create("/dev/null") {
@@ -82,14 +143,111 @@ fn discard(output: ChildStdout) {
     }
 }
 
-/// Execute the given command and return a timing summary
+/// Performance counters that are being attached to the child process of a command (see
+/// `attach_collector_before_exec`)
+#[cfg(target_os = "linux")]
+struct CollectorAttachment {
+    /// Our copy of the end of the pipe through which the child sends its process ID. It is
+    /// closed after spawning, such that the helper thread does not wait forever if the child
+    /// could not be created.
+    pid_writer: PipeWriter,
+    /// Our copy of the end of the pipe from which the child reads the signal to continue
+    go_reader: PipeReader,
+    thread: JoinHandle<Option<PerfEventsCollector>>,
+}
+
+#[cfg(target_os = "linux")]
+impl CollectorAttachment {
+    /// Wait for the helper thread after the command has been spawned
+    fn finish(self) -> Option<PerfEventsCollector> {
+        drop(self.pid_writer);
+        drop(self.go_reader);
+        self.thread.join().ok().flatten()
+    }
+}
+
+/// Attach the performance counters to the child process of `command` before it executes the
+/// command, such that the complete run is counted (including all descendants, if `inherit`
+/// is set). Since `spawn` only returns after the child has called `exec`, the counters are
+/// opened from a helper thread, while the child waits for them in a `pre_exec` hook.
+#[cfg(target_os = "linux")]
+fn attach_collector_before_exec(
+    command: &mut Command,
+    metrics_to_collect: Vec<MetricType>,
+    raw_events: Vec<RawEvent>,
+    inherit: bool,
+    debug_perf: bool,
+) -> io::Result<CollectorAttachment> {
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    // Both pipes are closed in the child when it calls `exec`
+    let (mut pid_reader, pid_writer) = io::pipe()?;
+    let (go_reader, mut go_writer) = io::pipe()?;
+
+    let pid_fd = pid_writer.as_raw_fd();
+    let go_fd = go_reader.as_raw_fd();
+    // SAFETY: Only async-signal-safe functions are called, and nothing is allocated
+    unsafe {
+        command.pre_exec(move || {
+            let pid = libc::getpid().to_ne_bytes();
+            libc::write(pid_fd, pid.as_ptr() as *const libc::c_void, pid.len());
+            // This returns as soon as the helper thread is done, even if that failed
+            let mut go = [0u8];
+            libc::read(go_fd, go.as_mut_ptr() as *mut libc::c_void, go.len());
+            Ok(())
+        })
+    };
+
+    let thread = thread::spawn(move || {
+        let mut pid = [0u8; 4];
+        pid_reader.read_exact(&mut pid).ok()?;
+        let pid = i32::from_ne_bytes(pid);
+
+        let collector = create_collector(pid, &metrics_to_collect, &raw_events, inherit);
+        let _ = go_writer.write_all(&[1]);
+
+        match collector {
+            Ok(collector) => Some(collector),
+            Err(e) => {
+                if debug_perf {
+                    eprintln!("[debug-perf] pid {pid}: could not create collector: {e}");
+                }
+                None
+            }
+        }
+    });
+
+    Ok(CollectorAttachment {
+        pid_writer,
+        go_reader,
+        thread,
+    })
+}
+
+/// Execute the given command and return a timing summary. If `output_capture` is given,
+/// the tail of the output is read from that pipe, bounded by the given number of bytes.
+/// If the command does not finish within `timeout`, it is killed along with all of its
+/// descendants.
+#[allow(clippy::too_many_arguments)]
 pub fn execute_and_measure(
     mut command: Command,
+    output_capture: Option<(PipeReader, usize)>,
+    timeout: Option<Duration>,
     collect_metrics: bool,
     metrics_to_collect: &[MetricType],
+    raw_events: &[RawEvent],
+    metrics_leader_only: bool,
+    debug_perf: bool,
+    cpu_time_accounting: CpuTimeAccounting,
 ) -> Result<TimerResult> {
     #[cfg(not(windows))]
-    let cpu_timer = self::unix_timer::CPUTimer::start();
+    let cpu_timer = self::unix_timer::CPUTimer::start(cpu_time_accounting);
+    // Only the time of the child process is available on Windows, which is ensured when
+    // the options are parsed
+    #[cfg(windows)]
+    debug_assert_eq!(cpu_time_accounting, CpuTimeAccounting::Children);
 
     #[cfg(windows)]
     {
@@ -99,26 +257,53 @@ pub fn execute_and_measure(
         command.creation_flags(CREATE_SUSPENDED);
     }
 
-    let wallclock_timer = WallClockTimer::start();
-    let mut child = command.spawn()?;
+    #[cfg(not(windows))]
+    if timeout.is_some() {
+        use std::os::unix::process::CommandExt;
+
+        // Start a new process group such that all descendants can be killed at once
+        command.process_group(0);
+    }
 
     // Initialize poop metrics collector if requested
     #[cfg(target_os = "linux")]
-    let metrics_collector = if collect_metrics {
-        let pid = child.id() as i32;
-        match create_collector(pid, metrics_to_collect) {
-            Ok(collector) => {
-                let _ = collector.enable();
-                Some(collector)
-            }
-            Err(_) => None,
-        }
+    let collector_attachment = if collect_metrics {
+        Some(attach_collector_before_exec(
+            &mut command,
+            metrics_to_collect.to_vec(),
+            raw_events.to_vec(),
+            !metrics_leader_only,
+            debug_perf,
+        )?)
     } else {
         None
     };
 
     #[cfg(not(target_os = "linux"))]
     let metrics_collector: Option<()> = None;
+    #[cfg(not(target_os = "linux"))]
+    let _ = (collect_metrics, metrics_to_collect, raw_events);
+    #[cfg(not(target_os = "linux"))]
+    let _ = (metrics_leader_only, debug_perf);
+
+    let watchdog = timeout.map(Watchdog::start);
+
+    let wallclock_timer = WallClockTimer::start();
+    let spawned = command.spawn();
+
+    // Close our copies of the write end of the capture pipe, if any
+    drop(command);
+
+    #[cfg(target_os = "linux")]
+    let metrics_collector = collector_attachment.and_then(CollectorAttachment::finish);
+    let mut child = spawned?;
+
+    #[cfg(target_os = "linux")]
+    if let Some(collector) = &metrics_collector {
+        // The counters have been started by `exec` already, unless the kernel does not
+        // support this
+        let _ = collector.enable();
+    }
 
     #[cfg(windows)]
     let cpu_timer = {
@@ -126,20 +311,44 @@ pub fn execute_and_measure(
         unsafe { self::windows_timer::CPUTimer::start_suspended_process(&child) }
     };
 
+    // The watchdog is stopped right after the child has been waited for. The ID of the
+    // process group can not be reused while any of the descendants are still running.
+    #[cfg(not(windows))]
+    let _signal_forwarding = watchdog.as_ref().map(|watchdog| {
+        watchdog.arm(watchdog::kill_process_group(child.id()));
+        watchdog::SignalForwarding::start(child.id())
+    });
+    #[cfg(windows)]
+    if let Some(watchdog) = &watchdog {
+        watchdog.arm(cpu_timer.terminator());
+    }
+
     if let Some(output) = child.stdout.take() {
         // Handle CommandOutputPolicy::Pipe
         discard(output);
     }
 
-    let status = child.wait()?;
+    let captured_output = output_capture.map(|(reader, limit)| capture(reader, limit));
+
+    #[cfg(not(windows))]
+    let (status, memory_usage_byte) = self::unix_timer::wait_for_child(&child)?;
+    #[cfg(windows)]
+    let (status, memory_usage_byte) = {
+        let status = child.wait()?;
+        (status, self::windows_timer::peak_working_set_size(&child))
+    };
 
     let time_real = wallclock_timer.stop();
-    let (time_user, time_system, memory_usage_byte) = cpu_timer.stop();
+    let timed_out = watchdog.is_some_and(Watchdog::stop);
+    let (time_user, time_system) = cpu_timer.stop();
 
     // Read poop metrics if we created a collector
     #[cfg(target_os = "linux")]
     let poop_metrics = if let Some(collector) = metrics_collector {
         let _ = collector.disable();
+        if debug_perf {
+            collector.dump_raw(child.id() as i32);
+        }
         collector.read().ok()
     } else {
         None
@@ -155,5 +364,190 @@ pub fn execute_and_measure(
         memory_usage_byte,
         status,
         poop_metrics,
+        captured_output,
+        timed_out,
     })
 }
+
+#[test]
+fn test_capture_keeps_everything_below_limit() {
+    let output = capture(&b"hello world"[..], 100);
+    assert_eq!(output.bytes, b"hello world");
+    assert_eq!(output.bytes_truncated, 0);
+    assert_eq!(output.to_string(), "hello world");
+}
+
+#[test]
+fn test_capture_keeps_tail_of_large_output() {
+    let data: Vec<u8> = (0..(3 * CHUNK_SIZE + 123))
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let output = capture(&data[..], 1000);
+    assert_eq!(output.bytes, &data[data.len() - 1000..]);
+    assert_eq!(output.bytes_truncated, (data.len() - 1000) as u64);
+
+    let output = capture(&data[..], 0);
+    assert!(output.bytes.is_empty());
+    assert_eq!(output.bytes_truncated, data.len() as u64);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_capture_large_output_of_child_process() {
+    let (reader, writer) = std::io::pipe().unwrap();
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("head -c 5000000 /dev/zero; echo done >&2")
+        .stdin(std::process::Stdio::null())
+        .stdout(writer.try_clone().unwrap())
+        .stderr(writer);
+
+    let result = execute_and_measure(
+        command,
+        Some((reader, 100)),
+        None,
+        false,
+        &[],
+        &[],
+        false,
+        false,
+        CpuTimeAccounting::Children,
+    )
+    .unwrap();
+    let output = result.captured_output.unwrap();
+
+    assert!(result.status.success());
+    assert_eq!(output.bytes.len(), 100);
+    assert!(output.bytes.ends_with(b"\0done\n"));
+    assert_eq!(output.bytes_truncated, 5000005 - 100);
+    assert!(output
+        .to_string()
+        .starts_with("[... 4999905 bytes truncated, showing the last 100 bytes of output]\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cpu_time_includes_descendants() {
+    // The CPU time is spent in a grandchild process of hyperfine
+    for accounting in [CpuTimeAccounting::Children, CpuTimeAccounting::IncludeSelf] {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("sh -c 'i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done'");
+
+        let result = execute_and_measure(
+            command,
+            None,
+            None,
+            false,
+            &[],
+            &[],
+            false,
+            false,
+            accounting,
+        )
+        .unwrap();
+
+        assert!(result.status.success());
+        assert!(
+            result.time_user + result.time_system > 0.5 * result.time_real,
+            "{:?}: user {} s, system {} s, real {} s",
+            accounting,
+            result.time_user,
+            result.time_system,
+            result.time_real
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_memory_usage_is_specific_to_each_child() {
+    let run = |script: &str| {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        execute_and_measure(
+            command,
+            None,
+            None,
+            false,
+            &[],
+            &[],
+            false,
+            false,
+            CpuTimeAccounting::Children,
+        )
+        .unwrap()
+        .memory_usage_byte
+    };
+
+    // The peak memory usage of a child includes the memory it inherited from the forked
+    // test process before calling exec, which can be large if other tests run in parallel.
+    // The shell therefore keeps 20 MB more than that in a variable.
+    let small = run("true");
+    let size = small + 20_000_000;
+    let large = run(&format!("x=$(head -c {} /dev/zero | tr '\\0' a)", size));
+    assert!(small > 0, "small: {}", small);
+    assert!(large > size, "small: {}, large: {}", small, large);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_memory_usage_on_windows() {
+    let mut command = Command::new("cmd.exe");
+    command.arg("/C").arg("exit 0");
+    let result = execute_and_measure(
+        command,
+        None,
+        None,
+        false,
+        &[],
+        &[],
+        false,
+        false,
+        CpuTimeAccounting::Children,
+    )
+    .unwrap();
+
+    assert!(result.status.success());
+    assert!(result.memory_usage_byte > 0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_metrics_of_leader_only_and_of_descendants() {
+    let page_faults = |leader_only| {
+        // The shell runs the first command in a child process
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("/bin/true; /bin/true");
+
+        let result = execute_and_measure(
+            command,
+            None,
+            None,
+            true,
+            &[MetricType::PageFaults],
+            &[],
+            leader_only,
+            false,
+            CpuTimeAccounting::Children,
+        )
+        .unwrap();
+        assert!(result.status.success());
+        result.poop_metrics.and_then(|m| m.page_faults)
+    };
+
+    // Counters are not available with perf_event_paranoid > 2
+    let Some(all) = page_faults(false) else {
+        return;
+    };
+    let leader = page_faults(true).unwrap();
+    assert!(leader > 0);
+    assert!(
+        all > leader,
+        "{} page faults in total, {} in the shell",
+        all,
+        leader
+    );
+}
@@ -1,8 +1,12 @@
 #![cfg(not(windows))]
 
 use std::convert::TryFrom;
+use std::io;
 use std::mem;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
 
+use crate::options::CpuTimeAccounting;
 use crate::timer::CPUTimes;
 use crate::util::units::Second;
 
@@ -17,53 +21,156 @@ pub struct CPUInterval {
 
 pub struct CPUTimer {
     start_cpu: CPUTimes,
+    accounting: CpuTimeAccounting,
 }
 
 impl CPUTimer {
-    pub fn start() -> Self {
+    pub fn start(accounting: CpuTimeAccounting) -> Self {
         CPUTimer {
-            start_cpu: get_cpu_times(),
+            start_cpu: get_cpu_times(accounting),
+            accounting,
         }
     }
 
-    pub fn stop(&self) -> (Second, Second, u64) {
-        let end_cpu = get_cpu_times();
+    pub fn stop(&self) -> (Second, Second) {
+        let end_cpu = get_cpu_times(self.accounting);
         let cpu_interval = cpu_time_interval(&self.start_cpu, &end_cpu);
-        (
-            cpu_interval.user,
-            cpu_interval.system,
-            end_cpu.memory_usage_byte,
+        (cpu_interval.user, cpu_interval.system)
+    }
+}
+
+/// Wait for the given child process to terminate. Returns its exit status and its maximum
+/// resident set size in bytes. Unlike the value for `RUSAGE_CHILDREN`, which is the maximum
+/// over all children that have ever been waited for, this is specific to the given child
+/// (and its own waited-for descendants). On macOS, the peak physical footprint of the child
+/// is taken into account as well, since the maximum resident set size is often not reliable.
+pub fn wait_for_child(child: &Child) -> io::Result<(ExitStatus, u64)> {
+    let pid = child.id() as libc::pid_t;
+
+    #[cfg(target_os = "macos")]
+    let peak_footprint = peak_footprint_byte(pid);
+
+    loop {
+        let mut status = 0;
+        // SAFETY: An all-zero rusage struct is valid
+        let mut usage: libc::rusage = unsafe { mem::zeroed() };
+
+        // SAFETY: The pointers are valid for the duration of the call
+        let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+        if ret == pid {
+            let memory_usage_byte = max_rss_byte(&usage);
+            #[cfg(target_os = "macos")]
+            let memory_usage_byte = std::cmp::max(memory_usage_byte, peak_footprint);
+            return Ok((ExitStatus::from_raw(status), memory_usage_byte));
+        }
+
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Wait for the given child process to terminate and return its peak physical footprint in
+/// bytes, as reported by `proc_pid_rusage`. The child is not reaped, such that it still has to
+/// be waited for afterwards. Zero is returned if the value can not be determined.
+#[cfg(target_os = "macos")]
+fn peak_footprint_byte(pid: libc::pid_t) -> u64 {
+    loop {
+        // SAFETY: An all-zero siginfo_t struct is valid
+        let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+
+        // SAFETY: The pointer is valid for the duration of the call
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if ret == 0 {
+            break;
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return 0;
+        }
+    }
+
+    // SAFETY: An all-zero rusage_info_v4 struct is valid
+    let mut usage: libc::rusage_info_v4 = unsafe { mem::zeroed() };
+
+    // SAFETY: The buffer is large enough for the requested flavor
+    let ret = unsafe {
+        libc::proc_pid_rusage(
+            pid,
+            libc::RUSAGE_INFO_V4,
+            (&mut usage as *mut libc::rusage_info_v4).cast(),
         )
+    };
+    if ret == 0 {
+        usage.ri_lifetime_max_phys_footprint
+    } else {
+        0
     }
 }
 
-/// Read CPU execution times ('user' and 'system')
-fn get_cpu_times() -> CPUTimes {
-    use libc::{getrusage, rusage, RUSAGE_CHILDREN};
+/// Unit of `ru_maxrss` in bytes: Darwin flavors report the value in bytes, Linux and *BSD
+/// in KibiBytes
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const MAX_RSS_UNIT_BYTE: u64 = 1;
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+const MAX_RSS_UNIT_BYTE: u64 = 1024;
+
+/// The maximum resident set size in bytes
+fn max_rss_byte(usage: &libc::rusage) -> u64 {
+    #[allow(clippy::useless_conversion)]
+    u64::try_from(usage.ru_maxrss)
+        .unwrap_or(0)
+        .saturating_mul(MAX_RSS_UNIT_BYTE)
+}
+
+/// Read CPU execution times ('user' and 'system').
+///
+/// `RUSAGE_CHILDREN` covers all child processes that have terminated and been waited for,
+/// including their own (waited-for) descendants. For a command that is run through a shell,
+/// this includes both the shell and the command. Processes that are still running (e.g.
+/// background processes that outlive the command) are not included.
+fn get_cpu_times(accounting: CpuTimeAccounting) -> CPUTimes {
+    use libc::{RUSAGE_CHILDREN, RUSAGE_SELF};
+
+    let children = read_rusage(RUSAGE_CHILDREN);
+
+    match accounting {
+        CpuTimeAccounting::Children => children,
+        CpuTimeAccounting::IncludeSelf => {
+            let own = read_rusage(RUSAGE_SELF);
+            CPUTimes {
+                user_usec: children.user_usec + own.user_usec,
+                system_usec: children.system_usec + own.system_usec,
+            }
+        }
+    }
+}
+
+fn read_rusage(who: libc::c_int) -> CPUTimes {
+    use libc::{getrusage, rusage};
 
     let result: rusage = unsafe {
         let mut buf = mem::zeroed();
-        let success = getrusage(RUSAGE_CHILDREN, &mut buf);
+        let success = getrusage(who, &mut buf);
         assert_eq!(0, success);
         buf
     };
 
     const MICROSEC_PER_SEC: i64 = 1000 * 1000;
 
-    // Linux and *BSD return the value in KibiBytes, Darwin flavors in bytes
-    let max_rss_byte = if cfg!(target_os = "macos") || cfg!(target_os = "ios") {
-        result.ru_maxrss
-    } else {
-        result.ru_maxrss * 1024
-    };
-
     #[allow(clippy::useless_conversion)]
     CPUTimes {
         user_usec: i64::from(result.ru_utime.tv_sec) * MICROSEC_PER_SEC
             + i64::from(result.ru_utime.tv_usec),
         system_usec: i64::from(result.ru_stime.tv_sec) * MICROSEC_PER_SEC
             + i64::from(result.ru_stime.tv_usec),
-        memory_usage_byte: u64::try_from(max_rss_byte).unwrap_or(0),
     }
 }
 
@@ -83,13 +190,11 @@ fn test_cpu_time_interval() {
     let t_a = CPUTimes {
         user_usec: 12345,
         system_usec: 54321,
-        memory_usage_byte: 0,
     };
 
     let t_b = CPUTimes {
         user_usec: 20000,
         system_usec: 70000,
-        memory_usage_byte: 0,
     };
 
     let t_zero = cpu_time_interval(&t_a, &t_a);
@@ -104,3 +209,35 @@ fn test_cpu_time_interval() {
     assert_relative_eq!(-0.007655, t_ba.user);
     assert_relative_eq!(-0.015679, t_ba.system);
 }
+
+#[cfg(target_os = "macos")]
+#[test]
+fn test_peak_footprint() {
+    // The shell keeps 20 MB in a variable
+    let size = 20_000_000;
+    let mut child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("x=$(head -c {} /dev/zero | tr '\\0' a)", size))
+        .spawn()
+        .unwrap();
+
+    let peak_footprint = peak_footprint_byte(child.id() as libc::pid_t);
+    assert!(child.wait().unwrap().success());
+    assert!(peak_footprint > size, "peak footprint: {}", peak_footprint);
+}
+
+#[test]
+fn test_max_rss_byte() {
+    // SAFETY: An all-zero rusage struct is valid
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+    usage.ru_maxrss = 2048;
+
+    if cfg!(any(target_os = "macos", target_os = "ios")) {
+        assert_eq!(max_rss_byte(&usage), 2048);
+    } else {
+        assert_eq!(max_rss_byte(&usage), 2 * 1024 * 1024);
+    }
+
+    usage.ru_maxrss = -1;
+    assert_eq!(max_rss_byte(&usage), 0);
+}
@@ -0,0 +1,159 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[cfg(not(windows))]
+use std::sync::atomic::{AtomicI32, Ordering};
+#[cfg(not(windows))]
+use std::sync::Once;
+
+type Kill = Box<dyn FnOnce() + Send>;
+
+/// The function that terminates the process, which is only known once the process has been
+/// spawned, and whether the timeout has already expired
+#[derive(Default)]
+struct Target {
+    kill: Option<Kill>,
+    expired: bool,
+}
+
+/// Terminates a child process (and all of its descendants) if it does not finish in time
+pub struct Watchdog {
+    cancel: mpsc::Sender<()>,
+    target: Arc<Mutex<Target>>,
+    thread: JoinHandle<bool>,
+}
+
+impl Watchdog {
+    /// Start the timeout in a background thread. This is done before the process is spawned,
+    /// such that starting the thread is not part of the measured time. The process is killed
+    /// once the timeout expires, unless the watchdog has been stopped before.
+    pub fn start(timeout: Duration) -> Self {
+        let (cancel, cancelled) = mpsc::channel();
+        let target = Arc::new(Mutex::new(Target::default()));
+        let thread_target = target.clone();
+        let thread = thread::spawn(move || match cancelled.recv_timeout(timeout) {
+            Err(RecvTimeoutError::Timeout) => {
+                let mut target = thread_target.lock().unwrap();
+                target.expired = true;
+                if let Some(kill) = target.kill.take() {
+                    kill();
+                }
+                true
+            }
+            _ => false,
+        });
+
+        Watchdog {
+            cancel,
+            target,
+            thread,
+        }
+    }
+
+    /// Set the function that kills the spawned process. It is called right away if the
+    /// timeout has already expired.
+    pub fn arm(&self, kill: impl FnOnce() + Send + 'static) {
+        let mut target = self.target.lock().unwrap();
+        if target.expired {
+            kill();
+        } else {
+            target.kill = Some(Box::new(kill));
+        }
+    }
+
+    /// Stop the watchdog and return whether the process has been killed
+    pub fn stop(self) -> bool {
+        let _ = self.cancel.send(());
+        self.thread.join().unwrap_or(false)
+    }
+}
+
+/// Return a function that kills the given process group with SIGKILL
+#[cfg(not(windows))]
+pub fn kill_process_group(process_group: u32) -> impl FnOnce() + Send + 'static {
+    move || {
+        // SAFETY: killpg has no memory safety requirements
+        unsafe { libc::killpg(process_group as libc::pid_t, libc::SIGKILL) };
+    }
+}
+
+/// Process group of the command that currently runs in its own process group (0 if none)
+#[cfg(not(windows))]
+static FORWARDED_PROCESS_GROUP: AtomicI32 = AtomicI32::new(0);
+
+/// Commands that run in their own process group do not receive the signals sent by the
+/// terminal (e.g. on Ctrl-C). While such a command is running, SIGINT and SIGTERM are
+/// forwarded to its process group, before hyperfine itself is terminated by them as usual.
+#[cfg(not(windows))]
+pub struct SignalForwarding;
+
+#[cfg(not(windows))]
+impl SignalForwarding {
+    pub fn start(process_group: u32) -> Self {
+        static INSTALL_HANDLERS: Once = Once::new();
+        INSTALL_HANDLERS.call_once(|| {
+            for signal in [libc::SIGINT, libc::SIGTERM] {
+                let handler = forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                // SAFETY: The handler only calls async-signal-safe functions
+                let previous = unsafe { libc::signal(signal, handler) };
+                if previous == libc::SIG_IGN {
+                    // Keep ignoring the signal, e.g. if hyperfine runs in the background
+                    // SAFETY: Restoring the previous disposition is always safe
+                    unsafe { libc::signal(signal, libc::SIG_IGN) };
+                }
+            }
+        });
+
+        FORWARDED_PROCESS_GROUP.store(process_group as i32, Ordering::SeqCst);
+        SignalForwarding
+    }
+}
+
+#[cfg(not(windows))]
+impl Drop for SignalForwarding {
+    fn drop(&mut self) {
+        FORWARDED_PROCESS_GROUP.store(0, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(windows))]
+extern "C" fn forward_signal(signal: libc::c_int) {
+    let process_group = FORWARDED_PROCESS_GROUP.load(Ordering::SeqCst);
+    // SAFETY: killpg, signal and raise are async-signal-safe
+    unsafe {
+        if process_group > 0 {
+            libc::killpg(process_group, signal);
+        }
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+#[test]
+fn test_watchdog_kills_after_timeout() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let run = |timeout: Duration, arm_delay: Duration| {
+        let killed = Arc::new(AtomicBool::new(false));
+        let flag = killed.clone();
+        let watchdog = Watchdog::start(timeout);
+        thread::sleep(arm_delay);
+        watchdog.arm(move || flag.store(true, Ordering::SeqCst));
+        thread::sleep(Duration::from_millis(100));
+        (watchdog.stop(), killed.load(Ordering::SeqCst))
+    };
+
+    assert_eq!(run(Duration::from_millis(10), Duration::ZERO), (true, true));
+    assert_eq!(
+        run(Duration::from_secs(100), Duration::ZERO),
+        (false, false)
+    );
+
+    // The process is killed as soon as it is known if the timeout expired before
+    assert_eq!(
+        run(Duration::from_millis(10), Duration::from_millis(50)),
+        (true, true)
+    );
+}
@@ -7,8 +7,9 @@ use windows_sys::Win32::{
     Foundation::{CloseHandle, HANDLE},
     System::JobObjects::{
         AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicAccountingInformation,
-        QueryInformationJobObject, JOBOBJECT_BASIC_ACCOUNTING_INFORMATION,
+        QueryInformationJobObject, TerminateJobObject, JOBOBJECT_BASIC_ACCOUNTING_INFORMATION,
     },
+    System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
 };
 
 #[cfg(feature = "windows_process_extensions_main_thread_handle")]
@@ -86,7 +87,18 @@ impl CPUTimer {
         Self { job_object }
     }
 
-    pub fn stop(&self) -> (Second, Second, u64) {
+    /// Return a function that terminates all processes in the job object. It must not be
+    /// called after the timer has been dropped.
+    pub fn terminator(&self) -> impl FnOnce() + Send + 'static {
+        // Handles are not `Send`, so we pass the raw value to the other thread
+        let job_object = self.job_object as usize;
+        move || {
+            // SAFETY: The job object handle is still valid (see above)
+            unsafe { TerminateJobObject(job_object as HANDLE, 1) };
+        }
+    }
+
+    pub fn stop(&self) -> (Second, Second) {
         let mut job_object_info =
             mem::MaybeUninit::<JOBOBJECT_BASIC_ACCOUNTING_INFORMATION>::uninit();
 
@@ -114,13 +126,38 @@ impl CPUTimer {
             // for all active processes associated with the job, as well as all terminated
             // processes no longer associated with the job, in 100-nanosecond ticks."
             let kernel: i64 = job_object_info.TotalKernelTime / HUNDRED_NS_PER_MS;
-            (user as f64 * 1e-6, kernel as f64 * 1e-6, 0)
+            (user as f64 * 1e-6, kernel as f64 * 1e-6)
         } else {
-            (0.0, 0.0, 0)
+            (0.0, 0.0)
         }
     }
 }
 
+/// The peak working set size of the given (terminated, but not yet dropped) child process in
+/// bytes, or zero if it can not be determined. Unlike the maximum resident set size on Unix,
+/// this does not include the descendants of the process.
+pub fn peak_working_set_size(child: &process::Child) -> u64 {
+    let mut counters = mem::MaybeUninit::<PROCESS_MEMORY_COUNTERS>::uninit();
+
+    // SAFETY: The process handle stays valid until the child is dropped, and the buffer has
+    // the given size
+    let res = unsafe {
+        GetProcessMemoryInfo(
+            child.as_raw_handle() as HANDLE,
+            counters.as_mut_ptr(),
+            mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+        )
+    };
+
+    if res != 0 {
+        // SAFETY: The counters got correctly initialized
+        let counters = unsafe { counters.assume_init() };
+        counters.PeakWorkingSetSize as u64
+    } else {
+        0
+    }
+}
+
 impl Drop for CPUTimer {
     fn drop(&mut self) {
         // SAFETY: A valid job object got created in `start_suspended_process`
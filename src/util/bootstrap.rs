@@ -0,0 +1,87 @@
+//! Bootstrap confidence intervals, which do not assume that the run times are normally
+//! distributed.
+//!
+//! The values are resampled (with replacement) many times, and the mean of each resample is
+//! computed. For a confidence level of e.g. 95%, the 2.5% and 97.5% percentiles of these means
+//! form the confidence interval of the mean (percentile method).
+
+use rand::distributions::Uniform;
+use rand::Rng;
+
+use crate::util::units::Scalar;
+
+/// Upper bound for the total number of values that are drawn for all resamples. For very
+/// many runs, the number of resamples is reduced accordingly to keep the computation fast.
+const MAX_BOOTSTRAP_SAMPLES: usize = 10_000_000;
+
+/// Lower bound for the number of resamples if it is reduced due to `MAX_BOOTSTRAP_SAMPLES`
+const MIN_BOOTSTRAP_RESAMPLES: usize = 100;
+
+/// Confidence interval of the mean of `values`, estimated from the given number of bootstrap
+/// resamples (but at most as many as needed to draw `MAX_BOOTSTRAP_SAMPLES` values in total).
+/// Returns `None` if there are less than two values, no resamples, or if the confidence level
+/// is not in the open interval (0, 1).
+pub fn bootstrap_mean_ci<R: Rng>(
+    values: &[Scalar],
+    resamples: usize,
+    confidence_level: Scalar,
+    rng: &mut R,
+) -> Option<(Scalar, Scalar)> {
+    if values.len() < 2 || resamples == 0 || !(confidence_level > 0.0 && confidence_level < 1.0) {
+        return None;
+    }
+
+    let resamples = resamples.min(max_resamples(values.len()));
+
+    let index = Uniform::new(0, values.len());
+    let mut means: Vec<Scalar> = (0..resamples)
+        .map(|_| {
+            let sum: Scalar = (0..values.len()).map(|_| values[rng.sample(index)]).sum();
+            sum / values.len() as Scalar
+        })
+        .collect();
+    means.sort_by(|a, b| a.total_cmp(b));
+
+    let alpha = (1.0 - confidence_level) / 2.0;
+    let percentile = |p: Scalar| means[(p * (resamples - 1) as Scalar).round() as usize];
+    Some((percentile(alpha), percentile(1.0 - alpha)))
+}
+
+/// Number of resamples for which at most `MAX_BOOTSTRAP_SAMPLES` values are drawn
+fn max_resamples(num_values: usize) -> usize {
+    (MAX_BOOTSTRAP_SAMPLES / num_values).max(MIN_BOOTSTRAP_RESAMPLES)
+}
+
+#[test]
+fn test_bootstrap_mean_ci() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let values: Vec<Scalar> = (0..100).map(|i| i as Scalar).collect();
+
+    let (lower, upper) = bootstrap_mean_ci(&values, 10_000, 0.95, &mut rng).unwrap();
+    assert!(lower < 49.5 && 49.5 < upper);
+    // Standard error of the mean is ~2.9, so the interval is roughly ±1.96 * 2.9
+    assert!(upper - lower > 9.0 && upper - lower < 13.5);
+
+    let (narrow_lower, narrow_upper) = bootstrap_mean_ci(&values, 10_000, 0.5, &mut rng).unwrap();
+    assert!(narrow_upper - narrow_lower < upper - lower);
+
+    let constant = [1.0; 10];
+    assert_eq!(
+        bootstrap_mean_ci(&constant, 100, 0.95, &mut rng),
+        Some((1.0, 1.0))
+    );
+
+    assert_eq!(bootstrap_mean_ci(&[1.0], 100, 0.95, &mut rng), None);
+    assert_eq!(bootstrap_mean_ci(&values, 0, 0.95, &mut rng), None);
+    assert_eq!(bootstrap_mean_ci(&values, 100, 1.0, &mut rng), None);
+}
+
+#[test]
+fn test_bootstrap_resamples_are_capped() {
+    assert_eq!(max_resamples(100), 100_000);
+    assert_eq!(max_resamples(10_000), 1_000);
+    assert_eq!(max_resamples(1_000_000), MIN_BOOTSTRAP_RESAMPLES);
+}
@@ -0,0 +1,141 @@
+/// Parse a list of CPU numbers like "0,2-3" into the sorted list of all contained CPUs.
+/// Returns `None` if the list is malformed.
+#[cfg(target_os = "linux")]
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = vec![];
+    for part in list.split(',').map(str::trim) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse().ok()?;
+                let last: usize = last.trim().parse().ok()?;
+                if first > last {
+                    return None;
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Some(cpus)
+}
+
+/// Format a sorted list of CPU numbers like "0,2-3", the inverse of `parse_cpu_list`
+#[cfg(target_os = "linux")]
+pub fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(first, last)| match first == last {
+            true => first.to_string(),
+            false => format!("{first}-{last}"),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The CPUs that hyperfine is allowed to run on. This can be fewer than the online CPUs, e.g.
+/// if hyperfine itself has been started with 'taskset' or inside of a container.
+#[cfg(target_os = "linux")]
+pub fn allowed_cpus() -> std::io::Result<Vec<usize>> {
+    // SAFETY: An all-zero cpu_set_t is a valid, empty set
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    // SAFETY: The pointer refers to a valid cpu_set_t of the given size
+    let result =
+        unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok((0..libc::CPU_SETSIZE as usize)
+        // SAFETY: `set` is a valid cpu_set_t and the CPU is smaller than CPU_SETSIZE
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect())
+}
+
+/// A set of CPUs that processes can be pinned to
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy)]
+pub struct CpuSet(libc::cpu_set_t);
+
+#[cfg(target_os = "linux")]
+impl CpuSet {
+    /// Create the set from a list of CPU numbers, which have to be smaller than `CPU_SETSIZE`
+    pub fn new(cpus: &[usize]) -> Self {
+        // SAFETY: An all-zero cpu_set_t is a valid, empty set
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &cpu in cpus {
+            // SAFETY: `set` is a valid cpu_set_t, out-of-range CPUs are ignored by CPU_SET
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        CpuSet(set)
+    }
+
+    /// Restrict the calling thread (and all processes that it starts afterwards) to this set
+    pub fn apply(&self) -> std::io::Result<()> {
+        // SAFETY: The pointer refers to a valid cpu_set_t of the given size
+        let result =
+            unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &self.0) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    /// Pin the process of the given command to this set, right before it is executed
+    pub fn pin_command(&self, command: &mut std::process::Command) {
+        use std::os::unix::process::CommandExt;
+
+        let set = *self;
+        // SAFETY: sched_setaffinity is async-signal-safe and does not allocate
+        unsafe { command.pre_exec(move || set.apply()) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("3"), Some(vec![3]));
+    assert_eq!(parse_cpu_list("0,2-4"), Some(vec![0, 2, 3, 4]));
+    assert_eq!(parse_cpu_list("4, 1-2, 2"), Some(vec![1, 2, 4]));
+    assert_eq!(parse_cpu_list(""), None);
+    assert_eq!(parse_cpu_list("1,"), None);
+    assert_eq!(parse_cpu_list("3-1"), None);
+    assert_eq!(parse_cpu_list("a"), None);
+    assert_eq!(parse_cpu_list("-1"), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_format_cpu_list() {
+    assert_eq!(format_cpu_list(&[3]), "3");
+    assert_eq!(format_cpu_list(&[0, 2, 3, 4]), "0,2-4");
+    assert_eq!(format_cpu_list(&[0, 1, 5, 7, 8]), "0-1,5,7-8");
+    assert_eq!(format_cpu_list(&[]), "");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_pin_command() {
+    // Not every CPU is necessarily available, e.g. inside of a container
+    let cpu = *allowed_cpus().unwrap().last().unwrap();
+
+    let mut command = std::process::Command::new("grep");
+    command.args(["^Cpus_allowed_list", "/proc/self/status"]);
+    CpuSet::new(&[cpu]).pin_command(&mut command);
+
+    let output = command.output().unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        output.split_whitespace().last(),
+        Some(cpu.to_string().as_str())
+    );
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::process::ExitStatus;
 
 #[cfg(unix)]
@@ -20,3 +21,12 @@ pub fn extract_exit_code(status: ExitStatus) -> Option<i32> {
 pub fn extract_exit_code(status: ExitStatus) -> Option<i32> {
     status.code()
 }
+
+/// Count how often each exit code occurs. Runs without an exit code are not included.
+pub fn count_exit_codes(exit_codes: &[Option<i32>]) -> BTreeMap<i32, usize> {
+    let mut counts = BTreeMap::new();
+    for code in exit_codes.iter().flatten() {
+        *counts.entry(*code).or_insert(0) += 1;
+    }
+    counts
+}
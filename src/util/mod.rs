@@ -1,5 +1,11 @@
+pub mod bootstrap;
+pub mod cpu_affinity;
 pub mod exit_code;
 pub mod min_max;
 pub mod number;
+pub mod percentile;
+pub mod priority;
 pub mod randomized_environment_offset;
+pub mod student_t;
 pub mod units;
+pub mod weighted;
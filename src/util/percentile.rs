@@ -0,0 +1,58 @@
+use crate::util::units::Scalar;
+
+/// The `p`-th percentile (0 ≤ p ≤ 100) of the given, sorted values. Interpolates linearly
+/// between the two closest order statistics (like `numpy.percentile` with the default
+/// 'linear' method), such that the 50th percentile is the median.
+pub fn percentile_of_sorted(sorted: &[Scalar], p: Scalar) -> Scalar {
+    assert!(!sorted.is_empty(), "percentile of an empty list of values");
+
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as Scalar;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let fraction = rank - lower as Scalar;
+
+    sorted[lower] + fraction * (sorted[upper] - sorted[lower])
+}
+
+/// Mean of the given, sorted values after discarding the given fraction (0 ≤ fraction < 0.5)
+/// of the values at each end. The number of discarded values is rounded down.
+pub fn trimmed_mean_of_sorted(sorted: &[Scalar], fraction: Scalar) -> Scalar {
+    assert!(
+        !sorted.is_empty(),
+        "trimmed mean of an empty list of values"
+    );
+
+    let trimmed = (fraction * sorted.len() as Scalar) as usize;
+    let kept = &sorted[trimmed..sorted.len() - trimmed];
+    kept.iter().sum::<Scalar>() / kept.len() as Scalar
+}
+
+#[test]
+fn test_percentile_of_sorted() {
+    use approx::assert_relative_eq;
+
+    let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+    assert_relative_eq!(percentile_of_sorted(&values, 0.0), 1.0);
+    assert_relative_eq!(percentile_of_sorted(&values, 50.0), 3.0);
+    assert_relative_eq!(percentile_of_sorted(&values, 90.0), 4.6);
+    assert_relative_eq!(percentile_of_sorted(&values, 100.0), 5.0);
+
+    let values = [10.0, 20.0];
+    assert_relative_eq!(percentile_of_sorted(&values, 50.0), 15.0);
+    assert_relative_eq!(percentile_of_sorted(&values, 99.0), 19.9);
+
+    assert_relative_eq!(percentile_of_sorted(&[7.0], 95.0), 7.0);
+}
+
+#[test]
+fn test_trimmed_mean_of_sorted() {
+    use approx::assert_relative_eq;
+
+    let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 100.0];
+    assert_relative_eq!(trimmed_mean_of_sorted(&values, 0.0), 14.5);
+    assert_relative_eq!(trimmed_mean_of_sorted(&values, 0.1), 5.5);
+    assert_relative_eq!(trimmed_mean_of_sorted(&values, 0.25), 5.5);
+
+    // Too few values to discard any of them
+    assert_relative_eq!(trimmed_mean_of_sorted(&[1.0, 2.0, 6.0], 0.1), 3.0);
+}
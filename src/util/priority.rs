@@ -0,0 +1,103 @@
+/// Nice values that are accepted by the operating system
+pub const NICE_RANGE: std::ops::RangeInclusive<i32> = -20..=19;
+
+/// Scheduling priority of the benchmarked commands
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulingPriority {
+    /// Nice value of the process, `None` keeps the nice value of hyperfine
+    pub nice: Option<i32>,
+
+    /// Run the process with the lowest priority of the real-time policy SCHED_FIFO (Linux only)
+    pub realtime: bool,
+}
+
+#[cfg(unix)]
+impl SchedulingPriority {
+    /// Whether the priority does not differ from the one of hyperfine itself
+    pub fn is_inherited(&self) -> bool {
+        self.nice.is_none() && !self.realtime
+    }
+
+    /// Set the priority of the calling process. This is async-signal-safe, such that it can
+    /// be called between `fork` and `exec`.
+    fn apply(&self) -> std::io::Result<()> {
+        if let Some(nice) = self.nice {
+            // SAFETY: setpriority has no memory safety requirements
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        if self.realtime {
+            // SAFETY: The pointer refers to a valid sched_param
+            let result = unsafe {
+                let param = libc::sched_param {
+                    sched_priority: libc::sched_get_priority_min(libc::SCHED_FIFO),
+                };
+                libc::sched_setscheduler(0, libc::SCHED_FIFO, &param)
+            };
+            if result != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check whether hyperfine is permitted to start processes with this priority (setting a
+    /// negative nice value or a real-time policy usually requires elevated privileges). This
+    /// applies the priority in a short-lived child process.
+    pub fn is_permitted(&self) -> bool {
+        // SAFETY: The child process only calls async-signal-safe functions before exiting
+        match unsafe { libc::fork() } {
+            -1 => false,
+            0 => {
+                let code = if self.apply().is_ok() { 0 } else { 1 };
+                // SAFETY: _exit is async-signal-safe
+                unsafe { libc::_exit(code) }
+            }
+            pid => {
+                let mut status = 0;
+                // SAFETY: `status` is a valid pointer to an integer
+                let result = unsafe { libc::waitpid(pid, &mut status, 0) };
+                result == pid && libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0
+            }
+        }
+    }
+
+    /// Run the process of the given command with this priority. Failures to set the priority
+    /// are ignored, such that the command runs with the priority of hyperfine instead.
+    pub fn set_for_command(&self, command: &mut std::process::Command) {
+        use std::os::unix::process::CommandExt;
+
+        if self.is_inherited() {
+            return;
+        }
+
+        let priority = *self;
+        // SAFETY: `apply` is async-signal-safe and does not allocate
+        unsafe {
+            command.pre_exec(move || {
+                let _ = priority.apply();
+                Ok(())
+            })
+        };
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_set_for_command() {
+    let priority = SchedulingPriority {
+        nice: Some(7),
+        realtime: false,
+    };
+    assert!(priority.is_permitted());
+
+    let mut command = std::process::Command::new("nice");
+    priority.set_for_command(&mut command);
+
+    let output = command.output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "7");
+}
@@ -8,36 +8,130 @@ pub type Second = Scalar;
 /// Supported time units
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Unit {
+    Hour,
+    Minute,
     Second,
     MilliSecond,
     MicroSecond,
+    NanoSecond,
 }
 
 impl Unit {
     /// The abbreviation of the Unit.
     pub fn short_name(self) -> String {
         match self {
+            Unit::Hour => String::from("h"),
+            Unit::Minute => String::from("min"),
             Unit::Second => String::from("s"),
             Unit::MilliSecond => String::from("ms"),
             Unit::MicroSecond => String::from("µs"),
+            Unit::NanoSecond => String::from("ns"),
         }
     }
 
+    /// The length of one unit in seconds
+    pub fn size(self) -> Second {
+        match self {
+            Unit::Hour => 3600.0,
+            Unit::Minute => 60.0,
+            Unit::Second => 1.0,
+            Unit::MilliSecond => 1e-3,
+            Unit::MicroSecond => 1e-6,
+            Unit::NanoSecond => 1e-9,
+        }
+    }
+
+    /// The largest unit in which the given duration is at least one
+    pub fn for_value(duration: Second) -> Self {
+        [
+            Unit::Hour,
+            Unit::Minute,
+            Unit::Second,
+            Unit::MilliSecond,
+            Unit::MicroSecond,
+        ]
+        .iter()
+        .copied()
+        .find(|unit| duration >= unit.size())
+        .unwrap_or(Unit::NanoSecond)
+    }
+
     /// Returns the Second value formatted for the Unit.
     pub fn format(self, value: Second) -> String {
         match self {
+            Unit::Hour | Unit::Minute => format!("{:.2}", value / self.size()),
             Unit::Second => format!("{value:.3}"),
             Unit::MilliSecond => format!("{:.1}", value * 1e3),
             Unit::MicroSecond => format!("{:.1}", value * 1e6),
+            Unit::NanoSecond => format!("{:.1}", value * 1e9),
+        }
+    }
+}
+
+/// Supported units of memory sizes, using binary prefixes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteUnit {
+    Byte,
+    KibiByte,
+    MebiByte,
+    GibiByte,
+    TebiByte,
+}
+
+impl ByteUnit {
+    /// The abbreviation of the unit.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            ByteUnit::Byte => "B",
+            ByteUnit::KibiByte => "KiB",
+            ByteUnit::MebiByte => "MiB",
+            ByteUnit::GibiByte => "GiB",
+            ByteUnit::TebiByte => "TiB",
+        }
+    }
+
+    /// The number of bytes in one unit
+    pub fn size(self) -> u64 {
+        match self {
+            ByteUnit::Byte => 1,
+            ByteUnit::KibiByte => 1 << 10,
+            ByteUnit::MebiByte => 1 << 20,
+            ByteUnit::GibiByte => 1 << 30,
+            ByteUnit::TebiByte => 1 << 40,
+        }
+    }
+
+    /// The largest unit in which the given number of bytes is at least one
+    pub fn for_value(bytes: u64) -> Self {
+        [
+            ByteUnit::TebiByte,
+            ByteUnit::GibiByte,
+            ByteUnit::MebiByte,
+            ByteUnit::KibiByte,
+        ]
+        .iter()
+        .copied()
+        .find(|unit| bytes >= unit.size())
+        .unwrap_or(ByteUnit::Byte)
+    }
+
+    /// Returns the number of bytes formatted for the unit.
+    pub fn format(self, bytes: u64) -> String {
+        match self {
+            ByteUnit::Byte => bytes.to_string(),
+            unit => format!("{:.1}", bytes as f64 / unit.size() as f64),
         }
     }
 }
 
 #[test]
 fn test_unit_short_name() {
+    assert_eq!("h", Unit::Hour.short_name());
+    assert_eq!("min", Unit::Minute.short_name());
     assert_eq!("s", Unit::Second.short_name());
     assert_eq!("ms", Unit::MilliSecond.short_name());
     assert_eq!("µs", Unit::MicroSecond.short_name());
+    assert_eq!("ns", Unit::NanoSecond.short_name());
 }
 
 // Note - the values are rounded when formatted.
@@ -46,6 +140,34 @@ fn test_unit_format() {
     let value: Second = 123.456789;
     assert_eq!("123.457", Unit::Second.format(value));
     assert_eq!("123456.8", Unit::MilliSecond.format(value));
+    assert_eq!("2.06", Unit::Minute.format(value));
+    assert_eq!("0.03", Unit::Hour.format(value));
 
     assert_eq!("1234.6", Unit::MicroSecond.format(0.00123456));
+    assert_eq!("1234.6", Unit::NanoSecond.format(0.00000123456));
+}
+
+#[test]
+fn test_unit_for_value() {
+    assert_eq!(Unit::NanoSecond, Unit::for_value(0.0));
+    assert_eq!(Unit::NanoSecond, Unit::for_value(0.999e-6));
+    assert_eq!(Unit::MicroSecond, Unit::for_value(1e-6));
+    assert_eq!(Unit::MilliSecond, Unit::for_value(0.001));
+    assert_eq!(Unit::Second, Unit::for_value(59.9));
+    assert_eq!(Unit::Minute, Unit::for_value(60.0));
+    assert_eq!(Unit::Minute, Unit::for_value(3599.0));
+    assert_eq!(Unit::Hour, Unit::for_value(3600.0));
+}
+
+#[test]
+fn test_byte_unit() {
+    assert_eq!(ByteUnit::Byte, ByteUnit::for_value(0));
+    assert_eq!(ByteUnit::Byte, ByteUnit::for_value(1023));
+    assert_eq!(ByteUnit::KibiByte, ByteUnit::for_value(1024));
+    assert_eq!(ByteUnit::MebiByte, ByteUnit::for_value(1 << 20));
+    assert_eq!(ByteUnit::TebiByte, ByteUnit::for_value(1 << 52));
+
+    assert_eq!("1023", ByteUnit::Byte.format(1023));
+    assert_eq!("1.5", ByteUnit::KibiByte.format(1536));
+    assert_eq!("0.5", ByteUnit::MebiByte.format(1 << 19));
 }
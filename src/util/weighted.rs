@@ -0,0 +1,98 @@
+//! Weighted statistics, for benchmarks where each run performs a different amount of work.
+//!
+//! With run times `t_i` and (non-negative) weights `w_i`, the weighted mean is
+//!
+//! ```text
+//! μ_w = Σ w_i t_i / Σ w_i
+//! ```
+//!
+//! and the weighted standard deviation is
+//!
+//! ```text
+//! σ_w = sqrt(Σ w_i (t_i - μ_w)² / Σ w_i)
+//! ```
+//!
+//! i.e. each run contributes in proportion to its weight. With equal weights, these reduce
+//! to the (population) mean and standard deviation.
+
+use crate::util::units::Scalar;
+
+/// The weighted mean of `values`. Returns `None` if the lengths do not match, weights are
+/// negative, or all weights are zero.
+pub fn weighted_mean(values: &[Scalar], weights: &[Scalar]) -> Option<Scalar> {
+    if values.len() != weights.len() || weights.iter().any(|&w| w < 0.0) {
+        return None;
+    }
+
+    let total_weight: Scalar = weights.iter().sum();
+    if total_weight <= 0.0 {
+        return None;
+    }
+
+    Some(
+        values
+            .iter()
+            .zip(weights)
+            .map(|(v, w)| v * w)
+            .sum::<Scalar>()
+            / total_weight,
+    )
+}
+
+/// The weighted standard deviation of `values` around the given weighted mean
+pub fn weighted_standard_deviation(
+    values: &[Scalar],
+    weights: &[Scalar],
+    weighted_mean: Scalar,
+) -> Option<Scalar> {
+    if values.len() != weights.len() {
+        return None;
+    }
+
+    let total_weight: Scalar = weights.iter().sum();
+    if total_weight <= 0.0 {
+        return None;
+    }
+
+    let variance = values
+        .iter()
+        .zip(weights)
+        .map(|(v, w)| w * (v - weighted_mean).powi(2))
+        .sum::<Scalar>()
+        / total_weight;
+
+    Some(variance.sqrt())
+}
+
+#[test]
+fn test_weighted_mean() {
+    use approx::assert_relative_eq;
+
+    assert_relative_eq!(
+        weighted_mean(&[1.0, 2.0, 3.0], &[1.0, 1.0, 1.0]).unwrap(),
+        2.0
+    );
+    assert_relative_eq!(weighted_mean(&[1.0, 3.0], &[3.0, 1.0]).unwrap(), 1.5);
+
+    assert_eq!(weighted_mean(&[1.0, 3.0], &[0.0, 0.0]), None);
+    assert_eq!(weighted_mean(&[1.0, 3.0], &[1.0, -1.0]), None);
+    assert_eq!(weighted_mean(&[1.0, 3.0], &[1.0]), None);
+}
+
+#[test]
+fn test_weighted_standard_deviation() {
+    use approx::assert_relative_eq;
+
+    let values = [1.0, 3.0];
+    assert_relative_eq!(
+        weighted_standard_deviation(&values, &[1.0, 1.0], 2.0).unwrap(),
+        1.0
+    );
+
+    // μ_w = 1.5, σ_w² = (3 * 0.25 + 1 * 2.25) / 4 = 0.75
+    let mean = weighted_mean(&values, &[3.0, 1.0]).unwrap();
+    assert_relative_eq!(
+        weighted_standard_deviation(&values, &[3.0, 1.0], mean).unwrap(),
+        0.75f64.sqrt()
+    );
+}
@@ -2,6 +2,8 @@ use std::process::Command;
 
 use assert_cmd::cargo::CommandCargoExt;
 
+// Deprecated in recent assert_cmd versions, but sufficient without a custom build-dir
+#[allow(deprecated)]
 pub fn hyperfine_raw_command() -> Command {
     let mut cmd = Command::cargo_bin("hyperfine").unwrap();
     cmd.current_dir("tests/");
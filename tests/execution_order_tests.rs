@@ -122,6 +122,29 @@ fn benchmarks_are_executed_sequentially() {
         .run();
 }
 
+#[test]
+fn benchmarks_are_interleaved() {
+    ExecutionOrderTest::new()
+        .arg("--runs=2")
+        .arg("--warmup=1")
+        .arg("--interleave")
+        .setup("setup")
+        .cleanup("cleanup")
+        .command("command 1")
+        .command("command 2")
+        .expect_output("setup")
+        .expect_output("command 1")
+        .expect_output("setup")
+        .expect_output("command 2")
+        .expect_output("command 1")
+        .expect_output("command 2")
+        .expect_output("command 1")
+        .expect_output("command 2")
+        .expect_output("cleanup")
+        .expect_output("cleanup")
+        .run();
+}
+
 #[test]
 fn warmup_runs_are_executed_before_benchmarking_runs() {
     ExecutionOrderTest::new()
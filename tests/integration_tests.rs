@@ -45,6 +45,104 @@ fn can_run_commands_without_a_shell() {
         .stdout(predicate::str::contains("hello world argument2"));
 }
 
+#[test]
+fn fails_for_shell_syntax_without_a_shell() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--no-shell")
+        .arg("echo a && echo b")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The command 'echo a && echo b' contains '&'",
+        ));
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--show-output")
+        .arg("--no-shell")
+        .arg("echo 'a && b' \\*")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a && b *"));
+}
+
+#[cfg(unix)]
+#[test]
+fn measures_startup_overhead_without_a_shell() {
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--no-shell")
+        .arg("--measure-overhead")
+        .arg("echo benchmark")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Startup overhead: true"))
+        .stdout(predicate::str::contains("Measuring shell spawning time").not());
+}
+
+#[test]
+fn fails_with_conflicting_numbers_of_runs() {
+    hyperfine()
+        .arg("--min-runs=5")
+        .arg("--max-runs=3")
+        .arg("echo a")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'--min-runs' (5) is larger than '--max-runs' (3)",
+        ));
+
+    for bound in ["--min-runs=2", "--max-runs=3"] {
+        hyperfine()
+            .arg("--runs=2")
+            .arg(bound)
+            .arg("echo a")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "'--runs <NUM>' cannot be used with",
+            ))
+            .stderr(predicate::str::contains(&bound[..10]));
+    }
+
+    hyperfine_debug()
+        .arg("--min-runs=3")
+        .arg("--max-runs=3")
+        .arg("sleep 0.01")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 runs"));
+
+    hyperfine()
+        .arg("--parameter-list")
+        .arg("n")
+        .arg("1,2")
+        .arg("--runs-for")
+        .arg("n=2")
+        .arg("0")
+        .arg("echo {n}")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The argument to '--runs-for' has to be a positive number",
+        ));
+}
+
+#[test]
+fn fails_with_non_positive_retry_on_variance() {
+    for percent in ["-5", "0", "inf", "NaN"] {
+        hyperfine()
+            .arg(format!("--retry-on-variance={percent}"))
+            .arg("echo a")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "The argument to '--retry-on-variance' has to be a positive number",
+            ));
+    }
+}
+
 #[test]
 fn fails_with_wrong_number_of_command_name_arguments() {
     hyperfine()
@@ -86,7 +184,7 @@ fn fails_with_wrong_number_of_prepare_options() {
         .arg("echo b")
         .arg("echo c")
         .assert()
-        .failure()
+        .code(2)
         .stderr(predicate::str::contains(
             "The '--prepare' option has to be provided",
         ));
@@ -99,12 +197,25 @@ fn fails_with_wrong_number_of_prepare_options() {
         .arg("echo a")
         .arg("echo b")
         .assert()
-        .failure()
+        .code(2)
         .stderr(predicate::str::contains(
             "The '--prepare' option has to be provided",
         ));
 }
 
+#[test]
+fn fails_with_unknown_metric() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--metric=cpu-cyles")
+        .arg("echo a")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unknown metric 'cpu-cyles'. Did you mean 'cpu-cycles'?",
+        ));
+}
+
 #[test]
 fn fails_with_wrong_number_of_conclude_options() {
     hyperfine()
@@ -135,7 +246,7 @@ fn fails_with_wrong_number_of_conclude_options() {
         .arg("echo b")
         .arg("echo c")
         .assert()
-        .failure()
+        .code(2)
         .stderr(predicate::str::contains(
             "The '--conclude' option has to be provided",
         ));
@@ -148,7 +259,7 @@ fn fails_with_wrong_number_of_conclude_options() {
         .arg("echo a")
         .arg("echo b")
         .assert()
-        .failure()
+        .code(2)
         .stderr(predicate::str::contains(
             "The '--conclude' option has to be provided",
         ));
@@ -165,7 +276,7 @@ fn fails_with_duplicate_parameter_names() {
         .arg("a,b,c")
         .arg("echo test")
         .assert()
-        .failure()
+        .code(2)
         .stderr(predicate::str::contains("Duplicate parameter names: x"));
 }
 
@@ -181,6 +292,228 @@ fn fails_for_unknown_command() {
         ));
 }
 
+#[test]
+fn shows_output_of_failing_runs() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--show-output-on-failure")
+        .arg("echo some output; exit 1")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "Output of the command:\nsome output",
+        ));
+
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--ignore-failure")
+        .arg("--show-output-on-failure")
+        .arg("echo output of run $HYPERFINE_ITERATION; exit $HYPERFINE_ITERATION")
+        .assert()
+        .success()
+        .stderr(
+            predicate::str::contains(
+                "Output of the failing command in benchmark iteration 1:\noutput of run 1",
+            )
+            .and(predicate::str::contains("output of run 0").not()),
+        );
+}
+
+#[test]
+fn exit_status_distinguishes_failed_benchmarks_from_other_errors() {
+    hyperfine().arg("--runs=1").arg("exit 1").assert().code(3);
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--prepare=exit 1")
+        .arg("echo a")
+        .assert()
+        .code(3);
+
+    hyperfine()
+        .arg("--timeout=-1")
+        .arg("echo a")
+        .assert()
+        .code(2);
+
+    hyperfine()
+        .arg("--from-json=some-nonexisting-file-b5d9574198b7e4b12a71fa4747c0a577.json")
+        .assert()
+        .code(1);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn pins_commands_to_the_given_cpus() {
+    // Not every CPU is necessarily available, e.g. inside of a container, so use the first CPU
+    // that the tests are allowed to run on
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let allowed = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+        .unwrap()
+        .trim();
+    let cpu: String = allowed.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg(format!("--cpu={cpu}"))
+        .arg("grep Cpus_allowed_list /proc/self/status")
+        .arg("--show-output")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(format!(r"Cpus_allowed_list:\s+{cpu}\n")).unwrap());
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--cpu=0,100000")
+        .arg("echo a")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "CPU 100000 given to '--cpu' is not available",
+        ));
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--cpu=3-1")
+        .arg("echo a")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Invalid CPU list '3-1'"));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_commands_with_the_given_nice_value() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--nice=5")
+        .arg("--show-output")
+        .arg("nice")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^5$").unwrap());
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--nice=20")
+        .arg("echo a")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("between -20 and 19"));
+}
+
+#[test]
+fn fails_if_command_exceeds_timeout() {
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--timeout=0.2")
+        .arg("sleep 10")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Command has been killed after exceeding the timeout of 200.0 ms in the first benchmark run",
+        ));
+
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--timeout=0.2")
+        .arg("--time-unit=second")
+        .arg("sleep 10")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Command has been killed after exceeding the timeout of 0.200 s",
+        ));
+
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--timeout=0.2")
+        .arg("--time-unit=second")
+        .arg("--ignore-failure")
+        .arg("sleep 10")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "2 run(s) exceeded the timeout of 0.200 s and have been killed",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn timeout_kills_all_descendants() {
+    let start = std::time::Instant::now();
+
+    // The background process keeps the output pipe open until it is killed as well
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--timeout=0.2")
+        .arg("--ignore-failure")
+        .arg("--output=pipe")
+        .arg("sleep 10 & sleep 10")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "2 run(s) exceeded the timeout of 200.0 ms and have been killed",
+        ));
+
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn interrupt_is_forwarded_to_commands_with_timeout() {
+    use std::time::{Duration, Instant};
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let pid_file = tempdir.path().join("pid");
+
+    // The command runs in its own process group, so it does not receive the SIGINT of the
+    // terminal directly
+    let mut hyperfine = common::hyperfine_raw_command()
+        .arg("--runs=1")
+        .arg("--timeout=100")
+        .arg(format!("echo $$ > {}; exec sleep 100", pid_file.display()))
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let start = Instant::now();
+    let pid = loop {
+        match std::fs::read_to_string(&pid_file) {
+            Ok(pid) if pid.ends_with('\n') => break pid.trim().to_string(),
+            _ if start.elapsed() < Duration::from_secs(10) => {
+                std::thread::sleep(Duration::from_millis(10))
+            }
+            _ => panic!("The benchmarked command has not been started"),
+        }
+    };
+
+    std::process::Command::new("kill")
+        .arg("-INT")
+        .arg(hyperfine.id().to_string())
+        .status()
+        .unwrap();
+    assert!(!hyperfine.wait().unwrap().success());
+
+    // The command has been terminated as well (it may remain a zombie until it is reaped)
+    let terminated = || match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        Ok(stat) => stat
+            .rsplit(')')
+            .next()
+            .unwrap()
+            .trim_start()
+            .starts_with('Z'),
+        Err(_) => true,
+    };
+    while !terminated() {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
 #[test]
 fn fails_for_unknown_command_without_shell() {
     hyperfine()
@@ -194,6 +527,44 @@ fn fails_for_unknown_command_without_shell() {
         ));
 }
 
+#[test]
+fn fails_for_shell_syntax_without_shell_before_running_benchmarks() {
+    hyperfine()
+        .arg("-N")
+        .arg("--runs=1")
+        .arg("sleep 0.01")
+        .arg("echo a | wc")
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("Benchmark 1").not())
+        .stderr(predicate::str::contains(
+            "The command 'echo a | wc' contains '|'",
+        ));
+
+    for (option, command) in [
+        ("--setup", "echo a && echo b"),
+        ("--prepare", "echo {x} > file"),
+        ("--conclude", "echo a; echo b"),
+        ("--cleanup", "echo a | wc"),
+    ] {
+        hyperfine()
+            .arg("-N")
+            .arg("--runs=1")
+            .arg("--parameter-list")
+            .arg("x")
+            .arg("1,2")
+            .arg(option)
+            .arg(command)
+            .arg("sleep 0.0{x}")
+            .assert()
+            .code(2)
+            .stdout(predicate::str::contains("Benchmark 1").not())
+            .stderr(predicate::str::contains(
+                "This is not supported when running commands without a shell",
+            ));
+    }
+}
+
 #[cfg(unix)]
 #[test]
 fn fails_for_failing_command_without_shell() {
@@ -258,6 +629,38 @@ fn fails_for_unknown_conclude_command() {
         ));
 }
 
+#[cfg(unix)]
+#[test]
+fn shows_output_of_failing_setup_command() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--setup=echo 'setup diagnostics'; echo 'setup error' >&2; false")
+        .arg("echo test")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Output of the command:\nsetup diagnostics\nsetup error\n",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn truncates_captured_output_of_failing_commands() {
+    hyperfine()
+        .arg("--output-capture-limit=10")
+        .arg("--prepare=head -c 1000000 /dev/zero; echo 'last line'; false")
+        .arg("echo test")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "[... 1000000 bytes truncated, showing the last 10 bytes of output]\nlast line",
+        ))
+        .stderr(predicate::str::contains(
+            "Warning: The output of the command exceeded the capture limit of 10 bytes, so the \
+             first 1000000 bytes have been discarded.",
+        ));
+}
+
 #[cfg(unix)]
 #[test]
 fn can_run_failing_commands_with_ignore_failure_option() {
@@ -300,6 +703,43 @@ fn can_ignore_specific_exit_codes() {
         ));
 }
 
+#[cfg(unix)]
+#[test]
+fn shows_distribution_of_exit_codes() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("results.json");
+
+    // Every other run fails
+    hyperfine()
+        .current_dir(tempdir.path())
+        .arg("--runs=4")
+        .arg("--ignore-failure")
+        .arg("--export-json")
+        .arg(&export_path)
+        .arg("if [ -e marker ]; then rm marker; else touch marker; exit 3; fi")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Exit codes:          0 (2×), 3 (2×)",
+        ));
+
+    let contents = std::fs::read_to_string(export_path).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    assert_eq!(
+        json["results"][0]["exit_code_counts"],
+        serde_json::json!({"0": 2, "3": 2})
+    );
+
+    hyperfine()
+        .arg("--runs=2")
+        .arg("true")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exit codes").not());
+}
+
 #[cfg(unix)]
 #[test]
 fn can_ignore_multiple_exit_codes() {
@@ -339,27 +779,58 @@ fn can_ignore_multiple_exit_codes() {
 
 #[cfg(unix)]
 #[test]
-fn ignore_failure_with_all_non_zero() {
-    // Test explicit "all-non-zero" mode
+fn propagates_exit_code_of_ignored_failures() {
     hyperfine()
-        .arg("--runs=1")
-        .arg("--ignore-failure=all-non-zero")
-        .arg("exit 5")
+        .arg("--runs=2")
+        .arg("--ignore-failure")
+        .arg("--error-exit-code")
+        .arg("exit 0")
+        .arg("exit 3")
+        .arg("exit 4")
         .assert()
-        .success();
-}
+        .code(3)
+        .stdout(predicate::str::contains("Benchmark 3: exit 4"));
 
-#[test]
-fn shows_output_of_benchmarked_command() {
     hyperfine()
         .arg("--runs=2")
-        .arg("--command-name=dummy")
-        .arg("--show-output")
-        .arg("echo 4fd47015")
+        .arg("--ignore-failure")
+        .arg("--error-exit-code")
+        .arg("exit 0")
         .assert()
-        .success()
-        .stdout(predicate::str::contains("4fd47015").count(2));
-}
+        .success();
+
+    // Without the flag, ignored failures do not change the exit status
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--ignore-failure")
+        .arg("exit 3")
+        .assert()
+        .success();
+}
+
+#[cfg(unix)]
+#[test]
+fn ignore_failure_with_all_non_zero() {
+    // Test explicit "all-non-zero" mode
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--ignore-failure=all-non-zero")
+        .arg("exit 5")
+        .assert()
+        .success();
+}
+
+#[test]
+fn shows_output_of_benchmarked_command() {
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--command-name=dummy")
+        .arg("--show-output")
+        .arg("echo 4fd47015")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("4fd47015").count(2));
+}
 
 #[test]
 fn runs_commands_using_user_defined_shell() {
@@ -378,6 +849,54 @@ fn runs_commands_using_user_defined_shell() {
         );
 }
 
+#[test]
+fn does_not_repeat_command_flag_of_user_defined_shell() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--show-output")
+        .arg("--shell")
+        .arg("echo custom_shell -c")
+        .arg("echo benchmark")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("custom_shell -c echo benchmark\n"));
+}
+
+#[test]
+fn uses_user_defined_shell_for_intermediate_commands() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--show-output")
+        .arg("--shell")
+        .arg("echo custom_shell")
+        .arg("--setup=setup")
+        .arg("--prepare=prepare")
+        .arg("--conclude=conclude")
+        .arg("--cleanup=cleanup")
+        .arg("benchmark")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("custom_shell -c setup")
+                .and(predicate::str::contains("custom_shell -c prepare"))
+                .and(predicate::str::contains("custom_shell -c conclude"))
+                .and(predicate::str::contains("custom_shell -c cleanup")),
+        );
+}
+
+#[test]
+fn fails_for_unknown_shell() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--shell=some-nonexisting-shell -x")
+        .arg("echo benchmark")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The shell 'some-nonexisting-shell' could not be found",
+        ));
+}
+
 #[test]
 fn can_pass_input_to_command_from_a_file() {
     hyperfine()
@@ -390,6 +909,45 @@ fn can_pass_input_to_command_from_a_file() {
         .stdout(predicate::str::contains("This text is part of a file"));
 }
 
+#[test]
+fn can_pass_input_to_command_from_a_string() {
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--input-string=This text is passed as a string")
+        .arg("--show-output")
+        .arg(STDIN_READ_COMMAND)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("This text is passed as a string").count(2));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_commands_with_the_given_environment() {
+    hyperfine()
+        .env("HYPERFINE_TEST_VARIABLE", "inherited")
+        .arg("--runs=1")
+        .arg("--show-output")
+        .arg("--clear-env")
+        .arg("--env=GREETING=hello world")
+        .arg("--setup=test -z \"$HYPERFINE_TEST_VARIABLE\"")
+        .arg("echo \"[$PATH] [$GREETING] [$HYPERFINE_TEST_VARIABLE]\"")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[/usr/local/bin:/usr/bin:/bin] [hello world] []",
+        ));
+
+    hyperfine()
+        .arg("--env=GREETING")
+        .arg("echo a")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The argument 'GREETING' to '--env' has to be of the form KEY=VALUE",
+        ));
+}
+
 #[test]
 fn fails_if_invalid_stdin_data_file_provided() {
     hyperfine()
@@ -433,6 +991,22 @@ fn returns_mean_time_in_correct_unit() {
         .stdout(predicate::str::contains(
             "Time (mean ± σ):     1234000.0 µs ±",
         ));
+
+    hyperfine_debug()
+        .arg("--time-unit=ns")
+        .arg("sleep 0.5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Time (mean ± σ):     500000000.0 ns ±",
+        ));
+
+    hyperfine_debug()
+        .arg("--output-unit=s")
+        .arg("sleep 0.001")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Time (mean ± σ):      0.001 s ±"));
 }
 
 #[test]
@@ -444,6 +1018,85 @@ fn performs_ten_runs_for_slow_commands() {
         .stdout(predicate::str::contains("10 runs"));
 }
 
+#[test]
+fn shows_robust_statistics() {
+    hyperfine_debug()
+        .arg("--robust-stats")
+        .arg("sleep 0.5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Robust:               trimmed mean: 500.0 ms   MAD: 0.0 ms",
+        ));
+
+    hyperfine_debug()
+        .arg("sleep 0.5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("trimmed mean").not());
+}
+
+#[test]
+fn shows_confidence_interval_of_mean() {
+    hyperfine_debug()
+        .arg("sleep 0.5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(95% CI: 500.0 ms … 500.0 ms)"));
+
+    hyperfine_debug()
+        .arg("--confidence-level=90")
+        .arg("--bootstrap-resamples=100")
+        .arg("sleep 0.5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(90% CI: 500.0 ms … 500.0 ms)"));
+
+    hyperfine_debug()
+        .arg("--runs=1")
+        .arg("sleep 0.5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("CI:").not());
+}
+
+#[test]
+fn shows_histogram_of_run_times() {
+    hyperfine_debug()
+        .arg("--runs=10")
+        .arg("--histogram")
+        .arg("--histogram-bins=3")
+        .arg("sleep 0.01")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "10.0 ms …    10.0 ms │{} 10",
+            "█".repeat(40)
+        )));
+}
+
+#[test]
+fn shows_histogram_of_measured_run_times() {
+    let output = hyperfine()
+        .arg("--runs=5")
+        .arg("--histogram")
+        .arg("--histogram-bins=2")
+        .arg("echo dummy benchmark")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Every run is contained in one of the (at most two) bins
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let counts: Vec<usize> = stdout
+        .lines()
+        .filter(|line| line.contains(" │"))
+        .map(|line| line.rsplit(' ').next().unwrap().parse().unwrap())
+        .collect();
+    assert!(!counts.is_empty() && counts.len() <= 2);
+    assert_eq!(counts.iter().sum::<usize>(), 5);
+}
+
 #[test]
 fn performs_three_seconds_of_benchmarking_for_fast_commands() {
     hyperfine_debug()
@@ -463,6 +1116,64 @@ fn takes_shell_spawning_time_into_account_for_computing_number_of_runs() {
         .stdout(predicate::str::contains("100 runs"));
 }
 
+#[test]
+fn shows_measured_shell_spawning_time_with_show_output() {
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--show-output")
+        .arg("echo benchmark")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Shell spawning time: "));
+}
+
+#[test]
+fn subtracts_given_shell_overhead() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("results.json");
+
+    // The given overhead exceeds the run time, such that all times are clamped to zero
+    hyperfine()
+        .arg("--runs=2")
+        .arg("--show-output")
+        .arg("--shell-overhead=10")
+        .arg("--export-json")
+        .arg(&export_path)
+        .arg("echo benchmark")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Shell spawning time").not());
+
+    let contents = std::fs::read_to_string(export_path).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    assert_eq!(json["results"][0]["mean"], 0.0);
+    assert_eq!(json["metadata"]["options"]["shell_overhead"], 10.0);
+}
+
+#[test]
+fn fails_for_invalid_shell_overhead() {
+    hyperfine()
+        .arg("--shell-overhead=-1")
+        .arg("echo benchmark")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The argument to '--shell-overhead' can not be negative",
+        ));
+
+    hyperfine()
+        .arg("--shell=none")
+        .arg("--shell-overhead=0.001")
+        .arg("echo benchmark")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "can not be used when running commands without a shell",
+        ));
+}
+
 #[test]
 fn takes_preparation_command_into_account_for_computing_number_of_runs() {
     hyperfine_debug()
@@ -525,6 +1236,123 @@ fn takes_both_preparation_and_conclusion_command_into_account_for_computing_numb
         .stdout(predicate::str::contains("30 runs"));
 }
 
+#[test]
+fn writes_progress_events_to_stderr() {
+    let output = hyperfine_debug()
+        .arg("--progress-json")
+        .arg("--warmup=1")
+        .arg("--runs=3")
+        .arg("sleep 0.1")
+        .arg("sleep 0.2")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let event_names: Vec<&str> = events
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        event_names,
+        [
+            "warmup",
+            "measure",
+            "measure",
+            "measure",
+            "benchmark_done",
+            "warmup",
+            "measure",
+            "measure",
+            "measure",
+            "benchmark_done"
+        ]
+    );
+    assert_eq!(
+        events[3],
+        serde_json::json!({"event": "measure", "benchmark": 0, "run": 2})
+    );
+    assert_eq!(events[9]["index"], 1);
+    assert!((events[9]["mean"].as_f64().unwrap() - 0.2).abs() < 1e-9);
+}
+
+#[test]
+fn quiet_mode_only_shows_warnings_and_summary() {
+    hyperfine_debug()
+        .arg("--quiet")
+        .arg("--runs=2")
+        .arg("sleep 0.1")
+        .arg("sleep 0.3")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Benchmark 1")
+                .not()
+                .and(predicate::str::contains("Time (mean").not())
+                .and(predicate::str::contains(
+                    "  sleep 0.1 ran\n    3.00 ± 0.00 times faster than sleep 0.3",
+                )),
+        )
+        .stderr(
+            predicate::str::contains("Benchmark 2: sleep 0.3\n  Warning: Only 2 run(s)")
+                .and(predicate::str::contains("Benchmark 1: sleep 0.1\n")),
+        );
+}
+
+#[test]
+fn shows_mean_preparation_and_conclusion_time() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("results.json");
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--prepare=sleep 0.2")
+        .arg("--conclude=sleep 0.03")
+        .arg("--show-setup-time")
+        .arg("--export-json")
+        .arg(&export_path)
+        .arg("sleep 0.01")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Prepare (mean):      200.0 ms")
+                .and(predicate::str::contains("Conclude (mean):      30.0 ms")),
+        );
+
+    let contents = std::fs::read_to_string(export_path).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    assert_eq!(json["results"][0]["prepare_mean"], 0.2);
+    assert_eq!(json["results"][0]["conclude_mean"], 0.03);
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--prepare=sleep 0.2")
+        .arg("--conclude=sleep 0.03")
+        .arg("--show-setup-time")
+        .arg("--time-unit=microsecond")
+        .arg("sleep 0.01")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Prepare (mean):      200000.0 µs")
+                .and(predicate::str::contains("Conclude (mean):     30000.0 µs")),
+        );
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--prepare=sleep 0.2")
+        .arg("sleep 0.01")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Prepare (mean)").not());
+}
+
 #[test]
 fn shows_benchmark_comparison_with_relative_times() {
     hyperfine_debug()
@@ -571,6 +1399,58 @@ fn shows_benchmark_comparison_relative_to_reference() {
         );
 }
 
+#[test]
+fn selects_benchmarked_command_as_reference() {
+    for reference in ["2", "before"] {
+        hyperfine_debug()
+            .arg(format!("--reference={reference}"))
+            .arg("--command-name=old")
+            .arg("--command-name=before")
+            .arg("--command-name=after")
+            .arg("sleep 1.0")
+            .arg("sleep 2.0")
+            .arg("sleep 3.0")
+            .assert()
+            .success()
+            .stdout(
+                predicate::str::contains("Benchmark 4")
+                    .not()
+                    .and(predicate::str::contains("before ran"))
+                    .and(predicate::str::contains(
+                        "2.00 ± 0.00 times slower than old",
+                    ))
+                    .and(predicate::str::contains(
+                        "1.50 ± 0.00 times faster than after",
+                    )),
+            );
+    }
+}
+
+#[test]
+fn shuffles_benchmark_order_reproducibly() {
+    hyperfine_debug()
+        .arg("--shuffle")
+        .arg("--shuffle-seed=2")
+        .arg("--sort=none")
+        .arg("sleep 1")
+        .arg("sleep 2")
+        .arg("sleep 3")
+        .arg("sleep 4")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                "(?s)Benchmark 3: .*Benchmark 2: .*Benchmark 4: .*Benchmark 1: sleep 1",
+            )
+            .unwrap()
+            // The results are still reported in the original order
+            .and(
+                predicate::str::is_match("(?s)1.00          sleep 1.*sleep 2.*sleep 3.*sleep 4")
+                    .unwrap(),
+            ),
+        );
+}
+
 #[test]
 fn shows_reference_name() {
     hyperfine_debug()
@@ -584,10 +1464,104 @@ fn shows_reference_name() {
 }
 
 #[test]
-fn performs_all_benchmarks_in_parameter_scan() {
+fn performs_benchmarks_for_parameter_values_from_file() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let values_path = tempdir.path().join("sizes.txt");
+    std::fs::write(&values_path, "# sizes\n0.1\n\n0.2  \n").unwrap();
+
     hyperfine_debug()
-        .arg("--parameter-scan")
-        .arg("time")
+        .arg("--runs=2")
+        .arg("--parameter-list-file")
+        .arg("size")
+        .arg(&values_path)
+        .arg("--parameter-list")
+        .arg("x")
+        .arg("1,2")
+        .arg("sleep {size}{x}")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Benchmark 1: sleep 0.11\n")
+                .and(predicate::str::contains("Benchmark 4: sleep 0.22\n"))
+                .and(predicate::str::contains("Benchmark 5").not()),
+        );
+
+    std::fs::write(&values_path, "# no values\n").unwrap();
+    hyperfine_debug()
+        .arg("--parameter-list-file")
+        .arg("size")
+        .arg(&values_path)
+        .arg("sleep {size}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not contain any values"));
+
+    hyperfine_debug()
+        .arg("--parameter-list-file")
+        .arg("size")
+        .arg(tempdir.path().join("missing.txt"))
+        .arg("sleep {size}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Could not read parameter values file",
+        ));
+}
+
+#[test]
+fn limits_the_number_of_benchmarks_in_parameter_sweeps() {
+    let sweep = || {
+        let mut cmd = hyperfine_debug();
+        cmd.arg("--runs=1")
+            .arg("--parameter-list")
+            .arg("x")
+            .arg("1,2,3")
+            .arg("--parameter-list")
+            .arg("y")
+            .arg("1,2")
+            .arg("sleep 0.{x}{y}")
+            .arg("sleep 0.0{x}{y}");
+        cmd
+    };
+
+    sweep().arg("--max-benchmarks=12").assert().success();
+
+    sweep()
+        .arg("--max-benchmarks=11")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The parameter sweep results in 12 benchmarks (2 commands × 3 values of 'x' × 2 \
+             values of 'y'), which is more than the maximum of 11",
+        ));
+
+    sweep()
+        .arg("--max-benchmarks=11")
+        .arg("--force")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Benchmark 12: sleep 0.032"));
+
+    hyperfine_debug()
+        .arg("--parameter-scan")
+        .arg("x")
+        .arg("1")
+        .arg("501")
+        .arg("sleep {x}")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "501 benchmarks (1 command × 501 values of 'x'), which is more than the maximum of 500",
+        ));
+}
+
+#[test]
+fn performs_all_benchmarks_in_parameter_scan() {
+    hyperfine_debug()
+        .arg("--parameter-scan")
+        .arg("time")
         .arg("30")
         .arg("45")
         .arg("--parameter-step-size")
@@ -604,6 +1578,27 @@ fn performs_all_benchmarks_in_parameter_scan() {
         );
 }
 
+#[test]
+fn performs_all_benchmarks_in_parameter_scan_with_decimal_step() {
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--parameter-scan")
+        .arg("time")
+        .arg("0")
+        .arg("1")
+        .arg("--parameter-step")
+        .arg("0.1")
+        .arg("sleep {time}")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Benchmark 1: sleep 0\n")
+                .and(predicate::str::contains("Benchmark 4: sleep 0.3\n"))
+                .and(predicate::str::contains("Benchmark 11: sleep 1.0\n"))
+                .and(predicate::str::contains("Benchmark 12").not()),
+        );
+}
+
 #[test]
 fn performs_reference_and_all_benchmarks_in_parameter_scan() {
     hyperfine_debug()
@@ -664,6 +1659,183 @@ fn exports_intermediate_results_to_file() {
     assert!(contents.contains("true"));
 }
 
+#[test]
+#[cfg(unix)]
+fn resumes_interrupted_benchmark_suite_from_checkpoint() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let checkpoint_path = tempdir.path().join("checkpoint.json");
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--checkpoint")
+        .arg(&checkpoint_path)
+        .arg("true")
+        .arg("false")
+        .assert()
+        .failure();
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--checkpoint")
+        .arg(&checkpoint_path)
+        .arg("--resume")
+        .arg("--ignore-failure")
+        .arg("true")
+        .arg("false")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Benchmark 1: true (resumed from checkpoint)",
+        ))
+        .stdout(predicate::str::contains("Benchmark 2: false\n"));
+
+    hyperfine()
+        .arg("--runs=1")
+        .arg("--checkpoint")
+        .arg(&checkpoint_path)
+        .arg("--resume")
+        .arg("true")
+        .arg("echo other")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "belongs to a different set of benchmark commands",
+        ));
+}
+
+#[test]
+#[cfg(unix)]
+fn keeps_benchmarks_of_the_same_command_apart_in_checkpoint() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let checkpoint_path = tempdir.path().join("checkpoint.json");
+    let export_path = tempdir.path().join("export.json");
+
+    let suite = || {
+        let mut cmd = hyperfine();
+        cmd.arg("--runs=1")
+            .arg("--checkpoint")
+            .arg(&checkpoint_path)
+            .arg("--export-json")
+            .arg(&export_path)
+            .arg("--command-name=fast")
+            .arg("--command-name=slow")
+            .arg("true")
+            .arg("true");
+        cmd
+    };
+
+    suite()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Benchmark 2: slow\n"))
+        .stdout(predicate::str::contains("resumed from checkpoint").not());
+    let export = std::fs::read_to_string(&export_path).unwrap();
+    assert!(export.contains("\"command\": \"fast\""));
+    assert!(export.contains("\"command\": \"slow\""));
+
+    suite()
+        .arg("--resume")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Benchmark 1: fast (resumed from checkpoint)",
+        ))
+        .stdout(predicate::str::contains(
+            "Benchmark 2: slow (resumed from checkpoint)",
+        ));
+    let export = std::fs::read_to_string(&export_path).unwrap();
+    assert!(export.contains("\"command\": \"fast\""));
+    assert!(export.contains("\"command\": \"slow\""));
+}
+
+#[test]
+fn reports_binary_size() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let binary_path = tempdir.path().join("binary");
+    std::fs::write(&binary_path, vec![0u8; 3 << 10]).unwrap();
+
+    hyperfine_debug()
+        .arg("--binary-path")
+        .arg(&binary_path)
+        .arg("sleep 0.1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Binary size:          3.0 KiB"));
+
+    hyperfine_debug()
+        .arg("--binary-size")
+        .arg("sleep 0.1 | cat")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Could not determine the binary of the command 'sleep 0.1 | cat' unambiguously",
+        ));
+}
+
+#[test]
+fn shows_descriptions_before_benchmarks() {
+    hyperfine_debug()
+        .arg("--description=tested the new allocator")
+        .arg("--description=after kernel upgrade")
+        .arg("sleep 1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Description: tested the new allocator\nDescription: after kernel upgrade\n",
+        ));
+}
+
+#[test]
+fn shows_startup_overhead_before_benchmarks() {
+    hyperfine_debug()
+        .arg("--measure-overhead=sleep 0.01")
+        .arg("sleep 0.1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Startup overhead: sleep 0.01\n  Time (mean ± σ):      10.0 ms ±",
+        ))
+        .stdout(predicate::str::contains("Benchmark 1: sleep 0.1"))
+        .stdout(predicate::str::contains("Summary").not());
+}
+
+#[test]
+fn shows_before_after_comparison() {
+    hyperfine_debug()
+        .arg("--before-after")
+        .arg("sleep 0.2")
+        .arg("sleep 0.15")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Before/after comparison\n  \
+               before: 200.0 ms ± 0.0 ms  (sleep 0.2)\n  \
+               after:  150.0 ms ± 0.0 ms  (sleep 0.15)\n  \
+               change: -25.0% (faster, 1.33 ± 0.00 times faster)",
+        ))
+        .stdout(predicate::str::contains("Summary").not());
+}
+
+#[test]
+fn fails_for_before_after_comparison_of_more_than_two_commands() {
+    hyperfine_debug()
+        .arg("--before-after")
+        .arg("sleep 0.1")
+        .arg("sleep 0.2")
+        .arg("sleep 0.3")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The '--before-after' option requires exactly two benchmark commands",
+        ));
+}
+
 #[test]
 fn unused_parameters_are_shown_in_benchmark_name() {
     hyperfine()
@@ -694,14 +1866,27 @@ fn speed_comparison_sort_order() {
             ));
     }
 
+    for sort_order in ["command", "none"] {
+        hyperfine_debug()
+            .arg("sleep 2")
+            .arg("sleep 1")
+            .arg(format!("--sort={sort_order}"))
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(
+                "2.00 ±  0.00  sleep 2\n        1.00          sleep 1",
+            ));
+    }
+
     hyperfine_debug()
         .arg("sleep 2")
+        .arg("sleep 3")
         .arg("sleep 1")
-        .arg("--sort=command")
+        .arg("--sort=name")
         .assert()
         .success()
         .stdout(predicate::str::contains(
-            "2.00 ±  0.00  sleep 2\n        1.00          sleep 1",
+            "1.00          sleep 1\n        2.00 ±  0.00  sleep 2\n        3.00 ±  0.00  sleep 3",
         ));
 }
 
@@ -722,3 +1907,421 @@ fn windows_quote_before_quote_args() {
         .assert()
         .success();
 }
+
+#[test]
+fn json_export_contains_options() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("results.json");
+
+    hyperfine_debug()
+        .arg("--runs=3")
+        .arg("--warmup=2")
+        .arg("--prepare=sleep 0.001")
+        .arg("--ignore-failure")
+        .arg("--time-unit=millisecond")
+        .arg("--export-json")
+        .arg(&export_path)
+        .arg("sleep 0.01")
+        .assert()
+        .success();
+
+    let contents = std::fs::read_to_string(export_path).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    let options = &json["metadata"]["options"];
+
+    assert_eq!(options["min_runs"], 3);
+    assert_eq!(options["max_runs"], 3);
+    assert_eq!(options["warmup_runs"], 2);
+    assert_eq!(options["prepare"], serde_json::json!(["sleep 0.001"]));
+    assert_eq!(options["failure_action"], "ignore-all-non-zero");
+    assert_eq!(options["time_unit"], "millisecond");
+    assert!(options.get("setup").is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn exports_to_special_files() {
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--export-json")
+        .arg("/dev/stdout")
+        .arg("sleep 0.1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"results\": ["));
+}
+
+#[test]
+fn compares_results_imported_from_json() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let json_path = tempdir.path().join("results.json");
+    let markdown_path = tempdir.path().join("results.md");
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--export-json")
+        .arg(&json_path)
+        .arg("sleep 0.1")
+        .arg("sleep 0.3")
+        .assert()
+        .success();
+
+    hyperfine()
+        .arg("--from-json")
+        .arg(&json_path)
+        .arg("--reference=sleep 0.3")
+        .arg("--export-markdown")
+        .arg(&markdown_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Benchmark 1").not())
+        .stdout(predicate::str::contains(
+            "  sleep 0.3 ran\n    3.00 ± 0.00 times slower than sleep 0.1",
+        ));
+
+    let markdown = std::fs::read_to_string(markdown_path).unwrap();
+    assert!(markdown.contains("`sleep 0.1`"));
+    assert!(markdown.contains("`sleep 0.3`"));
+
+    hyperfine()
+        .arg("--from-json")
+        .arg(&json_path)
+        .arg("--reference=sleep 0.2")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The reference 'sleep 0.2' is neither the number nor the command",
+        ));
+}
+
+#[test]
+fn fails_with_usage_error_for_invalid_reference_options() {
+    use tempfile::tempdir;
+
+    hyperfine()
+        .arg("--reference=1")
+        .arg("--reference-name=ref")
+        .arg("echo a")
+        .arg("echo b")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The '--reference-name' option can not be used if '--reference' refers to one of the \
+             benchmarked commands.",
+        ));
+
+    let tempdir = tempdir().unwrap();
+    let json_path = tempdir.path().join("results.json");
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--export-json")
+        .arg(&json_path)
+        .arg("sleep 0.1")
+        .arg("sleep 0.2")
+        .arg("sleep 0.3")
+        .assert()
+        .success();
+
+    hyperfine()
+        .arg("--from-json")
+        .arg(&json_path)
+        .arg("--reference=1")
+        .arg("--reference-name=ref")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The '--reference-name' option can not be used together with '--from-json'.",
+        ));
+
+    hyperfine()
+        .arg("--from-json")
+        .arg(&json_path)
+        .arg("--before-after")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "The '--before-after' option requires exactly two benchmark results, but 3 have been \
+             imported.",
+        ));
+}
+
+#[test]
+fn detects_regressions_against_baseline() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let baseline_path = tempdir.path().join("baseline.json");
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--export-json")
+        .arg(&baseline_path)
+        .arg("--command-name=a")
+        .arg("sleep 0.1")
+        .arg("--command-name=b")
+        .arg("sleep 0.3")
+        .assert()
+        .success();
+
+    let run_against_baseline = |a: &str, b: &str| {
+        hyperfine_debug()
+            .arg("--runs=2")
+            .arg("--baseline")
+            .arg(&baseline_path)
+            .arg("--regression-threshold=10")
+            .arg("--command-name=a")
+            .arg(a)
+            .arg("--command-name=b")
+            .arg(b)
+            .arg("--command-name=c")
+            .arg("sleep 0.2")
+            .assert()
+    };
+
+    run_against_baseline("sleep 0.105", "sleep 0.15")
+        .success()
+        .stdout(
+            predicate::str::contains("a  100.0 ms → 105.0 ms     +5.0%")
+                .and(predicate::str::contains("b  300.0 ms → 150.0 ms    -50.0%"))
+                .and(predicate::str::contains("c  no baseline result")),
+        );
+
+    run_against_baseline("sleep 0.12", "sleep 0.3")
+        .code(4)
+        .stdout(predicate::str::contains("a  100.0 ms → 120.0 ms    +20.0%"))
+        .stderr(predicate::str::contains(
+            "1 benchmark(s) regressed by more than 10.0% compared to the baseline",
+        ));
+}
+
+#[test]
+fn detects_metric_regressions_against_baseline() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("export.json");
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--export-json")
+        .arg(&export_path)
+        .arg("sleep 0.1")
+        .assert()
+        .success();
+
+    // The same run time, but with different counter values
+    let write_with_metrics = |name: &str, instructions: u64, cache_misses: u64| {
+        let contents = std::fs::read_to_string(&export_path).unwrap();
+        let mut json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        json["results"][0]["poop_metrics"] = serde_json::json!({
+            "instructions": instructions,
+            "cache_misses": cache_misses,
+        });
+        let path = tempdir.path().join(name);
+        std::fs::write(&path, json.to_string()).unwrap();
+        path
+    };
+    let baseline_path = write_with_metrics("baseline.json", 1000, 100);
+    let results_path = write_with_metrics("results.json", 1003, 141);
+
+    let run_against_baseline = |threshold: &str| {
+        hyperfine()
+            .arg("--from-json")
+            .arg(&results_path)
+            .arg("--baseline")
+            .arg(&baseline_path)
+            .arg("--regression-threshold=10")
+            .arg(format!("--regression-threshold={threshold}"))
+            .assert()
+    };
+
+    run_against_baseline("cache-misses=50").success().stdout(
+        predicate::str::contains("Instructions:            +0.3%")
+            .and(predicate::str::contains("Cache Misses:            +41.0%")),
+    );
+
+    run_against_baseline("cache-misses=20")
+        .code(4)
+        .stderr(predicate::str::contains(
+            "1 benchmark(s) regressed by more than 10.0% or 20.0% (cache-misses) compared to \
+             the baseline",
+        ));
+
+    run_against_baseline("foo=20")
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid argument to '--regression-threshold': unknown metric 'foo'",
+        ));
+}
+
+#[test]
+fn jsonl_export_contains_every_run() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("runs.jsonl");
+
+    hyperfine_debug()
+        .arg("--runs=3")
+        .arg("--export-jsonl")
+        .arg(&export_path)
+        .arg("sleep 0.01")
+        .arg("sleep 0.02")
+        .assert()
+        .success();
+
+    let contents = std::fs::read_to_string(export_path).unwrap();
+    let runs: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(runs.len(), 6);
+    assert_eq!(runs[0]["benchmark"], 0);
+    assert_eq!(runs[0]["run"], 0);
+    assert_eq!(runs[5]["benchmark"], 1);
+    assert_eq!(runs[5]["run"], 2);
+    assert_eq!(runs[5]["command"], "sleep 0.02");
+    assert_eq!(runs[5]["time_real"], 0.02);
+    assert_eq!(runs[5]["exit_code"], 0);
+}
+
+#[test]
+fn timings_csv_export_contains_every_run() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("timings.csv");
+
+    hyperfine_debug()
+        .arg("--runs=2")
+        .arg("--parameter-list")
+        .arg("time")
+        .arg("0.01,0.02")
+        .arg("--export-timings-csv")
+        .arg(&export_path)
+        .arg("sleep {time}")
+        .assert()
+        .success();
+
+    let contents = std::fs::read_to_string(export_path).unwrap();
+    let lines: Vec<_> = contents.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].starts_with("# Exported by hyperfine "));
+    assert!(lines[1].starts_with("command,run_index,time_real,"));
+    assert!(lines[1].ends_with(",exit_code,parameter_time"));
+    assert!(lines[5].starts_with("sleep 0.02,1,0.02,"));
+    assert!(lines[5].ends_with(",0,0.02"));
+}
+
+#[test]
+fn lists_metrics() {
+    hyperfine().arg("--list-metrics").assert().success().stdout(
+        predicate::str::contains("CPU Cycles").and(predicate::str::contains("page-faults, faults")),
+    );
+
+    hyperfine()
+        .arg("--list-metrics")
+        .arg("true")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn exports_to_several_files() {
+    use std::fs::read_to_string;
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let file = |name: &str| tempdir.path().join(name);
+
+    hyperfine_debug()
+        .arg("--export-json")
+        .arg(file("first.json"))
+        .arg("--export-json")
+        .arg(file("second.json"))
+        .arg("--export-markdown")
+        .arg(file("results.md"))
+        .arg("sleep 0.1")
+        .assert()
+        .success();
+
+    let first = read_to_string(file("first.json")).unwrap();
+    assert!(first.contains("\"command\": \"sleep 0.1\""));
+    assert_eq!(read_to_string(file("second.json")).unwrap(), first);
+    assert!(read_to_string(file("results.md"))
+        .unwrap()
+        .contains("| `sleep 0.1` |"));
+}
+
+#[test]
+fn json_export_can_be_compact() {
+    use std::fs::read_to_string;
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let export_path = tempdir.path().join("results.json");
+
+    let export = |flags: &[&str]| {
+        hyperfine_debug()
+            .args(flags)
+            .arg("--export-json")
+            .arg(&export_path)
+            .arg("sleep 0.1")
+            .assert()
+            .success();
+        read_to_string(&export_path).unwrap()
+    };
+
+    let compact = export(&["--json-compact"]);
+    assert_eq!(compact.lines().count(), 1);
+    assert!(compact.contains("\"command\":\"sleep 0.1\""));
+
+    let pretty = export(&["--json-compact", "--json-pretty"]);
+    assert!(pretty.lines().count() > 1);
+    assert!(pretty.contains("\"command\": \"sleep 0.1\""));
+}
+
+#[test]
+fn fails_if_an_export_file_is_used_twice() {
+    use tempfile::tempdir;
+
+    let tempdir = tempdir().unwrap();
+    let file = tempdir.path().join("results");
+
+    hyperfine_debug()
+        .arg("--export-json")
+        .arg(&file)
+        .arg("--export-csv")
+        .arg(&file)
+        .arg("sleep 0.1")
+        .assert()
+        .code(2)
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("is used for more than one export"));
+
+    assert!(!file.exists());
+
+    // Different spellings of the same file, and the appended exports are detected as well
+    for (flag, other) in [
+        ("--export-csv", "./results"),
+        ("--export-jsonl", "results"),
+        ("--export-csv", "../dir/results"),
+    ] {
+        std::fs::create_dir_all(tempdir.path().join("dir")).unwrap();
+        hyperfine_debug()
+            .current_dir(tempdir.path().join("dir"))
+            .arg("--export-json=results")
+            .arg(flag)
+            .arg(other)
+            .arg("sleep 0.1")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("is used for more than one export"));
+    }
+}